@@ -0,0 +1,597 @@
+use base64::{engine::general_purpose, Engine as _};
+use serde::{Deserialize, Serialize};
+use sqlx::{Pool, Row, Sqlite};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+use tauri::http::{header, Response, StatusCode};
+
+/// Name of the URI scheme the webview loads cached artwork from
+pub const SCHEME: &str = "artwork";
+
+// Custom schemes are exposed as http://<scheme>.localhost on Windows and Android
+#[cfg(any(target_os = "windows", target_os = "android"))]
+const URL_PREFIXES: &[&str] = &["http://artwork.localhost/", "artwork://localhost/"];
+#[cfg(not(any(target_os = "windows", target_os = "android")))]
+const URL_PREFIXES: &[&str] = &["artwork://localhost/", "http://artwork.localhost/"];
+
+static CACHE_DIR: OnceLock<PathBuf> = OnceLock::new();
+
+/// Set where artwork is stored. Called once at startup, before any cache is used.
+pub fn init(dir: PathBuf) {
+    let _ = CACHE_DIR.set(dir);
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ThumbnailSize {
+    /// Track rows and lists
+    Small,
+    /// Album and artist grids
+    Medium,
+    /// Now playing and OS media controls
+    Large,
+}
+
+impl ThumbnailSize {
+    pub fn px(&self) -> u32 {
+        match self {
+            ThumbnailSize::Small => 96,
+            ThumbnailSize::Medium => 320,
+            ThumbnailSize::Large => 640,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ThumbnailSize::Small => "small",
+            ThumbnailSize::Medium => "medium",
+            ThumbnailSize::Large => "large",
+        }
+    }
+}
+
+impl std::str::FromStr for ThumbnailSize {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "small" => Ok(ThumbnailSize::Small),
+            "medium" => Ok(ThumbnailSize::Medium),
+            "large" => Ok(ThumbnailSize::Large),
+            _ => Err(format!("Unknown thumbnail size: {}", s)),
+        }
+    }
+}
+
+/// Content-addressed cover art store. Images are keyed by the MD5 of their
+/// bytes, so the same cover embedded in a whole album is stored once, and
+/// remote covers are downloaded only the first time they are seen.
+///
+/// Layout: `<hash[..2]>/<hash>` holds the original, `<hash>_<size>.jpg` its
+/// thumbnails and `<hash>.origin` the remote URL it came from, if any.
+#[derive(Debug, Clone)]
+pub struct ArtworkCache {
+    dir: PathBuf,
+}
+
+impl Default for ArtworkCache {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ArtworkCache {
+    pub fn new() -> Self {
+        let dir = CACHE_DIR.get().cloned().unwrap_or_else(|| {
+            dirs::cache_dir()
+                .unwrap_or_else(std::env::temp_dir)
+                .join("sonami")
+                .join("artwork")
+        });
+        Self { dir }
+    }
+
+    /// URL the webview can load an image from
+    pub fn url(hash: &str, size: Option<ThumbnailSize>) -> String {
+        match size {
+            Some(size) => format!("{}{}/{}", URL_PREFIXES[0], hash, size.as_str()),
+            None => format!("{}{}", URL_PREFIXES[0], hash),
+        }
+    }
+
+    /// The content hash behind a cache URL, ignoring any thumbnail size
+    pub fn hash_from_url(url: &str) -> Option<&str> {
+        let rest = URL_PREFIXES.iter().find_map(|p| url.strip_prefix(p))?;
+        let hash = rest.split(['/', '?']).next()?;
+        is_hash(hash).then_some(hash)
+    }
+
+    /// Whether [`ArtworkCache::cache_url`] takes a cover reference
+    pub fn is_reference(cover: &str) -> bool {
+        Self::hash_from_url(cover).is_some() || cover.starts_with("data:") || is_remote(cover)
+    }
+
+    fn original_path(&self, hash: &str) -> PathBuf {
+        self.dir.join(&hash[..2]).join(hash)
+    }
+
+    fn thumbnail_path(&self, hash: &str, size: ThumbnailSize) -> PathBuf {
+        self.dir
+            .join(&hash[..2])
+            .join(format!("{}_{}.jpg", hash, size.as_str()))
+    }
+
+    fn origin_path(&self, hash: &str) -> PathBuf {
+        self.dir.join(&hash[..2]).join(format!("{}.origin", hash))
+    }
+
+    /// Remote URL -> hash of what it served
+    fn remote_index_path(&self, url: &str) -> PathBuf {
+        self.dir
+            .join("remote")
+            .join(format!("{:x}", md5::compute(url.as_bytes())))
+    }
+
+    /// Store image bytes and return their hash
+    pub fn store(&self, bytes: &[u8]) -> Result<String, String> {
+        if bytes.is_empty() {
+            return Err("Empty image".to_string());
+        }
+        // Servers answer missing covers with HTML error pages, among others
+        if sniff_mime(bytes) == "application/octet-stream" && image::guess_format(bytes).is_err() {
+            return Err("Not an image".to_string());
+        }
+        let hash = format!("{:x}", md5::compute(bytes));
+        let path = self.original_path(&hash);
+        if !path.exists() {
+            write_atomic(&path, bytes)?;
+        }
+        Ok(hash)
+    }
+
+    /// Store the image inside a `data:` URL and return its hash
+    pub fn store_data_url(&self, data_url: &str) -> Result<String, String> {
+        let (meta, data) = data_url
+            .strip_prefix("data:")
+            .and_then(|rest| rest.split_once(','))
+            .ok_or("Not a data URL")?;
+        let bytes = if meta.ends_with(";base64") {
+            general_purpose::STANDARD
+                .decode(data)
+                .map_err(|e| e.to_string())?
+        } else {
+            data.as_bytes().to_vec()
+        };
+        self.store(&bytes)
+    }
+
+    /// Download a remote cover, or reuse the copy fetched earlier
+    pub async fn fetch(&self, url: &str) -> Result<String, String> {
+        let index = self.remote_index_path(url);
+        if let Ok(hash) = std::fs::read_to_string(&index) {
+            let hash = hash.trim();
+            if is_hash(hash) && self.original_path(hash).exists() {
+                return Ok(hash.to_string());
+            }
+        }
+
+        let client = reqwest::Client::builder()
+            .user_agent("Mozilla/5.0")
+            .build()
+            .map_err(|e| e.to_string())?;
+        let response = client
+            .get(url)
+            .send()
+            .await
+            .and_then(|r| r.error_for_status())
+            .map_err(|e| format!("Failed to fetch image: {}", e))?;
+        let bytes = response
+            .bytes()
+            .await
+            .map_err(|e| format!("Failed to read image bytes: {}", e))?;
+
+        let hash = self.store(&bytes)?;
+        write_atomic(&index, hash.as_bytes())?;
+        let origin = self.origin_path(&hash);
+        if !origin.exists() {
+            write_atomic(&origin, url.as_bytes())?;
+        }
+        Ok(hash)
+    }
+
+    /// Store an image file the user picked and return its hash
+    pub fn store_file(&self, path: &str) -> Result<String, String> {
+        let bytes = std::fs::read(path.strip_prefix("file://").unwrap_or(path))
+            .map_err(|e| e.to_string())?;
+        self.store(&bytes)
+    }
+
+    /// Turn a cover reference (data URL, remote URL or cache URL) into a cache
+    /// URL. Local paths are refused, as these come from the webview; see
+    /// [`ArtworkCache::store_file`].
+    pub async fn cache_url(
+        &self,
+        cover: &str,
+        size: Option<ThumbnailSize>,
+    ) -> Result<String, String> {
+        let hash = if let Some(hash) = Self::hash_from_url(cover) {
+            hash.to_string()
+        } else if cover.starts_with("data:") {
+            self.store_data_url(cover)?
+        } else if is_remote(cover) {
+            self.fetch(cover).await?
+        } else {
+            return Err("Unsupported cover reference".to_string());
+        };
+        Ok(Self::url(&hash, size))
+    }
+
+    pub fn read(&self, hash: &str) -> Option<Vec<u8>> {
+        if !is_hash(hash) {
+            return None;
+        }
+        std::fs::read(self.original_path(hash)).ok()
+    }
+
+    /// Path of a thumbnail, generating it on first use
+    pub fn thumbnail(&self, hash: &str, size: ThumbnailSize) -> Result<PathBuf, String> {
+        if !is_hash(hash) {
+            return Err("Invalid artwork hash".to_string());
+        }
+        let path = self.thumbnail_path(hash, size);
+        if path.exists() {
+            return Ok(path);
+        }
+
+        let original = self.read(hash).ok_or("Artwork not found")?;
+        let image = image::load_from_memory(&original).map_err(|e| e.to_string())?;
+        let thumb = image.thumbnail(size.px(), size.px()).to_rgb8();
+
+        let mut encoded = Vec::new();
+        image::codecs::jpeg::JpegEncoder::new_with_quality(&mut encoded, 85)
+            .encode_image(&thumb)
+            .map_err(|e| e.to_string())?;
+        write_atomic(&path, &encoded)?;
+        Ok(path)
+    }
+
+    /// Tile two to four cached covers into a 2x2 mosaic, in reading order,
+    /// and store it. With fewer than four, the first cover repeats in the last
+    /// tile and two covers sit on the diagonals.
+    pub fn mosaic(&self, hashes: &[String]) -> Result<String, String> {
+        let order: &[usize] = match hashes.len() {
+            2 => &[0, 1, 1, 0],
+            3 => &[0, 1, 2, 0],
+            4 => &[0, 1, 2, 3],
+            _ => return Err("A mosaic needs two to four covers".to_string()),
+        };
+
+        let edge = ThumbnailSize::Large.px();
+        let tile = edge / 2;
+        let mut covers = Vec::with_capacity(hashes.len());
+        for hash in hashes {
+            let original = self.read(hash).ok_or("Artwork not found")?;
+            covers.push(
+                image::load_from_memory(&original)
+                    .map_err(|e| e.to_string())?
+                    .resize_to_fill(tile, tile, image::imageops::FilterType::Triangle)
+                    .to_rgb8(),
+            );
+        }
+
+        let mut canvas = image::RgbImage::new(edge, edge);
+        for (i, cover) in order.iter().map(|&c| &covers[c]).enumerate() {
+            let (x, y) = (i as u32 % 2 * tile, i as u32 / 2 * tile);
+            image::imageops::replace(&mut canvas, cover, x as i64, y as i64);
+        }
+
+        let mut encoded = Vec::new();
+        image::codecs::jpeg::JpegEncoder::new_with_quality(&mut encoded, 85)
+            .encode_image(&canvas)
+            .map_err(|e| e.to_string())?;
+        self.store(&encoded)
+    }
+
+    /// Delete an image with its thumbnails and origin. Callers check that
+    /// nothing refers to it any more, since identical images share a hash.
+    pub fn remove(&self, hash: &str) -> Result<(), String> {
+        if !is_hash(hash) {
+            return Err("Invalid artwork hash".to_string());
+        }
+        let mut paths = vec![self.original_path(hash), self.origin_path(hash)];
+        paths.extend(
+            [
+                ThumbnailSize::Small,
+                ThumbnailSize::Medium,
+                ThumbnailSize::Large,
+            ]
+            .map(|size| self.thumbnail_path(hash, size)),
+        );
+        for path in paths {
+            if let Err(e) = std::fs::remove_file(&path) {
+                if e.kind() != std::io::ErrorKind::NotFound {
+                    return Err(e.to_string());
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// A file on disk for a cover, for consumers that can't load our scheme
+    /// (MPRIS wants a `file://` URL). Remote URLs are not resolved here.
+    pub fn local_file(&self, cover: &str, size: Option<ThumbnailSize>) -> Option<PathBuf> {
+        let hash = match Self::hash_from_url(cover) {
+            Some(hash) => hash.to_string(),
+            None if cover.starts_with("data:") => self.store_data_url(cover).ok()?,
+            None => return None,
+        };
+        let path = match size {
+            Some(size) => self
+                .thumbnail(&hash, size)
+                .unwrap_or_else(|_| self.original_path(&hash)),
+            None => self.original_path(&hash),
+        };
+        path.exists().then_some(path)
+    }
+
+    /// The public URL a cached cover was downloaded from. Discord can only
+    /// show images it can fetch itself.
+    pub fn remote_origin(&self, cover: &str) -> Option<String> {
+        let hash = Self::hash_from_url(cover)?;
+        std::fs::read_to_string(self.origin_path(hash))
+            .ok()
+            .map(|s| s.trim().to_string())
+            .filter(|s| !s.is_empty())
+    }
+
+    /// Serve `/<hash>` or `/<hash>/<size>` for the URI scheme handler
+    pub fn respond(&self, path: &str) -> Response<Vec<u8>> {
+        let mut parts = path.trim_start_matches('/').split('/');
+        let hash = parts.next().unwrap_or_default();
+        let size = parts.next().map(|s| s.parse::<ThumbnailSize>());
+
+        let result = match (is_hash(hash), size) {
+            (false, _) | (_, Some(Err(_))) => Err(StatusCode::BAD_REQUEST),
+            (true, None) => self.read(hash).ok_or(StatusCode::NOT_FOUND),
+            (true, Some(Ok(size))) => self
+                .thumbnail(hash, size)
+                .ok()
+                .and_then(|p| std::fs::read(p).ok())
+                .ok_or(StatusCode::NOT_FOUND),
+        };
+
+        let response = match result {
+            Ok(bytes) => Response::builder()
+                .header(header::CONTENT_TYPE, sniff_mime(&bytes))
+                // Content never changes for a given hash
+                .header(header::CACHE_CONTROL, "public, max-age=31536000, immutable")
+                .header(header::ACCESS_CONTROL_ALLOW_ORIGIN, "*")
+                .body(bytes),
+            Err(status) => Response::builder().status(status).body(Vec::new()),
+        };
+        response.unwrap_or_default()
+    }
+
+    /// Move covers that were stored inline as `data:` URLs into the cache
+    pub async fn migrate_inline_covers(&self, pool: &Pool<Sqlite>) -> Result<usize, String> {
+        let mut migrated = 0;
+        for table in ["albums", "artists", "playlists"] {
+            let rows = sqlx::query(&format!(
+                "SELECT id, cover_url FROM {} WHERE cover_url LIKE 'data:%'",
+                table
+            ))
+            .fetch_all(pool)
+            .await
+            .map_err(|e| e.to_string())?;
+
+            for row in rows {
+                let id: String = row.try_get("id").unwrap_or_default();
+                let data_url: String = row.try_get("cover_url").unwrap_or_default();
+                let hash = match self.store_data_url(&data_url) {
+                    Ok(hash) => hash,
+                    Err(e) => {
+                        log::warn!("[Artwork] Skipping inline cover of {} {}: {}", table, id, e);
+                        continue;
+                    }
+                };
+                sqlx::query(&format!("UPDATE {} SET cover_url = ? WHERE id = ?", table))
+                    .bind(Self::url(&hash, None))
+                    .bind(&id)
+                    .execute(pool)
+                    .await
+                    .map_err(|e| e.to_string())?;
+                migrated += 1;
+            }
+        }
+
+        if migrated > 0 {
+            log::info!("[Artwork] Moved {} inline covers to the cache", migrated);
+        }
+        Ok(migrated)
+    }
+}
+
+fn is_remote(url: &str) -> bool {
+    url.starts_with("http://") || url.starts_with("https://")
+}
+
+/// Hashes are written in lowercase, so anything else would miss the cache
+/// on case-sensitive filesystems
+fn is_hash(s: &str) -> bool {
+    s.len() == 32 && s.bytes().all(|b| matches!(b, b'0'..=b'9' | b'a'..=b'f'))
+}
+
+pub(crate) fn sniff_mime(bytes: &[u8]) -> &'static str {
+    match bytes {
+        [0xFF, 0xD8, ..] => "image/jpeg",
+        [0x89, b'P', b'N', b'G', ..] => "image/png",
+        [b'G', b'I', b'F', ..] => "image/gif",
+        [b'B', b'M', ..] => "image/bmp",
+        [b'R', b'I', b'F', b'F', _, _, _, _, b'W', b'E', b'B', b'P', ..] => "image/webp",
+        _ => "application/octet-stream",
+    }
+}
+
+/// Write to a temporary file and rename it into place, so readers never see
+/// a partial image
+fn write_atomic(path: &Path, bytes: &[u8]) -> Result<(), String> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+    }
+    let tmp = path.with_extension(format!("tmp-{}", uuid::Uuid::new_v4()));
+    let mut file = std::fs::File::create(&tmp).map_err(|e| e.to_string())?;
+    file.write_all(bytes).map_err(|e| e.to_string())?;
+    drop(file);
+    std::fs::rename(&tmp, path).map_err(|e| {
+        let _ = std::fs::remove_file(&tmp);
+        e.to_string()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn temp_cache(dir: &TempDir) -> ArtworkCache {
+        ArtworkCache {
+            dir: dir.path().to_path_buf(),
+        }
+    }
+
+    fn store_color(cache: &ArtworkCache, rgb: [u8; 3]) -> String {
+        let image = image::RgbImage::from_pixel(8, 8, image::Rgb(rgb));
+        let mut png = std::io::Cursor::new(Vec::new());
+        image.write_to(&mut png, image::ImageFormat::Png).unwrap();
+        cache.store(png.get_ref()).unwrap()
+    }
+
+    /// The rough color at the center of each tile, in reading order
+    fn tile_colors(cache: &ArtworkCache, hash: &str) -> Vec<[u8; 3]> {
+        let image = image::load_from_memory(&cache.read(hash).unwrap())
+            .unwrap()
+            .to_rgb8();
+        let quarter = image.width() / 4;
+        [(1, 1), (3, 1), (1, 3), (3, 3)]
+            .iter()
+            .map(|&(x, y)| {
+                // Round off JPEG noise
+                image
+                    .get_pixel(x * quarter, y * quarter)
+                    .0
+                    .map(|c| if c > 127 { 255 } else { 0 })
+            })
+            .collect()
+    }
+
+    #[test]
+    fn hashes_are_32_hex_digits() {
+        assert!(is_hash("0123456789abcdef0123456789abcdef"));
+        assert!(!is_hash("0123456789abcdef0123456789ABCDEF"));
+        assert!(!is_hash("0123456789abcdef0123456789abcde"));
+        assert!(!is_hash("0123456789abcdef0123456789abcdeg"));
+        assert!(!is_hash("../../../../../../../../etc/pass"));
+        assert!(!is_hash(""));
+    }
+
+    #[test]
+    fn cache_urls_give_back_their_hash() {
+        let hash = "d41d8cd98f00b204e9800998ecf8427e";
+        for prefix in ["artwork://localhost/", "http://artwork.localhost/"] {
+            assert_eq!(
+                ArtworkCache::hash_from_url(&format!("{}{}", prefix, hash)),
+                Some(hash)
+            );
+            assert_eq!(
+                ArtworkCache::hash_from_url(&format!("{}{}/small", prefix, hash)),
+                Some(hash)
+            );
+            assert_eq!(
+                ArtworkCache::hash_from_url(&format!("{}{}?v=2", prefix, hash)),
+                Some(hash)
+            );
+        }
+        assert_eq!(
+            ArtworkCache::hash_from_url(&ArtworkCache::url(hash, Some(ThumbnailSize::Large))),
+            Some(hash)
+        );
+        assert_eq!(
+            ArtworkCache::hash_from_url("artwork://localhost/nothash"),
+            None
+        );
+        assert_eq!(
+            ArtworkCache::hash_from_url(&format!("https://example.com/{}", hash)),
+            None
+        );
+        assert_eq!(ArtworkCache::hash_from_url(hash), None);
+    }
+
+    #[tokio::test]
+    async fn only_images_are_stored() {
+        let dir = TempDir::new().unwrap();
+        let cache = temp_cache(&dir);
+        let html = b"<!DOCTYPE html><html><body>Not found</body></html>";
+        assert!(cache.store(html).is_err());
+        assert!(cache
+            .store_data_url(&format!(
+                "data:image/png;base64,{}",
+                general_purpose::STANDARD.encode(html)
+            ))
+            .is_err());
+
+        // Local files are only read through store_file
+        let files = TempDir::new().unwrap();
+        let path = files.path().join("cover.png");
+        let image = image::RgbImage::from_pixel(8, 8, image::Rgb([255, 0, 0]));
+        image.save(&path).unwrap();
+        let path = path.to_string_lossy().to_string();
+        assert!(!ArtworkCache::is_reference(&path));
+        assert!(cache.cache_url(&path, None).await.is_err());
+        assert!(cache
+            .cache_url(&format!("file://{}", path), None)
+            .await
+            .is_err());
+        assert!(is_hash(&cache.store_file(&path).unwrap()));
+    }
+
+    #[test]
+    fn mosaics_fill_the_grid_with_two_to_four_covers() {
+        let dir = TempDir::new().unwrap();
+        let cache = temp_cache(&dir);
+        let red = store_color(&cache, [255, 0, 0]);
+        let green = store_color(&cache, [0, 255, 0]);
+        let blue = store_color(&cache, [0, 0, 255]);
+        let white = store_color(&cache, [255, 255, 255]);
+        let (r, g, b, w) = ([255, 0, 0], [0, 255, 0], [0, 0, 255], [255, 255, 255]);
+
+        let four = cache
+            .mosaic(&[red.clone(), green.clone(), blue.clone(), white])
+            .unwrap();
+        assert_eq!(tile_colors(&cache, &four), vec![r, g, b, w]);
+
+        let three = cache.mosaic(&[red.clone(), green.clone(), blue]).unwrap();
+        assert_eq!(tile_colors(&cache, &three), vec![r, g, b, r]);
+
+        let two = cache.mosaic(&[red.clone(), green]).unwrap();
+        assert_eq!(tile_colors(&cache, &two), vec![r, g, g, r]);
+
+        assert!(cache.mosaic(&[red]).is_err());
+        let _ = std::fs::remove_dir_all(&cache.dir);
+    }
+
+    #[test]
+    fn removing_an_image_deletes_its_thumbnails() {
+        let dir = TempDir::new().unwrap();
+        let cache = temp_cache(&dir);
+        let hash = store_color(&cache, [255, 0, 0]);
+        let thumb = cache.thumbnail(&hash, ThumbnailSize::Small).unwrap();
+
+        cache.remove(&hash).unwrap();
+        assert!(cache.read(&hash).is_none());
+        assert!(!thumb.exists());
+        // Already gone is fine
+        cache.remove(&hash).unwrap();
+        let _ = std::fs::remove_dir_all(&cache.dir);
+    }
+}
@@ -70,8 +70,9 @@ pub fn decoder_thread(
         };
 
         if let Some(cmd) = command {
+            let autoplay = !matches!(cmd, DecoderCommand::Cue(_));
             match cmd {
-                DecoderCommand::Load(path) => {
+                DecoderCommand::Load(path) | DecoderCommand::Cue(path) => {
                     // Reset everything
                     buffer_a.clear();
                     buffer_b.clear();
@@ -104,7 +105,7 @@ pub fn decoder_thread(
                             }
 
                             std::sync::atomic::fence(Ordering::SeqCst);
-                            state.is_playing.store(true, Ordering::Release);
+                            state.is_playing.store(autoplay, Ordering::Release);
                         }
                         Err(e) => {
                             let _ = event_tx.send(DecoderEvent::Error(format!(
@@ -6,7 +6,8 @@ use tauri::AppHandle;
 
 use crate::dsp::DspChain;
 use crate::media_controls::MediaControlsManager;
-use crate::queue::PlayQueue;
+use crate::playback_notifier::{PlaybackNotifier, TrackMetadata};
+use crate::queue::{EndAction, PlayQueue};
 
 use super::buffer::AudioBuffer;
 use super::decoder::decoder_thread;
@@ -84,9 +85,7 @@ impl AudioManager {
         let controller_app = app_handle.clone();
         let controller_cmd_tx = command_tx.clone();
         let controller_shutdown = shutdown.clone();
-        let controller_discord = discord_rpc.clone();
         let controller_resolver = url_resolver.clone();
-        let controller_media = media_controls.clone();
         let controller_buffer = buffer_a.clone();
 
         // On Android, spawn threads with a small delay to ensure JNI env is ready
@@ -107,9 +106,7 @@ impl AudioManager {
                 controller_app,
                 controller_cmd_tx,
                 controller_shutdown,
-                controller_discord,
                 controller_resolver,
-                controller_media,
                 controller_buffer,
             );
         });
@@ -180,6 +177,7 @@ impl AudioManager {
         self.state.is_playing.store(true, Ordering::Relaxed);
     }
     pub fn stop(&self) {
+        self.queue.write().clear_now_playing();
         let _ = self.command_tx.send(DecoderCommand::Stop);
     }
     pub fn seek(&self, seconds: f64) {
@@ -212,9 +210,7 @@ pub fn audio_controller_loop(
     app: AppHandle,
     command_tx: std::sync::mpsc::Sender<DecoderCommand>,
     shutdown: Arc<AtomicBool>,
-    discord_rpc: Option<Arc<crate::discord::DiscordRpcManager>>,
     url_resolver: UrlResolver,
-    media_controls: Arc<MediaControlsManager>,
     buffer_monitor: Arc<AudioBuffer>, // Added buffer for monitoring drain
 ) {
     use super::types::DecoderEvent;
@@ -223,6 +219,8 @@ pub fn audio_controller_loop(
     log::info!("[AudioController] Started");
 
     let mut is_draining = false;
+    // Set when a stop-after trigger (rather than an empty queue) started the drain
+    let mut stopped_early = false;
 
     loop {
         if shutdown.load(Ordering::Relaxed) {
@@ -238,11 +236,69 @@ pub fn audio_controller_loop(
 
             // Threshold: if less than ~0.1s of audio left (4410 frames approx)
             if occupied < 4096 {
-                log::info!("[AudioController] Buffer drained. Top-level Stop.");
+                is_draining = false;
+                let end_action = queue.read().end_action;
+                log::info!(
+                    "[AudioController] Buffer drained. End action: {}",
+                    end_action.as_str()
+                );
                 state.is_playing.store(false, Ordering::Relaxed);
                 state.position_samples.store(0, Ordering::Relaxed);
-                media_controls.set_playback(false, Some(0.0));
-                is_draining = false;
+
+                let notifier = notifier(&app);
+                if let Some(ref notifier) = notifier {
+                    notifier.notify_track_finished();
+                }
+
+                match end_action {
+                    EndAction::Stop => {
+                        queue.write().clear_now_playing();
+                        if let Some(ref notifier) = notifier {
+                            notifier.notify_stopped();
+                        }
+                    }
+                    EndAction::Pause => {
+                        if let Some(ref notifier) = notifier {
+                            notifier.notify_paused(0.0);
+                        }
+                        // Cue whatever comes next so resume picks up from there,
+                        // or the start of the queue when it ran out. The OS media
+                        // controls then show the cued track as paused, not stopped.
+                        let next_track_opt = {
+                            let mut q = queue.write();
+                            let next = if stopped_early {
+                                q.get_next_track(false)
+                            } else {
+                                None
+                            };
+                            next.or_else(|| q.rewind())
+                        };
+                        if let Some(track) = next_track_opt {
+                            match url_resolver.resolve(&track.path) {
+                                Ok(resolved) => {
+                                    *state.current_path.write() = Some(resolved.path.clone());
+                                    let _ = command_tx.send(DecoderCommand::Cue(resolved.path));
+                                    let _ = app.emit("track-changed", track.clone());
+                                    if let Some(ref notifier) = notifier {
+                                        notifier
+                                            .notify_track_changed(TrackMetadata::from(&track), 0.0);
+                                    }
+                                }
+                                Err(e) => {
+                                    log::error!(
+                                        "[AudioController] Failed to cue next track: {}",
+                                        e
+                                    );
+                                }
+                            }
+                        }
+                    }
+                    EndAction::Quit => {
+                        log::info!("[AudioController] Quitting after queue end");
+                        app.exit(0);
+                    }
+                }
+                stopped_early = false;
             }
         }
 
@@ -261,10 +317,15 @@ pub fn audio_controller_loop(
                     state.is_playing.store(false, Ordering::Relaxed);
                 }
                 DecoderEvent::RequestNextTrack => {
-                    // Peek next track without advancing queue yet
+                    // Peek next track without advancing queue yet. Nothing is
+                    // preloaded when playback is due to stop after this track.
                     let next_track_opt = {
                         let q = queue.read();
-                        q.peek_next_track()
+                        if q.stop_pending() {
+                            None
+                        } else {
+                            q.peek_next_track()
+                        }
                     };
 
                     if let Some(track) = next_track_opt {
@@ -299,6 +360,18 @@ pub fn audio_controller_loop(
                     log::info!("[AudioController] Crossfade Handover Complete");
                     let _ = app.emit("track-ended", ());
 
+                    // Stop-after set once the next track was already preloaded:
+                    // cut the incoming track and end as if the old one ran out
+                    if queue.write().take_stop_trigger() {
+                        log::info!("[AudioController] Stop-after triggered during crossfade");
+                        let _ = command_tx.send(DecoderCommand::Stop);
+                        let _ = app.emit("stop-after-triggered", ());
+                        let _ = app.emit("stop-state-changed", queue.read().stop_state());
+                        stopped_early = true;
+                        is_draining = true;
+                        continue;
+                    }
+
                     // Advance queue silently (we are already playing the next track)
                     let next_track_opt = {
                         let mut q = queue.write();
@@ -324,32 +397,27 @@ pub fn audio_controller_loop(
 
                         let _ = app.emit("track-changed", track.clone());
 
-                        if let Some(ref rpc) = discord_rpc {
-                            rpc.set_playing(
-                                crate::discord::TrackInfo {
-                                    title: track.title.clone(),
-                                    artist: track.artist.clone(),
-                                    album: track.album.clone(),
-                                    duration_secs: track.duration,
-                                    cover_url: track.cover_image.clone(),
-                                },
-                                0,
-                            );
+                        if let Some(notifier) = notifier(&app) {
+                            notifier.notify_track_finished();
+                            notifier.notify_playing(TrackMetadata::from(&track), 0.0);
                         }
-
-                        media_controls.set_metadata(
-                            &track.title,
-                            &track.artist,
-                            &track.album,
-                            track.cover_image.as_deref(),
-                            track.duration as f64,
-                        );
                     }
                 }
                 DecoderEvent::EndOfStream => {
                     log::info!("[AudioController] End of Stream received");
                     let _ = app.emit("track-ended", ());
 
+                    if queue.write().take_stop_trigger() {
+                        log::info!(
+                            "[AudioController] Stop-after triggered. Waiting for buffer drain..."
+                        );
+                        let _ = app.emit("stop-after-triggered", ());
+                        let _ = app.emit("stop-state-changed", queue.read().stop_state());
+                        stopped_early = true;
+                        is_draining = true;
+                        continue;
+                    }
+
                     // Logic to handle next track - Now we actually advance the queue
                     let next_track_opt = {
                         let mut q = queue.write();
@@ -380,27 +448,11 @@ pub fn audio_controller_loop(
                                 let _ = app.emit("track-changed", track_clone.clone());
                                 let _ = app.emit("playback-quality-changed", resolved.clone());
 
-                                if let Some(ref rpc) = discord_rpc {
-                                    rpc.set_playing(
-                                        crate::discord::TrackInfo {
-                                            title: track_clone.title.clone(),
-                                            artist: track_clone.artist.clone(),
-                                            album: track_clone.album.clone(),
-                                            duration_secs: track_clone.duration,
-                                            cover_url: track_clone.cover_image.clone(),
-                                        },
-                                        0,
-                                    );
+                                if let Some(notifier) = notifier(&app) {
+                                    notifier.notify_track_finished();
+                                    notifier.notify_playing(TrackMetadata::from(&track_clone), 0.0);
                                 }
 
-                                media_controls.set_metadata(
-                                    &track_clone.title,
-                                    &track_clone.artist,
-                                    &track_clone.album,
-                                    track_clone.cover_image.as_deref(),
-                                    track_clone.duration as f64,
-                                );
-
                                 // Send Load Command if not already preloaded?
                                 // Actually, if we hit EOS, it means simple switch or crossfade finished?
                                 // If Crossfade finished, decoder takes care of swapping internally mostly?
@@ -438,3 +490,11 @@ pub fn audio_controller_loop(
         }
     }
 }
+
+/// The notifier is managed after the audio manager is created, so the
+/// controller looks it up when it needs it
+fn notifier(app: &AppHandle) -> Option<Arc<PlaybackNotifier>> {
+    use tauri::Manager;
+    app.try_state::<Arc<PlaybackNotifier>>()
+        .map(|notifier| notifier.inner().clone())
+}
@@ -67,14 +67,11 @@ impl UrlResolver {
             || uri.starts_with("subsonic:")
             || uri.starts_with("jellyfin:");
 
-        if !is_provider_uri {
-            if !uri.starts_with("http://")
-                && !uri.starts_with("https://")
-                && !Path::new(uri).exists()
-            {
-                log::warn!("[Resolver] Local file not found: {}. Cannot play.", uri);
-                return Err(format!("File not found: {}", uri));
-            }
+        // Existing files and URLs need no lookup; a missing file goes through
+        // the resolver thread so another copy of the work can stand in
+        let is_direct =
+            uri.starts_with("http://") || uri.starts_with("https://") || Path::new(uri).exists();
+        if !is_provider_uri && is_direct {
             return Ok(ResolvedAudio {
                 path: uri.to_string(),
                 source: "LOCAL".to_string(),
@@ -93,85 +90,162 @@ impl UrlResolver {
     }
 }
 
+/// Resolve a track URI to something the decoder can open. Other copies of the
+/// same work are considered too: a local file wins over a download, which wins
+/// over the best stream any provider can offer.
 pub async fn resolve_uri(app_handle: &AppHandle, uri: &str) -> Result<ResolvedAudio, String> {
-    if let Some((scheme, id_str)) = uri.split_once(':') {
-        if let Some(state) =
-            app_handle.try_state::<std::sync::Arc<crate::providers::ProviderManager>>()
-        {
-            if let Some(provider) = state.get_provider(scheme).await {
-                let (target_quality, prefer_high_quality) =
-                    if let Some(state) = app_handle.try_state::<crate::tidal::TidalConfigState>() {
-                        let config = state.lock();
-                        (config.quality.clone(), config.prefer_high_quality_stream)
-                    } else {
-                        (crate::tidal::Quality::LOSSLESS, false)
-                    };
+    let library = app_handle.try_state::<LibraryManager>();
+    let sources = match &library {
+        Some(library) => library
+            .get_work_sources_for_uri(uri)
+            .await
+            .unwrap_or_default(),
+        None => Vec::new(),
+    };
 
-                let unified_quality = match target_quality {
-                    crate::tidal::Quality::LOW => crate::models::Quality::LOW,
-                    crate::tidal::Quality::HIGH => crate::models::Quality::HIGH,
-                    crate::tidal::Quality::LOSSLESS => crate::models::Quality::LOSSLESS,
-                };
+    // 1. Local files from a library folder
+    for source in sources.iter().filter(|s| s.source_type == "LOCAL") {
+        if let Some(path) = &source.file_path {
+            if Path::new(path).exists() {
+                log::debug!("[Resolver] Using local copy {} for {}", path, uri);
+                return Ok(ResolvedAudio {
+                    path: path.clone(),
+                    source: "LOCAL".to_string(),
+                    quality: source
+                        .audio_quality
+                        .clone()
+                        .unwrap_or("UNKNOWN".to_string()),
+                });
+            }
+        }
+    }
 
-                if let Some(library) = app_handle.try_state::<LibraryManager>() {
-                    if let Ok(Some((path, quality_str))) =
-                        library.get_track_local_info(scheme, id_str).await
-                    {
-                        log::debug!(
-                            "[Resolver] Found local file: {} (Quality: {:?})",
-                            path,
-                            quality_str
-                        );
-
-                        if Path::new(&path).exists() {
-                            let local_is_sufficient = if prefer_high_quality {
-                                if let Some(ref q_str) = quality_str {
-                                    if let Ok(local_quality) =
-                                        q_str.parse::<crate::tidal::Quality>()
-                                    {
-                                        let sufficient = local_quality >= target_quality;
-                                        if !sufficient {
-                                            log::warn!("[Resolver] Local quality {:?} < Target {:?}. Streaming preferred.", local_quality, target_quality);
-                                        }
-                                        sufficient
-                                    } else {
-                                        true
-                                    }
-                                } else {
-                                    true
-                                }
-                            } else {
-                                true
-                            };
-
-                            if local_is_sufficient {
-                                return Ok(ResolvedAudio {
-                                    path,
-                                    source: "LOCAL".to_string(),
-                                    quality: quality_str.unwrap_or("UNKNOWN".to_string()),
-                                });
-                            }
-                        } else {
-                            let _ = library.clear_download_info(scheme, id_str).await;
-                        }
-                    }
+    let (target_quality, prefer_high_quality) =
+        if let Some(state) = app_handle.try_state::<crate::tidal::TidalConfigState>() {
+            let config = state.lock();
+            (config.quality.clone(), config.prefer_high_quality_stream)
+        } else {
+            (crate::tidal::Quality::LOSSLESS, false)
+        };
+
+    let unified_quality = match target_quality {
+        crate::tidal::Quality::LOW => crate::models::Quality::LOW,
+        crate::tidal::Quality::HIGH => crate::models::Quality::HIGH,
+        crate::tidal::Quality::LOSSLESS => crate::models::Quality::LOSSLESS,
+    };
+
+    // 2. Downloaded copies of provider tracks, best quality first
+    let mut best_download: Option<(String, Option<String>, Option<crate::tidal::Quality>)> = None;
+    for source in sources.iter().filter(|s| s.source_type != "LOCAL") {
+        let Some(path) = &source.file_path else {
+            continue;
+        };
+        if !Path::new(path).exists() {
+            // Keep the path so the download can be relinked if it turns up again
+            if let Some(library) = &library {
+                let _ = library
+                    .mark_tracks_missing(std::slice::from_ref(&source.track_id))
+                    .await;
+            }
+            continue;
+        }
+
+        let quality = source
+            .audio_quality
+            .as_deref()
+            .and_then(|q| q.parse::<crate::tidal::Quality>().ok());
+        if prefer_high_quality {
+            if let Some(q) = &quality {
+                if *q < target_quality {
+                    log::warn!(
+                        "[Resolver] Local quality {:?} < Target {:?}. Streaming preferred.",
+                        q,
+                        target_quality
+                    );
+                    continue;
                 }
+            }
+        }
 
-                log::debug!("[Resolver] Streaming {} from {}", id_str, scheme);
-                let stream_info = provider
-                    .get_stream_url(id_str, unified_quality)
-                    .await
-                    .map_err(|e| format!("Failed to resolve stream from {}: {}", scheme, e))?;
+        let better = match &best_download {
+            None => true,
+            Some((_, _, best)) => quality > *best,
+        };
+        if better {
+            best_download = Some((path.clone(), source.audio_quality.clone(), quality));
+        }
+    }
+    if let Some((path, quality_str, _)) = best_download {
+        log::debug!(
+            "[Resolver] Found local file: {} (Quality: {:?})",
+            path,
+            quality_str
+        );
+        return Ok(ResolvedAudio {
+            path,
+            source: "LOCAL".to_string(),
+            quality: quality_str.unwrap_or("UNKNOWN".to_string()),
+        });
+    }
 
-                return Ok(ResolvedAudio {
-                    path: stream_info.url,
-                    source: "STREAM".to_string(),
-                    quality: format!("{:?}", stream_info.quality),
-                });
+    // 3. Streams: the requested provider first, then the other copies
+    let mut candidates: Vec<(String, String)> = Vec::new();
+    if let Some((scheme, id)) = uri.split_once(':') {
+        candidates.push((scheme.to_string(), id.to_string()));
+    }
+    for source in &sources {
+        if let (Some(pid), Some(eid)) = (&source.provider_id, &source.external_id) {
+            let candidate = (pid.clone(), eid.clone());
+            if pid != "local" && !candidates.contains(&candidate) {
+                candidates.push(candidate);
             }
         }
     }
 
+    if let Some(state) = app_handle.try_state::<std::sync::Arc<crate::providers::ProviderManager>>()
+    {
+        let mut best_stream: Option<crate::models::StreamInfo> = None;
+        let mut last_error = None;
+        for (scheme, id) in &candidates {
+            let Some(provider) = state.get_provider(scheme).await else {
+                continue;
+            };
+
+            log::debug!("[Resolver] Streaming {} from {}", id, scheme);
+            match provider.get_stream_url(id, unified_quality.clone()).await {
+                Ok(info) => {
+                    // Nothing can beat the quality the user asked for
+                    let reached_target = info.quality >= unified_quality;
+                    let better = match &best_stream {
+                        None => true,
+                        Some(best) => info.quality > best.quality,
+                    };
+                    if better {
+                        best_stream = Some(info);
+                    }
+                    if reached_target {
+                        break;
+                    }
+                }
+                Err(e) => {
+                    log::warn!("[Resolver] {} could not stream {}: {}", scheme, id, e);
+                    last_error = Some(format!("Failed to resolve stream from {}: {}", scheme, e));
+                }
+            }
+        }
+
+        if let Some(stream_info) = best_stream {
+            return Ok(ResolvedAudio {
+                path: stream_info.url,
+                source: "STREAM".to_string(),
+                quality: format!("{:?}", stream_info.quality),
+            });
+        }
+        if let Some(e) = last_error {
+            return Err(e);
+        }
+    }
+
     if !uri.starts_with("http://") && !uri.starts_with("https://") && !Path::new(uri).exists() {
         log::warn!("[Resolver] Local file not found: {}. Cannot play.", uri);
         return Err(format!("File not found: {}", uri));
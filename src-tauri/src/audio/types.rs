@@ -104,6 +104,7 @@ impl PlaybackState {
 
 pub enum DecoderCommand {
     Load(String),
+    Cue(String), // Load but stay paused (queue end action "pause")
     LoadNext(String), // Legacy, to be removed?
     PreloadedDecoder(Box<dyn FormatReader>, Box<dyn Decoder>, u32, u64, u32), // Pre-loaded track data
     Chain(String), // Load without clearing buffer (Gapless/Append)
@@ -11,22 +11,17 @@ pub mod providers;
 pub mod recommendations;
 pub mod spotify;
 
+use crate::artwork::{ArtworkCache, ThumbnailSize};
 use crate::audio::AudioManager;
 use crate::library::LibraryManager;
 use crate::models::SearchResults;
 use crate::providers::ProviderManager;
 use base64::{engine::general_purpose, Engine as _};
-use lofty::picture::MimeType;
 use lofty::prelude::*;
 use lofty::probe::Probe;
 use serde::Serialize;
-use std::fs;
 use std::path::Path;
 
-const AUDIO_EXTENSIONS: &[&str] = &[
-    "mp3", "flac", "wav", "ogg", "m4a", "aac", "wma", "aiff", "ape", "opus", "webm",
-];
-
 use crate::queue::Track;
 
 fn parse_audio_file(path_str: &str) -> Option<Track> {
@@ -59,23 +54,10 @@ fn parse_audio_file(path_str: &str) -> Option<Track> {
 
     let duration = tagged_file.properties().duration().as_secs();
 
-    let mut cover_image = None;
-    if let Some(t) = tag {
-        if let Some(picture) = t.pictures().first() {
-            let b64 = general_purpose::STANDARD.encode(picture.data());
-            let mime = picture.mime_type();
-            let mime_type = mime.unwrap_or(&MimeType::Jpeg);
-            let mime_str = match mime_type {
-                MimeType::Png => "image/png",
-                MimeType::Jpeg => "image/jpeg",
-                MimeType::Tiff => "image/tiff",
-                MimeType::Bmp => "image/bmp",
-                MimeType::Gif => "image/gif",
-                _ => "application/octet-stream",
-            };
-            cover_image = Some(format!("data:{};base64,{}", mime_str, b64));
-        }
-    }
+    let cover_image = tag
+        .and_then(|t| t.pictures().first())
+        .and_then(|picture| ArtworkCache::new().store(picture.data()).ok())
+        .map(|hash| ArtworkCache::url(&hash, None));
 
     Some(Track {
         id: uuid::Uuid::new_v4().to_string(),
@@ -92,30 +74,6 @@ fn parse_audio_file(path_str: &str) -> Option<Track> {
     })
 }
 
-fn is_audio_file(path: &Path) -> bool {
-    path.extension()
-        .and_then(|ext| ext.to_str())
-        .map(|ext| AUDIO_EXTENSIONS.contains(&ext.to_lowercase().as_str()))
-        .unwrap_or(false)
-}
-
-fn scan_directory(dir: &Path, tracks: &mut Vec<Track>) {
-    if let Ok(entries) = fs::read_dir(dir) {
-        for entry in entries.flatten() {
-            let path = entry.path();
-            if path.is_dir() {
-                scan_directory(&path, tracks);
-            } else if is_audio_file(&path) {
-                if let Some(path_str) = path.to_str() {
-                    if let Some(track) = parse_audio_file(path_str) {
-                        tracks.push(track);
-                    }
-                }
-            }
-        }
-    }
-}
-
 #[tauri::command]
 pub async fn import_music(app: AppHandle) -> Result<Vec<Track>, String> {
     #[cfg(target_os = "android")]
@@ -140,10 +98,17 @@ pub async fn import_music(app: AppHandle) -> Result<Vec<Track>, String> {
     }
 }
 
+/// Register a picked folder as a watched library folder. The scan runs in
+/// the background and reports through `library-scan-progress` and
+/// `library-scan-complete`, as the startup scan does.
 #[tauri::command]
-pub async fn import_folder(app: AppHandle) -> Result<Vec<Track>, String> {
+pub async fn import_folder(
+    app: AppHandle,
+    scanner: State<'_, crate::library::scanner::LibraryScanner>,
+    watcher: State<'_, crate::library::watcher::LibraryWatcher>,
+) -> Result<Option<crate::library::scanner::LibraryFolder>, String> {
     #[cfg(target_os = "android")]
-    return Ok(vec![]);
+    return Ok(None);
 
     #[cfg(not(target_os = "android"))]
     {
@@ -152,22 +117,23 @@ pub async fn import_folder(app: AppHandle) -> Result<Vec<Track>, String> {
         let folder_path = app.dialog().file().blocking_pick_folder();
 
         if let Some(path_buf) = folder_path {
-            let path_str = path_buf.to_string();
-            let path = Path::new(&path_str);
-
-            let mut tracks = Vec::new();
-            scan_directory(path, &mut tracks);
+            // Imported folders become watched library folders
+            let folder = scanner.add_folder(&path_buf.to_string()).await?;
+            if let Err(e) = watcher.watch(&folder.path) {
+                log::warn!("Could not watch {}: {}", folder.path, e);
+            }
 
-            tracks.sort_by(|a, b| {
-                a.artist
-                    .cmp(&b.artist)
-                    .then_with(|| a.album.cmp(&b.album))
-                    .then_with(|| a.title.cmp(&b.title))
+            let scan_handle = app.clone();
+            let scan_folder = folder.clone();
+            tauri::async_runtime::spawn(async move {
+                let scanner = scan_handle.state::<crate::library::scanner::LibraryScanner>();
+                if let Err(e) = scanner.scan_folder(&scan_handle, &scan_folder).await {
+                    log::warn!("Scan of {} failed: {}", scan_folder.path, e);
+                }
             });
-
-            Ok(tracks)
+            Ok(Some(folder))
         } else {
-            Ok(vec![])
+            Ok(None)
         }
     }
 }
@@ -196,16 +162,7 @@ pub async fn play_track(
         let _ = app.emit("track-changed", t.clone());
 
         // Use centralized notifier for both Discord and MPRIS
-        notifier.notify_playing(
-            crate::playback_notifier::TrackMetadata::new(
-                &t.title,
-                &t.artist,
-                &t.album,
-                t.duration as f64,
-                t.cover_image.clone(),
-            ),
-            0.0,
-        );
+        notifier.notify_playing(crate::playback_notifier::TrackMetadata::from(t), 0.0);
     }
 
     Ok(())
@@ -279,11 +236,17 @@ pub async fn get_is_playing(state: State<'_, AudioManager>) -> Result<bool, Stri
     Ok(state.is_playing())
 }
 
-use crate::queue::RepeatMode;
+use crate::queue::{EndAction, PlayContext, RepeatMode, StopAfter};
 
 #[tauri::command]
-pub async fn set_queue(state: State<'_, AudioManager>, tracks: Vec<Track>) -> Result<(), String> {
-    state.queue.write().set_tracks(tracks);
+pub async fn set_queue(
+    state: State<'_, AudioManager>,
+    notifier: State<'_, std::sync::Arc<crate::playback_notifier::PlaybackNotifier>>,
+    tracks: Vec<Track>,
+    context: Option<PlayContext>,
+) -> Result<(), String> {
+    notifier.set_context(context.clone());
+    state.queue.write().set_tracks(tracks, context);
     Ok(())
 }
 
@@ -331,6 +294,60 @@ pub async fn set_repeat_mode(
     Ok(())
 }
 
+#[tauri::command]
+pub async fn set_stop_after(
+    app: AppHandle,
+    state: State<'_, AudioManager>,
+    mode: StopAfter,
+) -> Result<(), String> {
+    state.queue.write().stop_after = mode;
+    let _ = app.emit("stop-state-changed", state.queue.read().stop_state());
+    Ok(())
+}
+
+#[tauri::command]
+pub async fn get_stop_after(state: State<'_, AudioManager>) -> Result<StopAfter, String> {
+    Ok(state.queue.read().stop_after.clone())
+}
+
+#[tauri::command]
+pub async fn set_end_action(
+    app: AppHandle,
+    state: State<'_, AudioManager>,
+    db: State<'_, crate::database::DatabaseManager>,
+    action: EndAction,
+) -> Result<(), String> {
+    state.queue.write().end_action = action;
+    let _ = app.emit("stop-state-changed", state.queue.read().stop_state());
+    let _ = db.set_setting("player_end_action", action.as_str()).await;
+    Ok(())
+}
+
+#[tauri::command]
+pub async fn get_end_action(state: State<'_, AudioManager>) -> Result<EndAction, String> {
+    Ok(state.queue.read().end_action)
+}
+
+#[tauri::command]
+pub async fn set_loop_manual_queue(
+    app: AppHandle,
+    state: State<'_, AudioManager>,
+    db: State<'_, crate::database::DatabaseManager>,
+    enabled: bool,
+) -> Result<(), String> {
+    state.queue.write().loop_manual_queue = enabled;
+    let _ = app.emit("stop-state-changed", state.queue.read().stop_state());
+    let _ = db
+        .set_setting("player_loop_manual_queue", &enabled.to_string())
+        .await;
+    Ok(())
+}
+
+#[tauri::command]
+pub async fn get_loop_manual_queue(state: State<'_, AudioManager>) -> Result<bool, String> {
+    Ok(state.queue.read().loop_manual_queue)
+}
+
 #[tauri::command]
 pub async fn next_track(
     app: AppHandle,
@@ -354,16 +371,7 @@ pub async fn next_track(
         let _ = app.emit("track-changed", track.clone());
 
         // Use centralized notifier for both Discord and MPRIS
-        notifier.notify_playing(
-            crate::playback_notifier::TrackMetadata::new(
-                &track.title,
-                &track.artist,
-                &track.album,
-                track.duration as f64,
-                track.cover_image.clone(),
-            ),
-            0.0,
-        );
+        notifier.notify_playing(crate::playback_notifier::TrackMetadata::from(track), 0.0);
     }
     Ok(())
 }
@@ -391,16 +399,7 @@ pub async fn prev_track(
         let _ = app.emit("track-changed", track.clone());
 
         // Use centralized notifier for both Discord and MPRIS
-        notifier.notify_playing(
-            crate::playback_notifier::TrackMetadata::new(
-                &track.title,
-                &track.artist,
-                &track.album,
-                track.duration as f64,
-                track.cover_image.clone(),
-            ),
-            0.0,
-        );
+        notifier.notify_playing(crate::playback_notifier::TrackMetadata::from(track), 0.0);
     }
     Ok(())
 }
@@ -431,6 +430,13 @@ pub async fn get_queue(state: State<'_, AudioManager>) -> Result<Vec<Track>, Str
     Ok(state.queue.read().tracks.clone())
 }
 
+#[tauri::command]
+pub async fn get_queue_context(
+    state: State<'_, AudioManager>,
+) -> Result<Option<PlayContext>, String> {
+    Ok(state.queue.read().context.clone())
+}
+
 #[tauri::command]
 pub async fn get_shuffle_mode(state: State<'_, AudioManager>) -> Result<bool, String> {
     Ok(state.queue.read().shuffle)
@@ -664,16 +670,7 @@ pub async fn play_tidal_track(
     audio_state.play(stream_info.url);
 
     // Use centralized notifier for both Discord and MPRIS
-    notifier.notify_playing(
-        crate::playback_notifier::TrackMetadata::new(
-            &track.title,
-            &track.artist,
-            &track.album,
-            track.duration as f64,
-            track.cover_image.clone(),
-        ),
-        0.0,
-    );
+    notifier.notify_playing(crate::playback_notifier::TrackMetadata::from(&track), 0.0);
 
     let _ = app.emit("track-changed", track);
     Ok(())
@@ -699,33 +696,24 @@ pub async fn get_tidal_stream_url(
 
 #[tauri::command]
 pub async fn fetch_image_as_data_url(url: String) -> Result<String, String> {
-    use base64::{engine::general_purpose, Engine as _};
-
-    let client = reqwest::Client::builder()
-        .user_agent("Mozilla/5.0")
-        .build()
-        .map_err(|e| e.to_string())?;
-
-    let response = client
-        .get(&url)
-        .send()
-        .await
-        .map_err(|e| format!("Failed to fetch image: {}", e))?;
-
-    let content_type = response
-        .headers()
-        .get("content-type")
-        .and_then(|v| v.to_str().ok())
-        .unwrap_or("image/jpeg")
-        .to_string();
-
-    let bytes = response
-        .bytes()
-        .await
-        .map_err(|e| format!("Failed to read image bytes: {}", e))?;
+    let cache = ArtworkCache::new();
+    let cached = cache.cache_url(&url, None).await?;
+    let bytes = ArtworkCache::hash_from_url(&cached)
+        .and_then(|hash| cache.read(hash))
+        .ok_or("Artwork not found")?;
+
+    Ok(format!(
+        "data:{};base64,{}",
+        crate::artwork::sniff_mime(&bytes),
+        general_purpose::STANDARD.encode(&bytes)
+    ))
+}
 
-    let base64_data = general_purpose::STANDARD.encode(&bytes);
-    Ok(format!("data:{};base64,{}", content_type, base64_data))
+/// Cache a cover (remote URL, data URL or cache URL) and return the URL of
+/// the requested thumbnail, or of the original when no size is given
+#[tauri::command]
+pub async fn get_artwork_url(url: String, size: Option<ThumbnailSize>) -> Result<String, String> {
+    ArtworkCache::new().cache_url(&url, size).await
 }
 
 #[tauri::command]
@@ -1154,6 +1142,7 @@ pub async fn play_provider_track(
                 name: artist.clone(),
                 picture: None,
                 banner: None,
+                artist_type: None,
             }),
             album: Some(crate::tidal::Album {
                 id: 0,
@@ -1168,6 +1157,9 @@ pub async fn play_provider_track(
             audio_quality: None,
             cover: None,
             track_number: None,
+            volume_number: None,
+            isrc: None,
+            artists: None,
         };
 
         match library
@@ -1238,16 +1230,7 @@ pub async fn play_provider_track(
     audio_state.play(stream_url);
 
     // Use centralized notifier for both Discord and MPRIS
-    notifier.notify_playing(
-        crate::playback_notifier::TrackMetadata::new(
-            &track.title,
-            &track.artist,
-            &track.album,
-            track.duration as f64,
-            track.cover_image.clone(),
-        ),
-        0.0,
-    );
+    notifier.notify_playing(crate::playback_notifier::TrackMetadata::from(&track), 0.0);
 
     let _ = app.emit("track-changed", track);
     Ok(())
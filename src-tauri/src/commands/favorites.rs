@@ -1,11 +1,15 @@
+use crate::favorites::models::{FavoriteSort, LikeSyncReport, LikedFromArtist};
 use crate::favorites::FavoritesManager;
-use crate::library::models::UnifiedTrack;
+use crate::library::models::{LibraryAlbum, LibraryArtist, UnifiedTrack};
+use crate::providers::ProviderManager;
+use std::sync::Arc;
 use tauri::{command, State};
 
 #[command]
 pub async fn add_favorite(
     manager: State<'_, FavoritesManager>,
     library: State<'_, crate::library::LibraryManager>,
+    provider_manager: State<'_, Arc<ProviderManager>>,
     track: UnifiedTrack,
 ) -> Result<(), String> {
     let track_id =
@@ -34,13 +38,16 @@ pub async fn add_favorite(
             track.id.clone()
         };
 
-    manager.add_favorite(&track_id).await
+    manager.add_favorite(&track_id).await?;
+    let providers = provider_manager.get_all_providers().await;
+    manager.push_likes(&track_id, &providers).await
 }
 
 #[command]
 pub async fn remove_favorite(
     manager: State<'_, FavoritesManager>,
     library: State<'_, crate::library::LibraryManager>,
+    provider_manager: State<'_, Arc<ProviderManager>>,
     track: UnifiedTrack,
 ) -> Result<(), String> {
     let id_to_remove =
@@ -52,7 +59,9 @@ pub async fn remove_favorite(
         } else {
             track.id.clone()
         };
-    manager.remove_favorite(&id_to_remove).await
+    manager.remove_favorite(&id_to_remove).await?;
+    let providers = provider_manager.get_all_providers().await;
+    manager.push_likes(&id_to_remove, &providers).await
 }
 
 #[command]
@@ -69,3 +78,85 @@ pub async fn get_favorites(
 ) -> Result<Vec<UnifiedTrack>, String> {
     manager.get_favorites_with_tracks().await
 }
+
+/// Reconcile likes with Subsonic stars and Jellyfin favourites
+#[command]
+pub async fn sync_likes(
+    manager: State<'_, FavoritesManager>,
+    library: State<'_, crate::library::LibraryManager>,
+    provider_manager: State<'_, Arc<ProviderManager>>,
+) -> Result<LikeSyncReport, String> {
+    let providers = provider_manager.get_all_providers().await;
+    manager.sync_likes(&library, &providers).await
+}
+
+#[command]
+pub async fn like_album(
+    manager: State<'_, FavoritesManager>,
+    provider_manager: State<'_, Arc<ProviderManager>>,
+    album: LibraryAlbum,
+) -> Result<(), String> {
+    manager.like_album(&album).await?;
+    let providers = provider_manager.get_all_providers().await;
+    manager.push_item_likes(&providers).await
+}
+
+#[command]
+pub async fn unlike_album(
+    manager: State<'_, FavoritesManager>,
+    provider_manager: State<'_, Arc<ProviderManager>>,
+    album: LibraryAlbum,
+) -> Result<(), String> {
+    manager.unlike_album(&album).await?;
+    let providers = provider_manager.get_all_providers().await;
+    manager.push_item_likes(&providers).await
+}
+
+#[command]
+pub async fn like_artist(
+    manager: State<'_, FavoritesManager>,
+    provider_manager: State<'_, Arc<ProviderManager>>,
+    artist: LibraryArtist,
+) -> Result<(), String> {
+    manager.like_artist(&artist).await?;
+    let providers = provider_manager.get_all_providers().await;
+    manager.push_item_likes(&providers).await
+}
+
+#[command]
+pub async fn unlike_artist(
+    manager: State<'_, FavoritesManager>,
+    provider_manager: State<'_, Arc<ProviderManager>>,
+    artist: LibraryArtist,
+) -> Result<(), String> {
+    manager.unlike_artist(&artist).await?;
+    let providers = provider_manager.get_all_providers().await;
+    manager.push_item_likes(&providers).await
+}
+
+#[command]
+pub async fn get_favorite_albums(
+    manager: State<'_, FavoritesManager>,
+    sort: Option<FavoriteSort>,
+) -> Result<Vec<LibraryAlbum>, String> {
+    manager
+        .get_favorite_albums(sort.unwrap_or_default(), None)
+        .await
+}
+
+#[command]
+pub async fn get_favorite_artists(
+    manager: State<'_, FavoritesManager>,
+    sort: Option<FavoriteSort>,
+) -> Result<Vec<LibraryArtist>, String> {
+    manager.get_favorite_artists(sort.unwrap_or_default()).await
+}
+
+/// Liked tracks and albums by an artist, matched by name
+#[command]
+pub async fn get_liked_from_artist(
+    manager: State<'_, FavoritesManager>,
+    artist: String,
+) -> Result<LikedFromArtist, String> {
+    manager.get_liked_from_artist(&artist).await
+}
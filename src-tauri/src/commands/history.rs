@@ -1,19 +1,13 @@
-use crate::history::PlayHistoryManager;
+use crate::database::DatabaseManager;
+use crate::history::listening::{load_thresholds, PlayThresholds, THRESHOLDS_SETTING};
+use crate::history::models::{
+    ArtistDiscovery, DailyListening, HeatmapCell, ListeningStreaks, ListeningSummary,
+    RecentContext, StatsEntry, StatsKind, StatsRange, YearInReview,
+};
+use crate::history::{current_year, PlayHistoryManager};
 use crate::library::models::UnifiedTrack;
 use tauri::{command, State};
 
-#[command]
-pub async fn record_play(
-    manager: State<'_, PlayHistoryManager>,
-    track_id: String,
-    context_uri: Option<String>,
-    context_type: Option<String>,
-) -> Result<String, String> {
-    manager
-        .record_play(&track_id, context_uri, context_type)
-        .await
-}
-
 #[command]
 pub async fn update_play_completion(
     manager: State<'_, PlayHistoryManager>,
@@ -49,3 +43,96 @@ pub async fn get_play_count(
 ) -> Result<i64, String> {
     manager.get_play_count(&track_id).await
 }
+
+#[command]
+pub async fn get_recent_contexts(
+    manager: State<'_, PlayHistoryManager>,
+    limit: Option<i64>,
+) -> Result<Vec<RecentContext>, String> {
+    manager.get_recent_contexts(limit.unwrap_or(10)).await
+}
+
+#[command]
+pub async fn get_play_thresholds(db: State<'_, DatabaseManager>) -> Result<PlayThresholds, String> {
+    Ok(load_thresholds(&db).await)
+}
+
+/// Applies to plays that end from now on
+#[command]
+pub async fn set_play_thresholds(
+    db: State<'_, DatabaseManager>,
+    thresholds: PlayThresholds,
+) -> Result<(), String> {
+    if thresholds.complete_percent > 100 {
+        return Err("Completion share must be between 0 and 100%".to_string());
+    }
+    let value = serde_json::to_string(&thresholds).map_err(|e| e.to_string())?;
+    db.set_setting(THRESHOLDS_SETTING, &value).await
+}
+
+#[command]
+pub async fn get_listening_summary(
+    manager: State<'_, PlayHistoryManager>,
+    range: StatsRange,
+) -> Result<ListeningSummary, String> {
+    manager.get_listening_summary(range).await
+}
+
+#[command]
+pub async fn get_daily_listening(
+    manager: State<'_, PlayHistoryManager>,
+    range: StatsRange,
+) -> Result<Vec<DailyListening>, String> {
+    manager.get_daily_listening(range).await
+}
+
+#[command]
+pub async fn get_listening_heatmap(
+    manager: State<'_, PlayHistoryManager>,
+    range: StatsRange,
+) -> Result<Vec<HeatmapCell>, String> {
+    manager.get_listening_heatmap(range).await
+}
+
+#[command]
+pub async fn get_top_in_range(
+    manager: State<'_, PlayHistoryManager>,
+    range: StatsRange,
+    kind: StatsKind,
+    limit: Option<i64>,
+) -> Result<Vec<StatsEntry>, String> {
+    manager
+        .get_top_in_range(range, kind, limit.unwrap_or(10))
+        .await
+}
+
+/// Streaks over the whole history unless a range is given
+#[command]
+pub async fn get_listening_streaks(
+    manager: State<'_, PlayHistoryManager>,
+    range: Option<StatsRange>,
+) -> Result<ListeningStreaks, String> {
+    let range = range.unwrap_or(StatsRange::Custom {
+        from: 0,
+        to: i64::MAX,
+    });
+    manager.get_listening_streaks(range).await
+}
+
+#[command]
+pub async fn get_artist_discovery(
+    manager: State<'_, PlayHistoryManager>,
+    range: StatsRange,
+) -> Result<ArtistDiscovery, String> {
+    manager.get_artist_discovery(range).await
+}
+
+#[command]
+pub async fn get_year_in_review(
+    manager: State<'_, PlayHistoryManager>,
+    year: Option<i32>,
+) -> Result<YearInReview, String> {
+    manager
+        .get_year_in_review(year.unwrap_or_else(current_year))
+        .await
+}
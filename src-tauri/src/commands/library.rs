@@ -1,8 +1,18 @@
-use crate::library::models::UnifiedTrack;
+use crate::download::DownloadManager;
+use crate::library::backup::{Backup, BackupImportReport};
+use crate::library::models::{
+    BrowseFilter, BrowseGroup, FolderListing, IntegrityReport, Page, RatingSyncReport,
+    UnifiedTrack, WorkSource,
+};
+use crate::library::scanner::{LibraryFolder, LibraryScanner, ScanSummary};
+use crate::library::tag_editor::{TagEdit, TagEditResult, TrackTags};
+use crate::library::watcher::LibraryWatcher;
 use crate::library::LibraryManager;
+use crate::providers::ProviderManager;
 use crate::tidal::models::Track as TidalTrack;
 use sqlx::Acquire;
-use tauri::{command, State};
+use std::sync::Arc;
+use tauri::{command, AppHandle, State};
 
 #[command]
 pub async fn get_library_tracks(
@@ -59,20 +69,38 @@ pub async fn rebuild_search_index(library: State<'_, LibraryManager>) -> Result<
 }
 
 #[command]
-pub async fn factory_reset(library: State<'_, LibraryManager>) -> Result<(), String> {
+pub async fn factory_reset(
+    library: State<'_, LibraryManager>,
+    scanner: State<'_, LibraryScanner>,
+    watcher: State<'_, LibraryWatcher>,
+) -> Result<(), String> {
     // We delegate the reset logic to the LibraryManager which owns the DB pool
     let pool = &library.pool;
 
+    // The folders are forgotten below, so stop following them on disk
+    for folder in scanner.get_folders().await? {
+        let _ = watcher.unwatch(&folder.path);
+    }
+
     let tables = [
         "tracks",
         "albums",
         "artists",
         "playlists",
         "playlist_tracks",
+        "playlist_folders",
+        "playlist_links",
         "play_history",
         "user_favorites",
+        "user_favorite_items",
         "lyrics_cache",
         "provider_configs",
+        "library_folders",
+        "track_artists",
+        "track_genres",
+        "works",
+        "favorite_item_sync",
+        "search_index",
     ];
 
     // Acquire a connection from the pool to ensure we stay on the same connection
@@ -121,6 +149,36 @@ pub async fn factory_reset(library: State<'_, LibraryManager>) -> Result<(), Str
     Ok(())
 }
 
+/// Write playlists, likes, ratings, history, provider configs and settings to
+/// a zip archive at `path`
+#[command]
+pub async fn export_library_backup(
+    library: State<'_, LibraryManager>,
+    path: String,
+    include_secrets: bool,
+) -> Result<(), String> {
+    let archive = library.export_backup(include_secrets).await?.to_archive()?;
+    tauri::async_runtime::spawn_blocking(move || std::fs::write(&path, archive))
+        .await
+        .map_err(|e| e.to_string())?
+        .map_err(|e| format!("Failed to write backup: {}", e))
+}
+
+/// Merge a backup archive into the current library
+#[command]
+pub async fn import_library_backup(
+    library: State<'_, LibraryManager>,
+    path: String,
+) -> Result<BackupImportReport, String> {
+    let archive = tauri::async_runtime::spawn_blocking(move || std::fs::read(&path))
+        .await
+        .map_err(|e| e.to_string())?
+        .map_err(|e| format!("Failed to read backup: {}", e))?;
+    let backup =
+        Backup::from_archive(&archive).map_err(|e| format!("Invalid backup file: {}", e))?;
+    library.import_backup(&backup).await
+}
+
 #[command]
 pub async fn library_has_data(library: State<'_, LibraryManager>) -> Result<bool, String> {
     let pool = &library.pool;
@@ -148,3 +206,231 @@ pub async fn library_has_data(library: State<'_, LibraryManager>) -> Result<bool
 
     Ok(false)
 }
+
+#[command]
+pub async fn get_library_folders(
+    scanner: State<'_, LibraryScanner>,
+) -> Result<Vec<LibraryFolder>, String> {
+    scanner.get_folders().await
+}
+
+/// Playable tracks of one watched folder, e.g. once its scan has completed
+#[command]
+pub async fn get_library_folder_tracks(
+    scanner: State<'_, LibraryScanner>,
+    folder_id: String,
+) -> Result<Vec<crate::queue::Track>, String> {
+    let folder = scanner
+        .get_folders()
+        .await?
+        .into_iter()
+        .find(|f| f.id == folder_id)
+        .ok_or_else(|| format!("Library folder not found: {}", folder_id))?;
+    scanner.get_folder_tracks(&folder.path).await
+}
+
+#[command]
+pub async fn add_library_folder(
+    app: AppHandle,
+    scanner: State<'_, LibraryScanner>,
+    watcher: State<'_, LibraryWatcher>,
+    path: String,
+) -> Result<ScanSummary, String> {
+    let folder = scanner.add_folder(&path).await?;
+    if let Err(e) = watcher.watch(&folder.path) {
+        log::warn!("Could not watch {}: {}", folder.path, e);
+    }
+    scanner.scan_folder(&app, &folder).await
+}
+
+#[command]
+pub async fn remove_library_folder(
+    scanner: State<'_, LibraryScanner>,
+    watcher: State<'_, LibraryWatcher>,
+    folder_id: String,
+    delete_tracks: Option<bool>,
+) -> Result<usize, String> {
+    if let Some(folder) = scanner
+        .get_folders()
+        .await?
+        .into_iter()
+        .find(|f| f.id == folder_id)
+    {
+        let _ = watcher.unwatch(&folder.path);
+    }
+    scanner
+        .remove_folder(&folder_id, delete_tracks.unwrap_or(false))
+        .await
+}
+
+#[command]
+pub async fn rescan_library(
+    app: AppHandle,
+    scanner: State<'_, LibraryScanner>,
+    folder_id: Option<String>,
+) -> Result<Vec<ScanSummary>, String> {
+    match folder_id {
+        Some(id) => {
+            let folder = scanner
+                .get_folders()
+                .await?
+                .into_iter()
+                .find(|f| f.id == id)
+                .ok_or_else(|| format!("Library folder not found: {}", id))?;
+            Ok(vec![scanner.scan_folder(&app, &folder).await?])
+        }
+        None => scanner.scan_all(&app).await,
+    }
+}
+
+#[command]
+pub async fn get_track_tags(
+    scanner: State<'_, LibraryScanner>,
+    track_id: String,
+) -> Result<TrackTags, String> {
+    let path = scanner
+        .get_track_file_path(&track_id)
+        .await?
+        .ok_or_else(|| format!("Track {} has no local file", track_id))?;
+    crate::library::tag_editor::read_tags(std::path::Path::new(&path))
+}
+
+#[command]
+pub async fn edit_track_tags(
+    scanner: State<'_, LibraryScanner>,
+    track_ids: Vec<String>,
+    edit: TagEdit,
+) -> Result<TagEditResult, String> {
+    scanner.edit_tracks(&track_ids, &edit).await
+}
+
+#[command]
+pub async fn browse_genres(
+    library: State<'_, LibraryManager>,
+    offset: i64,
+    limit: i64,
+) -> Result<Page<BrowseGroup>, String> {
+    library.browse_genres(offset, limit).await
+}
+
+#[command]
+pub async fn browse_decades(
+    library: State<'_, LibraryManager>,
+    offset: i64,
+    limit: i64,
+) -> Result<Page<BrowseGroup>, String> {
+    library.browse_decades(offset, limit).await
+}
+
+#[command]
+pub async fn browse_years(
+    library: State<'_, LibraryManager>,
+    decade: u32,
+    offset: i64,
+    limit: i64,
+) -> Result<Page<BrowseGroup>, String> {
+    library.browse_years(decade, offset, limit).await
+}
+
+#[command]
+pub async fn browse_providers(
+    library: State<'_, LibraryManager>,
+    offset: i64,
+    limit: i64,
+) -> Result<Page<BrowseGroup>, String> {
+    library.browse_providers(offset, limit).await
+}
+
+#[command]
+pub async fn browse_folder(
+    library: State<'_, LibraryManager>,
+    path: Option<String>,
+    offset: i64,
+    limit: i64,
+) -> Result<FolderListing, String> {
+    library.browse_folder(path.as_deref(), offset, limit).await
+}
+
+#[command]
+pub async fn browse_tracks(
+    library: State<'_, LibraryManager>,
+    filter: BrowseFilter,
+    offset: i64,
+    limit: i64,
+) -> Result<Page<UnifiedTrack>, String> {
+    library.browse_tracks(&filter, offset, limit).await
+}
+
+#[command]
+pub async fn get_work_sources(
+    library: State<'_, LibraryManager>,
+    track_id: String,
+) -> Result<Vec<WorkSource>, String> {
+    library.get_work_sources(&track_id).await
+}
+
+#[command]
+pub async fn merge_works(
+    library: State<'_, LibraryManager>,
+    track_ids: Vec<String>,
+) -> Result<String, String> {
+    library.merge_works(&track_ids).await
+}
+
+#[command]
+pub async fn split_track_work(
+    library: State<'_, LibraryManager>,
+    track_id: String,
+) -> Result<String, String> {
+    library.split_track_work(&track_id).await
+}
+
+#[command]
+pub async fn check_library_integrity(
+    library: State<'_, LibraryManager>,
+    downloads: State<'_, DownloadManager>,
+) -> Result<IntegrityReport, String> {
+    library
+        .check_integrity(&[downloads.get_download_path()], true)
+        .await
+}
+
+#[command]
+pub async fn relink_track(
+    library: State<'_, LibraryManager>,
+    track_id: String,
+    new_path: String,
+) -> Result<(), String> {
+    library.relink_track(&track_id, &new_path).await
+}
+
+#[command]
+pub async fn remove_missing_tracks(
+    library: State<'_, LibraryManager>,
+    track_ids: Vec<String>,
+) -> Result<usize, String> {
+    library.remove_missing_tracks(&track_ids).await
+}
+
+/// `rating` is in half-stars, 0 to 10; 0 or null clears it
+#[command]
+pub async fn set_track_rating(
+    library: State<'_, LibraryManager>,
+    provider_manager: State<'_, Arc<ProviderManager>>,
+    track_id: String,
+    rating: Option<u8>,
+) -> Result<(), String> {
+    let providers = provider_manager.get_all_providers().await;
+    library
+        .set_track_rating(&track_id, rating, &providers)
+        .await
+}
+
+#[command]
+pub async fn sync_track_ratings(
+    library: State<'_, LibraryManager>,
+    provider_manager: State<'_, Arc<ProviderManager>>,
+) -> Result<RatingSyncReport, String> {
+    let providers = provider_manager.get_all_providers().await;
+    library.sync_ratings(&providers).await
+}
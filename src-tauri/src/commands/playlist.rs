@@ -1,8 +1,19 @@
 use crate::library::models::UnifiedTrack;
+use crate::library::scanner::LibraryScanner;
 use crate::library::LibraryManager;
+use crate::models::Playlist as RemotePlaylist;
+use crate::playlist::files::{read_playlist_file, PlaylistFormat};
 use crate::playlist::manager::PlaylistManager;
-use crate::playlist::models::{Playlist, PlaylistDetails};
+use crate::playlist::models::{
+    Playlist, PlaylistDetails, PlaylistExportReport, PlaylistFolder, PlaylistImportReport,
+    PlaylistSyncReport, PlaylistTree,
+};
+use crate::playlist::smart::{SmartRules, SmartSort, SortField};
+use crate::providers::ProviderManager;
 use crate::tidal::models::Track as TidalTrack;
+use crate::tidal::TidalClient;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
 use tauri::{command, State};
 
 #[command]
@@ -19,6 +30,43 @@ pub async fn create_playlist(
     manager.create_playlist(name, description).await
 }
 
+#[command]
+pub async fn create_smart_playlist(
+    manager: State<'_, PlaylistManager>,
+    name: String,
+    description: Option<String>,
+    rules: SmartRules,
+) -> Result<Playlist, String> {
+    manager
+        .create_smart_playlist(name, description, &rules)
+        .await
+}
+
+#[command]
+pub async fn update_smart_playlist_rules(
+    manager: State<'_, PlaylistManager>,
+    id: String,
+    rules: SmartRules,
+) -> Result<(), String> {
+    manager.update_smart_rules(&id, &rules).await
+}
+
+#[command]
+pub async fn preview_smart_playlist(
+    manager: State<'_, PlaylistManager>,
+    rules: SmartRules,
+) -> Result<Vec<UnifiedTrack>, String> {
+    manager.evaluate_smart_rules(&rules).await
+}
+
+#[command]
+pub async fn convert_smart_playlist(
+    manager: State<'_, PlaylistManager>,
+    id: String,
+) -> Result<(), String> {
+    manager.convert_to_static(&id).await
+}
+
 #[command]
 pub async fn delete_playlist(
     manager: State<'_, PlaylistManager>,
@@ -36,6 +84,17 @@ pub async fn rename_playlist(
     manager.rename_playlist(&id, &new_name).await
 }
 
+/// Use an image (data URL, file path or web URL) as the cover, or clear it
+/// to show the generated mosaic again
+#[command]
+pub async fn set_playlist_cover(
+    manager: State<'_, PlaylistManager>,
+    playlist_id: String,
+    cover: Option<String>,
+) -> Result<(), String> {
+    manager.set_playlist_cover(&playlist_id, cover).await
+}
+
 #[command]
 pub async fn get_playlist_details(
     manager: State<'_, PlaylistManager>,
@@ -75,6 +134,67 @@ pub async fn remove_from_playlist(
     manager.remove_track_entry(&playlist_id, &track_id).await
 }
 
+/// Insert a library track before `index`, appending when it is omitted.
+/// Returns the new entry id.
+#[command]
+pub async fn insert_into_playlist(
+    manager: State<'_, PlaylistManager>,
+    playlist_id: String,
+    track_id: String,
+    index: Option<usize>,
+) -> Result<String, String> {
+    manager
+        .insert_track_at(&playlist_id, &track_id, index)
+        .await
+}
+
+#[command]
+pub async fn remove_playlist_entry(
+    manager: State<'_, PlaylistManager>,
+    playlist_id: String,
+    entry_id: String,
+) -> Result<(), String> {
+    manager.remove_entry(&playlist_id, &entry_id).await
+}
+
+#[command]
+pub async fn move_playlist_entry(
+    manager: State<'_, PlaylistManager>,
+    playlist_id: String,
+    entry_id: String,
+    index: usize,
+) -> Result<(), String> {
+    manager.move_entry(&playlist_id, &entry_id, index).await
+}
+
+/// Returns how many repeated entries were dropped when forbidding duplicates
+#[command]
+pub async fn set_playlist_allow_duplicates(
+    manager: State<'_, PlaylistManager>,
+    playlist_id: String,
+    allow: bool,
+) -> Result<usize, String> {
+    manager.set_allow_duplicates(&playlist_id, allow).await
+}
+
+#[command]
+pub async fn sort_playlist(
+    manager: State<'_, PlaylistManager>,
+    playlist_id: String,
+    field: SortField,
+    descending: bool,
+) -> Result<(), String> {
+    manager.sort_entries(&playlist_id, field, descending).await
+}
+
+#[command]
+pub async fn compact_playlist(
+    manager: State<'_, PlaylistManager>,
+    playlist_id: String,
+) -> Result<(), String> {
+    manager.compact_positions(&playlist_id).await
+}
+
 #[command]
 pub async fn add_to_playlist(
     library: State<'_, LibraryManager>,
@@ -186,3 +306,212 @@ pub async fn get_playlists_containing_track(
 ) -> Result<Vec<String>, String> {
     manager.get_playlists_containing_track(&track_id).await
 }
+
+/// Create a playlist from an M3U/M3U8, PLS or XSPF file. `title` defaults to
+/// the file name.
+#[command]
+pub async fn import_playlist_file(
+    manager: State<'_, PlaylistManager>,
+    scanner: State<'_, LibraryScanner>,
+    path: String,
+    title: Option<String>,
+) -> Result<PlaylistImportReport, String> {
+    let path = Path::new(&path);
+    let entries = read_playlist_file(path)?;
+
+    // Files the library has not seen yet are scanned in so they can be added
+    let files: Vec<PathBuf> = entries
+        .iter()
+        .filter_map(|e| e.path.clone())
+        .filter(|p| p.is_file())
+        .collect();
+    if !files.is_empty() {
+        scanner.scan_paths(files).await?;
+    }
+
+    let title = title.unwrap_or_else(|| {
+        path.file_stem()
+            .map(|s| s.to_string_lossy().into_owned())
+            .unwrap_or_else(|| "Imported Playlist".to_string())
+    });
+    manager.import_entries(title, &entries).await
+}
+
+/// Write a playlist to `path`, in `format` or else the one its extension names
+#[command]
+pub async fn export_playlist_file(
+    manager: State<'_, PlaylistManager>,
+    playlist_id: String,
+    path: String,
+    format: Option<PlaylistFormat>,
+    relative: bool,
+) -> Result<PlaylistExportReport, String> {
+    let path = Path::new(&path);
+    let format = format
+        .or_else(|| PlaylistFormat::from_path(path))
+        .ok_or("Unknown playlist format")?;
+    manager
+        .export_playlist_file(&playlist_id, path, format, relative)
+        .await
+}
+
+#[command]
+pub async fn get_playlist_tree(
+    manager: State<'_, PlaylistManager>,
+) -> Result<PlaylistTree, String> {
+    manager.get_playlist_tree().await
+}
+
+#[command]
+pub async fn create_playlist_folder(
+    manager: State<'_, PlaylistManager>,
+    name: String,
+    parent_id: Option<String>,
+) -> Result<PlaylistFolder, String> {
+    manager.create_folder(name, parent_id).await
+}
+
+#[command]
+pub async fn rename_playlist_folder(
+    manager: State<'_, PlaylistManager>,
+    id: String,
+    name: String,
+) -> Result<(), String> {
+    manager.rename_folder(&id, &name).await
+}
+
+/// Subfolders and playlists of the deleted folder move up to its parent
+#[command]
+pub async fn delete_playlist_folder(
+    manager: State<'_, PlaylistManager>,
+    id: String,
+) -> Result<(), String> {
+    manager.delete_folder(&id).await
+}
+
+#[command]
+pub async fn move_playlist_folder(
+    manager: State<'_, PlaylistManager>,
+    id: String,
+    parent_id: Option<String>,
+    index: Option<usize>,
+) -> Result<(), String> {
+    manager.move_folder(&id, parent_id, index).await
+}
+
+#[command]
+pub async fn move_playlist(
+    manager: State<'_, PlaylistManager>,
+    playlist_id: String,
+    folder_id: Option<String>,
+    index: Option<usize>,
+) -> Result<(), String> {
+    manager.move_playlist(&playlist_id, folder_id, index).await
+}
+
+#[command]
+pub async fn set_playlist_pinned(
+    manager: State<'_, PlaylistManager>,
+    playlist_id: String,
+    pinned: bool,
+) -> Result<(), String> {
+    manager.set_playlist_pinned(&playlist_id, pinned).await
+}
+
+#[command]
+pub async fn set_playlist_sort(
+    manager: State<'_, PlaylistManager>,
+    playlist_id: String,
+    sort: Option<SmartSort>,
+) -> Result<(), String> {
+    manager.set_playlist_sort(&playlist_id, sort).await
+}
+
+/// Playlists stored on a Subsonic or Jellyfin server
+#[command]
+pub async fn list_remote_playlists(
+    provider_manager: State<'_, Arc<ProviderManager>>,
+    provider_id: String,
+) -> Result<Vec<RemotePlaylist>, String> {
+    let provider = provider_manager
+        .get_provider(&provider_id)
+        .await
+        .ok_or_else(|| format!("Provider {} not found", provider_id))?;
+    provider.get_playlists().await.map_err(|e| e.to_string())
+}
+
+#[command]
+pub async fn import_remote_playlist(
+    manager: State<'_, PlaylistManager>,
+    library: State<'_, LibraryManager>,
+    provider_manager: State<'_, Arc<ProviderManager>>,
+    provider_id: String,
+    remote_id: String,
+) -> Result<Playlist, String> {
+    let provider = provider_manager
+        .get_provider(&provider_id)
+        .await
+        .ok_or_else(|| format!("Provider {} not found", provider_id))?;
+    manager
+        .import_remote_playlist(&library, provider.as_ref(), &remote_id)
+        .await
+}
+
+/// Create a server copy of a local playlist and keep the two in sync
+#[command]
+pub async fn publish_playlist(
+    manager: State<'_, PlaylistManager>,
+    provider_manager: State<'_, Arc<ProviderManager>>,
+    playlist_id: String,
+    provider_id: String,
+) -> Result<(), String> {
+    let provider = provider_manager
+        .get_provider(&provider_id)
+        .await
+        .ok_or_else(|| format!("Provider {} not found", provider_id))?;
+    manager
+        .publish_playlist(&playlist_id, provider.as_ref())
+        .await
+}
+
+#[command]
+pub async fn unlink_playlist(
+    manager: State<'_, PlaylistManager>,
+    playlist_id: String,
+) -> Result<(), String> {
+    manager.unlink_playlist(&playlist_id).await
+}
+
+#[command]
+pub async fn sync_remote_playlists(
+    manager: State<'_, PlaylistManager>,
+    library: State<'_, LibraryManager>,
+    provider_manager: State<'_, Arc<ProviderManager>>,
+) -> Result<PlaylistSyncReport, String> {
+    let providers = provider_manager.get_all_providers().await;
+    manager.sync_remote_playlists(&library, &providers).await
+}
+
+/// Create a playlist from a Tidal playlist or album link
+#[command]
+pub async fn import_tidal_playlist(
+    manager: State<'_, PlaylistManager>,
+    library: State<'_, LibraryManager>,
+    client: State<'_, TidalClient>,
+    url: String,
+) -> Result<Playlist, String> {
+    manager.import_tidal_source(&library, &client, &url).await
+}
+
+/// Pick up tracks added to a Tidal source since it was imported
+#[command]
+pub async fn resync_tidal_playlist(
+    manager: State<'_, PlaylistManager>,
+    library: State<'_, LibraryManager>,
+    client: State<'_, TidalClient>,
+    playlist_id: String,
+) -> Result<usize, String> {
+    manager
+        .resync_tidal_playlist(&library, &client, &playlist_id)
+        .await
+}
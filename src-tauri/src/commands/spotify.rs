@@ -1,4 +1,5 @@
 use crate::database::DatabaseManager;
+use crate::library::models::TrackDetails;
 use crate::providers::manager::ProviderManagerArc;
 use crate::spotify::{
     models::{SpotifyPlaylistResult, VerificationProgress, VerifiedSpotifyTrack},
@@ -371,6 +372,7 @@ pub async fn add_spotify_tracks_to_playlist(
                 name: track.spotify.artist.clone(),
                 picture: None,
                 banner: None,
+                artist_type: None,
             }),
             album: track.tidal_album_id.map(|id| crate::tidal::Album {
                 id,
@@ -388,6 +390,9 @@ pub async fn add_spotify_tracks_to_playlist(
             audio_quality: None,
             cover: None,
             track_number: None,
+            volume_number: None,
+            isrc: None,
+            artists: None,
         };
 
         if let Err(e) = library
@@ -511,6 +516,10 @@ async fn add_spotify_tracks_internal(
                 album_id.as_deref(),
                 Some(track.spotify.duration_ms / 1000),
                 track.cover_url.clone(),
+                &TrackDetails {
+                    isrc: Some(track.spotify.isrc.clone()).filter(|s| !s.is_empty()),
+                    ..Default::default()
+                },
             )
             .await
         {
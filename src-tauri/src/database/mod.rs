@@ -36,6 +36,30 @@ impl DatabaseManager {
         Ok(Self { pool })
     }
 
+    /// Fresh, fully migrated in-memory database for tests
+    #[cfg(test)]
+    pub async fn in_memory() -> Self {
+        // A single connection, as each one would get its own in-memory database
+        let pool = SqlitePoolOptions::new()
+            .max_connections(1)
+            .connect("sqlite::memory:")
+            .await
+            .expect("in-memory database");
+        Self::run_migrations(&pool).await.expect("migrations");
+        Self { pool }
+    }
+
+    /// Run fixture statements against a test database, in order
+    #[cfg(test)]
+    pub async fn seed(&self, statements: &[&str]) {
+        for sql in statements {
+            sqlx::query(sql)
+                .execute(&self.pool)
+                .await
+                .unwrap_or_else(|e| panic!("{}: {}", sql, e));
+        }
+    }
+
     async fn run_migrations(pool: &Pool<Sqlite>) -> Result<(), String> {
         // 1. Get current version
         let row: (i32,) = sqlx::query_as("PRAGMA user_version")
@@ -181,6 +205,221 @@ impl DatabaseManager {
             CREATE INDEX IF NOT EXISTS idx_recommendation_cache_expires
             ON recommendation_cache(expires_at);
             "#,
+            // Migration 10: Play context display names for "jump back in"
+            r#"
+            ALTER TABLE play_history ADD COLUMN context_name TEXT;
+
+            CREATE INDEX IF NOT EXISTS idx_play_history_context
+            ON play_history(context_type, context_uri, played_at DESC);
+            "#,
+            // Migration 11: Watched library folders for the local scanner
+            r#"
+            CREATE TABLE IF NOT EXISTS library_folders (
+                id TEXT PRIMARY KEY,
+                path TEXT NOT NULL UNIQUE,
+                added_at INTEGER NOT NULL,
+                last_scanned_at INTEGER
+            );
+
+            CREATE INDEX IF NOT EXISTS idx_tracks_file_path ON tracks(file_path);
+            "#,
+            // Migration 12: Richer track metadata and multi-artist credits
+            r#"
+            ALTER TABLE tracks ADD COLUMN track_number INTEGER;
+            ALTER TABLE tracks ADD COLUMN disc_number INTEGER;
+            ALTER TABLE tracks ADD COLUMN year INTEGER;
+            ALTER TABLE tracks ADD COLUMN genre TEXT;
+            ALTER TABLE tracks ADD COLUMN isrc TEXT;
+            ALTER TABLE tracks ADD COLUMN musicbrainz_id TEXT;
+
+            ALTER TABLE albums ADD COLUMN year INTEGER;
+            ALTER TABLE albums ADD COLUMN musicbrainz_id TEXT;
+
+            ALTER TABLE artists ADD COLUMN musicbrainz_id TEXT;
+
+            CREATE TABLE IF NOT EXISTS track_artists (
+                track_id TEXT NOT NULL,
+                artist_id TEXT NOT NULL,
+                role TEXT NOT NULL DEFAULT 'main', -- 'main', 'featured' or 'composer'
+                position INTEGER NOT NULL DEFAULT 0,
+                PRIMARY KEY(track_id, artist_id, role),
+                FOREIGN KEY(track_id) REFERENCES tracks(id) ON DELETE CASCADE,
+                FOREIGN KEY(artist_id) REFERENCES artists(id) ON DELETE CASCADE
+            );
+
+            INSERT OR IGNORE INTO track_artists (track_id, artist_id, role, position)
+            SELECT id, artist_id, 'main', 0 FROM tracks;
+
+            CREATE TABLE IF NOT EXISTS track_genres (
+                track_id TEXT NOT NULL,
+                genre TEXT NOT NULL,
+                PRIMARY KEY(track_id, genre),
+                FOREIGN KEY(track_id) REFERENCES tracks(id) ON DELETE CASCADE
+            );
+
+            CREATE INDEX IF NOT EXISTS idx_track_artists_artist ON track_artists(artist_id, role);
+            CREATE INDEX IF NOT EXISTS idx_track_genres_genre ON track_genres(genre);
+            CREATE INDEX IF NOT EXISTS idx_tracks_isrc ON tracks(isrc);
+            CREATE INDEX IF NOT EXISTS idx_tracks_album_order ON tracks(album_id, disc_number, track_number);
+            "#,
+            // Migration 13: Indexes for browsing by year and provider
+            r#"
+            CREATE INDEX IF NOT EXISTS idx_tracks_year ON tracks(year);
+            CREATE INDEX IF NOT EXISTS idx_tracks_provider ON tracks(provider_id);
+            "#,
+            // Migration 14: Canonical works linking the same recording across providers
+            r#"
+            CREATE TABLE IF NOT EXISTS works (
+                id TEXT PRIMARY KEY,
+                title_key TEXT NOT NULL,
+                artist_key TEXT NOT NULL,
+                duration INTEGER NOT NULL DEFAULT 0,
+                created_at INTEGER DEFAULT (strftime('%s', 'now'))
+            );
+
+            ALTER TABLE tracks ADD COLUMN work_id TEXT REFERENCES works(id) ON DELETE SET NULL;
+            -- Set when the user merged or split a work by hand, so relinking leaves it alone
+            ALTER TABLE tracks ADD COLUMN work_pinned INTEGER DEFAULT 0;
+            ALTER TABLE tracks ADD COLUMN acoustid TEXT;
+
+            CREATE INDEX IF NOT EXISTS idx_works_key ON works(artist_key, title_key);
+            CREATE INDEX IF NOT EXISTS idx_tracks_work ON tracks(work_id);
+            CREATE INDEX IF NOT EXISTS idx_tracks_acoustid ON tracks(acoustid);
+            "#,
+            // Migration 15: Rule-based smart playlists
+            r#"
+            ALTER TABLE playlists ADD COLUMN smart_rules TEXT;
+            "#,
+            // Migration 16: Index vocabulary for typo-tolerant search
+            r#"
+            CREATE VIRTUAL TABLE IF NOT EXISTS search_vocab USING fts5vocab(search_index, 'row');
+            "#,
+            // Migration 17: Romanized and script-folded aliases in the search index.
+            // The index is left empty and refilled by the next search.
+            r#"
+            DROP TABLE IF EXISTS search_vocab;
+            DROP TABLE IF EXISTS search_index;
+
+            CREATE VIRTUAL TABLE search_index USING fts5(
+                track_id UNINDEXED,
+                title,
+                artist,
+                album,
+                title_alt,
+                artist_alt,
+                album_alt,
+                tokenize='porter'
+            );
+
+            CREATE VIRTUAL TABLE search_vocab USING fts5vocab(search_index, 'row');
+            "#,
+            // Migration 18: Keep tracks whose files went missing, for relinking
+            r#"
+            ALTER TABLE tracks ADD COLUMN missing_since INTEGER;
+            ALTER TABLE tracks ADD COLUMN file_size INTEGER;
+
+            CREATE INDEX IF NOT EXISTS idx_tracks_missing ON tracks(missing_since);
+            "#,
+            // Migration 19: Half-star ratings, with the last value agreed with
+            // the file tag or server so both sides' changes can be told apart
+            r#"
+            ALTER TABLE tracks ADD COLUMN rating INTEGER;
+            ALTER TABLE tracks ADD COLUMN rating_updated_at INTEGER;
+            ALTER TABLE tracks ADD COLUMN rating_synced INTEGER;
+            ALTER TABLE tracks ADD COLUMN rating_synced_at INTEGER;
+
+            CREATE INDEX IF NOT EXISTS idx_tracks_rating ON tracks(rating);
+            "#,
+            // Migration 20: Per-playlist duplicate policy
+            r#"
+            ALTER TABLE playlists ADD COLUMN allow_duplicates INTEGER NOT NULL DEFAULT 1;
+            "#,
+            // Migration 21: Playlist folders, manual ordering, pinning and a
+            // remembered track sort per playlist
+            r#"
+            CREATE TABLE IF NOT EXISTS playlist_folders (
+                id TEXT PRIMARY KEY,
+                name TEXT NOT NULL,
+                parent_id TEXT REFERENCES playlist_folders(id) ON DELETE CASCADE,
+                position INTEGER NOT NULL DEFAULT 0,
+                created_at DATETIME DEFAULT CURRENT_TIMESTAMP
+            );
+
+            ALTER TABLE playlists ADD COLUMN folder_id TEXT REFERENCES playlist_folders(id) ON DELETE SET NULL;
+            ALTER TABLE playlists ADD COLUMN position INTEGER NOT NULL DEFAULT 0;
+            ALTER TABLE playlists ADD COLUMN pinned_at INTEGER;
+            ALTER TABLE playlists ADD COLUMN sort_order TEXT;
+
+            CREATE INDEX IF NOT EXISTS idx_playlist_folders_parent ON playlist_folders(parent_id, position);
+            CREATE INDEX IF NOT EXISTS idx_playlists_folder ON playlists(folder_id, position);
+            "#,
+            // Migration 22: Playlists linked to a server playlist. The snapshot
+            // holds the server track ids agreed at the last sync, so edits on
+            // either side can be told apart.
+            r#"
+            CREATE TABLE IF NOT EXISTS playlist_links (
+                playlist_id TEXT PRIMARY KEY REFERENCES playlists(id) ON DELETE CASCADE,
+                provider_id TEXT NOT NULL,
+                remote_id TEXT NOT NULL,
+                snapshot TEXT NOT NULL,
+                remote_changed TEXT,
+                synced_at INTEGER NOT NULL,
+                UNIQUE(provider_id, remote_id)
+            );
+            "#,
+            // Migration 23: Generated cover mosaics. The key lists the album
+            // covers a mosaic was made from, so it is only redrawn when they change.
+            r#"
+            ALTER TABLE playlists ADD COLUMN mosaic_url TEXT;
+            ALTER TABLE playlists ADD COLUMN mosaic_key TEXT;
+            "#,
+            // Migration 24: Liked albums and artists. Provider-backed items are
+            // keyed by provider/external id so they can be liked without being
+            // in the library; local ones use provider 'local' and their
+            // lowercased name (album artist, then title, for albums), since
+            // library ids change on rescans.
+            r#"
+            CREATE TABLE IF NOT EXISTS user_favorite_items (
+                id TEXT PRIMARY KEY,
+                kind TEXT NOT NULL,
+                provider_id TEXT NOT NULL,
+                external_id TEXT NOT NULL,
+                title TEXT NOT NULL,
+                artist TEXT,
+                cover_url TEXT,
+                liked_at INTEGER NOT NULL,
+                UNIQUE(kind, provider_id, external_id)
+            );
+            CREATE INDEX IF NOT EXISTS idx_favorite_items_liked_at ON user_favorite_items(kind, liked_at DESC);
+            "#,
+            // Migration 25: Likes synced with servers. Like ratings, the server's
+            // state at the last sync tells which side changed; the update time
+            // survives an unlike, which removes the favorite row.
+            r#"
+            ALTER TABLE tracks ADD COLUMN like_updated_at INTEGER;
+            ALTER TABLE tracks ADD COLUMN like_synced INTEGER;
+            ALTER TABLE tracks ADD COLUMN like_synced_at INTEGER;
+            "#,
+            // Migration 26: Plays recorded by the player. Skips are kept in the
+            // history for statistics but don't count as plays. A play stays
+            // in progress until the player reports how it ended.
+            r#"
+            ALTER TABLE play_history ADD COLUMN skipped INTEGER DEFAULT 0;
+            ALTER TABLE play_history ADD COLUMN in_progress INTEGER NOT NULL DEFAULT 0;
+            "#,
+            // Migration 27: Sync state of album and artist likes. Kept apart
+            // from the likes themselves so an unlike still records when it happened.
+            r#"
+            CREATE TABLE IF NOT EXISTS favorite_item_sync (
+                kind TEXT NOT NULL,
+                provider_id TEXT NOT NULL,
+                external_id TEXT NOT NULL,
+                updated_at INTEGER,
+                synced INTEGER,
+                synced_at INTEGER,
+                PRIMARY KEY(kind, provider_id, external_id)
+            );
+            "#,
         ];
 
         // 3. Apply Migrations
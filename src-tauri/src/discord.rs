@@ -13,6 +13,8 @@
 //! Note: Discord IPC is not available on Android, so this module provides
 //! no-op stubs on that platform.
 
+#[cfg(not(target_os = "android"))]
+use crate::artwork::ArtworkCache;
 #[cfg(not(target_os = "android"))]
 use discord_rich_presence::{activity, DiscordIpc, DiscordIpcClient};
 use parking_lot::RwLock;
@@ -433,6 +435,8 @@ fn update_playing_activity(
     let details = &track.title;
     let state_text = &track.artist;
 
+    let cover = track.cover_url.as_deref().and_then(presence_image);
+
     let mut activity_builder = activity::Activity::new()
         .activity_type(activity::ActivityType::Listening)
         .details(details)
@@ -448,17 +452,14 @@ fn update_playing_activity(
                 .large_text(&track.album),
         );
 
-    if let Some(ref cover_url) = track.cover_url {
-        // Only use cover URL if it's not a data URL (Discord doesn't support those)
-        if !cover_url.starts_with("data:") {
-            activity_builder = activity_builder.assets(
-                activity::Assets::new()
-                    .large_image(cover_url)
-                    .large_text(&track.album)
-                    .small_image("sonami_logo")
-                    .small_text("Sonami"),
-            );
-        }
+    if let Some(ref cover_url) = cover {
+        activity_builder = activity_builder.assets(
+            activity::Assets::new()
+                .large_image(cover_url)
+                .large_text(&track.album)
+                .small_image("sonami_logo")
+                .small_text("Sonami"),
+        );
     }
 
     log::debug!(
@@ -473,6 +474,19 @@ fn update_playing_activity(
     Ok(())
 }
 
+/// Cover art Discord can show. Discord fetches images itself, so only public
+/// URLs work; cached covers are mapped back to where they were downloaded from.
+#[cfg(not(target_os = "android"))]
+fn presence_image(cover_url: &str) -> Option<String> {
+    if ArtworkCache::hash_from_url(cover_url).is_some() {
+        ArtworkCache::new().remote_origin(cover_url)
+    } else if cover_url.starts_with("http://") || cover_url.starts_with("https://") {
+        Some(cover_url.to_string())
+    } else {
+        None
+    }
+}
+
 /// Update Discord activity for paused state
 #[cfg(not(target_os = "android"))]
 fn update_paused_activity(
@@ -499,6 +513,8 @@ fn update_paused_activity(
         secs
     );
 
+    let cover = track.cover_url.as_deref().and_then(presence_image);
+
     let mut activity_builder = activity::Activity::new()
         .activity_type(activity::ActivityType::Listening)
         .details(details)
@@ -509,16 +525,14 @@ fn update_paused_activity(
                 .large_text(&position_text),
         );
 
-    if let Some(ref cover_url) = track.cover_url {
-        if !cover_url.starts_with("data:") {
-            activity_builder = activity_builder.assets(
-                activity::Assets::new()
-                    .large_image(cover_url)
-                    .large_text(&position_text)
-                    .small_image("sonami_logo")
-                    .small_text("Sonami"),
-            );
-        }
+    if let Some(ref cover_url) = cover {
+        activity_builder = activity_builder.assets(
+            activity::Assets::new()
+                .large_image(cover_url)
+                .large_text(&position_text)
+                .small_image("sonami_logo")
+                .small_text("Sonami"),
+        );
     }
 
     client.set_activity(activity_builder)?;
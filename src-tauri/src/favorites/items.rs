@@ -0,0 +1,379 @@
+use super::models::{FavoriteKind, FavoriteSort, LikedFromArtist};
+use super::FavoritesManager;
+use crate::library::models::{LibraryAlbum, LibraryArtist};
+use crate::providers::bare_id;
+use sqlx::{Sqlite, Transaction};
+use std::time::{SystemTime, UNIX_EPOCH};
+use uuid::Uuid;
+
+/// Provider id for likes on albums and artists without a provider
+const LOCAL: &str = "local";
+
+/// Library album matched by a like, for `albums` aliased `x`. Local likes
+/// hold the `local_key` of the album, which survives rescans.
+const MATCH_LIBRARY_ALBUM: &str = r#"
+    (f.provider_id = 'local'
+        AND lower(trim(COALESCE((SELECT name FROM artists WHERE id = x.artist_id), '')))
+            || char(31) || lower(trim(x.title)) = f.external_id)
+    OR (x.provider_id = f.provider_id
+        AND x.external_id IN (f.external_id, f.provider_id || ':' || f.external_id))
+"#;
+
+/// Library artist matched by a like, for `artists` aliased `x`
+const MATCH_LIBRARY_ARTIST: &str = r#"
+    (f.provider_id = 'local' AND lower(trim(x.name)) = f.external_id)
+    OR (x.provider_id = f.provider_id
+        AND x.external_id IN (f.external_id, f.provider_id || ':' || f.external_id))
+"#;
+
+impl FavoritesManager {
+    pub async fn like_album(&self, album: &LibraryAlbum) -> Result<(), String> {
+        let (provider_id, external_id) = favorite_key(
+            &album.id,
+            album.provider_id.as_deref(),
+            album.external_id.as_deref(),
+            &local_key(&album.title, Some(&album.artist)),
+        );
+        self.like_item(
+            FavoriteKind::Album,
+            &provider_id,
+            &external_id,
+            &album.title,
+            Some(&album.artist),
+            album.cover_image.as_deref(),
+        )
+        .await
+    }
+
+    pub async fn like_artist(&self, artist: &LibraryArtist) -> Result<(), String> {
+        let (provider_id, external_id) = favorite_key(
+            &artist.id,
+            artist.provider_id.as_deref(),
+            artist.external_id.as_deref(),
+            &local_key(&artist.name, None),
+        );
+        self.like_item(
+            FavoriteKind::Artist,
+            &provider_id,
+            &external_id,
+            &artist.name,
+            None,
+            artist.cover_image.as_deref(),
+        )
+        .await
+    }
+
+    pub async fn unlike_album(&self, album: &LibraryAlbum) -> Result<(), String> {
+        let (provider_id, external_id) = favorite_key(
+            &album.id,
+            album.provider_id.as_deref(),
+            album.external_id.as_deref(),
+            &local_key(&album.title, Some(&album.artist)),
+        );
+        self.unlike_item(FavoriteKind::Album, &provider_id, &external_id)
+            .await
+    }
+
+    pub async fn unlike_artist(&self, artist: &LibraryArtist) -> Result<(), String> {
+        let (provider_id, external_id) = favorite_key(
+            &artist.id,
+            artist.provider_id.as_deref(),
+            artist.external_id.as_deref(),
+            &local_key(&artist.name, None),
+        );
+        self.unlike_item(FavoriteKind::Artist, &provider_id, &external_id)
+            .await
+    }
+
+    /// Liking again keeps the original date but refreshes the stored details
+    async fn like_item(
+        &self,
+        kind: FavoriteKind,
+        provider_id: &str,
+        external_id: &str,
+        title: &str,
+        artist: Option<&str>,
+        cover_url: Option<&str>,
+    ) -> Result<(), String> {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs() as i64;
+
+        let mut tx = self.pool.begin().await.map_err(|e| e.to_string())?;
+        sqlx::query(
+            r#"
+            INSERT INTO user_favorite_items (id, kind, provider_id, external_id, title, artist, cover_url, liked_at)
+            VALUES (?, ?, ?, ?, ?, ?, ?, ?)
+            ON CONFLICT(kind, provider_id, external_id) DO UPDATE SET
+                title = excluded.title,
+                artist = COALESCE(excluded.artist, artist),
+                cover_url = COALESCE(excluded.cover_url, cover_url)
+            "#,
+        )
+        .bind(Uuid::new_v4().to_string())
+        .bind(kind.as_str())
+        .bind(provider_id)
+        .bind(external_id)
+        .bind(title)
+        .bind(artist)
+        .bind(cover_url)
+        .bind(now)
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| e.to_string())?;
+
+        stamp_item(&mut tx, kind, provider_id, external_id, now).await?;
+        tx.commit().await.map_err(|e| e.to_string())
+    }
+
+    async fn unlike_item(
+        &self,
+        kind: FavoriteKind,
+        provider_id: &str,
+        external_id: &str,
+    ) -> Result<(), String> {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs() as i64;
+
+        let mut tx = self.pool.begin().await.map_err(|e| e.to_string())?;
+        sqlx::query(
+            "DELETE FROM user_favorite_items WHERE kind = ? AND provider_id = ? AND external_id = ?",
+        )
+        .bind(kind.as_str())
+        .bind(provider_id)
+        .bind(external_id)
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| e.to_string())?;
+
+        stamp_item(&mut tx, kind, provider_id, external_id, now).await?;
+        tx.commit().await.map_err(|e| e.to_string())
+    }
+
+    /// Liked albums, with library details where the album is in the library.
+    /// Albums that aren't get `provider:external_id` as their id.
+    pub async fn get_favorite_albums(
+        &self,
+        sort: FavoriteSort,
+        artist: Option<&str>,
+    ) -> Result<Vec<LibraryAlbum>, String> {
+        sqlx::query_as::<_, LibraryAlbum>(&format!(
+            r#"
+            SELECT
+                COALESCE(al.id, f.provider_id || ':' || f.external_id) as id,
+                COALESCE(al.title, f.title) as title,
+                COALESCE(a.name, f.artist, '') as artist,
+                COALESCE(al.cover_url, f.cover_url) as cover_image,
+                CASE WHEN f.provider_id = 'local' THEN al.provider_id ELSE f.provider_id END as provider_id,
+                CASE WHEN f.provider_id = 'local' THEN al.external_id ELSE f.external_id END as external_id,
+                al.year,
+                f.liked_at
+            FROM user_favorite_items f
+            LEFT JOIN albums al ON al.id = (SELECT x.id FROM albums x WHERE {} LIMIT 1)
+            LEFT JOIN artists a ON a.id = al.artist_id
+            WHERE f.kind = 'album' AND (?1 IS NULL OR COALESCE(a.name, f.artist) = ?1 COLLATE NOCASE)
+            ORDER BY {}
+            "#,
+            MATCH_LIBRARY_ALBUM,
+            sort.order_by("COALESCE(al.title, f.title)")
+        ))
+        .bind(artist)
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| e.to_string())
+    }
+
+    /// Liked artists, with library details where the artist is in the library
+    pub async fn get_favorite_artists(
+        &self,
+        sort: FavoriteSort,
+    ) -> Result<Vec<LibraryArtist>, String> {
+        sqlx::query_as::<_, LibraryArtist>(&format!(
+            r#"
+            SELECT
+                COALESCE(a.id, f.provider_id || ':' || f.external_id) as id,
+                COALESCE(a.name, f.title) as name,
+                COALESCE(a.cover_url, f.cover_url) as cover_image,
+                CASE WHEN f.provider_id = 'local' THEN a.provider_id ELSE f.provider_id END as provider_id,
+                CASE WHEN f.provider_id = 'local' THEN a.external_id ELSE f.external_id END as external_id,
+                f.liked_at
+            FROM user_favorite_items f
+            LEFT JOIN artists a ON a.id = (SELECT x.id FROM artists x WHERE {} LIMIT 1)
+            WHERE f.kind = 'artist'
+            ORDER BY {}
+            "#,
+            MATCH_LIBRARY_ARTIST,
+            sort.order_by("COALESCE(a.name, f.title)")
+        ))
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| e.to_string())
+    }
+
+    /// Liked tracks and albums by one artist, most recently liked first
+    pub async fn get_liked_from_artist(&self, artist: &str) -> Result<LikedFromArtist, String> {
+        Ok(LikedFromArtist {
+            tracks: self.liked_tracks(Some(artist)).await?,
+            albums: self
+                .get_favorite_albums(FavoriteSort::Recent, Some(artist))
+                .await?,
+        })
+    }
+}
+
+/// Note when a server item was liked or unliked, for the next sync
+async fn stamp_item(
+    tx: &mut Transaction<'_, Sqlite>,
+    kind: FavoriteKind,
+    provider_id: &str,
+    external_id: &str,
+    now: i64,
+) -> Result<(), String> {
+    if provider_id == LOCAL {
+        return Ok(());
+    }
+    sqlx::query(
+        r#"
+        INSERT INTO favorite_item_sync (kind, provider_id, external_id, updated_at)
+        VALUES (?, ?, ?, ?)
+        ON CONFLICT(kind, provider_id, external_id) DO UPDATE SET updated_at = excluded.updated_at
+        "#,
+    )
+    .bind(kind.as_str())
+    .bind(provider_id)
+    .bind(external_id)
+    .bind(now)
+    .execute(&mut **tx)
+    .await
+    .map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+/// Provider and id a like is stored under. Provider ids survive re-imports,
+/// so they are preferred; local items fall back to `local_key`, as library
+/// ids change when a folder is rescanned. Tidal imports leave artists with an
+/// external id of "0", which doesn't count.
+fn favorite_key(
+    id: &str,
+    provider_id: Option<&str>,
+    external_id: Option<&str>,
+    local_key: &str,
+) -> (String, String) {
+    if let (Some(provider_id), Some(external_id)) = (provider_id, external_id) {
+        let external_id = bare_id(provider_id, external_id);
+        if !provider_id.is_empty()
+            && provider_id != LOCAL
+            && !external_id.is_empty()
+            && external_id != "0"
+        {
+            return (provider_id.to_string(), external_id);
+        }
+    }
+
+    // Provider search results carry `provider:external_id` ids
+    match id.split_once(':') {
+        Some((provider_id, external_id)) if !provider_id.is_empty() && !external_id.is_empty() => {
+            (provider_id.to_string(), external_id.to_string())
+        }
+        _ => (LOCAL.to_string(), local_key.to_string()),
+    }
+}
+
+/// Lowercased name, and for albums the album artist, as SQLite's `lower`
+/// and `trim` would give them so stored likes can be matched in SQL
+fn local_key(title: &str, artist: Option<&str>) -> String {
+    let normalize = |s: &str| s.trim_matches(' ').to_ascii_lowercase();
+    match artist {
+        Some(artist) => format!("{}\u{1f}{}", normalize(artist), normalize(title)),
+        None => normalize(title),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::DatabaseManager;
+
+    fn key(id: &str, provider_id: Option<&str>, external_id: Option<&str>) -> (String, String) {
+        favorite_key(id, provider_id, external_id, "name")
+    }
+
+    #[test]
+    fn prefers_provider_ids_over_library_ids() {
+        let expected = ("tidal".to_string(), "42".to_string());
+        assert_eq!(key("uuid", Some("tidal"), Some("42")), expected);
+        assert_eq!(key("uuid", Some("tidal"), Some("tidal:42")), expected);
+        assert_eq!(key("tidal:42", None, None), expected);
+        assert_eq!(
+            key("uuid", Some("tidal"), Some("0")),
+            ("local".to_string(), "name".to_string())
+        );
+        assert_eq!(
+            key("uuid", None, None),
+            ("local".to_string(), "name".to_string())
+        );
+        assert_eq!(
+            key("local:name", None, None),
+            ("local".to_string(), "name".to_string())
+        );
+    }
+
+    #[test]
+    fn local_keys_ignore_case_and_padding() {
+        assert_eq!(
+            local_key(" Abbey Road ", Some("The BEATLES")),
+            local_key("abbey road", Some("the beatles"))
+        );
+        assert_ne!(
+            local_key("Abbey Road", Some("The Beatles")),
+            local_key("Abbey Road", None)
+        );
+    }
+
+    #[tokio::test]
+    async fn local_likes_survive_rescans() {
+        let db = DatabaseManager::in_memory().await;
+        let favorites = FavoritesManager::new(db.pool.clone());
+        db.seed(&[
+            "INSERT INTO artists (id, name) VALUES ('ar', 'The Band')",
+            "INSERT INTO albums (id, title, artist_id) VALUES ('al', 'Record', 'ar')",
+        ])
+        .await;
+        let album = LibraryAlbum {
+            id: "al".to_string(),
+            title: "Record".to_string(),
+            artist: "The Band".to_string(),
+            cover_image: None,
+            provider_id: None,
+            external_id: None,
+            year: None,
+            liked_at: None,
+        };
+        favorites.like_album(&album).await.unwrap();
+
+        // A rescan recreates the rows under new ids
+        db.seed(&[
+            "DELETE FROM albums",
+            "DELETE FROM artists",
+            "INSERT INTO artists (id, name) VALUES ('ar2', 'the band')",
+            "INSERT INTO albums (id, title, artist_id) VALUES ('al2', 'Record', 'ar2')",
+        ])
+        .await;
+        let liked = favorites
+            .get_favorite_albums(FavoriteSort::Recent, None)
+            .await
+            .unwrap();
+        assert_eq!(liked.len(), 1);
+        assert_eq!(liked[0].id, "al2");
+
+        favorites.unlike_album(&liked[0]).await.unwrap();
+        assert!(favorites
+            .get_favorite_albums(FavoriteSort::Recent, None)
+            .await
+            .unwrap()
+            .is_empty());
+    }
+}
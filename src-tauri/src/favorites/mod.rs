@@ -1,6 +1,11 @@
+pub mod items;
 pub mod models;
+pub mod sync;
 
-use crate::library::models::{TrackSource, UnifiedTrack};
+use crate::library::models::{
+    ExtendedTrackInfo, TrackSource, UnifiedTrack, TRACK_METADATA_COLUMNS, TRACK_METADATA_CTES,
+    TRACK_METADATA_JOINS,
+};
 use models::Favorite;
 use sqlx::{Pool, Row, Sqlite};
 use std::time::{SystemTime, UNIX_EPOCH};
@@ -22,35 +27,83 @@ impl FavoritesManager {
             .unwrap()
             .as_secs() as i64;
 
+        let mut tx = self.pool.begin().await.map_err(|e| e.to_string())?;
         sqlx::query(
             "INSERT OR IGNORE INTO user_favorites (id, track_id, liked_at) VALUES (?, ?, ?)",
         )
         .bind(id)
         .bind(track_id)
         .bind(now)
-        .execute(&self.pool)
+        .execute(&mut *tx)
         .await
         .map_err(|e| e.to_string())?;
 
-        Ok(())
-    }
-
-    pub async fn remove_favorite(&self, track_id: &str) -> Result<(), String> {
-        sqlx::query("DELETE FROM user_favorites WHERE track_id = ?")
+        sqlx::query("UPDATE tracks SET like_updated_at = ? WHERE id = ?")
+            .bind(now)
             .bind(track_id)
-            .execute(&self.pool)
+            .execute(&mut *tx)
             .await
             .map_err(|e| e.to_string())?;
 
-        Ok(())
+        tx.commit().await.map_err(|e| e.to_string())
+    }
+
+    /// Unlike a track along with every other copy of its work
+    pub async fn remove_favorite(&self, track_id: &str) -> Result<(), String> {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs() as i64;
+
+        let mut tx = self.pool.begin().await.map_err(|e| e.to_string())?;
+        // Stamp the copies before their favorite rows go, so syncing knows
+        // which were unliked
+        sqlx::query(
+            r#"
+            UPDATE tracks SET like_updated_at = ?2
+            WHERE id = ?1
+               OR (id IN (SELECT track_id FROM user_favorites)
+                   AND work_id = (SELECT work_id FROM tracks WHERE id = ?1))
+            "#,
+        )
+        .bind(track_id)
+        .bind(now)
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| e.to_string())?;
+
+        sqlx::query(
+            r#"
+            DELETE FROM user_favorites
+            WHERE track_id = ?1
+               OR track_id IN (
+                   SELECT id FROM tracks WHERE work_id = (SELECT work_id FROM tracks WHERE id = ?1)
+               )
+            "#,
+        )
+        .bind(track_id)
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| e.to_string())?;
+
+        tx.commit().await.map_err(|e| e.to_string())
     }
 
+    /// A track counts as liked when any copy of its work is
     pub async fn is_favorited(&self, track_id: &str) -> Result<bool, String> {
-        let result = sqlx::query("SELECT 1 FROM user_favorites WHERE track_id = ?")
-            .bind(track_id)
-            .fetch_optional(&self.pool)
-            .await
-            .map_err(|e| e.to_string())?;
+        let result = sqlx::query(
+            r#"
+            SELECT 1 FROM user_favorites f
+            JOIN tracks t ON t.id = f.track_id
+            WHERE f.track_id = ?1
+               OR t.work_id = (SELECT work_id FROM tracks WHERE id = ?1)
+            LIMIT 1
+            "#,
+        )
+        .bind(track_id)
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(|e| e.to_string())?;
 
         Ok(result.is_some())
     }
@@ -67,12 +120,20 @@ impl FavoritesManager {
     }
 
     pub async fn get_favorites_with_tracks(&self) -> Result<Vec<UnifiedTrack>, String> {
-        let rows = sqlx::query(
+        self.liked_tracks(None).await
+    }
+
+    /// Liked tracks, newest first, optionally only those crediting an artist
+    /// (by name, as the main, album or featured artist)
+    async fn liked_tracks(&self, artist: Option<&str>) -> Result<Vec<UnifiedTrack>, String> {
+        let rows = sqlx::query(&format!(
             r#"
+            WITH {}
             SELECT 
                 t.id, t.title, t.duration, t.source_type, t.file_path,
                 t.play_count, t.skip_count, t.last_played_at, t.added_at, t.audio_quality,
                 t.provider_id, t.external_id, t.artist_id, t.album_id,
+                {},
                 a.name as artist_name,
                 a.provider_id as artist_provider_id,
                 a.external_id as artist_external_id,
@@ -85,9 +146,29 @@ impl FavoritesManager {
             JOIN tracks t ON f.track_id = t.id
             JOIN artists a ON t.artist_id = a.id
             LEFT JOIN albums al ON t.album_id = al.id
+            {}
+            -- One entry per work: the most recently liked copy
+            WHERE (t.work_id IS NULL OR NOT EXISTS (
+                SELECT 1 FROM user_favorites f2
+                JOIN tracks t2 ON t2.id = f2.track_id
+                WHERE t2.work_id = t.work_id
+                  AND (f2.liked_at > f.liked_at OR (f2.liked_at = f.liked_at AND f2.id > f.id))
+            ))
+            AND (
+                ?1 IS NULL
+                OR a.name = ?1 COLLATE NOCASE
+                OR EXISTS (SELECT 1 FROM artists aa WHERE aa.id = al.artist_id AND aa.name = ?1 COLLATE NOCASE)
+                OR EXISTS (
+                    SELECT 1 FROM track_artists ta
+                    JOIN artists ar ON ar.id = ta.artist_id
+                    WHERE ta.track_id = t.id AND ar.name = ?1 COLLATE NOCASE
+                )
+            )
             ORDER BY f.liked_at DESC
             "#,
-        )
+            TRACK_METADATA_CTES, TRACK_METADATA_COLUMNS, TRACK_METADATA_JOINS
+        ))
+        .bind(artist)
         .fetch_all(&self.pool)
         .await
         .map_err(|e| e.to_string())?;
@@ -175,6 +256,7 @@ impl FavoritesManager {
                 added_at,
                 provider_id,
                 external_id,
+                metadata: ExtendedTrackInfo::from_row(&row),
             });
         }
 
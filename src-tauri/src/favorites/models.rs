@@ -1,3 +1,4 @@
+use crate::library::models::{LibraryAlbum, UnifiedTrack};
 use serde::{Deserialize, Serialize};
 
 #[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
@@ -6,3 +7,58 @@ pub struct Favorite {
     pub track_id: String,
     pub liked_at: i64,
 }
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FavoriteKind {
+    Album,
+    Artist,
+}
+
+impl FavoriteKind {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            FavoriteKind::Album => "album",
+            FavoriteKind::Artist => "artist",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FavoriteSort {
+    /// Most recently liked first
+    #[default]
+    Recent,
+    Oldest,
+    Name,
+}
+
+impl FavoriteSort {
+    pub fn order_by(self, name_column: &str) -> String {
+        match self {
+            FavoriteSort::Recent => "f.liked_at DESC".to_string(),
+            FavoriteSort::Oldest => "f.liked_at ASC".to_string(),
+            FavoriteSort::Name => format!("{} COLLATE NOCASE, f.liked_at DESC", name_column),
+        }
+    }
+}
+
+/// What the user liked from one artist
+#[derive(Debug, Clone, Serialize)]
+pub struct LikedFromArtist {
+    pub tracks: Vec<UnifiedTrack>,
+    pub albums: Vec<LibraryAlbum>,
+}
+
+#[derive(Debug, Clone, Serialize, Default)]
+pub struct LikeSyncReport {
+    /// Likes and unlikes taken from servers
+    pub pulled: usize,
+    /// Likes and unlikes written to servers
+    pub pushed: usize,
+    /// Tracks, albums or artists liked or unliked on both sides since the last sync
+    pub conflicts: usize,
+    /// Servers or tracks that could not be reached; retried next sync
+    pub failed: Vec<String>,
+}
@@ -0,0 +1,780 @@
+use super::models::{FavoriteKind, LikeSyncReport};
+use super::FavoritesManager;
+use crate::library::ratings::{resolve, RatingState, Resolution};
+use crate::library::LibraryManager;
+use crate::models::{LikedItem, Track};
+use crate::providers::bare_id;
+use crate::providers::traits::MusicProvider;
+use chrono::Utc;
+use sqlx::Row;
+use std::collections::HashMap;
+use std::sync::Arc;
+use uuid::Uuid;
+
+/// A track's like state and what its server held at the last sync
+struct LikeRow {
+    track_id: String,
+    external_id: String,
+    liked: bool,
+    updated_at: Option<i64>,
+    synced: Option<bool>,
+    synced_at: Option<i64>,
+}
+
+impl LikeRow {
+    fn from_row(row: &sqlx::sqlite::SqliteRow) -> Self {
+        Self {
+            track_id: row.get("id"),
+            external_id: row.get("external_id"),
+            liked: row.get::<i64, _>("liked") != 0,
+            updated_at: row.try_get("like_updated_at").ok().flatten(),
+            synced: row
+                .try_get::<Option<i64>, _>("like_synced")
+                .ok()
+                .flatten()
+                .map(|v| v != 0),
+            synced_at: row.try_get("like_synced_at").ok().flatten(),
+        }
+    }
+
+    fn state(&self) -> RatingState {
+        like_state(self.liked, self.updated_at, self.synced, self.synced_at)
+    }
+}
+
+/// Likes reuse the rating merge, with a like counting as a rating of 1
+fn like_state(
+    liked: bool,
+    updated_at: Option<i64>,
+    synced: Option<bool>,
+    synced_at: Option<i64>,
+) -> RatingState {
+    RatingState {
+        rating: liked.then_some(1),
+        updated_at,
+        synced: synced.filter(|liked| *liked).map(|_| 1),
+        synced_at,
+    }
+}
+
+/// An album or artist like on one server, and that server's state at the last sync
+#[derive(Default)]
+struct ItemLikeRow {
+    external_id: String,
+    liked: bool,
+    updated_at: Option<i64>,
+    synced: Option<bool>,
+    synced_at: Option<i64>,
+}
+
+impl ItemLikeRow {
+    fn from_row(row: &sqlx::sqlite::SqliteRow) -> Self {
+        Self {
+            external_id: row.get("external_id"),
+            liked: row.get::<i64, _>("liked") != 0,
+            updated_at: row.try_get("updated_at").ok().flatten(),
+            synced: row
+                .try_get::<Option<i64>, _>("synced")
+                .ok()
+                .flatten()
+                .map(|v| v != 0),
+            synced_at: row.try_get("synced_at").ok().flatten(),
+        }
+    }
+
+    fn state(&self) -> RatingState {
+        like_state(self.liked, self.updated_at, self.synced, self.synced_at)
+    }
+
+    /// Changed in the app since the server last took it
+    fn pending(&self) -> bool {
+        let changed = match (self.updated_at, self.synced_at) {
+            (Some(updated_at), Some(synced_at)) => updated_at >= synced_at,
+            (Some(_), None) => true,
+            (None, _) => false,
+        };
+        changed && self.synced != Some(self.liked)
+    }
+}
+
+async fn set_item_liked(
+    provider: &dyn MusicProvider,
+    kind: FavoriteKind,
+    external_id: &str,
+    liked: bool,
+) -> anyhow::Result<()> {
+    match kind {
+        FavoriteKind::Album => provider.set_album_liked(external_id, liked).await,
+        FavoriteKind::Artist => provider.set_artist_liked(external_id, liked).await,
+    }
+}
+
+const LIKE_COLUMNS: &str = r#"
+    t.id, t.external_id, t.like_updated_at, t.like_synced, t.like_synced_at,
+    EXISTS (SELECT 1 FROM user_favorites f WHERE f.track_id = t.id) as liked
+"#;
+
+impl FavoritesManager {
+    /// Copy likes and unlikes of a track and its other copies to their
+    /// servers right away. A failed copy is retried by the next `sync_likes`.
+    pub async fn push_likes(
+        &self,
+        track_id: &str,
+        providers: &[Arc<dyn MusicProvider>],
+    ) -> Result<(), String> {
+        let rows = sqlx::query(&format!(
+            r#"
+            SELECT t.provider_id, {}
+            FROM tracks t
+            WHERE (t.id = ?1 OR t.work_id = (SELECT work_id FROM tracks WHERE id = ?1))
+              AND t.external_id IS NOT NULL AND t.like_updated_at IS NOT NULL
+              AND (t.like_synced_at IS NULL OR t.like_updated_at >= t.like_synced_at)
+            "#,
+            LIKE_COLUMNS
+        ))
+        .bind(track_id)
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| e.to_string())?;
+
+        for row in &rows {
+            let provider_id: Option<String> = row.try_get("provider_id").ok().flatten();
+            let Some(provider) = providers
+                .iter()
+                .find(|p| Some(p.id()) == provider_id.as_deref() && p.supports_likes())
+            else {
+                continue;
+            };
+
+            let like = LikeRow::from_row(row);
+            if like.synced == Some(like.liked) {
+                continue;
+            }
+            match provider
+                .set_track_liked(&like.external_id, like.liked)
+                .await
+            {
+                Ok(()) => self.record(&like.track_id, like.liked).await?,
+                Err(e) => log::warn!(
+                    "[Favorites] Like of {} saved but not yet synced: {}",
+                    like.track_id,
+                    e
+                ),
+            }
+        }
+        Ok(())
+    }
+
+    /// Copy album and artist likes and unlikes made since the last sync to
+    /// their servers. A failed copy is retried by the next `sync_likes`.
+    pub async fn push_item_likes(
+        &self,
+        providers: &[Arc<dyn MusicProvider>],
+    ) -> Result<(), String> {
+        for provider in providers.iter().filter(|p| p.supports_likes()) {
+            for kind in [FavoriteKind::Album, FavoriteKind::Artist] {
+                let likes = self.item_likes(kind, provider.id()).await?;
+                for like in likes.iter().filter(|like| like.pending()) {
+                    match set_item_liked(provider.as_ref(), kind, &like.external_id, like.liked)
+                        .await
+                    {
+                        Ok(()) => {
+                            self.record_item(kind, provider.id(), &like.external_id, like.liked)
+                                .await?
+                        }
+                        Err(e) => log::warn!(
+                            "[Favorites] Like of {} {} saved but not yet synced: {}",
+                            kind.as_str(),
+                            like.external_id,
+                            e
+                        ),
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Reconcile likes with every server that keeps them. Tracks liked on a
+    /// server but missing from the library are imported; albums and artists
+    /// are liked by their server ids. Server and network errors go into the
+    /// report; only database errors are returned.
+    pub async fn sync_likes(
+        &self,
+        library: &LibraryManager,
+        providers: &[Arc<dyn MusicProvider>],
+    ) -> Result<LikeSyncReport, String> {
+        let mut report = LikeSyncReport::default();
+        for provider in providers.iter().filter(|p| p.supports_likes()) {
+            let liked = match provider.get_liked_tracks().await {
+                Ok(liked) => liked,
+                Err(e) => {
+                    report.failed.push(format!("{}: {}", provider.name(), e));
+                    continue;
+                }
+            };
+            let remote: HashMap<String, (Option<i64>, Track)> = liked
+                .into_iter()
+                .map(|l| (bare_id(provider.id(), &l.track.id), (l.liked_at, l.track)))
+                .collect();
+
+            for (external_id, (_, track)) in &remote {
+                self.ensure_track(library, provider.id(), external_id, track)
+                    .await?;
+            }
+
+            let rows = sqlx::query(&format!(
+                "SELECT {} FROM tracks t WHERE t.provider_id = ? AND t.external_id IS NOT NULL",
+                LIKE_COLUMNS
+            ))
+            .bind(provider.id())
+            .fetch_all(&self.pool)
+            .await
+            .map_err(|e| e.to_string())?;
+
+            for like in rows.iter().map(LikeRow::from_row) {
+                let remote_like = remote.get(&bare_id(provider.id(), &like.external_id));
+                if remote_like.is_none() && !like.liked && like.synced != Some(true) {
+                    continue;
+                }
+                self.sync_track(provider.as_ref(), &like, remote_like, &mut report)
+                    .await?;
+            }
+
+            match provider.get_liked_items().await {
+                Ok(items) => {
+                    self.sync_items(
+                        provider.as_ref(),
+                        FavoriteKind::Album,
+                        &items.albums,
+                        &mut report,
+                    )
+                    .await?;
+                    self.sync_items(
+                        provider.as_ref(),
+                        FavoriteKind::Artist,
+                        &items.artists,
+                        &mut report,
+                    )
+                    .await?;
+                }
+                Err(e) => report.failed.push(format!("{}: {}", provider.name(), e)),
+            }
+        }
+
+        log::info!(
+            "[Favorites] Synced likes: {} pulled, {} pushed, {} conflicts, {} failed",
+            report.pulled,
+            report.pushed,
+            report.conflicts,
+            report.failed.len()
+        );
+        Ok(report)
+    }
+
+    async fn sync_track(
+        &self,
+        provider: &dyn MusicProvider,
+        like: &LikeRow,
+        remote: Option<&(Option<i64>, Track)>,
+        report: &mut LikeSyncReport,
+    ) -> Result<(), String> {
+        let remote_liked_at = remote.and_then(|(liked_at, _)| *liked_at);
+        let (resolution, conflict) = resolve(&like.state(), remote.map(|_| 1), remote_liked_at, 1);
+        if conflict {
+            report.conflicts += 1;
+        }
+
+        let liked = match resolution {
+            Resolution::InSync(value) => {
+                let liked = value.is_some();
+                if like.synced == Some(liked) {
+                    return Ok(());
+                }
+                liked
+            }
+            Resolution::Push(value) => {
+                let liked = value.is_some();
+                if let Err(e) = provider.set_track_liked(&like.external_id, liked).await {
+                    log::warn!(
+                        "[Favorites] Could not sync like of {}: {}",
+                        like.track_id,
+                        e
+                    );
+                    report.failed.push(like.track_id.clone());
+                    return Ok(());
+                }
+                report.pushed += 1;
+                liked
+            }
+            Resolution::Pull(value) => {
+                let liked = value.is_some();
+                self.apply_remote_like(&like.track_id, liked, remote_liked_at)
+                    .await?;
+                report.pulled += 1;
+                liked
+            }
+        };
+        self.record(&like.track_id, liked).await
+    }
+
+    /// Album or artist likes go the same way as track likes, keyed by server id
+    async fn sync_items(
+        &self,
+        provider: &dyn MusicProvider,
+        kind: FavoriteKind,
+        items: &[LikedItem],
+        report: &mut LikeSyncReport,
+    ) -> Result<(), String> {
+        let mut remote: HashMap<String, &LikedItem> = items
+            .iter()
+            .map(|item| (bare_id(provider.id(), &item.id), item))
+            .collect();
+
+        let mut likes = self.item_likes(kind, provider.id()).await?;
+        let remote_only: Vec<ItemLikeRow> = remote
+            .keys()
+            .filter(|id| !likes.iter().any(|like| &like.external_id == *id))
+            .map(|id| ItemLikeRow {
+                external_id: id.clone(),
+                ..Default::default()
+            })
+            .collect();
+        likes.extend(remote_only);
+
+        for like in &likes {
+            let item = remote.remove(&like.external_id);
+            let liked_at = item.and_then(|item| item.liked_at);
+            let (resolution, conflict) = resolve(&like.state(), item.map(|_| 1), liked_at, 1);
+            if conflict {
+                report.conflicts += 1;
+            }
+
+            let liked = match resolution {
+                Resolution::InSync(value) => {
+                    let liked = value.is_some();
+                    if like.synced == Some(liked) {
+                        continue;
+                    }
+                    liked
+                }
+                Resolution::Push(value) => {
+                    let liked = value.is_some();
+                    if let Err(e) = set_item_liked(provider, kind, &like.external_id, liked).await {
+                        log::warn!(
+                            "[Favorites] Could not sync like of {} {}: {}",
+                            kind.as_str(),
+                            like.external_id,
+                            e
+                        );
+                        report
+                            .failed
+                            .push(format!("{} {}", kind.as_str(), like.external_id));
+                        continue;
+                    }
+                    report.pushed += 1;
+                    liked
+                }
+                Resolution::Pull(value) => {
+                    let liked = value.is_some();
+                    self.apply_remote_item_like(kind, provider.id(), &like.external_id, item)
+                        .await?;
+                    report.pulled += 1;
+                    liked
+                }
+            };
+            self.record_item(kind, provider.id(), &like.external_id, liked)
+                .await?;
+        }
+        Ok(())
+    }
+
+    /// Album or artist likes stored under a server, with their sync state
+    async fn item_likes(
+        &self,
+        kind: FavoriteKind,
+        provider_id: &str,
+    ) -> Result<Vec<ItemLikeRow>, String> {
+        let rows = sqlx::query(
+            r#"
+            SELECT k.external_id, s.updated_at, s.synced, s.synced_at,
+                   EXISTS (SELECT 1 FROM user_favorite_items f
+                           WHERE f.kind = ?1 AND f.provider_id = ?2 AND f.external_id = k.external_id) as liked
+            FROM (SELECT external_id FROM user_favorite_items WHERE kind = ?1 AND provider_id = ?2
+                  UNION
+                  SELECT external_id FROM favorite_item_sync WHERE kind = ?1 AND provider_id = ?2) k
+            LEFT JOIN favorite_item_sync s
+                ON s.kind = ?1 AND s.provider_id = ?2 AND s.external_id = k.external_id
+            "#,
+        )
+        .bind(kind.as_str())
+        .bind(provider_id)
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| e.to_string())?;
+
+        Ok(rows.iter().map(ItemLikeRow::from_row).collect())
+    }
+
+    /// Like an album or artist with the server's details, or unlike it
+    async fn apply_remote_item_like(
+        &self,
+        kind: FavoriteKind,
+        provider_id: &str,
+        external_id: &str,
+        item: Option<&LikedItem>,
+    ) -> Result<(), String> {
+        let query = match item {
+            Some(item) => sqlx::query(
+                r#"
+                INSERT OR IGNORE INTO user_favorite_items
+                    (id, kind, provider_id, external_id, title, artist, cover_url, liked_at)
+                VALUES (?, ?, ?, ?, ?, ?, ?, ?)
+                "#,
+            )
+            .bind(Uuid::new_v4().to_string())
+            .bind(kind.as_str())
+            .bind(provider_id)
+            .bind(external_id)
+            .bind(&item.title)
+            .bind(&item.artist)
+            .bind(&item.cover_url)
+            .bind(item.liked_at.unwrap_or_else(|| Utc::now().timestamp())),
+            None => sqlx::query(
+                "DELETE FROM user_favorite_items WHERE kind = ? AND provider_id = ? AND external_id = ?",
+            )
+            .bind(kind.as_str())
+            .bind(provider_id)
+            .bind(external_id),
+        };
+        query.execute(&self.pool).await.map_err(|e| e.to_string())?;
+        Ok(())
+    }
+
+    async fn record_item(
+        &self,
+        kind: FavoriteKind,
+        provider_id: &str,
+        external_id: &str,
+        liked: bool,
+    ) -> Result<(), String> {
+        sqlx::query(
+            r#"
+            INSERT INTO favorite_item_sync (kind, provider_id, external_id, synced, synced_at)
+            VALUES (?, ?, ?, ?, ?)
+            ON CONFLICT(kind, provider_id, external_id) DO UPDATE SET
+                synced = excluded.synced,
+                synced_at = excluded.synced_at
+            "#,
+        )
+        .bind(kind.as_str())
+        .bind(provider_id)
+        .bind(external_id)
+        .bind(liked as i64)
+        .bind(Utc::now().timestamp())
+        .execute(&self.pool)
+        .await
+        .map_err(|e| e.to_string())?;
+        Ok(())
+    }
+
+    /// Like or unlike a single track as its server has it, keeping the
+    /// server's like time when it gives one
+    async fn apply_remote_like(
+        &self,
+        track_id: &str,
+        liked: bool,
+        liked_at: Option<i64>,
+    ) -> Result<(), String> {
+        let query = if liked {
+            sqlx::query(
+                "INSERT OR IGNORE INTO user_favorites (id, track_id, liked_at) VALUES (?, ?, ?)",
+            )
+            .bind(Uuid::new_v4().to_string())
+            .bind(track_id)
+            .bind(liked_at.unwrap_or_else(|| Utc::now().timestamp()))
+        } else {
+            sqlx::query("DELETE FROM user_favorites WHERE track_id = ?").bind(track_id)
+        };
+        query.execute(&self.pool).await.map_err(|e| e.to_string())?;
+        Ok(())
+    }
+
+    async fn record(&self, track_id: &str, liked: bool) -> Result<(), String> {
+        sqlx::query("UPDATE tracks SET like_synced = ?, like_synced_at = ? WHERE id = ?")
+            .bind(liked as i64)
+            .bind(Utc::now().timestamp())
+            .bind(track_id)
+            .execute(&self.pool)
+            .await
+            .map_err(|e| e.to_string())?;
+        Ok(())
+    }
+
+    /// Import a server-liked track the library doesn't have yet
+    async fn ensure_track(
+        &self,
+        library: &LibraryManager,
+        provider_id: &str,
+        external_id: &str,
+        track: &Track,
+    ) -> Result<(), String> {
+        // Older imports kept the provider prefix on external ids
+        let existing: Option<String> = sqlx::query_scalar(
+            "SELECT id FROM tracks WHERE provider_id = ? AND external_id IN (?, ?) LIMIT 1",
+        )
+        .bind(provider_id)
+        .bind(external_id)
+        .bind(format!("{}:{}", provider_id, external_id))
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(|e| e.to_string())?;
+
+        if existing.is_none() {
+            let track = Track {
+                id: external_id.to_string(),
+                ..track.clone()
+            };
+            library.import_external_track(&track, provider_id).await?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::DatabaseManager;
+    use crate::library::models::LibraryAlbum;
+    use crate::models::{
+        Album, Artist, LikedItems, LikedTrack, Quality, SearchResults, StreamInfo,
+    };
+    use anyhow::anyhow;
+    use async_trait::async_trait;
+    use serde_json::Value;
+    use std::sync::Mutex;
+
+    /// A server that keeps likes in memory and logs every write
+    #[derive(Default)]
+    struct FakeServer {
+        tracks: Mutex<Vec<String>>,
+        albums: Mutex<Vec<String>>,
+        calls: Mutex<Vec<String>>,
+    }
+
+    impl FakeServer {
+        fn calls(&self) -> Vec<String> {
+            std::mem::take(&mut *self.calls.lock().unwrap())
+        }
+
+        fn set(&self, list: &Mutex<Vec<String>>, kind: &str, id: &str, liked: bool) {
+            let mut list = list.lock().unwrap();
+            list.retain(|x| x != id);
+            if liked {
+                list.push(id.to_string());
+            }
+            self.calls
+                .lock()
+                .unwrap()
+                .push(format!("{} {} {}", kind, id, liked));
+        }
+    }
+
+    fn track(id: &str) -> Track {
+        Track {
+            id: id.to_string(),
+            title: format!("Song {}", id),
+            artist: "Artist".to_string(),
+            artist_id: None,
+            album: "Album".to_string(),
+            album_id: None,
+            duration: 1,
+            cover_url: None,
+        }
+    }
+
+    #[async_trait]
+    impl MusicProvider for FakeServer {
+        fn id(&self) -> &str {
+            "subsonic"
+        }
+        fn name(&self) -> &str {
+            "Fake"
+        }
+        async fn initialize(&mut self, _config: Value) -> anyhow::Result<()> {
+            Ok(())
+        }
+        async fn search(&self, _query: &str) -> anyhow::Result<SearchResults> {
+            Err(anyhow!("unused"))
+        }
+        async fn get_stream_url(
+            &self,
+            _track_id: &str,
+            _quality: Quality,
+        ) -> anyhow::Result<StreamInfo> {
+            Err(anyhow!("unused"))
+        }
+        async fn get_track_details(&self, _track_id: &str) -> anyhow::Result<Track> {
+            Err(anyhow!("unused"))
+        }
+        async fn get_artist_details(&self, _artist_id: &str) -> anyhow::Result<Artist> {
+            Err(anyhow!("unused"))
+        }
+        async fn get_album_details(&self, _album_id: &str) -> anyhow::Result<Album> {
+            Err(anyhow!("unused"))
+        }
+        async fn get_artist_top_tracks(&self, _artist_id: &str) -> anyhow::Result<Vec<Track>> {
+            Err(anyhow!("unused"))
+        }
+        async fn get_artist_albums(&self, _artist_id: &str) -> anyhow::Result<Vec<Album>> {
+            Err(anyhow!("unused"))
+        }
+        fn supports_likes(&self) -> bool {
+            true
+        }
+        async fn get_liked_tracks(&self) -> anyhow::Result<Vec<LikedTrack>> {
+            Ok(self
+                .tracks
+                .lock()
+                .unwrap()
+                .iter()
+                .map(|id| LikedTrack {
+                    track: track(id),
+                    liked_at: None,
+                })
+                .collect())
+        }
+        async fn set_track_liked(&self, track_id: &str, liked: bool) -> anyhow::Result<()> {
+            self.set(&self.tracks, "track", track_id, liked);
+            Ok(())
+        }
+        async fn get_liked_items(&self) -> anyhow::Result<LikedItems> {
+            let albums = self
+                .albums
+                .lock()
+                .unwrap()
+                .iter()
+                .map(|id| LikedItem {
+                    id: id.clone(),
+                    title: format!("Album {}", id),
+                    artist: Some("Artist".to_string()),
+                    cover_url: None,
+                    liked_at: None,
+                })
+                .collect();
+            Ok(LikedItems {
+                albums,
+                artists: Vec::new(),
+            })
+        }
+        async fn set_album_liked(&self, album_id: &str, liked: bool) -> anyhow::Result<()> {
+            self.set(&self.albums, "album", album_id, liked);
+            Ok(())
+        }
+    }
+
+    async fn setup() -> (DatabaseManager, FavoritesManager, LibraryManager) {
+        let db = DatabaseManager::in_memory().await;
+        db.seed(&[
+            "INSERT INTO artists (id, name) VALUES ('ar', 'Artist')",
+            "INSERT INTO tracks (id, title, artist_id, duration, source_type, provider_id, external_id) VALUES ('t1', 'One', 'ar', 1, 'subsonic', 'subsonic', 's1')",
+        ])
+        .await;
+        let favorites = FavoritesManager::new(db.pool.clone());
+        let library = LibraryManager::new(db.pool.clone());
+        (db, favorites, library)
+    }
+
+    fn album(external_id: &str) -> LibraryAlbum {
+        LibraryAlbum {
+            id: format!("subsonic:{}", external_id),
+            title: "Album".to_string(),
+            artist: "Artist".to_string(),
+            cover_image: None,
+            provider_id: Some("subsonic".to_string()),
+            external_id: Some(external_id.to_string()),
+            year: None,
+            liked_at: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn local_likes_and_unlikes_are_pushed_once() {
+        let (_db, favorites, library) = setup().await;
+        let server = Arc::new(FakeServer::default());
+        let providers: Vec<Arc<dyn MusicProvider>> = vec![server.clone()];
+
+        favorites.add_favorite("t1").await.unwrap();
+        favorites.push_likes("t1", &providers).await.unwrap();
+        assert_eq!(server.calls(), ["track s1 true"]);
+
+        favorites.remove_favorite("t1").await.unwrap();
+        let report = favorites.sync_likes(&library, &providers).await.unwrap();
+        assert_eq!(server.calls(), ["track s1 false"]);
+        assert_eq!(report.pushed, 1);
+
+        let report = favorites.sync_likes(&library, &providers).await.unwrap();
+        assert!(server.calls().is_empty());
+        assert_eq!((report.pushed, report.pulled), (0, 0));
+    }
+
+    #[tokio::test]
+    async fn server_likes_are_pulled_and_missing_tracks_imported() {
+        let (db, favorites, library) = setup().await;
+        let server = Arc::new(FakeServer::default());
+        server
+            .tracks
+            .lock()
+            .unwrap()
+            .extend(["s1".to_string(), "s2".to_string()]);
+        let providers: Vec<Arc<dyn MusicProvider>> = vec![server.clone()];
+
+        let report = favorites.sync_likes(&library, &providers).await.unwrap();
+        assert_eq!(report.pulled, 2);
+        assert!(server.calls().is_empty());
+        assert!(favorites.is_favorited("t1").await.unwrap());
+
+        let imported: String = sqlx::query_scalar(
+            "SELECT id FROM tracks WHERE provider_id = 'subsonic' AND external_id = 's2'",
+        )
+        .fetch_one(&db.pool)
+        .await
+        .unwrap();
+        assert!(favorites.is_favorited(&imported).await.unwrap());
+
+        server.tracks.lock().unwrap().retain(|id| id != "s1");
+        let report = favorites.sync_likes(&library, &providers).await.unwrap();
+        assert_eq!(report.pulled, 1);
+        assert!(!favorites.is_favorited("t1").await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn album_likes_sync_both_ways() {
+        let (_db, favorites, library) = setup().await;
+        let server = Arc::new(FakeServer::default());
+        server.albums.lock().unwrap().push("remote".to_string());
+        let providers: Vec<Arc<dyn MusicProvider>> = vec![server.clone()];
+
+        favorites.like_album(&album("mine")).await.unwrap();
+        let report = favorites.sync_likes(&library, &providers).await.unwrap();
+        assert_eq!(server.calls(), ["album mine true"]);
+        assert_eq!((report.pushed, report.pulled), (1, 1));
+
+        let liked: Vec<String> = favorites
+            .get_favorite_albums(Default::default(), None)
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|a| a.title)
+            .collect();
+        assert!(liked.contains(&"Album remote".to_string()));
+
+        favorites.unlike_album(&album("mine")).await.unwrap();
+        favorites.push_item_likes(&providers).await.unwrap();
+        assert_eq!(server.calls(), ["album mine false"]);
+
+        favorites.sync_likes(&library, &providers).await.unwrap();
+        favorites.push_item_likes(&providers).await.unwrap();
+        assert!(server.calls().is_empty());
+    }
+}
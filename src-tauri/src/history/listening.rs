@@ -0,0 +1,246 @@
+//! Listening-time accounting for play history.
+//!
+//! The playback notifier keeps a `ListeningSession` for the current track and
+//! reports when listening starts and ends. The recorder task turns those
+//! reports into `play_history` entries, in order, so plays are counted by the
+//! player itself rather than by whichever view happens to be polling.
+
+use super::PlayHistoryManager;
+use crate::database::DatabaseManager;
+use crate::queue::PlayContext;
+use serde::{Deserialize, Serialize};
+use std::time::{Duration, Instant};
+use tauri::{AppHandle, Emitter, Manager};
+use tokio::sync::mpsc::{unbounded_channel, UnboundedSender};
+
+/// Settings key the thresholds are stored under, as JSON
+pub const THRESHOLDS_SETTING: &str = "play_thresholds";
+
+/// When a play counts as completed or skipped
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PlayThresholds {
+    /// Share of the track, in percent, after which a play is completed
+    pub complete_percent: u32,
+    /// Listening time after which a play is completed whatever the track length
+    pub complete_secs: u64,
+    /// Moving on to another track before this much listening is a skip
+    pub skip_secs: u64,
+}
+
+impl Default for PlayThresholds {
+    fn default() -> Self {
+        Self {
+            complete_percent: 50,
+            complete_secs: 240,
+            skip_secs: 30,
+        }
+    }
+}
+
+/// How listening to a track ended
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PlayEnd {
+    /// The track played to its end
+    Finished,
+    /// Another track was started
+    MovedOn,
+    /// Playback was stopped
+    Stopped,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PlayOutcome {
+    Completed,
+    /// Counted as a play, but not listened to far enough to be completed
+    Partial,
+    Skipped,
+    /// Stopped too early to count as a play or a skip
+    Abandoned,
+}
+
+impl PlayThresholds {
+    pub fn classify(&self, listened_secs: f64, duration_secs: f64, end: PlayEnd) -> PlayOutcome {
+        let mut complete_at = self.complete_secs as f64;
+        if duration_secs > 0.0 {
+            complete_at = complete_at.min(duration_secs * self.complete_percent as f64 / 100.0);
+        }
+
+        if listened_secs >= complete_at {
+            PlayOutcome::Completed
+        } else if end == PlayEnd::Finished || listened_secs >= self.skip_secs as f64 {
+            PlayOutcome::Partial
+        } else if end == PlayEnd::MovedOn {
+            PlayOutcome::Skipped
+        } else {
+            PlayOutcome::Abandoned
+        }
+    }
+}
+
+/// Time actually spent listening to the current track. Seeking doesn't
+/// count towards it, only time spent playing.
+#[derive(Debug)]
+pub struct ListeningSession {
+    pub track_id: String,
+    pub duration_secs: f64,
+    /// Where the queue was started from when this track began
+    pub context: Option<PlayContext>,
+    listened: Duration,
+    resumed_at: Option<Instant>,
+    started: bool,
+}
+
+impl ListeningSession {
+    pub fn new(track_id: String, duration_secs: f64, context: Option<PlayContext>) -> Self {
+        Self {
+            track_id,
+            duration_secs,
+            context,
+            listened: Duration::ZERO,
+            resumed_at: None,
+            started: false,
+        }
+    }
+
+    /// Returns true the first time listening starts, when the play should
+    /// be opened. Tracks that are cued but never played aren't recorded.
+    pub fn resume(&mut self) -> bool {
+        if self.resumed_at.is_none() {
+            self.resumed_at = Some(Instant::now());
+        }
+        !std::mem::replace(&mut self.started, true)
+    }
+
+    pub fn pause(&mut self) {
+        if let Some(resumed_at) = self.resumed_at.take() {
+            self.listened += resumed_at.elapsed();
+        }
+    }
+
+    pub fn started(&self) -> bool {
+        self.started
+    }
+
+    pub fn listened_secs(&self) -> f64 {
+        let current = self.resumed_at.map(|t| t.elapsed()).unwrap_or_default();
+        (self.listened + current).as_secs_f64()
+    }
+}
+
+#[derive(Debug)]
+pub enum PlayEvent {
+    Started {
+        track_id: String,
+        context: Option<PlayContext>,
+    },
+    Ended {
+        listened_secs: f64,
+        duration_secs: f64,
+        end: PlayEnd,
+    },
+}
+
+pub type PlayRecorder = UnboundedSender<PlayEvent>;
+
+/// Record plays reported by the playback notifier. Events are handled one at
+/// a time so a play is always closed before the next one is opened.
+pub fn spawn_recorder(app: AppHandle) -> PlayRecorder {
+    let (tx, mut rx) = unbounded_channel::<PlayEvent>();
+
+    tauri::async_runtime::spawn(async move {
+        let mut open_entry: Option<String> = None;
+
+        while let Some(event) = rx.recv().await {
+            let history = app.state::<PlayHistoryManager>();
+            match event {
+                PlayEvent::Started { track_id, context } => {
+                    let (uri, context_type, name) = match context {
+                        Some(ctx) => (
+                            Some(ctx.uri),
+                            Some(ctx.context_type.as_str().to_string()),
+                            ctx.name,
+                        ),
+                        None => (None, None, None),
+                    };
+
+                    match history.open_play(&track_id, uri, context_type, name).await {
+                        Ok(entry_id) => open_entry = Some(entry_id),
+                        Err(e) => {
+                            log::warn!("[History] Could not record play of {}: {}", track_id, e);
+                            open_entry = None;
+                        }
+                    }
+                }
+                PlayEvent::Ended {
+                    listened_secs,
+                    duration_secs,
+                    end,
+                } => {
+                    let Some(entry_id) = open_entry.take() else {
+                        continue;
+                    };
+                    let thresholds = load_thresholds(&app.state::<DatabaseManager>()).await;
+                    let outcome = thresholds.classify(listened_secs, duration_secs, end);
+
+                    if let Err(e) = history
+                        .close_play(&entry_id, listened_secs.round() as i64, outcome)
+                        .await
+                    {
+                        log::warn!("[History] Could not finish play {}: {}", entry_id, e);
+                        continue;
+                    }
+                    let _ = app.emit("play-history-changed", ());
+                }
+            }
+        }
+    });
+
+    tx
+}
+
+pub async fn load_thresholds(db: &DatabaseManager) -> PlayThresholds {
+    db.get_setting(THRESHOLDS_SETTING)
+        .await
+        .ok()
+        .flatten()
+        .and_then(|value| serde_json::from_str(&value).ok())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn classifies_by_listened_time() {
+        let t = PlayThresholds::default();
+
+        assert_eq!(
+            t.classify(100.0, 200.0, PlayEnd::MovedOn),
+            PlayOutcome::Completed
+        );
+        // Long tracks complete after four minutes
+        assert_eq!(
+            t.classify(240.0, 3600.0, PlayEnd::Stopped),
+            PlayOutcome::Completed
+        );
+        assert_eq!(
+            t.classify(60.0, 200.0, PlayEnd::MovedOn),
+            PlayOutcome::Partial
+        );
+        assert_eq!(
+            t.classify(10.0, 200.0, PlayEnd::MovedOn),
+            PlayOutcome::Skipped
+        );
+        assert_eq!(
+            t.classify(10.0, 200.0, PlayEnd::Stopped),
+            PlayOutcome::Abandoned
+        );
+        // Seeking to the end and letting it finish still counts as a play
+        assert_eq!(
+            t.classify(10.0, 200.0, PlayEnd::Finished),
+            PlayOutcome::Partial
+        );
+    }
+}
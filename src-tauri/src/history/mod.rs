@@ -1,8 +1,16 @@
+pub mod listening;
 pub mod models;
+mod stats;
 
-use crate::library::models::{TrackSource, UnifiedTrack};
-use models::PlayHistoryEntry;
-use sqlx::{Pool, Row, Sqlite};
+pub use stats::current_year;
+
+use crate::library::models::{
+    ExtendedTrackInfo, TrackSource, UnifiedTrack, TRACK_METADATA_COLUMNS, TRACK_METADATA_CTES,
+    TRACK_METADATA_JOINS,
+};
+use listening::PlayOutcome;
+use models::{PlayHistoryEntry, RecentContext};
+use sqlx::{Pool, Row, Sqlite, Transaction};
 use std::time::{SystemTime, UNIX_EPOCH};
 use uuid::Uuid;
 
@@ -15,11 +23,15 @@ impl PlayHistoryManager {
         Self { pool }
     }
 
-    pub async fn record_play(
+    /// Start a play without counting it yet. `close_play` counts it once
+    /// it's known whether the track was actually listened to; until then the
+    /// entry is marked in progress.
+    pub async fn open_play(
         &self,
         track_id: &str,
         context_uri: Option<String>,
         context_type: Option<String>,
+        context_name: Option<String>,
     ) -> Result<String, String> {
         let id = Uuid::new_v4().to_string();
         let now = SystemTime::now()
@@ -27,68 +39,121 @@ impl PlayHistoryManager {
             .unwrap()
             .as_secs() as i64;
 
-        let mut tx = self.pool.begin().await.map_err(|e| e.to_string())?;
-
-        // 1. Insert into history log
         sqlx::query(
-            "INSERT INTO play_history (id, track_id, played_at, context_uri, context_type) VALUES (?, ?, ?, ?, ?)",
+            "INSERT INTO play_history (id, track_id, played_at, context_uri, context_type, context_name, in_progress) VALUES (?, ?, ?, ?, ?, ?, 1)",
         )
         .bind(&id)
         .bind(track_id)
         .bind(now)
         .bind(&context_uri)
         .bind(&context_type)
-        .execute(&mut *tx)
+        .bind(&context_name)
+        .execute(&self.pool)
         .await
         .map_err(|e| e.to_string())?;
 
-        // 2. Update track statistics
-        sqlx::query(
-            "UPDATE tracks SET play_count = play_count + 1, last_played_at = ? WHERE id = ?",
-        )
-        .bind(now)
-        .bind(track_id)
-        .execute(&mut *tx)
-        .await
-        .map_err(|e| e.to_string())?;
+        Ok(id)
+    }
 
-        // 3. Update artist statistics
-        sqlx::query(
-            r#"
-            UPDATE artists SET play_count = COALESCE(play_count, 0) + 1, last_played_at = ?
-            WHERE id = (SELECT artist_id FROM tracks WHERE id = ?)
-            "#,
-        )
-        .bind(now)
-        .bind(track_id)
-        .execute(&mut *tx)
-        .await
-        .map_err(|e| e.to_string())?;
+    /// Finish a play opened with `open_play`. Plays count towards play
+    /// statistics, skips towards the track's skip count, and plays stopped
+    /// too early to be either are dropped.
+    pub async fn close_play(
+        &self,
+        entry_id: &str,
+        duration_played: i64,
+        outcome: PlayOutcome,
+    ) -> Result<(), String> {
+        let mut tx = self.pool.begin().await.map_err(|e| e.to_string())?;
 
-        // 4. Update album statistics (if track has album)
-        sqlx::query(
-            r#"
-            UPDATE albums SET play_count = COALESCE(play_count, 0) + 1, last_played_at = ?
-            WHERE id = (SELECT album_id FROM tracks WHERE id = ? AND album_id IS NOT NULL)
-            "#,
+        let Some(row) = sqlx::query(
+            "SELECT track_id, played_at, context_uri, context_type FROM play_history WHERE id = ?",
         )
-        .bind(now)
-        .bind(track_id)
-        .execute(&mut *tx)
+        .bind(entry_id)
+        .fetch_optional(&mut *tx)
         .await
-        .map_err(|e| e.to_string())?;
-
-        // 5. Update playlist statistics if context is playlist?
-        // Optional feature for later.
+        .map_err(|e| e.to_string())?
+        else {
+            return Ok(());
+        };
+        let track_id: String = row.get("track_id");
+
+        match outcome {
+            PlayOutcome::Abandoned => {
+                sqlx::query("DELETE FROM play_history WHERE id = ?")
+                    .bind(entry_id)
+                    .execute(&mut *tx)
+                    .await
+                    .map_err(|e| e.to_string())?;
+            }
+            PlayOutcome::Skipped => {
+                sqlx::query(
+                    "UPDATE play_history SET duration_played = ?, completed = 0, skipped = 1, in_progress = 0 WHERE id = ?",
+                )
+                .bind(duration_played)
+                .bind(entry_id)
+                .execute(&mut *tx)
+                .await
+                .map_err(|e| e.to_string())?;
+
+                sqlx::query(
+                    "UPDATE tracks SET skip_count = COALESCE(skip_count, 0) + 1 WHERE id = ?",
+                )
+                .bind(&track_id)
+                .execute(&mut *tx)
+                .await
+                .map_err(|e| e.to_string())?;
+            }
+            PlayOutcome::Completed | PlayOutcome::Partial => {
+                sqlx::query(
+                    "UPDATE play_history SET duration_played = ?, completed = ?, skipped = 0, in_progress = 0 WHERE id = ?",
+                )
+                .bind(duration_played)
+                .bind((outcome == PlayOutcome::Completed) as i64)
+                .bind(entry_id)
+                .execute(&mut *tx)
+                .await
+                .map_err(|e| e.to_string())?;
+
+                let context_uri: Option<String> = row.get("context_uri");
+                let context_type: Option<String> = row.get("context_type");
+                count_play(
+                    &mut tx,
+                    &track_id,
+                    row.get("played_at"),
+                    context_uri.as_deref(),
+                    context_type.as_deref(),
+                )
+                .await?;
+            }
+        }
 
         tx.commit().await.map_err(|e| e.to_string())?;
 
         log::info!(
-            "Recorded playback for track {} (Context: {:?})",
+            "Finished playback of track {} after {}s: {:?}",
             track_id,
-            context_type
+            duration_played,
+            outcome
         );
-        Ok(id)
+        Ok(())
+    }
+
+    /// Drop plays that were never closed because the app quit or crashed
+    /// mid-track. How long they were listened to is unknown, so they are
+    /// neither counted nor kept for statistics.
+    pub async fn drop_unfinished_plays(&self) -> Result<u64, String> {
+        let result = sqlx::query("DELETE FROM play_history WHERE in_progress = 1")
+            .execute(&self.pool)
+            .await
+            .map_err(|e| e.to_string())?;
+        if result.rows_affected() > 0 {
+            log::info!(
+                "[History] Dropped {} plays interrupted by a shutdown",
+                result.rows_affected()
+            );
+        }
+        Ok(result.rows_affected())
     }
 
     pub async fn update_play_completion(
@@ -123,24 +188,64 @@ impl PlayHistoryManager {
         Ok(entries)
     }
 
+    /// Get the most recently played contexts (albums, playlists, artists...) for "jump back in".
+    /// Playlists that no longer exist are skipped and their current title is preferred.
+    pub async fn get_recent_contexts(&self, limit: i64) -> Result<Vec<RecentContext>, String> {
+        let contexts = sqlx::query_as::<_, RecentContext>(
+            r#"
+            SELECT
+                ph.context_type,
+                ph.context_uri,
+                COALESCE(MAX(p.title), (
+                    SELECT h2.context_name FROM play_history h2
+                    WHERE h2.context_type = ph.context_type
+                      AND h2.context_uri = ph.context_uri
+                      AND h2.context_name IS NOT NULL
+                    ORDER BY h2.played_at DESC
+                    LIMIT 1
+                )) as context_name,
+                MAX(ph.played_at) as last_played_at,
+                COUNT(*) as play_count
+            FROM play_history ph
+            LEFT JOIN playlists p ON ph.context_type = 'playlist' AND p.id = ph.context_uri
+            WHERE ph.context_uri IS NOT NULL AND ph.context_type IS NOT NULL
+              AND COALESCE(ph.skipped, 0) = 0 AND ph.in_progress = 0
+            GROUP BY ph.context_type, ph.context_uri
+            HAVING ph.context_type != 'playlist' OR MAX(p.id) IS NOT NULL
+            ORDER BY last_played_at DESC
+            LIMIT ?
+            "#,
+        )
+        .bind(limit)
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| e.to_string())?;
+
+        Ok(contexts)
+    }
+
     pub async fn get_unique_recent_tracks(&self, limit: i64) -> Result<Vec<UnifiedTrack>, String> {
         // Optimized query using the new last_played_at column on tracks table
-        let rows = sqlx::query(
+        let rows = sqlx::query(&format!(
             r#"
+            WITH {}
             SELECT 
                 t.id, t.title, t.duration, t.source_type, t.file_path,
                 t.play_count, t.skip_count, t.last_played_at, t.added_at, t.audio_quality,
                 t.provider_id, t.external_id, t.artist_id, t.album_id,
+                {},
                 a.name as artist_name,
                 al.title as album_title, al.cover_url
             FROM tracks t
             JOIN artists a ON t.artist_id = a.id
             LEFT JOIN albums al ON t.album_id = al.id
+            {}
             WHERE t.last_played_at IS NOT NULL
             ORDER BY t.last_played_at DESC
             LIMIT ?
             "#,
-        )
+            TRACK_METADATA_CTES, TRACK_METADATA_COLUMNS, TRACK_METADATA_JOINS
+        ))
         .bind(limit)
         .fetch_all(&self.pool)
         .await
@@ -199,6 +304,7 @@ impl PlayHistoryManager {
                 added_at,
                 provider_id,
                 external_id,
+                metadata: ExtendedTrackInfo::from_row(&row),
             });
         }
 
@@ -206,22 +312,26 @@ impl PlayHistoryManager {
     }
 
     pub async fn get_most_played_tracks(&self, limit: i64) -> Result<Vec<UnifiedTrack>, String> {
-        let rows = sqlx::query(
+        let rows = sqlx::query(&format!(
             r#"
+            WITH {}
             SELECT 
                 t.id, t.title, t.duration, t.source_type, t.file_path,
                 t.play_count, t.skip_count, t.last_played_at, t.added_at, t.audio_quality,
                 t.provider_id, t.external_id, t.artist_id, t.album_id,
+                {},
                 a.name as artist_name,
                 al.title as album_title, al.cover_url
             FROM tracks t
             JOIN artists a ON t.artist_id = a.id
             LEFT JOIN albums al ON t.album_id = al.id
+            {}
             WHERE t.play_count > 0
             ORDER BY t.play_count DESC
             LIMIT ?
             "#,
-        )
+            TRACK_METADATA_CTES, TRACK_METADATA_COLUMNS, TRACK_METADATA_JOINS
+        ))
         .bind(limit)
         .fetch_all(&self.pool)
         .await
@@ -280,6 +390,7 @@ impl PlayHistoryManager {
                 added_at,
                 provider_id,
                 external_id,
+                metadata: ExtendedTrackInfo::from_row(&row),
             });
         }
 
@@ -287,11 +398,13 @@ impl PlayHistoryManager {
     }
 
     pub async fn get_play_count(&self, track_id: &str) -> Result<i64, String> {
-        let count: (i64,) = sqlx::query_as("SELECT COUNT(*) FROM play_history WHERE track_id = ?")
-            .bind(track_id)
-            .fetch_one(&self.pool)
-            .await
-            .map_err(|e| e.to_string())?;
+        let count: (i64,) = sqlx::query_as(
+            "SELECT COUNT(*) FROM play_history WHERE track_id = ? AND COALESCE(skipped, 0) = 0 AND in_progress = 0",
+        )
+        .bind(track_id)
+        .fetch_one(&self.pool)
+        .await
+        .map_err(|e| e.to_string())?;
 
         Ok(count.0)
     }
@@ -342,3 +455,119 @@ impl PlayHistoryManager {
         Ok(rows)
     }
 }
+
+/// Bump play statistics of a track and what it was played from
+async fn count_play(
+    tx: &mut Transaction<'_, Sqlite>,
+    track_id: &str,
+    played_at: i64,
+    context_uri: Option<&str>,
+    context_type: Option<&str>,
+) -> Result<(), String> {
+    sqlx::query("UPDATE tracks SET play_count = play_count + 1, last_played_at = ? WHERE id = ?")
+        .bind(played_at)
+        .bind(track_id)
+        .execute(&mut **tx)
+        .await
+        .map_err(|e| e.to_string())?;
+
+    sqlx::query(
+        r#"
+        UPDATE artists SET play_count = COALESCE(play_count, 0) + 1, last_played_at = ?
+        WHERE id = (SELECT artist_id FROM tracks WHERE id = ?)
+        "#,
+    )
+    .bind(played_at)
+    .bind(track_id)
+    .execute(&mut **tx)
+    .await
+    .map_err(|e| e.to_string())?;
+
+    // Only when the track has an album
+    sqlx::query(
+        r#"
+        UPDATE albums SET play_count = COALESCE(play_count, 0) + 1, last_played_at = ?
+        WHERE id = (SELECT album_id FROM tracks WHERE id = ? AND album_id IS NOT NULL)
+        "#,
+    )
+    .bind(played_at)
+    .bind(track_id)
+    .execute(&mut **tx)
+    .await
+    .map_err(|e| e.to_string())?;
+
+    if context_type == Some("playlist") {
+        if let Some(playlist_id) = context_uri {
+            sqlx::query("UPDATE playlists SET last_played_at = ? WHERE id = ?")
+                .bind(played_at)
+                .bind(playlist_id)
+                .execute(&mut **tx)
+                .await
+                .map_err(|e| e.to_string())?;
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::DatabaseManager;
+
+    #[tokio::test]
+    async fn unfinished_plays_are_dropped() {
+        let db = DatabaseManager::in_memory().await;
+        db.seed(&[
+            "INSERT INTO artists (id, name) VALUES ('ar', 'Artist')",
+            "INSERT INTO tracks (id, title, artist_id, duration, source_type) VALUES ('t', 'Song', 'ar', 180, 'LOCAL')",
+        ])
+        .await;
+        let history = PlayHistoryManager::new(db.pool.clone());
+
+        let finished = history.open_play("t", None, None, None).await.unwrap();
+        history
+            .close_play(&finished, 170, PlayOutcome::Completed)
+            .await
+            .unwrap();
+        history.open_play("t", None, None, None).await.unwrap();
+        assert_eq!(history.get_play_count("t").await.unwrap(), 1);
+
+        assert_eq!(history.drop_unfinished_plays().await.unwrap(), 1);
+        let ids: Vec<String> = sqlx::query_scalar("SELECT id FROM play_history")
+            .fetch_all(&db.pool)
+            .await
+            .unwrap();
+        assert_eq!(ids, vec![finished]);
+    }
+
+    #[tokio::test]
+    async fn recent_contexts_skip_plays_in_progress() {
+        let db = DatabaseManager::in_memory().await;
+        db.seed(&[
+            "INSERT INTO artists (id, name) VALUES ('ar', 'Artist')",
+            "INSERT INTO tracks (id, title, artist_id, duration, source_type) VALUES ('t', 'Song', 'ar', 180, 'LOCAL')",
+        ])
+        .await;
+        let history = PlayHistoryManager::new(db.pool.clone());
+
+        let entry = history
+            .open_play(
+                "t",
+                Some("al".to_string()),
+                Some("album".to_string()),
+                Some("Album".to_string()),
+            )
+            .await
+            .unwrap();
+        assert!(history.get_recent_contexts(10).await.unwrap().is_empty());
+
+        history
+            .close_play(&entry, 170, PlayOutcome::Completed)
+            .await
+            .unwrap();
+        let contexts = history.get_recent_contexts(10).await.unwrap();
+        assert_eq!(contexts.len(), 1);
+        assert_eq!(contexts[0].context_uri, "al");
+    }
+}
@@ -9,3 +9,115 @@ pub struct PlayHistoryEntry {
     pub completed: i64, // SQLite uses INTEGER for BOOLEAN
     pub source: Option<String>,
 }
+
+/// A recently played source (album, playlist, artist...) for "jump back in"
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+pub struct RecentContext {
+    pub context_type: String,
+    pub context_uri: String,
+    pub context_name: Option<String>,
+    pub last_played_at: i64,
+    pub play_count: i64,
+}
+
+/// Period listening stats are computed over
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum StatsRange {
+    /// The last `days` calendar days, today included
+    Days { days: u32 },
+    /// Unix seconds, `to` exclusive
+    Custom { from: i64, to: i64 },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum StatsKind {
+    Track,
+    Artist,
+    Album,
+    Genre,
+}
+
+/// Totals for a period. Minutes include skipped plays, counts don't.
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+pub struct ListeningSummary {
+    pub minutes: f64,
+    pub plays: i64,
+    pub skips: i64,
+    pub tracks: i64,
+    pub artists: i64,
+    pub days_listened: i64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+pub struct DailyListening {
+    /// Local date, `YYYY-MM-DD`
+    pub day: String,
+    pub minutes: f64,
+    pub plays: i64,
+}
+
+/// Listening started in one hour of the week, in local time
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+pub struct HeatmapCell {
+    /// 0 is Sunday
+    pub weekday: i64,
+    pub hour: i64,
+    pub minutes: f64,
+    pub plays: i64,
+}
+
+/// A track, artist, album or genre ranked by plays in a period
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+pub struct StatsEntry {
+    pub id: String,
+    pub name: String,
+    /// Artist of a track or album
+    pub subtitle: Option<String>,
+    pub cover_url: Option<String>,
+    pub plays: i64,
+    pub minutes: f64,
+}
+
+/// Consecutive local days with at least one play
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ListeningStreak {
+    pub start: String,
+    pub end: String,
+    pub days: i64,
+}
+
+#[derive(Debug, Clone, Serialize, Default)]
+pub struct ListeningStreaks {
+    /// Streak still going, i.e. ending today or yesterday
+    pub current: Option<ListeningStreak>,
+    pub longest: Option<ListeningStreak>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ArtistDiscovery {
+    /// Artists played in the period
+    pub artists: i64,
+    /// Of those, artists first played in the period
+    pub new_artists: i64,
+    pub rate: f64,
+    /// New artists, most played first
+    pub discovered: Vec<StatsEntry>,
+}
+
+/// A "wrapped"-style look back at one calendar year
+#[derive(Debug, Clone, Serialize)]
+pub struct YearInReview {
+    pub year: i32,
+    pub summary: ListeningSummary,
+    pub top_tracks: Vec<StatsEntry>,
+    pub top_artists: Vec<StatsEntry>,
+    pub top_albums: Vec<StatsEntry>,
+    pub top_genres: Vec<StatsEntry>,
+    pub busiest_day: Option<DailyListening>,
+    /// Local hour most listening started in
+    pub top_hour: Option<i64>,
+    pub longest_streak: Option<ListeningStreak>,
+    pub new_artists: i64,
+}
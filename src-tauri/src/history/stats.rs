@@ -0,0 +1,472 @@
+use super::models::{
+    ArtistDiscovery, DailyListening, HeatmapCell, ListeningStreak, ListeningStreaks,
+    ListeningSummary, StatsEntry, StatsKind, StatsRange, YearInReview,
+};
+use super::PlayHistoryManager;
+use chrono::{Datelike, Duration, Local, NaiveDate, TimeZone};
+use sqlx::Row;
+
+/// Entries shown per list in a year in review
+const REVIEW_TOP_LIMIT: i64 = 5;
+
+/// Most new artists listed by `get_artist_discovery`
+const DISCOVERED_LIMIT: usize = 20;
+
+/// Finished history entries between ?1 and ?2, with the time actually
+/// listened. Entries from before the player recorded durations count as the
+/// whole track; plays still in progress are left out. Skipped plays count
+/// towards listening time but not towards plays.
+const PLAYS: &str = r#"
+    plays AS (
+        SELECT
+            ph.track_id,
+            ph.played_at,
+            COALESCE(ph.duration_played, t.duration, 0) AS listened,
+            COALESCE(ph.skipped, 0) = 0 AS counted,
+            t.artist_id,
+            t.album_id
+        FROM play_history ph
+        LEFT JOIN tracks t ON t.id = ph.track_id
+        WHERE ph.played_at >= ?1 AND ph.played_at < ?2 AND ph.in_progress = 0
+    )
+"#;
+
+impl StatsRange {
+    /// Unix seconds the range covers, end exclusive
+    pub fn bounds(&self) -> Result<(i64, i64), String> {
+        match *self {
+            StatsRange::Days { days } => {
+                let today = Local::now().date_naive();
+                let first = today - Duration::days(days.max(1) as i64 - 1);
+                Ok((
+                    local_midnight(first)?,
+                    local_midnight(today + Duration::days(1))?,
+                ))
+            }
+            StatsRange::Custom { from, to } if from < to => Ok((from, to)),
+            StatsRange::Custom { .. } => Err("The range must end after it starts".to_string()),
+        }
+    }
+}
+
+impl StatsKind {
+    /// Columns, joins and grouping ranking this kind, over `plays p`
+    fn ranking(self) -> (&'static str, &'static str, &'static str) {
+        match self {
+            StatsKind::Track => (
+                "p.track_id AS id, t.title AS name, a.name AS subtitle, al.cover_url",
+                r#"JOIN tracks t ON t.id = p.track_id
+                   LEFT JOIN artists a ON a.id = t.artist_id
+                   LEFT JOIN albums al ON al.id = t.album_id"#,
+                "p.track_id",
+            ),
+            StatsKind::Artist => (
+                "a.id, a.name, NULL AS subtitle, a.cover_url",
+                "JOIN artists a ON a.id = p.artist_id",
+                "a.id",
+            ),
+            StatsKind::Album => (
+                "al.id, al.title AS name, a.name AS subtitle, al.cover_url",
+                r#"JOIN albums al ON al.id = p.album_id
+                   LEFT JOIN artists a ON a.id = al.artist_id"#,
+                "al.id",
+            ),
+            StatsKind::Genre => (
+                "g.genre AS id, g.genre AS name, NULL AS subtitle, NULL AS cover_url",
+                "JOIN track_genres g ON g.track_id = p.track_id",
+                "g.genre",
+            ),
+        }
+    }
+}
+
+impl PlayHistoryManager {
+    pub async fn get_listening_summary(
+        &self,
+        range: StatsRange,
+    ) -> Result<ListeningSummary, String> {
+        let (from, to) = range.bounds()?;
+        sqlx::query_as::<_, ListeningSummary>(&format!(
+            r#"
+            WITH {}
+            SELECT
+                COALESCE(SUM(listened), 0) / 60.0 AS minutes,
+                COALESCE(SUM(counted), 0) AS plays,
+                COALESCE(SUM(NOT counted), 0) AS skips,
+                COUNT(DISTINCT CASE WHEN counted THEN track_id END) AS tracks,
+                COUNT(DISTINCT CASE WHEN counted THEN artist_id END) AS artists,
+                COUNT(DISTINCT date(played_at, 'unixepoch', 'localtime')) AS days_listened
+            FROM plays
+            "#,
+            PLAYS
+        ))
+        .bind(from)
+        .bind(to)
+        .fetch_one(&self.pool)
+        .await
+        .map_err(|e| e.to_string())
+    }
+
+    /// Minutes listened on each local day of the range, quiet days included.
+    /// Ranges reaching back before the first play start at the first play,
+    /// but never after the range's last day.
+    pub async fn get_daily_listening(
+        &self,
+        range: StatsRange,
+    ) -> Result<Vec<DailyListening>, String> {
+        let (from, to) = range.bounds()?;
+        sqlx::query_as::<_, DailyListening>(&format!(
+            r#"
+            WITH RECURSIVE {},
+            days(day) AS (
+                SELECT date(MIN(MAX(?1, COALESCE((SELECT MIN(played_at) FROM play_history WHERE in_progress = 0), ?1)),
+                                ?2 - 1),
+                            'unixepoch', 'localtime')
+                UNION ALL
+                SELECT date(day, '+1 day') FROM days
+                WHERE day < date(?2 - 1, 'unixepoch', 'localtime')
+            ),
+            per_day AS (
+                SELECT date(played_at, 'unixepoch', 'localtime') AS day,
+                       SUM(listened) AS listened, SUM(counted) AS plays
+                FROM plays
+                GROUP BY 1
+            )
+            SELECT d.day, COALESCE(pd.listened, 0) / 60.0 AS minutes, COALESCE(pd.plays, 0) AS plays
+            FROM days d
+            LEFT JOIN per_day pd ON pd.day = d.day
+            ORDER BY d.day
+            "#,
+            PLAYS
+        ))
+        .bind(from)
+        .bind(to)
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| e.to_string())
+    }
+
+    /// Listening by hour of the week, by when plays started. Hours without
+    /// any listening are left out.
+    pub async fn get_listening_heatmap(
+        &self,
+        range: StatsRange,
+    ) -> Result<Vec<HeatmapCell>, String> {
+        let (from, to) = range.bounds()?;
+        sqlx::query_as::<_, HeatmapCell>(&format!(
+            r#"
+            WITH {}
+            SELECT
+                CAST(strftime('%w', played_at, 'unixepoch', 'localtime') AS INTEGER) AS weekday,
+                CAST(strftime('%H', played_at, 'unixepoch', 'localtime') AS INTEGER) AS hour,
+                SUM(listened) / 60.0 AS minutes,
+                SUM(counted) AS plays
+            FROM plays
+            GROUP BY weekday, hour
+            ORDER BY weekday, hour
+            "#,
+            PLAYS
+        ))
+        .bind(from)
+        .bind(to)
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| e.to_string())
+    }
+
+    pub async fn get_top_in_range(
+        &self,
+        range: StatsRange,
+        kind: StatsKind,
+        limit: i64,
+    ) -> Result<Vec<StatsEntry>, String> {
+        let (from, to) = range.bounds()?;
+        let (columns, joins, group_by) = kind.ranking();
+        sqlx::query_as::<_, StatsEntry>(&format!(
+            r#"
+            WITH {}
+            SELECT {}, SUM(p.counted) AS plays, SUM(p.listened) / 60.0 AS minutes
+            FROM plays p
+            {}
+            GROUP BY {}
+            HAVING SUM(p.counted) > 0
+            ORDER BY plays DESC, minutes DESC
+            LIMIT ?3
+            "#,
+            PLAYS, columns, joins, group_by
+        ))
+        .bind(from)
+        .bind(to)
+        .bind(limit)
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| e.to_string())
+    }
+
+    /// Current and longest run of days with plays in the range
+    pub async fn get_listening_streaks(
+        &self,
+        range: StatsRange,
+    ) -> Result<ListeningStreaks, String> {
+        let (from, to) = range.bounds()?;
+        let rows = sqlx::query(&format!(
+            r#"
+            WITH {},
+            days AS (
+                SELECT DISTINCT date(played_at, 'unixepoch', 'localtime') AS day
+                FROM plays WHERE counted
+            ),
+            islands AS (
+                SELECT day, julianday(day) - ROW_NUMBER() OVER (ORDER BY day) AS island
+                FROM days
+            )
+            SELECT MIN(day) AS start, MAX(day) AS end, COUNT(*) AS days,
+                   MAX(day) >= date('now', 'localtime', '-1 day') AS ongoing
+            FROM islands
+            GROUP BY island
+            ORDER BY days DESC, end DESC
+            "#,
+            PLAYS
+        ))
+        .bind(from)
+        .bind(to)
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| e.to_string())?;
+
+        let mut streaks = ListeningStreaks::default();
+        for row in rows {
+            let streak = ListeningStreak {
+                start: row.get("start"),
+                end: row.get("end"),
+                days: row.get("days"),
+            };
+            if streaks.current.is_none() && row.get::<i64, _>("ongoing") != 0 {
+                streaks.current = Some(streak.clone());
+            }
+            if streaks.longest.is_none() {
+                streaks.longest = Some(streak);
+            }
+        }
+        Ok(streaks)
+    }
+
+    /// How many of the artists played in the range were played for the
+    /// first time in it
+    pub async fn get_artist_discovery(&self, range: StatsRange) -> Result<ArtistDiscovery, String> {
+        let (from, to) = range.bounds()?;
+        let rows = sqlx::query(&format!(
+            r#"
+            WITH {},
+            played AS (
+                SELECT artist_id, SUM(counted) AS plays, SUM(listened) / 60.0 AS minutes
+                FROM plays
+                WHERE artist_id IS NOT NULL
+                GROUP BY artist_id
+                HAVING SUM(counted) > 0
+            ),
+            first_played AS (
+                SELECT t.artist_id, MIN(ph.played_at) AS first_at
+                FROM play_history ph
+                JOIN tracks t ON t.id = ph.track_id
+                WHERE COALESCE(ph.skipped, 0) = 0 AND ph.in_progress = 0
+                  AND t.artist_id IN (SELECT artist_id FROM played)
+                GROUP BY t.artist_id
+            )
+            SELECT a.id, a.name, NULL AS subtitle, a.cover_url, pl.plays, pl.minutes,
+                   f.first_at >= ?1 AS is_new
+            FROM played pl
+            JOIN artists a ON a.id = pl.artist_id
+            JOIN first_played f ON f.artist_id = pl.artist_id
+            ORDER BY pl.plays DESC, pl.minutes DESC
+            "#,
+            PLAYS
+        ))
+        .bind(from)
+        .bind(to)
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| e.to_string())?;
+
+        let artists = rows.len() as i64;
+        let discovered: Vec<StatsEntry> = rows
+            .iter()
+            .filter(|row| row.get::<i64, _>("is_new") != 0)
+            .map(|row| StatsEntry {
+                id: row.get("id"),
+                name: row.get("name"),
+                subtitle: None,
+                cover_url: row.get("cover_url"),
+                plays: row.get("plays"),
+                minutes: row.get("minutes"),
+            })
+            .collect();
+        let new_artists = discovered.len() as i64;
+
+        Ok(ArtistDiscovery {
+            artists,
+            new_artists,
+            rate: if artists > 0 {
+                new_artists as f64 / artists as f64
+            } else {
+                0.0
+            },
+            discovered: discovered.into_iter().take(DISCOVERED_LIMIT).collect(),
+        })
+    }
+
+    pub async fn get_year_in_review(&self, year: i32) -> Result<YearInReview, String> {
+        let start = NaiveDate::from_ymd_opt(year, 1, 1).ok_or("Invalid year")?;
+        let end = NaiveDate::from_ymd_opt(year + 1, 1, 1).ok_or("Invalid year")?;
+        let range = StatsRange::Custom {
+            from: local_midnight(start)?,
+            to: local_midnight(end)?,
+        };
+
+        let daily = self.get_daily_listening(range).await?;
+        let busiest_day = daily
+            .into_iter()
+            .filter(|d| d.minutes > 0.0)
+            .max_by(|a, b| a.minutes.total_cmp(&b.minutes));
+
+        let mut by_hour = [0.0; 24];
+        for cell in self.get_listening_heatmap(range).await? {
+            if let Some(minutes) = by_hour.get_mut(cell.hour as usize) {
+                *minutes += cell.minutes;
+            }
+        }
+        let top_hour = (0..24)
+            .filter(|&h| by_hour[h] > 0.0)
+            .max_by(|&a, &b| by_hour[a].total_cmp(&by_hour[b]))
+            .map(|h| h as i64);
+
+        Ok(YearInReview {
+            year,
+            summary: self.get_listening_summary(range).await?,
+            top_tracks: self
+                .get_top_in_range(range, StatsKind::Track, REVIEW_TOP_LIMIT)
+                .await?,
+            top_artists: self
+                .get_top_in_range(range, StatsKind::Artist, REVIEW_TOP_LIMIT)
+                .await?,
+            top_albums: self
+                .get_top_in_range(range, StatsKind::Album, REVIEW_TOP_LIMIT)
+                .await?,
+            top_genres: self
+                .get_top_in_range(range, StatsKind::Genre, REVIEW_TOP_LIMIT)
+                .await?,
+            busiest_day,
+            top_hour,
+            longest_streak: self.get_listening_streaks(range).await?.longest,
+            new_artists: self.get_artist_discovery(range).await?.new_artists,
+        })
+    }
+}
+
+pub fn current_year() -> i32 {
+    Local::now().year()
+}
+
+/// Start of a local day in unix seconds. Days starting in a DST gap take
+/// the earliest valid time.
+fn local_midnight(day: NaiveDate) -> Result<i64, String> {
+    let midnight = day.and_hms_opt(0, 0, 0).ok_or("Invalid date")?;
+    Local
+        .from_local_datetime(&midnight)
+        .earliest()
+        .or_else(|| {
+            Local
+                .from_local_datetime(&(midnight + Duration::hours(1)))
+                .earliest()
+        })
+        .map(|t| t.timestamp())
+        .ok_or_else(|| format!("No local midnight on {}", day))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::DatabaseManager;
+
+    #[test]
+    fn day_ranges_cover_whole_local_days() {
+        let (from, to) = StatsRange::Days { days: 7 }.bounds().unwrap();
+        let now = Local::now().timestamp();
+        assert!(from <= now && now < to);
+
+        let days = (to - from) as f64 / 86_400.0;
+        assert!((days - 7.0).abs() < 0.1, "{} days", days);
+
+        assert!(StatsRange::Custom { from: 10, to: 10 }.bounds().is_err());
+    }
+
+    #[tokio::test]
+    async fn daily_listening_stays_inside_the_range() {
+        let db = DatabaseManager::in_memory().await;
+        db.seed(&[
+            "INSERT INTO artists (id, name) VALUES ('ar', 'Artist')",
+            "INSERT INTO tracks (id, title, artist_id, duration, source_type) VALUES ('t', 'Song', 'ar', 180, 'LOCAL')",
+        ])
+        .await;
+        let history = PlayHistoryManager::new(db.pool.clone());
+        let day = |d| local_midnight(NaiveDate::from_ymd_opt(2024, 6, d).unwrap()).unwrap();
+        let range = StatsRange::Custom {
+            from: day(10),
+            to: day(13),
+        };
+        let play = |id: &'static str, at: i64| {
+            sqlx::query(
+                "INSERT INTO play_history (id, track_id, played_at, duration_played) VALUES (?, 't', ?, 120)",
+            )
+            .bind(id)
+            .bind(at)
+        };
+        let daily = |daily: Vec<DailyListening>| -> Vec<(String, f64)> {
+            daily.into_iter().map(|d| (d.day, d.minutes)).collect()
+        };
+
+        // First played after the range ends
+        play("later", day(20) + 3600)
+            .execute(&db.pool)
+            .await
+            .unwrap();
+        assert_eq!(
+            daily(history.get_daily_listening(range).await.unwrap()),
+            vec![("2024-06-12".to_string(), 0.0)]
+        );
+
+        play("inside", day(11) + 3600)
+            .execute(&db.pool)
+            .await
+            .unwrap();
+        assert_eq!(
+            daily(history.get_daily_listening(range).await.unwrap()),
+            vec![
+                ("2024-06-11".to_string(), 2.0),
+                ("2024-06-12".to_string(), 0.0),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn open_plays_are_left_out_and_old_ones_count_in_full() {
+        let db = DatabaseManager::in_memory().await;
+        db.seed(&[
+            "INSERT INTO artists (id, name) VALUES ('ar', 'Artist')",
+            "INSERT INTO tracks (id, title, artist_id, duration, source_type) VALUES ('t', 'Song', 'ar', 180, 'LOCAL')",
+            // Recorded before durations were
+            "INSERT INTO play_history (id, track_id, played_at) VALUES ('old', 't', 1000)",
+            // Still playing, or cut off by a crash
+            "INSERT INTO play_history (id, track_id, played_at, in_progress) VALUES ('open', 't', 2000, 1)",
+        ])
+        .await;
+        let history = PlayHistoryManager::new(db.pool.clone());
+
+        let summary = history
+            .get_listening_summary(StatsRange::Custom { from: 0, to: 3000 })
+            .await
+            .unwrap();
+        assert_eq!(summary.plays, 1);
+        assert_eq!(summary.minutes, 3.0);
+    }
+}
@@ -40,6 +40,9 @@ pub struct BaseItemDto {
     pub parent_index_number: Option<u32>,
     pub image_tags: Option<ImageTags>,
     pub primary_image_aspect_ratio: Option<f64>,
+    pub child_count: Option<u32>,
+    /// Id of this entry within a playlist, set on playlist item listings
+    pub playlist_item_id: Option<String>,
 }
 
 #[derive(Debug, Clone, Deserialize)]
@@ -1,13 +1,20 @@
-use crate::models::{Album, Artist, Quality, SearchResults, StreamInfo, Track};
+use crate::models::{
+    Album, Artist, LikedItem, LikedItems, LikedTrack, Playlist, Quality, SearchResults, StreamInfo,
+    Track,
+};
 use crate::providers::traits::MusicProvider;
 use anyhow::{anyhow, Result};
 use async_trait::async_trait;
 use reqwest::header::{HeaderMap, HeaderValue, AUTHORIZATION};
 use reqwest::Client;
 use serde_json::Value;
+use std::collections::HashMap;
 
 use super::models::*;
 
+/// Playlist entries added or removed per request
+const PLAYLIST_BATCH: usize = 100;
+
 pub struct JellyfinProvider {
     client: Client,
     pub server_url: String,
@@ -71,6 +78,82 @@ impl JellyfinProvider {
         )
     }
 
+    async fn playlist_items(&self, playlist_id: &str) -> Result<Vec<BaseItemDto>> {
+        if !self.initialized {
+            return Err(anyhow!("Jellyfin provider not initialized"));
+        }
+        let base = self.server_url.trim_end_matches('/');
+        let url = format!(
+            "{}/Playlists/{}/Items?UserId={}",
+            base, playlist_id, self.user_id
+        );
+
+        let resp: ItemsResult = self
+            .client
+            .get(&url)
+            .headers(self.headers())
+            .send()
+            .await?
+            .json()
+            .await?;
+        Ok(resp.items)
+    }
+
+    /// `(entry id, item id)` for each entry of a playlist, in order
+    async fn playlist_entries(&self, playlist_id: &str) -> Result<Vec<(String, String)>> {
+        Ok(self
+            .playlist_items(playlist_id)
+            .await?
+            .into_iter()
+            .filter_map(|item| item.playlist_item_id.map(|entry| (entry, item.id)))
+            .collect())
+    }
+
+    fn audio_track(&self, item: BaseItemDto) -> Track {
+        let artist = item.primary_artist();
+        let artist_id = item
+            .primary_artist_id()
+            .map(|id| format!("jellyfin:{}", id));
+        let duration = item.ticks_to_seconds();
+        let cover_url = Some(self.image_url(&item.id, 640));
+
+        Track {
+            id: format!("jellyfin:{}", item.id),
+            title: item.name,
+            artist,
+            artist_id,
+            album: item.album.unwrap_or_default(),
+            album_id: item.album_id.map(|id| format!("jellyfin:{}", id)),
+            duration,
+            cover_url,
+        }
+    }
+
+    /// Add or remove an item of any kind from the user's favourites
+    async fn set_favorite(&self, item_id: &str, favorite: bool) -> Result<()> {
+        if !self.initialized {
+            return Err(anyhow!("Jellyfin provider not initialized"));
+        }
+        let base = self.server_url.trim_end_matches('/');
+        let clean_id = item_id.strip_prefix("jellyfin:").unwrap_or(item_id);
+        let url = format!(
+            "{}/UserFavoriteItems/{}?userId={}",
+            base, clean_id, self.user_id
+        );
+
+        let request = if favorite {
+            self.client.post(&url)
+        } else {
+            self.client.delete(&url)
+        };
+        request
+            .headers(self.headers())
+            .send()
+            .await?
+            .error_for_status()?;
+        Ok(())
+    }
+
     pub async fn authenticate(&mut self, username: &str, password: &str) -> Result<()> {
         let base = self.server_url.trim_end_matches('/');
         let url = format!("{}/Users/AuthenticateByName", base);
@@ -474,4 +557,313 @@ impl MusicProvider for JellyfinProvider {
 
         Ok(tracks)
     }
+
+    fn supports_playlists(&self) -> bool {
+        true
+    }
+
+    async fn get_playlists(&self) -> Result<Vec<Playlist>> {
+        if !self.initialized {
+            return Err(anyhow!("Jellyfin provider not initialized"));
+        }
+        let base = self.server_url.trim_end_matches('/');
+        let url = format!(
+            "{}/Users/{}/Items?IncludeItemTypes=Playlist&MediaTypes=Audio&Recursive=true&Fields=ChildCount",
+            base, self.user_id
+        );
+
+        let resp: ItemsResult = self
+            .client
+            .get(&url)
+            .headers(self.headers())
+            .send()
+            .await?
+            .json()
+            .await?;
+
+        Ok(resp
+            .items
+            .into_iter()
+            .map(|item| Playlist {
+                cover_url: Some(self.image_url(&item.id, 640)),
+                id: item.id,
+                title: item.name,
+                description: None,
+                track_count: item.child_count.unwrap_or(0),
+                changed: None,
+            })
+            .collect())
+    }
+
+    async fn get_playlist_tracks(&self, playlist_id: &str) -> Result<Vec<Track>> {
+        Ok(self
+            .playlist_items(playlist_id)
+            .await?
+            .into_iter()
+            .map(|item| self.audio_track(item))
+            .collect())
+    }
+
+    async fn create_playlist(&self, name: &str, track_ids: &[String]) -> Result<String> {
+        if !self.initialized {
+            return Err(anyhow!("Jellyfin provider not initialized"));
+        }
+        let base = self.server_url.trim_end_matches('/');
+        let url = format!("{}/Playlists", base);
+
+        let ids: Vec<&str> = track_ids
+            .iter()
+            .map(|id| id.strip_prefix("jellyfin:").unwrap_or(id))
+            .collect();
+        let body = serde_json::json!({
+            "Name": name,
+            "Ids": ids,
+            "UserId": self.user_id,
+            "MediaType": "Audio"
+        });
+
+        let resp: Value = self
+            .client
+            .post(&url)
+            .headers(self.headers())
+            .json(&body)
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+
+        resp.get("Id")
+            .and_then(|v| v.as_str())
+            .map(str::to_string)
+            .ok_or_else(|| anyhow!("Jellyfin did not return the new playlist id"))
+    }
+
+    async fn set_playlist_tracks(&self, playlist_id: &str, track_ids: &[String]) -> Result<()> {
+        let base = self.server_url.trim_end_matches('/');
+        let ids: Vec<&str> = track_ids
+            .iter()
+            .map(|id| id.strip_prefix("jellyfin:").unwrap_or(id))
+            .collect();
+
+        // Newer servers skip items already in the playlist, so only the
+        // missing tracks are added and only the surplus entries removed.
+        // Adding first leaves the tracks on the server if a step fails.
+        let entries = self.playlist_entries(playlist_id).await?;
+        let (surplus, missing) = diff_playlist(&entries, &ids);
+
+        for batch in missing.chunks(PLAYLIST_BATCH) {
+            let url = format!(
+                "{}/Playlists/{}/Items?Ids={}&UserId={}",
+                base,
+                playlist_id,
+                batch.join(","),
+                self.user_id
+            );
+            self.client
+                .post(&url)
+                .headers(self.headers())
+                .send()
+                .await?
+                .error_for_status()?;
+        }
+
+        for batch in surplus.chunks(PLAYLIST_BATCH) {
+            let url = format!(
+                "{}/Playlists/{}/Items?EntryIds={}",
+                base,
+                playlist_id,
+                batch.join(",")
+            );
+            self.client
+                .delete(&url)
+                .headers(self.headers())
+                .send()
+                .await?
+                .error_for_status()?;
+        }
+
+        let entries = self.playlist_entries(playlist_id).await?;
+        for (entry_id, index) in playlist_moves(entries, &ids) {
+            let url = format!(
+                "{}/Playlists/{}/Items/{}/Move/{}",
+                base, playlist_id, entry_id, index
+            );
+            self.client
+                .post(&url)
+                .headers(self.headers())
+                .send()
+                .await?
+                .error_for_status()?;
+        }
+        Ok(())
+    }
+
+    fn supports_likes(&self) -> bool {
+        true
+    }
+
+    async fn get_liked_tracks(&self) -> Result<Vec<LikedTrack>> {
+        if !self.initialized {
+            return Err(anyhow!("Jellyfin provider not initialized"));
+        }
+        let base = self.server_url.trim_end_matches('/');
+        let url = format!(
+            "{}/Users/{}/Items?IncludeItemTypes=Audio&Recursive=true&Filters=IsFavorite",
+            base, self.user_id
+        );
+
+        let resp: ItemsResult = self
+            .client
+            .get(&url)
+            .headers(self.headers())
+            .send()
+            .await?
+            .json()
+            .await?;
+
+        // Jellyfin doesn't record when an item was made a favourite
+        Ok(resp
+            .items
+            .into_iter()
+            .map(|item| LikedTrack {
+                track: self.audio_track(item),
+                liked_at: None,
+            })
+            .collect())
+    }
+
+    async fn set_track_liked(&self, track_id: &str, liked: bool) -> Result<()> {
+        self.set_favorite(track_id, liked).await
+    }
+
+    async fn get_liked_items(&self) -> Result<LikedItems> {
+        if !self.initialized {
+            return Err(anyhow!("Jellyfin provider not initialized"));
+        }
+        let base = self.server_url.trim_end_matches('/');
+        let url = format!(
+            "{}/Users/{}/Items?IncludeItemTypes=MusicAlbum,MusicArtist&Recursive=true&Filters=IsFavorite",
+            base, self.user_id
+        );
+
+        let resp: ItemsResult = self
+            .client
+            .get(&url)
+            .headers(self.headers())
+            .send()
+            .await?
+            .json()
+            .await?;
+
+        let mut liked = LikedItems::default();
+        for item in resp.items {
+            let is_album = item.item_type == "MusicAlbum";
+            let entry = LikedItem {
+                id: format!("jellyfin:{}", item.id),
+                cover_url: Some(self.image_url(&item.id, 640)),
+                artist: is_album.then(|| {
+                    item.album_artist
+                        .clone()
+                        .unwrap_or_else(|| item.primary_artist())
+                }),
+                title: item.name,
+                liked_at: None,
+            };
+            if is_album {
+                liked.albums.push(entry);
+            } else {
+                liked.artists.push(entry);
+            }
+        }
+        Ok(liked)
+    }
+
+    async fn set_album_liked(&self, album_id: &str, liked: bool) -> Result<()> {
+        self.set_favorite(album_id, liked).await
+    }
+
+    async fn set_artist_liked(&self, artist_id: &str, liked: bool) -> Result<()> {
+        self.set_favorite(artist_id, liked).await
+    }
+}
+
+/// Entries to delete and items to add to turn a playlist's `(entry id, item
+/// id)` list into `ids`, keeping the earliest entries of each item
+fn diff_playlist<'a>(entries: &[(String, String)], ids: &[&'a str]) -> (Vec<String>, Vec<&'a str>) {
+    let mut wanted: HashMap<&str, usize> = HashMap::new();
+    for id in ids {
+        *wanted.entry(*id).or_default() += 1;
+    }
+
+    let mut surplus = Vec::new();
+    for (entry, item) in entries {
+        match wanted.get_mut(item.as_str()) {
+            Some(count) if *count > 0 => *count -= 1,
+            _ => surplus.push(entry.clone()),
+        }
+    }
+
+    // Whatever is still wanted goes in, duplicates included
+    let mut missing = Vec::new();
+    for id in ids {
+        if let Some(count) = wanted.get_mut(id) {
+            if *count > 0 {
+                *count -= 1;
+                missing.push(*id);
+            }
+        }
+    }
+    (surplus, missing)
+}
+
+/// `(entry id, index)` moves that put a playlist's entries in the order of
+/// `ids`. Items the server didn't take are skipped.
+fn playlist_moves(mut entries: Vec<(String, String)>, ids: &[&str]) -> Vec<(String, usize)> {
+    let mut moves = Vec::new();
+    let mut index = 0;
+    for id in ids {
+        let Some(offset) = entries[index..].iter().position(|(_, item)| item == id) else {
+            continue;
+        };
+        if offset > 0 {
+            let entry = entries.remove(index + offset);
+            moves.push((entry.0.clone(), index));
+            entries.insert(index, entry);
+        }
+        index += 1;
+    }
+    moves
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entries(items: &[&str]) -> Vec<(String, String)> {
+        items
+            .iter()
+            .enumerate()
+            .map(|(i, item)| (format!("e{}", i), item.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn diffs_keep_shared_entries() {
+        let (surplus, missing) =
+            diff_playlist(&entries(&["a", "b", "b", "c"]), &["c", "b", "d", "a", "d"]);
+        assert_eq!(surplus, vec!["e2".to_string()]);
+        assert_eq!(missing, vec!["d", "d"]);
+    }
+
+    #[test]
+    fn moves_reorder_entries() {
+        let current = entries(&["a", "b", "c", "d"]);
+        let moves = playlist_moves(current, &["c", "a", "b", "d"]);
+        assert_eq!(moves, vec![("e2".to_string(), 0)]);
+
+        let current = entries(&["a", "b", "c"]);
+        let moves = playlist_moves(current, &["c", "x", "b", "a"]);
+        assert_eq!(moves, vec![("e2".to_string(), 0), ("e1".to_string(), 1)]);
+    }
 }
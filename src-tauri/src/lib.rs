@@ -1,3 +1,4 @@
+pub mod artwork;
 pub mod audio;
 pub mod commands;
 pub mod database;
@@ -26,7 +27,7 @@ use discord::DiscordRpcManager;
 use download::DownloadManager;
 use media_controls::MediaControlEvent;
 use playback_notifier::PlaybackNotifier;
-use tauri::Manager;
+use tauri::{Emitter, Manager};
 
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
@@ -41,9 +42,20 @@ pub fn run() {
     }
 
     builder
+        .register_asynchronous_uri_scheme_protocol(artwork::SCHEME, |_ctx, request, responder| {
+            let path = request.uri().path().to_string();
+            tauri::async_runtime::spawn_blocking(move || {
+                responder.respond(artwork::ArtworkCache::new().respond(&path));
+            });
+        })
         .setup(|app| {
             let handle = app.handle().clone();
 
+            // Cover art lives in the app cache dir; set before anything reads it
+            if let Ok(cache_dir) = app.path().app_cache_dir() {
+                artwork::init(cache_dir.join("artwork"));
+            }
+
             // Initialize Discord RPC Manager
             let discord_rpc = std::sync::Arc::new(DiscordRpcManager::new());
 
@@ -134,8 +146,20 @@ pub fn run() {
                         handle_clone_db.manage(fav_manager);
 
                         let hist_manager = history::PlayHistoryManager::new(pool.clone());
+                        if let Err(e) = hist_manager.drop_unfinished_plays().await {
+                            log::warn!("Failed to drop unfinished plays: {}", e);
+                        }
                         handle_clone_db.manage(hist_manager);
 
+                        // Plays are recorded as the player reports them from here on
+                        handle_clone_db
+                            .state::<std::sync::Arc<PlaybackNotifier>>()
+                            .attach_recorder(history::listening::spawn_recorder(handle_clone_db.clone()));
+
+                        let scanner = library::scanner::LibraryScanner::new(pool.clone());
+                        handle_clone_db.manage(scanner);
+                        handle_clone_db.manage(library::watcher::LibraryWatcher::new());
+
                         log::info!("Database, Library, Playlist, Favorites & History Managers initialized successfully");
 
                         // Wire persistent cache into recommendation engine
@@ -180,6 +204,70 @@ pub fn run() {
                              log::info!("Restored repeat mode: {:?}", mode);
                         }
 
+                        if let Ok(Some(action_str)) = db_ref.get_setting("player_end_action").await {
+                             if let Ok(action) = action_str.parse::<queue::EndAction>() {
+                                 am.queue.write().end_action = action;
+                             }
+                        }
+
+                        if let Ok(Some(loop_str)) = db_ref.get_setting("player_loop_manual_queue").await {
+                             if let Ok(enabled) = loop_str.parse::<bool>() {
+                                 am.queue.write().loop_manual_queue = enabled;
+                             }
+                        }
+
+
+                        // Follow watched folders on disk, then pick up changes made while
+                        // the app was closed. The watcher starts first so folders added
+                        // during the startup scan are watched too.
+                        let scan_handle = handle_clone_db.clone();
+                        tauri::async_runtime::spawn(async move {
+                            let scanner = scan_handle.state::<library::scanner::LibraryScanner>();
+                            let roots: Vec<String> = scanner
+                                .get_folders()
+                                .await
+                                .unwrap_or_default()
+                                .into_iter()
+                                .map(|f| f.path)
+                                .collect();
+                            let watcher = scan_handle.state::<library::watcher::LibraryWatcher>();
+                            if let Err(e) = watcher.start(scan_handle.clone(), &roots) {
+                                log::warn!("Failed to start library watcher: {}", e);
+                            }
+
+                            let library = scan_handle.state::<library::LibraryManager>();
+                            if let Err(e) = library.link_unassigned_works().await {
+                                log::warn!("Failed to link tracks to works: {}", e);
+                            }
+
+                            if let Err(e) = artwork::ArtworkCache::new()
+                                .migrate_inline_covers(&library.pool)
+                                .await
+                            {
+                                log::warn!("Failed to move inline covers to the artwork cache: {}", e);
+                            }
+
+                            if let Err(e) = scanner.scan_all(&scan_handle).await {
+                                log::warn!("Startup library scan failed: {}", e);
+                            }
+
+                            // Relink suggestions read tags across whole folders, so
+                            // startup leaves them to an on-demand check
+                            let download_root = scan_handle.state::<DownloadManager>().get_download_path();
+                            match library.check_integrity(&[download_root], false).await {
+                                Ok(report) if !report.missing.is_empty() => {
+                                    let _ = scan_handle.emit("library-integrity-report", report);
+                                }
+                                Ok(_) => {}
+                                Err(e) => log::warn!("Library integrity check failed: {}", e),
+                            }
+
+                            // Album art may have changed during the scan
+                            let playlists = scan_handle.state::<playlist::PlaylistManager>();
+                            if let Err(e) = playlists.refresh_covers().await {
+                                log::warn!("Failed to refresh playlist covers: {}", e);
+                            }
+                        });
 
                         let configs: Vec<(String, String, String, String)> = sqlx::query_as(
                             "SELECT provider_id, server_url, username, password FROM provider_configs WHERE enabled = 1"
@@ -223,6 +311,23 @@ pub fn run() {
                                 _ => {}
                             }
                         }
+
+                        // Catch up on ratings changed here, in tags or on servers while closed
+                        let library = handle_clone_db.state::<library::LibraryManager>();
+                        let providers = provider_manager_for_db.get_all_providers().await;
+                        if let Err(e) = library.sync_ratings(&providers).await {
+                            log::warn!("Rating sync failed: {}", e);
+                        }
+
+                        let favorites = handle_clone_db.state::<favorites::FavoritesManager>();
+                        if let Err(e) = favorites.sync_likes(&library, &providers).await {
+                            log::warn!("Like sync failed: {}", e);
+                        }
+
+                        let playlists = handle_clone_db.state::<playlist::PlaylistManager>();
+                        if let Err(e) = playlists.sync_remote_playlists(&library, &providers).await {
+                            log::warn!("Playlist sync failed: {}", e);
+                        }
                     }
                     Err(e) => {
                         log::error!("Failed to initialize database: {}", e);
@@ -287,13 +392,7 @@ pub fn run() {
 
                                 // Use notifier for both MPRIS and Discord updates
                                 notifier_for_handler.notify_playing(
-                                    playback_notifier::TrackMetadata::new(
-                                        &track.title,
-                                        &track.artist,
-                                        &track.album,
-                                        track.duration as f64,
-                                        track.cover_image.clone(),
-                                    ),
+                                    playback_notifier::TrackMetadata::from(track),
                                     0.0,
                                 );
                             }
@@ -305,18 +404,13 @@ pub fn run() {
 
                                 // Use notifier for both MPRIS and Discord updates
                                 notifier_for_handler.notify_playing(
-                                    playback_notifier::TrackMetadata::new(
-                                        &track.title,
-                                        &track.artist,
-                                        &track.album,
-                                        track.duration as f64,
-                                        track.cover_image.clone(),
-                                    ),
+                                    playback_notifier::TrackMetadata::from(track),
                                     0.0,
                                 );
                             }
                         }
                         MediaControlEvent::Stop => {
+                            queue_for_controls.write().clear_now_playing();
                             let _ = cmd_tx.send(audio::DecoderCommand::Stop);
                             state_for_controls
                                 .is_playing
@@ -374,6 +468,10 @@ pub fn run() {
         .invoke_handler(tauri::generate_handler![
             commands::playlist::get_playlists,
             commands::playlist::create_playlist,
+            commands::playlist::create_smart_playlist,
+            commands::playlist::update_smart_playlist_rules,
+            commands::playlist::preview_smart_playlist,
+            commands::playlist::convert_smart_playlist,
             commands::playlist::delete_playlist,
             commands::playlist::rename_playlist,
 
@@ -381,18 +479,59 @@ pub fn run() {
             commands::playlist::add_tidal_track_to_playlist,
             commands::playlist::add_to_playlist,
             commands::playlist::remove_from_playlist,
+            commands::playlist::insert_into_playlist,
+            commands::playlist::remove_playlist_entry,
+            commands::playlist::move_playlist_entry,
+            commands::playlist::set_playlist_allow_duplicates,
+            commands::playlist::sort_playlist,
+            commands::playlist::compact_playlist,
             commands::playlist::get_playlists_containing_track,
+            commands::playlist::import_playlist_file,
+            commands::playlist::export_playlist_file,
+            commands::playlist::get_playlist_tree,
+            commands::playlist::create_playlist_folder,
+            commands::playlist::rename_playlist_folder,
+            commands::playlist::delete_playlist_folder,
+            commands::playlist::move_playlist_folder,
+            commands::playlist::move_playlist,
+            commands::playlist::set_playlist_pinned,
+            commands::playlist::set_playlist_sort,
+            commands::playlist::set_playlist_cover,
+            commands::playlist::list_remote_playlists,
+            commands::playlist::import_remote_playlist,
+            commands::playlist::publish_playlist,
+            commands::playlist::unlink_playlist,
+            commands::playlist::sync_remote_playlists,
+            commands::playlist::import_tidal_playlist,
+            commands::playlist::resync_tidal_playlist,
 
             commands::favorites::add_favorite,
             commands::favorites::remove_favorite,
             commands::favorites::is_favorited,
             commands::favorites::get_favorites,
+            commands::favorites::sync_likes,
+            commands::favorites::like_album,
+            commands::favorites::unlike_album,
+            commands::favorites::like_artist,
+            commands::favorites::unlike_artist,
+            commands::favorites::get_favorite_albums,
+            commands::favorites::get_favorite_artists,
+            commands::favorites::get_liked_from_artist,
 
-            commands::history::record_play,
             commands::history::update_play_completion,
             commands::history::get_recently_played,
             commands::history::get_most_played,
             commands::history::get_play_count,
+            commands::history::get_recent_contexts,
+            commands::history::get_play_thresholds,
+            commands::history::set_play_thresholds,
+            commands::history::get_listening_summary,
+            commands::history::get_daily_listening,
+            commands::history::get_listening_heatmap,
+            commands::history::get_top_in_range,
+            commands::history::get_listening_streaks,
+            commands::history::get_artist_discovery,
+            commands::history::get_year_in_review,
 
             commands::import_music,
             commands::import_folder,
@@ -414,8 +553,15 @@ pub fn run() {
             commands::get_playback_info,
             commands::get_current_track,
             commands::get_queue,
+            commands::get_queue_context,
             commands::get_shuffle_mode,
             commands::get_repeat_mode,
+            commands::set_stop_after,
+            commands::get_stop_after,
+            commands::set_end_action,
+            commands::get_end_action,
+            commands::set_loop_manual_queue,
+            commands::get_loop_manual_queue,
             commands::get_crossfade_duration,
             commands::set_crossfade_duration,
             commands::get_lyrics,
@@ -433,8 +579,30 @@ pub fn run() {
             commands::get_tidal_stream_url,
             commands::refresh_tidal_cache,
             commands::fetch_image_as_data_url,
+            commands::get_artwork_url,
             commands::set_tidal_config,
             commands::library::get_library_tracks,
+            commands::library::get_library_folders,
+            commands::library::get_library_folder_tracks,
+            commands::library::add_library_folder,
+            commands::library::remove_library_folder,
+            commands::library::rescan_library,
+            commands::library::get_track_tags,
+            commands::library::edit_track_tags,
+            commands::library::browse_genres,
+            commands::library::browse_decades,
+            commands::library::browse_years,
+            commands::library::browse_providers,
+            commands::library::browse_folder,
+            commands::library::browse_tracks,
+            commands::library::get_work_sources,
+            commands::library::merge_works,
+            commands::library::split_track_work,
+            commands::library::check_library_integrity,
+            commands::library::relink_track,
+            commands::library::remove_missing_tracks,
+            commands::library::set_track_rating,
+            commands::library::sync_track_ratings,
             commands::library::get_library_albums,
             commands::library::get_library_artists,
             commands::library::search_library,
@@ -442,6 +610,8 @@ pub fn run() {
             commands::library::add_tidal_track,
             commands::library::rebuild_search_index,
             commands::library::factory_reset,
+            commands::library::export_library_backup,
+            commands::library::import_library_backup,
             commands::library::library_has_data,
 
             commands::spotify::fetch_spotify_playlist,
@@ -0,0 +1,1008 @@
+use super::models::TrackDetails;
+use super::{find_or_create_artist, search, write_track_details, LibraryManager};
+use serde::{Deserialize, Serialize};
+use sqlx::{Sqlite, Transaction};
+use std::collections::HashMap;
+use std::io::{Cursor, Read, Write};
+use std::path::Path;
+use uuid::Uuid;
+use zip::write::SimpleFileOptions;
+use zip::{CompressionMethod, ZipArchive, ZipWriter};
+
+/// Bumped whenever the archive layout changes; older archives stay importable
+pub const BACKUP_VERSION: u32 = 5;
+
+const BACKUP_FORMAT: &str = "sonami-backup";
+
+/// The JSON backup inside the zip archive; version 5 onwards. Earlier
+/// backups are the bare JSON file.
+const MANIFEST_NAME: &str = "manifest.json";
+
+/// Settings holding a credential are only exported along with passwords
+fn is_credential(key: &str) -> bool {
+    let key = key.to_ascii_lowercase();
+    ["password", "token", "secret", "api_key"]
+        .iter()
+        .any(|word| key.contains(word))
+}
+
+/// Everything a factory reset would lose, keyed so it can be merged back
+/// into another database. Tracks carry their provider/external ids or file
+/// path; everything else refers to tracks by their id within the archive.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Backup {
+    pub format: String,
+    pub version: u32,
+    pub exported_at: i64,
+    /// Whether provider passwords were written out
+    pub includes_secrets: bool,
+    pub tracks: Vec<BackupTrack>,
+    pub playlists: Vec<BackupPlaylist>,
+    /// Version 2 onwards
+    #[serde(default)]
+    pub folders: Vec<BackupFolder>,
+    pub favorites: Vec<BackupFavorite>,
+    /// Liked albums and artists; version 3 onwards
+    #[serde(default)]
+    pub liked_items: Vec<BackupLikedItem>,
+    pub history: Vec<BackupPlay>,
+    pub providers: Vec<BackupProvider>,
+    pub settings: Vec<BackupSetting>,
+}
+
+#[derive(Debug, Serialize, Deserialize, sqlx::FromRow)]
+pub struct BackupTrack {
+    pub id: String,
+    pub title: String,
+    pub artist: String,
+    #[serde(default)]
+    pub album: Option<String>,
+    #[serde(default)]
+    pub cover_url: Option<String>,
+    pub duration: i64,
+    pub source_type: String,
+    #[serde(default)]
+    pub provider_id: Option<String>,
+    #[serde(default)]
+    pub external_id: Option<String>,
+    #[serde(default)]
+    pub file_path: Option<String>,
+    #[serde(default)]
+    pub isrc: Option<String>,
+    #[serde(default)]
+    pub play_count: Option<i64>,
+    #[serde(default)]
+    pub skip_count: Option<i64>,
+    #[serde(default)]
+    pub last_played_at: Option<i64>,
+    #[serde(default)]
+    pub added_at: Option<i64>,
+    #[serde(default)]
+    pub rating: Option<i64>,
+    #[serde(default)]
+    pub rating_updated_at: Option<i64>,
+}
+
+#[derive(Debug, Serialize, Deserialize, sqlx::FromRow)]
+pub struct BackupPlaylist {
+    pub id: String,
+    pub title: String,
+    #[serde(default)]
+    pub description: Option<String>,
+    #[serde(default)]
+    pub cover_url: Option<String>,
+    #[serde(default)]
+    pub smart_rules: Option<String>,
+    #[serde(default)]
+    pub allow_duplicates: Option<bool>,
+    #[serde(default)]
+    pub created_at: Option<String>,
+    #[serde(default)]
+    pub folder_id: Option<String>,
+    #[serde(default)]
+    pub position: Option<i64>,
+    #[serde(default)]
+    pub pinned_at: Option<i64>,
+    #[serde(default)]
+    pub sort_order: Option<String>,
+    #[sqlx(skip)]
+    #[serde(default)]
+    pub entries: Vec<BackupPlaylistEntry>,
+    /// Server copy the playlist syncs with
+    #[sqlx(skip)]
+    #[serde(default)]
+    pub link: Option<BackupPlaylistLink>,
+}
+
+#[derive(Debug, Serialize, Deserialize, sqlx::FromRow)]
+pub struct BackupPlaylistLink {
+    pub provider_id: String,
+    pub remote_id: String,
+    /// Server track ids as of the last sync, as JSON
+    pub snapshot: String,
+    #[serde(default)]
+    pub remote_changed: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, sqlx::FromRow)]
+pub struct BackupPlaylistEntry {
+    pub id: String,
+    pub track_id: String,
+    /// Unix seconds
+    #[serde(default)]
+    pub added_at: Option<i64>,
+}
+
+#[derive(Debug, Serialize, Deserialize, sqlx::FromRow)]
+pub struct BackupFolder {
+    pub id: String,
+    pub name: String,
+    #[serde(default)]
+    pub parent_id: Option<String>,
+    #[serde(default)]
+    pub position: i64,
+}
+
+#[derive(Debug, Serialize, Deserialize, sqlx::FromRow)]
+pub struct BackupFavorite {
+    pub track_id: String,
+    pub liked_at: i64,
+}
+
+/// Keyed by server id, or for local items by lowercased name
+#[derive(Debug, Serialize, Deserialize, sqlx::FromRow)]
+pub struct BackupLikedItem {
+    pub kind: String,
+    pub provider_id: String,
+    pub external_id: String,
+    pub title: String,
+    #[serde(default)]
+    pub artist: Option<String>,
+    #[serde(default)]
+    pub cover_url: Option<String>,
+    pub liked_at: i64,
+}
+
+#[derive(Debug, Serialize, Deserialize, sqlx::FromRow)]
+pub struct BackupPlay {
+    pub id: String,
+    pub track_id: String,
+    pub played_at: i64,
+    #[serde(default)]
+    pub duration_played: Option<i64>,
+    #[serde(default)]
+    pub completed: Option<i64>,
+    #[serde(default)]
+    pub skipped: Option<i64>,
+    #[serde(default)]
+    pub source: Option<String>,
+    #[serde(default)]
+    pub context_uri: Option<String>,
+    #[serde(default)]
+    pub context_type: Option<String>,
+    #[serde(default)]
+    pub context_name: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, sqlx::FromRow)]
+pub struct BackupProvider {
+    pub provider_id: String,
+    pub server_url: String,
+    pub username: String,
+    /// Empty when exported without secrets
+    #[serde(default)]
+    pub password: String,
+    #[serde(default)]
+    pub enabled: Option<i64>,
+}
+
+#[derive(Debug, Serialize, Deserialize, sqlx::FromRow)]
+pub struct BackupSetting {
+    pub key: String,
+    pub value: String,
+}
+
+impl Backup {
+    /// Pack the backup into a zip archive holding its JSON manifest
+    pub fn to_archive(&self) -> Result<Vec<u8>, String> {
+        let json = serde_json::to_vec_pretty(self).map_err(|e| e.to_string())?;
+        let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+        zip.start_file(
+            MANIFEST_NAME,
+            SimpleFileOptions::default().compression_method(CompressionMethod::Deflated),
+        )
+        .map_err(|e| e.to_string())?;
+        zip.write_all(&json).map_err(|e| e.to_string())?;
+        Ok(zip.finish().map_err(|e| e.to_string())?.into_inner())
+    }
+
+    /// Read a zip archive, or the bare JSON file of a version 4 or older backup
+    pub fn from_archive(bytes: &[u8]) -> Result<Self, String> {
+        if !bytes.starts_with(b"PK") {
+            return serde_json::from_slice(bytes).map_err(|e| e.to_string());
+        }
+        let mut zip = ZipArchive::new(Cursor::new(bytes)).map_err(|e| e.to_string())?;
+        let mut json = Vec::new();
+        zip.by_name(MANIFEST_NAME)
+            .map_err(|e| format!("{}: {}", MANIFEST_NAME, e))?
+            .read_to_end(&mut json)
+            .map_err(|e| e.to_string())?;
+        serde_json::from_slice(&json).map_err(|e| e.to_string())
+    }
+}
+
+#[derive(Debug, Serialize, Clone, Default)]
+pub struct BackupImportReport {
+    pub tracks_matched: usize,
+    pub tracks_created: usize,
+    pub playlists_created: usize,
+    pub playlists_merged: usize,
+    pub playlist_entries_added: usize,
+    pub folders_created: usize,
+    pub favorites_added: usize,
+    pub liked_items_added: usize,
+    pub plays_added: usize,
+    pub providers_added: usize,
+    pub settings_restored: usize,
+}
+
+/// Unix seconds from a column that holds either an integer or SQLite datetime text
+fn unix_column(column: &str) -> String {
+    format!(
+        "CASE typeof({0}) WHEN 'integer' THEN {0} ELSE CAST(strftime('%s', {0}) AS INTEGER) END",
+        column
+    )
+}
+
+impl LibraryManager {
+    /// Snapshot playlists and their folders, likes, ratings, play history,
+    /// provider configs and settings. Passwords are blanked and credential
+    /// settings left out unless `include_secrets` is set.
+    pub async fn export_backup(&self, include_secrets: bool) -> Result<Backup, String> {
+        // One transaction so the parts agree with each other
+        let mut tx = self.pool.begin().await.map_err(|e| e.to_string())?;
+
+        let tracks: Vec<BackupTrack> = sqlx::query_as(
+            r#"
+            SELECT
+                t.id, t.title, a.name as artist, al.title as album, al.cover_url,
+                t.duration, t.source_type, t.provider_id, t.external_id, t.file_path, t.isrc,
+                t.play_count, t.skip_count, t.last_played_at, t.added_at,
+                t.rating, t.rating_updated_at
+            FROM tracks t
+            JOIN artists a ON a.id = t.artist_id
+            LEFT JOIN albums al ON al.id = t.album_id
+            "#,
+        )
+        .fetch_all(&mut *tx)
+        .await
+        .map_err(|e| e.to_string())?;
+
+        let mut playlists: Vec<BackupPlaylist> = sqlx::query_as(
+            "SELECT id, title, description, cover_url, smart_rules, allow_duplicates, CAST(created_at AS TEXT) as created_at, folder_id, position, pinned_at, sort_order FROM playlists",
+        )
+        .fetch_all(&mut *tx)
+        .await
+        .map_err(|e| e.to_string())?;
+        for playlist in &mut playlists {
+            playlist.entries = sqlx::query_as(&format!(
+                "SELECT id, track_id, {} as added_at FROM playlist_tracks WHERE playlist_id = ? ORDER BY position ASC",
+                unix_column("added_at")
+            ))
+            .bind(&playlist.id)
+            .fetch_all(&mut *tx)
+            .await
+            .map_err(|e| e.to_string())?;
+            playlist.link = sqlx::query_as(
+                "SELECT provider_id, remote_id, snapshot, remote_changed FROM playlist_links WHERE playlist_id = ?",
+            )
+            .bind(&playlist.id)
+            .fetch_optional(&mut *tx)
+            .await
+            .map_err(|e| e.to_string())?;
+        }
+
+        let folders: Vec<BackupFolder> =
+            sqlx::query_as("SELECT id, name, parent_id, position FROM playlist_folders")
+                .fetch_all(&mut *tx)
+                .await
+                .map_err(|e| e.to_string())?;
+
+        let favorites: Vec<BackupFavorite> =
+            sqlx::query_as("SELECT track_id, liked_at FROM user_favorites")
+                .fetch_all(&mut *tx)
+                .await
+                .map_err(|e| e.to_string())?;
+
+        let liked_items: Vec<BackupLikedItem> = sqlx::query_as(
+            "SELECT kind, provider_id, external_id, title, artist, cover_url, liked_at FROM user_favorite_items",
+        )
+        .fetch_all(&mut *tx)
+        .await
+        .map_err(|e| e.to_string())?;
+
+        let history: Vec<BackupPlay> = sqlx::query_as(
+            r#"
+            SELECT id, track_id, played_at, duration_played, completed, skipped, source,
+                   context_uri, context_type, context_name
+            FROM play_history
+            WHERE in_progress = 0
+            ORDER BY played_at ASC
+            "#,
+        )
+        .fetch_all(&mut *tx)
+        .await
+        .map_err(|e| e.to_string())?;
+
+        let mut providers: Vec<BackupProvider> = sqlx::query_as(
+            "SELECT provider_id, server_url, username, password, enabled FROM provider_configs",
+        )
+        .fetch_all(&mut *tx)
+        .await
+        .map_err(|e| e.to_string())?;
+        if !include_secrets {
+            for provider in &mut providers {
+                provider.password.clear();
+            }
+        }
+
+        let settings: Vec<BackupSetting> = sqlx::query_as("SELECT key, value FROM settings")
+            .fetch_all(&mut *tx)
+            .await
+            .map_err(|e| e.to_string())?
+            .into_iter()
+            .filter(|setting: &BackupSetting| include_secrets || !is_credential(&setting.key))
+            .collect();
+
+        tx.commit().await.map_err(|e| e.to_string())?;
+
+        Ok(Backup {
+            format: BACKUP_FORMAT.to_string(),
+            version: BACKUP_VERSION,
+            exported_at: chrono::Utc::now().timestamp(),
+            includes_secrets: include_secrets,
+            tracks,
+            playlists,
+            folders,
+            favorites,
+            liked_items,
+            history,
+            providers,
+            settings,
+        })
+    }
+
+    /// Merge a backup into the database. Tracks are matched by provider and
+    /// external id, then by file path, and created when missing; rows that
+    /// are already present are left alone, so importing twice changes nothing.
+    pub async fn import_backup(&self, backup: &Backup) -> Result<BackupImportReport, String> {
+        if backup.format != BACKUP_FORMAT {
+            return Err("Not a Sonami backup".to_string());
+        }
+        if backup.version > BACKUP_VERSION {
+            return Err(format!(
+                "Backup version {} is newer than this app supports ({})",
+                backup.version, BACKUP_VERSION
+            ));
+        }
+
+        let mut report = BackupImportReport::default();
+        let mut tx = self.pool.begin().await.map_err(|e| e.to_string())?;
+
+        // Archive track id -> track id in this database
+        let mut track_ids: HashMap<&str, String> = HashMap::new();
+        for track in &backup.tracks {
+            let id = match find_track(&mut tx, track).await? {
+                Some(id) => {
+                    report.tracks_matched += 1;
+                    id
+                }
+                None => {
+                    report.tracks_created += 1;
+                    create_track(&mut tx, track).await?
+                }
+            };
+            merge_track_stats(&mut tx, &id, track).await?;
+            track_ids.insert(track.id.as_str(), id);
+        }
+
+        // Folders go first so playlists can be filed into them
+        import_folders(&mut tx, &backup.folders, &mut report).await?;
+
+        for playlist in &backup.playlists {
+            import_playlist(&mut tx, playlist, &track_ids, &mut report).await?;
+        }
+
+        for favorite in &backup.favorites {
+            let Some(track_id) = track_ids.get(favorite.track_id.as_str()) else {
+                continue;
+            };
+            let result = sqlx::query(
+                "INSERT OR IGNORE INTO user_favorites (id, track_id, liked_at) VALUES (?, ?, ?)",
+            )
+            .bind(Uuid::new_v4().to_string())
+            .bind(track_id)
+            .bind(favorite.liked_at)
+            .execute(&mut *tx)
+            .await
+            .map_err(|e| e.to_string())?;
+            report.favorites_added += result.rows_affected() as usize;
+        }
+
+        for item in &backup.liked_items {
+            let result = sqlx::query(
+                r#"
+                INSERT OR IGNORE INTO user_favorite_items
+                    (id, kind, provider_id, external_id, title, artist, cover_url, liked_at)
+                VALUES (?, ?, ?, ?, ?, ?, ?, ?)
+                "#,
+            )
+            .bind(Uuid::new_v4().to_string())
+            .bind(&item.kind)
+            .bind(&item.provider_id)
+            .bind(&item.external_id)
+            .bind(&item.title)
+            .bind(&item.artist)
+            .bind(&item.cover_url)
+            .bind(item.liked_at)
+            .execute(&mut *tx)
+            .await
+            .map_err(|e| e.to_string())?;
+            report.liked_items_added += result.rows_affected() as usize;
+        }
+
+        for play in &backup.history {
+            let Some(track_id) = track_ids.get(play.track_id.as_str()) else {
+                continue;
+            };
+            let exists = sqlx::query(
+                "SELECT 1 FROM play_history WHERE id = ? OR (track_id = ? AND played_at = ?)",
+            )
+            .bind(&play.id)
+            .bind(track_id)
+            .bind(play.played_at)
+            .fetch_optional(&mut *tx)
+            .await
+            .map_err(|e| e.to_string())?;
+            if exists.is_some() {
+                continue;
+            }
+            sqlx::query(
+                r#"
+                INSERT INTO play_history
+                    (id, track_id, played_at, duration_played, completed, skipped, source, context_uri, context_type, context_name)
+                VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+                "#,
+            )
+            .bind(&play.id)
+            .bind(track_id)
+            .bind(play.played_at)
+            .bind(play.duration_played)
+            .bind(play.completed.unwrap_or(0))
+            .bind(play.skipped.unwrap_or(0))
+            .bind(&play.source)
+            .bind(&play.context_uri)
+            .bind(&play.context_type)
+            .bind(&play.context_name)
+            .execute(&mut *tx)
+            .await
+            .map_err(|e| e.to_string())?;
+            report.plays_added += 1;
+        }
+
+        for provider in &backup.providers {
+            // An existing config wins, except that a password can fill in a blank one
+            let exists = sqlx::query("SELECT 1 FROM provider_configs WHERE provider_id = ?")
+                .bind(&provider.provider_id)
+                .fetch_optional(&mut *tx)
+                .await
+                .map_err(|e| e.to_string())?
+                .is_some();
+            sqlx::query(
+                r#"
+                INSERT INTO provider_configs (provider_id, server_url, username, password, enabled, updated_at)
+                VALUES (?, ?, ?, ?, ?, strftime('%s', 'now'))
+                ON CONFLICT(provider_id) DO UPDATE SET password = excluded.password
+                WHERE provider_configs.password = '' AND provider_configs.server_url = excluded.server_url
+                "#,
+            )
+            .bind(&provider.provider_id)
+            .bind(&provider.server_url)
+            .bind(&provider.username)
+            .bind(&provider.password)
+            .bind(provider.enabled.unwrap_or(1))
+            .execute(&mut *tx)
+            .await
+            .map_err(|e| e.to_string())?;
+            if !exists {
+                report.providers_added += 1;
+            }
+        }
+
+        // Settings made on this device win over the archived ones
+        for setting in &backup.settings {
+            let result = sqlx::query(
+                "INSERT OR IGNORE INTO settings (key, value, updated_at) VALUES (?, ?, strftime('%s', 'now'))",
+            )
+            .bind(&setting.key)
+            .bind(&setting.value)
+            .execute(&mut *tx)
+            .await
+            .map_err(|e| e.to_string())?;
+            report.settings_restored += result.rows_affected() as usize;
+        }
+
+        tx.commit().await.map_err(|e| e.to_string())?;
+
+        log::info!(
+            "[Backup] Imported: {} tracks matched, {} created, {} playlists created, {} merged, {} plays",
+            report.tracks_matched,
+            report.tracks_created,
+            report.playlists_created,
+            report.playlists_merged,
+            report.plays_added
+        );
+        Ok(report)
+    }
+}
+
+async fn find_track(
+    tx: &mut Transaction<'_, Sqlite>,
+    track: &BackupTrack,
+) -> Result<Option<String>, String> {
+    if let (Some(provider_id), Some(external_id)) = (&track.provider_id, &track.external_id) {
+        let found: Option<String> =
+            sqlx::query_scalar("SELECT id FROM tracks WHERE provider_id = ? AND external_id = ?")
+                .bind(provider_id)
+                .bind(external_id)
+                .fetch_optional(&mut **tx)
+                .await
+                .map_err(|e| e.to_string())?;
+        if found.is_some() {
+            return Ok(found);
+        }
+    }
+    if let Some(file_path) = &track.file_path {
+        return sqlx::query_scalar("SELECT id FROM tracks WHERE file_path = ?")
+            .bind(file_path)
+            .fetch_optional(&mut **tx)
+            .await
+            .map_err(|e| e.to_string());
+    }
+    Ok(None)
+}
+
+/// Recreate a track the database no longer has. Local tracks whose file is
+/// gone come back as missing, so they can be relinked.
+async fn create_track(
+    tx: &mut Transaction<'_, Sqlite>,
+    track: &BackupTrack,
+) -> Result<String, String> {
+    let provider_id = track.provider_id.as_deref().unwrap_or("local");
+    let artist_id = find_or_create_artist(tx, &track.artist, provider_id).await?;
+
+    let album_id = match &track.album {
+        Some(title) => {
+            let existing: Option<String> =
+                sqlx::query_scalar("SELECT id FROM albums WHERE title = ? AND artist_id = ?")
+                    .bind(title)
+                    .bind(&artist_id)
+                    .fetch_optional(&mut **tx)
+                    .await
+                    .map_err(|e| e.to_string())?;
+            match existing {
+                Some(id) => Some(id),
+                None => {
+                    let id = Uuid::new_v4().to_string();
+                    sqlx::query(
+                        "INSERT INTO albums (id, title, artist_id, cover_url, provider_id) VALUES (?, ?, ?, ?, ?)",
+                    )
+                    .bind(&id)
+                    .bind(title)
+                    .bind(&artist_id)
+                    .bind(&track.cover_url)
+                    .bind(provider_id)
+                    .execute(&mut **tx)
+                    .await
+                    .map_err(|e| e.to_string())?;
+                    Some(id)
+                }
+            }
+        }
+        None => None,
+    };
+
+    let missing = track
+        .file_path
+        .as_deref()
+        .is_some_and(|path| provider_id == "local" && !Path::new(path).is_file());
+    let tidal_id = match provider_id {
+        "tidal" => track
+            .external_id
+            .as_deref()
+            .and_then(|id| id.parse::<i64>().ok()),
+        _ => None,
+    };
+
+    // file_modified stays empty so the next scan reads the file's tags
+    let id = Uuid::new_v4().to_string();
+    sqlx::query(
+        r#"
+        INSERT INTO tracks
+            (id, title, artist_id, album_id, duration, source_type, provider_id, external_id, tidal_id,
+             file_path, added_at, missing_since)
+        VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, COALESCE(?, strftime('%s', 'now')),
+                CASE WHEN ? THEN strftime('%s', 'now') END)
+        "#,
+    )
+    .bind(&id)
+    .bind(&track.title)
+    .bind(&artist_id)
+    .bind(&album_id)
+    .bind(track.duration)
+    .bind(&track.source_type)
+    .bind(provider_id)
+    .bind(&track.external_id)
+    .bind(tidal_id)
+    .bind(&track.file_path)
+    .bind(track.added_at.filter(|t| *t > 0))
+    .bind(missing)
+    .execute(&mut **tx)
+    .await
+    .map_err(|e| e.to_string())?;
+
+    let details = TrackDetails {
+        isrc: track.isrc.clone(),
+        ..Default::default()
+    };
+    write_track_details(
+        tx,
+        &id,
+        &artist_id,
+        album_id.as_deref(),
+        provider_id,
+        &details,
+    )
+    .await?;
+    search::index_track(
+        tx,
+        &id,
+        &track.title,
+        &track.artist,
+        track.album.as_deref().unwrap_or_default(),
+    )
+    .await?;
+
+    Ok(id)
+}
+
+/// Counts keep the larger value so re-importing does not add them up again;
+/// the more recently set rating wins
+async fn merge_track_stats(
+    tx: &mut Transaction<'_, Sqlite>,
+    track_id: &str,
+    track: &BackupTrack,
+) -> Result<(), String> {
+    sqlx::query(
+        r#"
+        UPDATE tracks SET
+            play_count = MAX(COALESCE(play_count, 0), COALESCE(?1, 0)),
+            skip_count = MAX(COALESCE(skip_count, 0), COALESCE(?2, 0)),
+            last_played_at = NULLIF(MAX(COALESCE(last_played_at, 0), COALESCE(?3, 0)), 0),
+            added_at = CASE
+                WHEN ?4 > 0 AND (added_at IS NULL OR added_at = 0 OR ?4 < added_at) THEN ?4
+                ELSE added_at
+            END,
+            rating = CASE
+                WHEN ?6 IS NOT NULL AND (rating_updated_at IS NULL OR ?6 > rating_updated_at) THEN ?5
+                ELSE rating
+            END,
+            rating_updated_at = CASE
+                WHEN ?6 IS NOT NULL AND (rating_updated_at IS NULL OR ?6 > rating_updated_at) THEN ?6
+                ELSE rating_updated_at
+            END
+        WHERE id = ?7
+        "#,
+    )
+    .bind(track.play_count)
+    .bind(track.skip_count)
+    .bind(track.last_played_at)
+    .bind(track.added_at)
+    .bind(track.rating)
+    .bind(track.rating_updated_at)
+    .bind(track_id)
+    .execute(&mut **tx)
+    .await
+    .map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+/// Folders match by id; existing folders keep their name and place. New
+/// folders are nested once they all exist, as the archive isn't ordered.
+async fn import_folders(
+    tx: &mut Transaction<'_, Sqlite>,
+    folders: &[BackupFolder],
+    report: &mut BackupImportReport,
+) -> Result<(), String> {
+    let mut created = Vec::new();
+    for folder in folders {
+        let result = sqlx::query(
+            "INSERT OR IGNORE INTO playlist_folders (id, name, position) VALUES (?, ?, ?)",
+        )
+        .bind(&folder.id)
+        .bind(&folder.name)
+        .bind(folder.position)
+        .execute(&mut **tx)
+        .await
+        .map_err(|e| e.to_string())?;
+        if result.rows_affected() > 0 {
+            created.push(folder);
+        }
+    }
+
+    for folder in &created {
+        sqlx::query(
+            "UPDATE playlist_folders SET parent_id = (SELECT id FROM playlist_folders WHERE id = ?) WHERE id = ?",
+        )
+        .bind(&folder.parent_id)
+        .bind(&folder.id)
+        .execute(&mut **tx)
+        .await
+        .map_err(|e| e.to_string())?;
+    }
+    report.folders_created += created.len();
+    Ok(())
+}
+
+/// Playlists match by id, then by title. Entries already present by id are
+/// skipped, and so are tracks already in a playlist that forbids duplicates.
+/// Server links are only restored onto the playlist they were made for.
+async fn import_playlist(
+    tx: &mut Transaction<'_, Sqlite>,
+    playlist: &BackupPlaylist,
+    track_ids: &HashMap<&str, String>,
+    report: &mut BackupImportReport,
+) -> Result<(), String> {
+    let by_id: Option<String> = sqlx::query_scalar("SELECT id FROM playlists WHERE id = ?")
+        .bind(&playlist.id)
+        .fetch_optional(&mut **tx)
+        .await
+        .map_err(|e| e.to_string())?;
+    let by_title: Option<String> = match by_id {
+        Some(_) => None,
+        None => sqlx::query_scalar("SELECT id FROM playlists WHERE title = ? LIMIT 1")
+            .bind(&playlist.title)
+            .fetch_optional(&mut **tx)
+            .await
+            .map_err(|e| e.to_string())?,
+    };
+
+    let matched_by_title = by_title.is_some();
+    let playlist_id = match by_id.or(by_title) {
+        Some(id) => {
+            report.playlists_merged += 1;
+            id
+        }
+        None => {
+            sqlx::query(
+                r#"
+                INSERT INTO playlists
+                    (id, title, description, cover_url, smart_rules, allow_duplicates, created_at,
+                     folder_id, position, pinned_at, sort_order)
+                VALUES (?, ?, ?, ?, ?, COALESCE(?, 1), COALESCE(?, CURRENT_TIMESTAMP),
+                        (SELECT id FROM playlist_folders WHERE id = ?), COALESCE(?, 0), ?, ?)
+                "#,
+            )
+            .bind(&playlist.id)
+            .bind(&playlist.title)
+            .bind(&playlist.description)
+            .bind(&playlist.cover_url)
+            .bind(&playlist.smart_rules)
+            .bind(playlist.allow_duplicates)
+            .bind(&playlist.created_at)
+            .bind(&playlist.folder_id)
+            .bind(playlist.position)
+            .bind(playlist.pinned_at)
+            .bind(&playlist.sort_order)
+            .execute(&mut **tx)
+            .await
+            .map_err(|e| e.to_string())?;
+            report.playlists_created += 1;
+            playlist.id.clone()
+        }
+    };
+
+    if let (Some(link), false) = (&playlist.link, matched_by_title) {
+        sqlx::query(
+            r#"
+            INSERT OR IGNORE INTO playlist_links (playlist_id, provider_id, remote_id, snapshot, remote_changed, synced_at)
+            VALUES (?, ?, ?, ?, ?, strftime('%s', 'now'))
+            "#,
+        )
+        .bind(&playlist_id)
+        .bind(&link.provider_id)
+        .bind(&link.remote_id)
+        .bind(&link.snapshot)
+        .bind(&link.remote_changed)
+        .execute(&mut **tx)
+        .await
+        .map_err(|e| e.to_string())?;
+    }
+
+    let allow_duplicates: bool =
+        sqlx::query_scalar("SELECT allow_duplicates FROM playlists WHERE id = ?")
+            .bind(&playlist_id)
+            .fetch_one(&mut **tx)
+            .await
+            .map_err(|e| e.to_string())?;
+
+    let mut position: i64 = sqlx::query_scalar(
+        "SELECT COALESCE(MAX(position) + 1, 0) FROM playlist_tracks WHERE playlist_id = ?",
+    )
+    .bind(&playlist_id)
+    .fetch_one(&mut **tx)
+    .await
+    .map_err(|e| e.to_string())?;
+
+    for entry in &playlist.entries {
+        let Some(track_id) = track_ids.get(entry.track_id.as_str()) else {
+            continue;
+        };
+        let duplicate = sqlx::query(
+            "SELECT 1 FROM playlist_tracks WHERE id = ? OR (NOT ? AND playlist_id = ? AND track_id = ?)",
+        )
+        .bind(&entry.id)
+        .bind(allow_duplicates)
+        .bind(&playlist_id)
+        .bind(track_id)
+        .fetch_optional(&mut **tx)
+        .await
+        .map_err(|e| e.to_string())?;
+        if duplicate.is_some() {
+            continue;
+        }
+
+        sqlx::query(
+            "INSERT INTO playlist_tracks (id, playlist_id, track_id, position, added_at) VALUES (?, ?, ?, ?, ?)",
+        )
+        .bind(&entry.id)
+        .bind(&playlist_id)
+        .bind(track_id)
+        .bind(position)
+        .bind(entry.added_at.unwrap_or_else(|| chrono::Utc::now().timestamp()))
+        .execute(&mut **tx)
+        .await
+        .map_err(|e| e.to_string())?;
+        position += 1;
+        report.playlist_entries_added += 1;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::DatabaseManager;
+
+    const COUNTED: [&str; 9] = [
+        "tracks",
+        "albums",
+        "playlists",
+        "playlist_tracks",
+        "playlist_folders",
+        "playlist_links",
+        "user_favorites",
+        "user_favorite_items",
+        "play_history",
+    ];
+
+    async fn counts(library: &LibraryManager) -> Vec<i64> {
+        let mut counts = Vec::new();
+        for table in COUNTED {
+            let n: i64 = sqlx::query_scalar(&format!("SELECT COUNT(*) FROM {}", table))
+                .fetch_one(&library.pool)
+                .await
+                .unwrap();
+            counts.push(n);
+        }
+        counts
+    }
+
+    #[tokio::test]
+    async fn importing_twice_adds_nothing_the_second_time() {
+        let db = DatabaseManager::in_memory().await;
+        db.seed(&[
+            "INSERT INTO artists (id, name, provider_id) VALUES ('ar', 'Band', 'tidal')",
+            "INSERT INTO albums (id, title, artist_id) VALUES ('al', 'Record', 'ar')",
+            "INSERT INTO tracks (id, title, artist_id, album_id, duration, source_type, provider_id, external_id) VALUES ('t1', 'One', 'ar', 'al', 100, 'TIDAL', 'tidal', '1'), ('t2', 'Two', 'ar', 'al', 100, 'TIDAL', 'tidal', '2')",
+            "INSERT INTO playlist_folders (id, name) VALUES ('f', 'Folder')",
+            "INSERT INTO playlists (id, title, folder_id, pinned_at) VALUES ('p', 'Mix', 'f', 5)",
+            "INSERT INTO playlist_tracks (id, playlist_id, track_id, position, added_at) VALUES ('e1', 'p', 't1', 0, 10), ('e2', 'p', 't2', 1, 10), ('e3', 'p', 't1', 2, 10)",
+            "INSERT INTO playlist_links (playlist_id, provider_id, remote_id, snapshot, synced_at) VALUES ('p', 'subsonic', 'r', '[]', 0)",
+            "INSERT INTO user_favorites (id, track_id, liked_at) VALUES ('l', 't2', 20)",
+            "INSERT INTO user_favorite_items (id, kind, provider_id, external_id, title, liked_at) VALUES ('i1', 'album', 'tidal', '7', 'Record', 20), ('i2', 'artist', 'local', 'band', 'Band', 20)",
+            "INSERT INTO play_history (id, track_id, played_at) VALUES ('h', 't1', 30)",
+        ])
+        .await;
+        let source = LibraryManager::new(db.pool.clone());
+        let backup = source.export_backup(false).await.unwrap();
+
+        let target = LibraryManager::new(DatabaseManager::in_memory().await.pool);
+        target.import_backup(&backup).await.unwrap();
+        let after_first = counts(&target).await;
+        assert_eq!(after_first, counts(&source).await);
+
+        let report = target.import_backup(&backup).await.unwrap();
+        assert_eq!(counts(&target).await, after_first);
+        assert_eq!(report.tracks_created, 0);
+        assert_eq!(report.playlist_entries_added, 0);
+    }
+
+    #[tokio::test]
+    async fn history_keeps_skips_and_leaves_out_plays_in_progress() {
+        let db = DatabaseManager::in_memory().await;
+        db.seed(&[
+            "INSERT INTO artists (id, name, provider_id) VALUES ('ar', 'Band', 'tidal')",
+            "INSERT INTO tracks (id, title, artist_id, duration, source_type, provider_id, external_id) VALUES ('t1', 'One', 'ar', 100, 'TIDAL', 'tidal', '1')",
+            "INSERT INTO play_history (id, track_id, played_at, completed, skipped, in_progress) VALUES ('done', 't1', 10, 1, 0, 0), ('skip', 't1', 20, 0, 1, 0), ('now', 't1', 30, 0, 0, 1)",
+        ])
+        .await;
+        let source = LibraryManager::new(db.pool.clone());
+        let backup = source.export_backup(false).await.unwrap();
+        assert_eq!(backup.history.len(), 2);
+
+        let target = LibraryManager::new(DatabaseManager::in_memory().await.pool);
+        target.import_backup(&backup).await.unwrap();
+        let plays: Vec<(i64, i64, i64)> = sqlx::query_as(
+            "SELECT played_at, skipped, in_progress FROM play_history ORDER BY played_at",
+        )
+        .fetch_all(&target.pool)
+        .await
+        .unwrap();
+        assert_eq!(plays, vec![(10, 0, 0), (20, 1, 0)]);
+    }
+
+    #[tokio::test]
+    async fn archives_keep_credentials_and_local_settings_safe() {
+        let db = DatabaseManager::in_memory().await;
+        db.seed(&[
+            "INSERT INTO settings (key, value) VALUES ('player_volume', '0.3')",
+            "INSERT INTO settings (key, value) VALUES ('lastfm_session_token', 'abc')",
+        ])
+        .await;
+        let source = LibraryManager::new(db.pool.clone());
+        let archive = source
+            .export_backup(false)
+            .await
+            .unwrap()
+            .to_archive()
+            .unwrap();
+        let backup = Backup::from_archive(&archive).unwrap();
+        assert_eq!(backup.version, BACKUP_VERSION);
+        assert!(backup
+            .settings
+            .iter()
+            .all(|s| s.key != "lastfm_session_token"));
+
+        // Bare JSON from older versions still reads
+        let json = serde_json::to_vec(&backup).unwrap();
+        assert_eq!(
+            Backup::from_archive(&json).unwrap().settings.len(),
+            backup.settings.len()
+        );
+
+        let target_db = DatabaseManager::in_memory().await;
+        target_db
+            .seed(&["INSERT INTO settings (key, value) VALUES ('player_volume', '0.8')"])
+            .await;
+        let target = LibraryManager::new(target_db.pool.clone());
+        target.import_backup(&backup).await.unwrap();
+        let volume: String =
+            sqlx::query_scalar("SELECT value FROM settings WHERE key = 'player_volume'")
+                .fetch_one(&target.pool)
+                .await
+                .unwrap();
+        assert_eq!(volume, "0.8");
+    }
+}
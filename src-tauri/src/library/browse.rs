@@ -0,0 +1,428 @@
+use super::models::{
+    BrowseFilter, BrowseGroup, FolderListing, Page, UnifiedTrack, TRACK_METADATA_COLUMNS,
+    TRACK_METADATA_CTES, TRACK_METADATA_JOINS,
+};
+use super::LibraryManager;
+use sqlx::query::Query;
+use sqlx::sqlite::SqliteArguments;
+use sqlx::{Row, Sqlite};
+use std::path::MAIN_SEPARATOR;
+
+/// Values bound to a dynamically built browse, search or smart playlist query
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum Arg {
+    Text(String),
+    Int(i64),
+}
+
+/// Bind `args` to their placeholders in order
+pub(crate) fn bind_args<'q>(
+    mut query: Query<'q, Sqlite, SqliteArguments<'q>>,
+    args: &'q [Arg],
+) -> Query<'q, Sqlite, SqliteArguments<'q>> {
+    for arg in args {
+        query = match arg {
+            Arg::Text(s) => query.bind(s.as_str()),
+            Arg::Int(i) => query.bind(*i),
+        };
+    }
+    query
+}
+
+/// Track columns plus artist and album joins, ready for a WHERE clause
+pub(crate) fn track_select() -> String {
+    format!(
+        r#"
+        WITH {}
+        SELECT
+            t.id, t.title, t.duration, t.source_type, t.file_path,
+            t.play_count, t.skip_count, t.last_played_at, t.added_at, t.audio_quality,
+            t.provider_id, t.external_id, t.artist_id, t.album_id,
+            {},
+            a.name as artist_name,
+            al.title as album_title, al.cover_url
+        FROM tracks t
+        JOIN artists a ON t.artist_id = a.id
+        LEFT JOIN albums al ON t.album_id = al.id
+        {}
+        "#,
+        TRACK_METADATA_CTES, TRACK_METADATA_COLUMNS, TRACK_METADATA_JOINS
+    )
+}
+
+/// Bounds such that `lower <= path < upper` selects every path starting with
+/// `prefix`, letting SQLite range-scan the file_path index instead of LIKE.
+/// Queries write `+provider_id` so the provider index isn't picked instead.
+pub(super) fn prefix_range(prefix: &str) -> (String, String) {
+    let mut upper = prefix.to_string();
+    let last = upper.pop().unwrap_or(MAIN_SEPARATOR);
+    upper.push(char::from_u32(last as u32 + 1).unwrap_or(char::MAX));
+    (prefix.to_string(), upper)
+}
+
+/// A folder path with exactly one trailing separator, so prefix matches
+/// stop at whole path components
+pub(super) fn folder_prefix(path: &str) -> String {
+    if path.ends_with(MAIN_SEPARATOR) {
+        path.to_string()
+    } else {
+        format!("{}{}", path, MAIN_SEPARATOR)
+    }
+}
+
+fn provider_label(key: &str) -> String {
+    match key {
+        "local" => "Local files".to_string(),
+        "tidal" => "Tidal".to_string(),
+        "subsonic" => "Subsonic".to_string(),
+        "jellyfin" => "Jellyfin".to_string(),
+        other => other.to_string(),
+    }
+}
+
+impl LibraryManager {
+    pub async fn browse_genres(
+        &self,
+        offset: i64,
+        limit: i64,
+    ) -> Result<Page<BrowseGroup>, String> {
+        let total: i64 = sqlx::query_scalar("SELECT COUNT(DISTINCT genre) FROM track_genres")
+            .fetch_one(&self.pool)
+            .await
+            .map_err(|e| e.to_string())?;
+
+        let items = sqlx::query_as::<_, BrowseGroup>(
+            r#"
+            SELECT genre as key, genre as label, COUNT(*) as track_count
+            FROM track_genres
+            GROUP BY genre
+            ORDER BY genre COLLATE NOCASE ASC
+            LIMIT ? OFFSET ?
+            "#,
+        )
+        .bind(limit)
+        .bind(offset)
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| e.to_string())?;
+
+        Ok(Page {
+            items,
+            total,
+            offset,
+            limit,
+        })
+    }
+
+    pub async fn browse_decades(
+        &self,
+        offset: i64,
+        limit: i64,
+    ) -> Result<Page<BrowseGroup>, String> {
+        let total: i64 = sqlx::query_scalar(
+            "SELECT COUNT(DISTINCT (year / 10) * 10) FROM tracks WHERE year > 0",
+        )
+        .fetch_one(&self.pool)
+        .await
+        .map_err(|e| e.to_string())?;
+
+        let rows = sqlx::query(
+            r#"
+            SELECT (year / 10) * 10 as decade, COUNT(*) as track_count
+            FROM tracks
+            WHERE year > 0
+            GROUP BY decade
+            ORDER BY decade DESC
+            LIMIT ? OFFSET ?
+            "#,
+        )
+        .bind(limit)
+        .bind(offset)
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| e.to_string())?;
+
+        let items = rows
+            .iter()
+            .map(|row| {
+                let decade: i64 = row.try_get("decade").unwrap_or(0);
+                BrowseGroup {
+                    key: decade.to_string(),
+                    label: format!("{}s", decade),
+                    track_count: row.try_get("track_count").unwrap_or(0),
+                }
+            })
+            .collect();
+
+        Ok(Page {
+            items,
+            total,
+            offset,
+            limit,
+        })
+    }
+
+    pub async fn browse_years(
+        &self,
+        decade: u32,
+        offset: i64,
+        limit: i64,
+    ) -> Result<Page<BrowseGroup>, String> {
+        let total: i64 = sqlx::query_scalar(
+            "SELECT COUNT(DISTINCT year) FROM tracks WHERE year >= ? AND year < ?",
+        )
+        .bind(decade as i64)
+        .bind(decade as i64 + 10)
+        .fetch_one(&self.pool)
+        .await
+        .map_err(|e| e.to_string())?;
+
+        let items = sqlx::query_as::<_, BrowseGroup>(
+            r#"
+            SELECT CAST(year AS TEXT) as key, CAST(year AS TEXT) as label, COUNT(*) as track_count
+            FROM tracks
+            WHERE year >= ? AND year < ?
+            GROUP BY year
+            ORDER BY year DESC
+            LIMIT ? OFFSET ?
+            "#,
+        )
+        .bind(decade as i64)
+        .bind(decade as i64 + 10)
+        .bind(limit)
+        .bind(offset)
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| e.to_string())?;
+
+        Ok(Page {
+            items,
+            total,
+            offset,
+            limit,
+        })
+    }
+
+    pub async fn browse_providers(
+        &self,
+        offset: i64,
+        limit: i64,
+    ) -> Result<Page<BrowseGroup>, String> {
+        let total: i64 = sqlx::query_scalar(
+            "SELECT COUNT(DISTINCT COALESCE(provider_id, lower(source_type))) FROM tracks",
+        )
+        .fetch_one(&self.pool)
+        .await
+        .map_err(|e| e.to_string())?;
+
+        let rows = sqlx::query(
+            r#"
+            SELECT COALESCE(provider_id, lower(source_type)) as provider, COUNT(*) as track_count
+            FROM tracks
+            GROUP BY provider
+            ORDER BY track_count DESC, provider ASC
+            LIMIT ? OFFSET ?
+            "#,
+        )
+        .bind(limit)
+        .bind(offset)
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| e.to_string())?;
+
+        let items = rows
+            .iter()
+            .map(|row| {
+                let key: String = row.try_get("provider").unwrap_or_default();
+                BrowseGroup {
+                    label: provider_label(&key),
+                    key,
+                    track_count: row.try_get("track_count").unwrap_or(0),
+                }
+            })
+            .collect();
+
+        Ok(Page {
+            items,
+            total,
+            offset,
+            limit,
+        })
+    }
+
+    /// List the sub-folders and tracks directly inside a local folder. With no
+    /// path, the watched library roots are listed instead.
+    pub async fn browse_folder(
+        &self,
+        path: Option<&str>,
+        offset: i64,
+        limit: i64,
+    ) -> Result<FolderListing, String> {
+        let Some(path) = path else {
+            return self.browse_folder_roots(offset, limit).await;
+        };
+
+        let prefix = folder_prefix(path);
+        let (lower, upper) = prefix_range(&prefix);
+        let rest_start = prefix.chars().count() as i64 + 1;
+        let sep = MAIN_SEPARATOR.to_string();
+
+        let folder_rows = sqlx::query(
+            r#"
+            SELECT substr(rest, 1, instr(rest, ?) - 1) as name, COUNT(*) as track_count
+            FROM (
+                SELECT substr(file_path, ?) as rest
+                FROM tracks
+                WHERE +provider_id = 'local' AND file_path >= ? AND file_path < ?
+            )
+            WHERE instr(rest, ?) > 0
+            GROUP BY name
+            ORDER BY name COLLATE NOCASE ASC
+            "#,
+        )
+        .bind(&sep)
+        .bind(rest_start)
+        .bind(&lower)
+        .bind(&upper)
+        .bind(&sep)
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| e.to_string())?;
+
+        let folders = folder_rows
+            .iter()
+            .map(|row| {
+                let name: String = row.try_get("name").unwrap_or_default();
+                BrowseGroup {
+                    key: format!("{}{}", prefix, name),
+                    label: name,
+                    track_count: row.try_get("track_count").unwrap_or(0),
+                }
+            })
+            .collect();
+
+        let clause = "+t.provider_id = 'local' AND t.file_path >= ? AND t.file_path < ? AND instr(substr(t.file_path, ?), ?) = 0";
+        let args = vec![
+            Arg::Text(lower),
+            Arg::Text(upper),
+            Arg::Int(rest_start),
+            Arg::Text(sep),
+        ];
+        let tracks = self
+            .page_tracks(clause, args, "t.file_path ASC", offset, limit)
+            .await?;
+
+        Ok(FolderListing {
+            path: Some(path.to_string()),
+            folders,
+            tracks,
+        })
+    }
+
+    async fn browse_folder_roots(&self, offset: i64, limit: i64) -> Result<FolderListing, String> {
+        let roots: Vec<String> =
+            sqlx::query_scalar("SELECT path FROM library_folders ORDER BY path ASC")
+                .fetch_all(&self.pool)
+                .await
+                .map_err(|e| e.to_string())?;
+
+        let mut folders = Vec::new();
+        for root in roots {
+            let (lower, upper) = prefix_range(&folder_prefix(&root));
+            let track_count: i64 = sqlx::query_scalar(
+                "SELECT COUNT(*) FROM tracks WHERE +provider_id = 'local' AND file_path >= ? AND file_path < ?",
+            )
+            .bind(&lower)
+            .bind(&upper)
+            .fetch_one(&self.pool)
+            .await
+            .map_err(|e| e.to_string())?;
+
+            folders.push(BrowseGroup {
+                key: root.clone(),
+                label: root,
+                track_count,
+            });
+        }
+
+        Ok(FolderListing {
+            path: None,
+            folders,
+            tracks: Page {
+                items: Vec::new(),
+                total: 0,
+                offset,
+                limit,
+            },
+        })
+    }
+
+    /// Tracks in one genre, decade, year or provider, one page at a time
+    pub async fn browse_tracks(
+        &self,
+        filter: &BrowseFilter,
+        offset: i64,
+        limit: i64,
+    ) -> Result<Page<UnifiedTrack>, String> {
+        let (clause, args) = match filter {
+            BrowseFilter::Genre(genre) => (
+                "t.id IN (SELECT track_id FROM track_genres WHERE genre = ?)",
+                vec![Arg::Text(genre.clone())],
+            ),
+            BrowseFilter::Decade(decade) => (
+                "t.year >= ? AND t.year < ?",
+                vec![Arg::Int(*decade as i64), Arg::Int(*decade as i64 + 10)],
+            ),
+            BrowseFilter::Year(year) => ("t.year = ?", vec![Arg::Int(*year as i64)]),
+            BrowseFilter::Provider(provider) => (
+                "(t.provider_id = ? OR (t.provider_id IS NULL AND lower(t.source_type) = ?))",
+                vec![Arg::Text(provider.clone()), Arg::Text(provider.clone())],
+            ),
+        };
+
+        self.page_tracks(
+            clause,
+            args,
+            "a.name COLLATE NOCASE ASC, al.title COLLATE NOCASE ASC, t.disc_number ASC, t.track_number ASC, t.title ASC",
+            offset,
+            limit,
+        )
+        .await
+    }
+
+    async fn page_tracks(
+        &self,
+        clause: &str,
+        args: Vec<Arg>,
+        order_by: &str,
+        offset: i64,
+        limit: i64,
+    ) -> Result<Page<UnifiedTrack>, String> {
+        let count_sql = format!("SELECT COUNT(*) FROM tracks t WHERE {}", clause);
+        let total: i64 = bind_args(sqlx::query(&count_sql), &args)
+            .fetch_one(&self.pool)
+            .await
+            .map_err(|e| e.to_string())?
+            .get(0);
+
+        let sql = format!(
+            "{} WHERE {} ORDER BY {} LIMIT ? OFFSET ?",
+            track_select(),
+            clause,
+            order_by
+        );
+        let rows = bind_args(sqlx::query(&sql), &args)
+            .bind(limit)
+            .bind(offset)
+            .fetch_all(&self.pool)
+            .await
+            .map_err(|e| e.to_string())?;
+
+        Ok(Page {
+            items: rows.iter().map(UnifiedTrack::from_row).collect(),
+            total,
+            offset,
+            limit,
+        })
+    }
+}
@@ -0,0 +1,573 @@
+use super::models::{IntegrityReport, MissingTrack, RelinkCandidate};
+use super::scanner::{delete_orphans, delete_tracks, is_audio_file, read_details, split_featured};
+use super::works::{artist_key, title_key};
+use super::LibraryManager;
+use lofty::prelude::*;
+use lofty::probe::Probe;
+use sqlx::{Row, Sqlite, Transaction};
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use walkdir::WalkDir;
+
+/// Durations further apart than this don't count as a match
+const DURATION_TOLERANCE_SECS: i64 = 2;
+
+/// Candidates scoring below this are not suggested
+const MIN_SUGGESTION_SCORE: f64 = 0.5;
+
+const MAX_SUGGESTIONS: usize = 3;
+
+/// Files outside the library whose tags are read in one check
+const MAX_PROBED_FILES: usize = 5000;
+
+/// What a file is known to contain, from the database or from its tags
+#[derive(Debug, Clone, Default)]
+struct FileIdentity {
+    path: String,
+    /// Set for files that already back a library track
+    track_id: Option<String>,
+    file_name: String,
+    size: Option<u64>,
+    duration: i64,
+    title_key: String,
+    artist_key: String,
+    isrc: Option<String>,
+    acoustid: Option<String>,
+}
+
+struct TrackFile {
+    id: String,
+    title: String,
+    artist: String,
+    album: Option<String>,
+    provider_id: Option<String>,
+    missing_since: Option<i64>,
+    identity: FileIdentity,
+}
+
+impl TrackFile {
+    fn missing(&self, suggestions: Vec<RelinkCandidate>) -> MissingTrack {
+        MissingTrack {
+            track_id: self.id.clone(),
+            title: self.title.clone(),
+            artist: self.artist.clone(),
+            album: self.album.clone(),
+            provider_id: self.provider_id.clone(),
+            file_path: self.identity.path.clone(),
+            missing_since: self.missing_since,
+            suggestions,
+        }
+    }
+}
+
+fn file_name(path: &str) -> String {
+    Path::new(path)
+        .file_name()
+        .map(|n| n.to_string_lossy().to_lowercase())
+        .unwrap_or_default()
+}
+
+/// Read just enough of a file to match it against a missing track
+fn probe_file(path: &Path) -> Option<FileIdentity> {
+    let tagged_file = Probe::open(path).and_then(|p| p.read()).ok()?;
+    let tag = tagged_file.primary_tag();
+    let title = tag
+        .and_then(|t| t.title().map(|c| c.into_owned()))
+        .unwrap_or_default();
+    let artist = tag
+        .and_then(|t| t.artist().map(|c| c.into_owned()))
+        .unwrap_or_default();
+    let details = tag.map(read_details).unwrap_or_default();
+    let (artist, _) = split_featured(&artist);
+    let path_str = path.to_str()?.to_string();
+
+    Some(FileIdentity {
+        file_name: file_name(&path_str),
+        path: path_str,
+        track_id: None,
+        size: std::fs::metadata(path).ok().map(|m| m.len()),
+        duration: tagged_file.properties().duration().as_secs() as i64,
+        title_key: title_key(&title),
+        artist_key: artist_key(&artist),
+        isrc: details.isrc,
+        acoustid: details.acoustid,
+    })
+}
+
+/// How likely `candidate` is the file `missing` used to point at
+fn score(missing: &FileIdentity, candidate: &FileIdentity) -> Option<RelinkCandidate> {
+    let same = |a: &Option<String>, b: &Option<String>| matches!((a, b), (Some(a), Some(b)) if !a.is_empty() && a.eq_ignore_ascii_case(b));
+
+    let mut reasons = Vec::new();
+    let mut total = 0.0;
+    let mut add = |reason: &str, weight: f64| {
+        reasons.push(reason.to_string());
+        total += weight;
+    };
+
+    if same(&missing.acoustid, &candidate.acoustid) {
+        add("acoustid", 1.0);
+    }
+    if same(&missing.isrc, &candidate.isrc) {
+        add("isrc", 0.4);
+    }
+    if !missing.title_key.is_empty()
+        && missing.title_key == candidate.title_key
+        && missing.artist_key == candidate.artist_key
+    {
+        add("tags", 0.35);
+    }
+    if missing.duration > 0
+        && (missing.duration - candidate.duration).abs() <= DURATION_TOLERANCE_SECS
+    {
+        add("duration", 0.15);
+    }
+    if missing.size.is_some() && missing.size == candidate.size {
+        add("size", 0.35);
+    }
+    if !missing.file_name.is_empty() && missing.file_name == candidate.file_name {
+        add("filename", 0.25);
+    }
+
+    if total < MIN_SUGGESTION_SCORE {
+        return None;
+    }
+    Some(RelinkCandidate {
+        path: candidate.path.clone(),
+        score: f64::min(total, 1.0),
+        reasons,
+        track_id: candidate.track_id.clone(),
+    })
+}
+
+fn suggestions(missing: &FileIdentity, candidates: &[FileIdentity]) -> Vec<RelinkCandidate> {
+    let mut found: Vec<RelinkCandidate> = candidates
+        .iter()
+        .filter_map(|candidate| score(missing, candidate))
+        .collect();
+    found.sort_by(|a, b| b.score.total_cmp(&a.score));
+    found.truncate(MAX_SUGGESTIONS);
+    found
+}
+
+/// Flag tracks as unavailable. Rows, history, likes and playlist entries stay
+/// so the file can be relinked later.
+pub(super) async fn mark_missing(
+    tx: &mut Transaction<'_, Sqlite>,
+    ids: &[String],
+) -> Result<(), String> {
+    for id in ids {
+        sqlx::query(
+            "UPDATE tracks SET missing_since = COALESCE(missing_since, strftime('%s', 'now')) WHERE id = ?",
+        )
+        .bind(id)
+        .execute(&mut **tx)
+        .await
+        .map_err(|e| e.to_string())?;
+    }
+    Ok(())
+}
+
+/// Move a duplicate row's plays, likes and playlist entries onto `track_id`
+/// and drop it. Play counts add up and the more recently set rating wins.
+pub(super) async fn merge_duplicate(
+    tx: &mut Transaction<'_, Sqlite>,
+    track_id: &str,
+    duplicate: &str,
+) -> Result<(), String> {
+    for table in ["play_history", "playlist_tracks"] {
+        sqlx::query(&format!(
+            "UPDATE {} SET track_id = ? WHERE track_id = ?",
+            table
+        ))
+        .bind(track_id)
+        .bind(duplicate)
+        .execute(&mut **tx)
+        .await
+        .map_err(|e| e.to_string())?;
+    }
+    // A like on both copies keeps the existing one
+    sqlx::query("UPDATE OR IGNORE user_favorites SET track_id = ? WHERE track_id = ?")
+        .bind(track_id)
+        .bind(duplicate)
+        .execute(&mut **tx)
+        .await
+        .map_err(|e| e.to_string())?;
+    sqlx::query(
+        r#"
+        UPDATE tracks SET
+            play_count = COALESCE(tracks.play_count, 0) + COALESCE(d.play_count, 0),
+            skip_count = COALESCE(tracks.skip_count, 0) + COALESCE(d.skip_count, 0),
+            last_played_at = NULLIF(MAX(COALESCE(tracks.last_played_at, 0), COALESCE(d.last_played_at, 0)), 0),
+            rating = CASE
+                WHEN d.rating IS NOT NULL AND (tracks.rating IS NULL
+                    OR COALESCE(d.rating_updated_at, 0) > COALESCE(tracks.rating_updated_at, 0))
+                THEN d.rating
+                ELSE tracks.rating
+            END,
+            rating_updated_at = CASE
+                WHEN d.rating IS NOT NULL AND (tracks.rating IS NULL
+                    OR COALESCE(d.rating_updated_at, 0) > COALESCE(tracks.rating_updated_at, 0))
+                THEN d.rating_updated_at
+                ELSE tracks.rating_updated_at
+            END,
+            like_updated_at = NULLIF(MAX(COALESCE(tracks.like_updated_at, 0), COALESCE(d.like_updated_at, 0)), 0)
+        FROM (SELECT * FROM tracks WHERE id = ?1) AS d
+        WHERE tracks.id = ?2
+        "#,
+    )
+    .bind(duplicate)
+    .bind(track_id)
+    .execute(&mut **tx)
+    .await
+    .map_err(|e| e.to_string())?;
+    delete_tracks(tx, &[duplicate.to_string()]).await
+}
+
+impl LibraryManager {
+    pub async fn mark_tracks_missing(&self, ids: &[String]) -> Result<(), String> {
+        let mut tx = self.pool.begin().await.map_err(|e| e.to_string())?;
+        mark_missing(&mut tx, ids).await?;
+        tx.commit().await.map_err(|e| e.to_string())
+    }
+
+    /// Check that every local and downloaded track still has its file. Tracks
+    /// that lost it are marked unavailable. With `suggest`, they also get
+    /// relink suggestions from files under the library folders and
+    /// `extra_roots`, which means reading tags across those trees.
+    pub async fn check_integrity(
+        &self,
+        extra_roots: &[PathBuf],
+        suggest: bool,
+    ) -> Result<IntegrityReport, String> {
+        let rows = sqlx::query(
+            r#"
+            SELECT t.id, t.title, t.duration, t.file_path, t.file_size, t.provider_id,
+                   t.isrc, t.acoustid, t.missing_since,
+                   a.name as artist_name, al.title as album_title
+            FROM tracks t
+            JOIN artists a ON t.artist_id = a.id
+            LEFT JOIN albums al ON t.album_id = al.id
+            WHERE t.file_path IS NOT NULL AND t.file_path <> ''
+            "#,
+        )
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| e.to_string())?;
+
+        let tracks: Vec<TrackFile> = rows
+            .iter()
+            .map(|row| {
+                let path: String = row.try_get("file_path").unwrap_or_default();
+                let title: String = row.try_get("title").unwrap_or_default();
+                let artist: String = row.try_get("artist_name").unwrap_or_default();
+                TrackFile {
+                    id: row.try_get("id").unwrap_or_default(),
+                    album: row.try_get("album_title").ok().flatten(),
+                    provider_id: row.try_get("provider_id").ok().flatten(),
+                    missing_since: row.try_get("missing_since").ok().flatten(),
+                    identity: FileIdentity {
+                        file_name: file_name(&path),
+                        track_id: row.try_get("id").ok(),
+                        size: row
+                            .try_get::<Option<i64>, _>("file_size")
+                            .ok()
+                            .flatten()
+                            .map(|n| n as u64),
+                        duration: row.try_get("duration").unwrap_or(0),
+                        title_key: title_key(&title),
+                        artist_key: artist_key(&artist),
+                        isrc: row.try_get("isrc").ok().flatten(),
+                        acoustid: row.try_get("acoustid").ok().flatten(),
+                        path,
+                    },
+                    title,
+                    artist,
+                }
+            })
+            .collect();
+
+        let paths: Vec<String> = tracks.iter().map(|t| t.identity.path.clone()).collect();
+        let exists: Vec<bool> = tauri::async_runtime::spawn_blocking(move || {
+            paths.iter().map(|p| Path::new(p).is_file()).collect()
+        })
+        .await
+        .map_err(|e| e.to_string())?;
+
+        let mut restored = Vec::new();
+        let mut newly_missing = Vec::new();
+        for (track, present) in tracks.iter().zip(&exists) {
+            match (present, track.missing_since) {
+                (true, Some(_)) => restored.push(track.id.clone()),
+                (false, None) => newly_missing.push(track.id.clone()),
+                _ => {}
+            }
+        }
+
+        let mut tx = self.pool.begin().await.map_err(|e| e.to_string())?;
+        for id in &restored {
+            sqlx::query("UPDATE tracks SET missing_since = NULL WHERE id = ?")
+                .bind(id)
+                .execute(&mut *tx)
+                .await
+                .map_err(|e| e.to_string())?;
+        }
+        mark_missing(&mut tx, &newly_missing).await?;
+        tx.commit().await.map_err(|e| e.to_string())?;
+
+        let mut report = IntegrityReport {
+            checked: tracks.len(),
+            restored: restored.len(),
+            missing: Vec::new(),
+        };
+        if exists.iter().all(|present| *present) {
+            return Ok(report);
+        }
+        if !suggest {
+            report.missing = tracks
+                .iter()
+                .zip(&exists)
+                .filter(|(_, present)| !**present)
+                .map(|(track, _)| track.missing(Vec::new()))
+                .collect();
+            return Ok(report);
+        }
+
+        // Files already in the library are candidates too: a file moved while
+        // the app was closed gets scanned in as a new track
+        let mut candidates: Vec<FileIdentity> = tracks
+            .iter()
+            .zip(&exists)
+            .filter(|(t, present)| **present && t.provider_id.as_deref() == Some("local"))
+            .map(|(t, _)| t.identity.clone())
+            .collect();
+
+        let mut roots: Vec<PathBuf> =
+            sqlx::query_scalar::<_, String>("SELECT path FROM library_folders")
+                .fetch_all(&self.pool)
+                .await
+                .map_err(|e| e.to_string())?
+                .into_iter()
+                .map(PathBuf::from)
+                .collect();
+        roots.extend(extra_roots.iter().cloned());
+
+        let known: HashSet<String> = tracks.iter().map(|t| t.identity.path.clone()).collect();
+        let probed: Vec<FileIdentity> = tauri::async_runtime::spawn_blocking(move || {
+            roots
+                .iter()
+                .filter(|root| root.is_dir())
+                .flat_map(|root| WalkDir::new(root).follow_links(true).into_iter())
+                .filter_map(|e| e.ok())
+                .filter(|e| e.file_type().is_file() && is_audio_file(e.path()))
+                .filter(|e| {
+                    e.path()
+                        .to_str()
+                        .map(|p| !known.contains(p))
+                        .unwrap_or(false)
+                })
+                .take(MAX_PROBED_FILES)
+                .filter_map(|e| probe_file(e.path()))
+                .collect()
+        })
+        .await
+        .map_err(|e| e.to_string())?;
+        candidates.extend(probed);
+
+        for (track, present) in tracks.iter().zip(&exists) {
+            if *present {
+                continue;
+            }
+            report
+                .missing
+                .push(track.missing(suggestions(&track.identity, &candidates)));
+        }
+
+        log::info!(
+            "[Integrity] Checked {} files: {} missing, {} restored",
+            report.checked,
+            report.missing.len(),
+            report.restored
+        );
+        Ok(report)
+    }
+
+    /// Point a track at a new file. If that file already backs another track,
+    /// the duplicate's plays, likes and playlist entries move over and it is
+    /// dropped. Play counts add up and the more recently set rating wins.
+    pub async fn relink_track(&self, track_id: &str, new_path: &str) -> Result<(), String> {
+        let path = Path::new(new_path);
+        if !path.is_file() {
+            return Err(format!("File not found: {}", new_path));
+        }
+        let size = std::fs::metadata(path).ok().map(|m| m.len() as i64);
+
+        let duplicate: Option<String> =
+            sqlx::query_scalar("SELECT id FROM tracks WHERE file_path = ? AND id <> ?")
+                .bind(new_path)
+                .bind(track_id)
+                .fetch_optional(&self.pool)
+                .await
+                .map_err(|e| e.to_string())?;
+
+        let mut tx = self.pool.begin().await.map_err(|e| e.to_string())?;
+
+        if let Some(duplicate) = &duplicate {
+            merge_duplicate(&mut tx, track_id, duplicate).await?;
+        }
+
+        // Clearing file_modified makes the next scan re-read the tags
+        let result = sqlx::query(
+            "UPDATE tracks SET file_path = ?, file_size = ?, file_modified = NULL, missing_since = NULL WHERE id = ?",
+        )
+        .bind(new_path)
+        .bind(size)
+        .bind(track_id)
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| e.to_string())?;
+        if result.rows_affected() == 0 {
+            return Err("Track not found".to_string());
+        }
+
+        delete_orphans(&mut tx).await?;
+        tx.commit().await.map_err(|e| e.to_string())?;
+
+        log::info!(
+            "[Integrity] Relinked {} to {}{}",
+            track_id,
+            new_path,
+            if duplicate.is_some() {
+                " (merged duplicate)"
+            } else {
+                ""
+            }
+        );
+        Ok(())
+    }
+
+    /// Delete tracks that are still marked unavailable, for files the user
+    /// doesn't intend to bring back
+    pub async fn remove_missing_tracks(&self, track_ids: &[String]) -> Result<usize, String> {
+        let mut ids = Vec::new();
+        for id in track_ids {
+            let missing: Option<Option<i64>> =
+                sqlx::query_scalar("SELECT missing_since FROM tracks WHERE id = ?")
+                    .bind(id)
+                    .fetch_optional(&self.pool)
+                    .await
+                    .map_err(|e| e.to_string())?;
+            if matches!(missing, Some(Some(_))) {
+                ids.push(id.clone());
+            }
+        }
+
+        let mut tx = self.pool.begin().await.map_err(|e| e.to_string())?;
+        delete_tracks(&mut tx, &ids).await?;
+        delete_orphans(&mut tx).await?;
+        tx.commit().await.map_err(|e| e.to_string())?;
+        Ok(ids.len())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::DatabaseManager;
+
+    fn identity(path: &str) -> FileIdentity {
+        FileIdentity {
+            file_name: file_name(path),
+            path: path.to_string(),
+            duration: 200,
+            title_key: "song".to_string(),
+            artist_key: "band".to_string(),
+            size: Some(1234),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn moved_file_scores_above_retagged_lookalike() {
+        let missing = identity("/old/Song.flac");
+        let moved = identity("/new/Song.flac");
+        let other = FileIdentity {
+            size: Some(999),
+            ..identity("/new/Song (Live).flac")
+        };
+
+        let found = suggestions(&missing, &[other, moved]);
+        assert_eq!(found.len(), 2);
+        assert_eq!(found[0].path, "/new/Song.flac");
+        assert_eq!(found[0].score, 1.0);
+        assert_eq!(found[1].reasons, vec!["tags", "duration"]);
+    }
+
+    #[test]
+    fn acoustid_alone_is_enough() {
+        let missing = FileIdentity {
+            acoustid: Some("abc".to_string()),
+            ..Default::default()
+        };
+        let candidate = FileIdentity {
+            acoustid: Some("ABC".to_string()),
+            ..identity("/x.mp3")
+        };
+        assert!(score(&missing, &candidate).is_some());
+        assert!(score(&missing, &identity("/y.mp3")).is_none());
+    }
+
+    #[tokio::test]
+    async fn relinking_onto_a_duplicate_keeps_its_stats() {
+        let db = DatabaseManager::in_memory().await;
+        let dir = std::env::temp_dir().join(format!("sonami-relink-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let new_path = dir.join("Song.flac");
+        std::fs::write(&new_path, b"").unwrap();
+        let new_path = new_path.to_string_lossy().into_owned();
+
+        db.seed(&[
+            "INSERT INTO artists (id, name) VALUES ('ar', 'Band')",
+            "INSERT INTO tracks (id, title, artist_id, duration, source_type, provider_id, file_path, missing_since, play_count, rating, rating_updated_at) VALUES ('old', 'Song', 'ar', 200, 'local', 'local', '/gone/Song.flac', 1, 5, 4, 100)",
+            "INSERT INTO tracks (id, title, artist_id, duration, source_type, provider_id, play_count, rating, rating_updated_at, like_updated_at) VALUES ('dup', 'Song', 'ar', 200, 'local', 'local', 2, 8, 200, 300)",
+            "INSERT INTO user_favorites (id, track_id, liked_at) VALUES ('f', 'dup', 300)",
+            "INSERT INTO play_history (id, track_id, played_at) VALUES ('p', 'dup', 300)",
+        ])
+        .await;
+        sqlx::query("UPDATE tracks SET file_path = ? WHERE id = 'dup'")
+            .bind(&new_path)
+            .execute(&db.pool)
+            .await
+            .unwrap();
+
+        let library = LibraryManager::new(db.pool.clone());
+        library.relink_track("old", &new_path).await.unwrap();
+
+        let row = sqlx::query(
+            "SELECT file_path, missing_since, play_count, rating, like_updated_at FROM tracks WHERE id = 'old'",
+        )
+        .fetch_one(&db.pool)
+        .await
+        .unwrap();
+        assert_eq!(row.get::<String, _>("file_path"), new_path);
+        assert_eq!(row.get::<Option<i64>, _>("missing_since"), None);
+        assert_eq!(row.get::<i64, _>("play_count"), 7);
+        assert_eq!(row.get::<Option<i64>, _>("rating"), Some(8));
+        assert_eq!(row.get::<Option<i64>, _>("like_updated_at"), Some(300));
+
+        let moved: (i64, i64) = sqlx::query_as(
+            "SELECT (SELECT COUNT(*) FROM user_favorites WHERE track_id = 'old'), (SELECT COUNT(*) FROM play_history WHERE track_id = 'old')",
+        )
+        .fetch_one(&db.pool)
+        .await
+        .unwrap();
+        assert_eq!(moved, (1, 1));
+        let dup: Option<String> = sqlx::query_scalar("SELECT id FROM tracks WHERE id = 'dup'")
+            .fetch_optional(&db.pool)
+            .await
+            .unwrap();
+        assert_eq!(dup, None);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -1,8 +1,22 @@
+pub mod backup;
+pub mod browse;
+pub mod integrity;
 pub mod models;
+pub mod ratings;
+pub mod scanner;
+pub mod search;
+pub mod tag_editor;
+pub mod watcher;
+pub mod works;
 
 use crate::tidal::models::{get_cover_url, CoverSize};
-use models::{LibraryAlbum, LibraryArtist, LocalSearchResults, TrackSource, UnifiedTrack};
-use sqlx::{Pool, Row, Sqlite};
+use models::{
+    ArtistRole, ExtendedTrackInfo, LibraryAlbum, LibraryArtist, LocalSearchResults, TrackDetails,
+    TrackSource, UnifiedTrack, ALBUM_LIKED_AT, ARTIST_LIKED_AT, TRACK_METADATA_COLUMNS,
+    TRACK_METADATA_CTES, TRACK_METADATA_JOINS,
+};
+use search::{SearchQuery, TextField};
+use sqlx::{Pool, Row, Sqlite, Transaction};
 use uuid::Uuid;
 
 pub struct LibraryManager {
@@ -15,15 +29,17 @@ impl LibraryManager {
     }
 
     pub async fn get_all_albums(&self) -> Result<Vec<LibraryAlbum>, String> {
-        let result = sqlx::query_as::<_, LibraryAlbum>(
+        let result = sqlx::query_as::<_, LibraryAlbum>(&format!(
             r#"
             SELECT 
-                al.id, al.title, a.name as artist, al.cover_url as cover_image, al.provider_id, al.external_id
+                al.id, al.title, a.name as artist, al.cover_url as cover_image, al.provider_id, al.external_id, al.year,
+                {}
             FROM albums al
             JOIN artists a ON al.artist_id = a.id
             ORDER BY al.title ASC
             "#,
-        )
+            ALBUM_LIKED_AT
+        ))
         .fetch_all(&self.pool)
         .await
         .map_err(|e| {
@@ -36,13 +52,14 @@ impl LibraryManager {
     }
 
     pub async fn get_all_artists(&self) -> Result<Vec<LibraryArtist>, String> {
-        let result = sqlx::query_as::<_, LibraryArtist>(
+        let result = sqlx::query_as::<_, LibraryArtist>(&format!(
             r#"
-            SELECT id, name, cover_url as cover_image, provider_id, external_id
-            FROM artists
-            ORDER BY name ASC
+            SELECT a.id, a.name, a.cover_url as cover_image, a.provider_id, a.external_id, {}
+            FROM artists a
+            ORDER BY a.name ASC
             "#,
-        )
+            ARTIST_LIKED_AT
+        ))
         .fetch_all(&self.pool)
         .await
         .map_err(|e| {
@@ -81,16 +98,7 @@ impl LibraryManager {
             let artist: String = row.try_get("artist").unwrap_or_default();
             let album: String = row.try_get("album").unwrap_or_default();
 
-            sqlx::query(
-                "INSERT INTO search_index (track_id, title, artist, album) VALUES (?, ?, ?, ?)",
-            )
-            .bind(id)
-            .bind(title)
-            .bind(artist)
-            .bind(album)
-            .execute(&mut *tx)
-            .await
-            .map_err(|e| e.to_string())?;
+            search::index_track(&mut tx, &id, &title, &artist, &album).await?;
         }
 
         tx.commit().await.map_err(|e| e.to_string())?;
@@ -129,108 +137,7 @@ impl LibraryManager {
             }
         }
 
-        let fts_query = query
-            .split_whitespace()
-            .map(|word| {
-                let clean: String = word.chars().filter(|c| c.is_alphanumeric()).collect();
-                format!("{}*", clean)
-            })
-            .filter(|w| w.len() > 1)
-            .collect::<Vec<_>>()
-            .join(" ");
-
-        if fts_query.is_empty() {
-            log::info!("Query is empty after sanitization");
-            return Ok(Vec::new());
-        }
-
-        log::info!("FTS5 query: {}", fts_query);
-
-        let rows = sqlx::query(
-            r#"
-            SELECT 
-                t.id, t.title, t.duration, t.source_type, t.file_path,
-                t.play_count, t.skip_count, t.last_played_at, t.added_at, t.audio_quality,
-                t.provider_id, t.external_id, t.artist_id, t.album_id,
-                a.name as artist_name,
-                al.title as album_title, al.cover_url
-            FROM search_index si
-            JOIN tracks t ON t.id = si.track_id
-            JOIN artists a ON t.artist_id = a.id
-            LEFT JOIN albums al ON t.album_id = al.id
-            WHERE search_index MATCH ?
-            ORDER BY rank
-            LIMIT 50
-            "#,
-        )
-        .bind(&fts_query)
-        .fetch_all(&self.pool)
-        .await
-        .map_err(|e| {
-            log::error!("FTS5 Search failed: {}", e);
-            e.to_string()
-        })?;
-
-        log::info!("Found {} search results", rows.len());
-
-        let mut tracks = Vec::new();
-        for row in rows {
-            let duration: i64 = row.try_get("duration").unwrap_or(0);
-            let provider_id: Option<String> = row.try_get("provider_id").ok();
-            let external_id: Option<String> = row.try_get("external_id").ok();
-            let source = TrackSource::from(
-                row.try_get::<String, _>("source_type")
-                    .unwrap_or_else(|_| "LOCAL".to_string()),
-            );
-            let local_path: Option<String> = row.try_get("file_path").ok();
-
-            let path = match source {
-                TrackSource::Tidal => {
-                    // Fallback to "tidal:external_id" format
-                    let eid = external_id.clone().unwrap_or_else(|| "0".to_string());
-                    format!("tidal:{}", eid)
-                }
-                TrackSource::Local => local_path.clone().unwrap_or_default(),
-                _ => {
-                    if let (Some(pid), Some(eid)) = (&provider_id, &external_id) {
-                        format!("{}:{}", pid, eid)
-                    } else {
-                        String::new()
-                    }
-                }
-            };
-
-            // Analytics with defaults
-            let play_count: i64 = row.try_get("play_count").unwrap_or(0);
-            let skip_count: i64 = row.try_get("skip_count").unwrap_or(0);
-            let last_played_at: Option<i64> = row.try_get("last_played_at").ok();
-            let added_at: Option<i64> = row.try_get("added_at").ok();
-            let audio_quality: Option<String> = row.try_get("audio_quality").ok();
-
-            tracks.push(UnifiedTrack {
-                id: row.try_get("id").unwrap_or_default(),
-                title: row.try_get("title").unwrap_or_default(),
-                artist: row.try_get("artist_name").unwrap_or_default(),
-                artist_id: row.try_get("artist_id").ok(),
-                album: row.try_get("album_title").unwrap_or_default(),
-                album_id: row.try_get("album_id").ok(),
-                duration: duration as u64,
-                source,
-                cover_image: row.try_get("cover_url").ok(),
-                path,
-                local_path,
-                audio_quality,
-                play_count: play_count as u64,
-                skip_count: skip_count as u64,
-                last_played_at,
-                liked_at: None,
-                added_at,
-                provider_id,
-                external_id,
-            });
-        }
-
-        Ok(tracks)
+        self.search_tracks(&SearchQuery::parse(query), 50).await
     }
 
     pub async fn search_albums(&self, query: &str) -> Result<Vec<LibraryAlbum>, String> {
@@ -240,18 +147,19 @@ impl LibraryManager {
 
         let like_pattern = format!("%{}%", query);
 
-        let albums = sqlx::query_as::<_, LibraryAlbum>(
+        let albums = sqlx::query_as::<_, LibraryAlbum>(&format!(
             r#"
             SELECT 
                 al.id, al.title, a.name as artist, al.cover_url as cover_image, 
-                al.provider_id, al.external_id
+                al.provider_id, al.external_id, al.year, {}
             FROM albums al
             JOIN artists a ON al.artist_id = a.id
             WHERE al.title LIKE ? COLLATE NOCASE
             ORDER BY al.title ASC
             LIMIT 20
             "#,
-        )
+            ALBUM_LIKED_AT
+        ))
         .bind(&like_pattern)
         .fetch_all(&self.pool)
         .await
@@ -271,16 +179,17 @@ impl LibraryManager {
 
         let like_pattern = format!("%{}%", query);
 
-        let artists = sqlx::query_as::<_, LibraryArtist>(
+        let artists = sqlx::query_as::<_, LibraryArtist>(&format!(
             r#"
             SELECT 
-                id, name, cover_url as cover_image, provider_id, external_id
-            FROM artists
-            WHERE name LIKE ? COLLATE NOCASE
-            ORDER BY name ASC
+                a.id, a.name, a.cover_url as cover_image, a.provider_id, a.external_id, {}
+            FROM artists a
+            WHERE a.name LIKE ? COLLATE NOCASE
+            ORDER BY a.name ASC
             LIMIT 20
             "#,
-        )
+            ARTIST_LIKED_AT
+        ))
         .bind(&like_pattern)
         .fetch_all(&self.pool)
         .await
@@ -294,10 +203,13 @@ impl LibraryManager {
     }
 
     pub async fn search_full(&self, query: &str) -> Result<LocalSearchResults, String> {
+        let parsed = SearchQuery::parse(query);
+        let album_text = parsed.text_for(TextField::Album);
+        let artist_text = parsed.text_for(TextField::Artist);
         let (tracks, albums, artists) = tokio::join!(
             self.search_library(query),
-            self.search_albums(query),
-            self.search_artists(query)
+            self.search_albums(&album_text),
+            self.search_artists(&artist_text)
         );
 
         Ok(LocalSearchResults {
@@ -308,20 +220,24 @@ impl LibraryManager {
     }
 
     pub async fn get_all_tracks(&self) -> Result<Vec<UnifiedTrack>, String> {
-        let rows = sqlx::query(
+        let rows = sqlx::query(&format!(
             r#"
+            WITH {}
             SELECT 
                 t.id, t.title, t.duration, t.source_type, t.file_path,
                 t.play_count, t.skip_count, t.last_played_at, t.added_at, t.audio_quality,
                 t.provider_id, t.external_id, t.artist_id, t.album_id,
+                {},
                 a.name as artist_name,
                 al.title as album_title, al.cover_url
             FROM tracks t
             JOIN artists a ON t.artist_id = a.id
             LEFT JOIN albums al ON t.album_id = al.id
+            {}
             ORDER BY t.title ASC
             "#,
-        )
+            TRACK_METADATA_CTES, TRACK_METADATA_COLUMNS, TRACK_METADATA_JOINS
+        ))
         .fetch_all(&self.pool)
         .await
         .map_err(|e| e.to_string())?;
@@ -379,6 +295,7 @@ impl LibraryManager {
                 added_at,
                 provider_id,
                 external_id,
+                metadata: ExtendedTrackInfo::from_row(&row),
             });
         }
 
@@ -563,11 +480,13 @@ impl LibraryManager {
         }
 
         // 2. Find or Create Artist
-        let artist_name = if track.artist.is_empty() {
-            "Unknown Artist"
+        // Providers only give a display string here, so guests are split off it
+        let (artist_name, featured_artists) = if track.artist.is_empty() {
+            ("Unknown Artist".to_string(), Vec::new())
         } else {
-            &track.artist
+            scanner::split_featured(&track.artist)
         };
+        let artist_name = artist_name.as_str();
 
         // Extract external artist ID if available
         let artist_external_id = track.artist_id.as_ref().map(|aid| {
@@ -762,17 +681,28 @@ impl LibraryManager {
         .await
         .map_err(|e| e.to_string())?;
 
+        write_track_details(
+            &mut tx,
+            &new_track_id,
+            &artist_id,
+            album_id.as_deref(),
+            provider_id,
+            &TrackDetails {
+                featured_artists,
+                ..Default::default()
+            },
+        )
+        .await?;
+
         // 5. Index for Search
-        sqlx::query(
-            "INSERT INTO search_index (track_id, title, artist, album) VALUES (?, ?, ?, ?)",
+        search::index_track(
+            &mut tx,
+            &new_track_id,
+            &track.title,
+            artist_name,
+            album_name,
         )
-        .bind(&new_track_id)
-        .bind(&track.title)
-        .bind(artist_name)
-        .bind(album_name)
-        .execute(&mut *tx)
-        .await
-        .map_err(|e| e.to_string())?;
+        .await?;
 
         tx.commit().await.map_err(|e| e.to_string())?;
         Ok(new_track_id)
@@ -833,6 +763,14 @@ impl LibraryManager {
             new_id
         };
 
+        let details = tidal_track_details(track);
+        let album_artist_id = match &details.album_artist {
+            Some(name) if name != &artist_name => {
+                find_or_create_artist(&mut tx, name, "tidal").await?
+            }
+            _ => artist_id.clone(),
+        };
+
         let mut album_id = None;
         let mut album_name = String::new();
         if let Some(album) = &track.album {
@@ -842,7 +780,7 @@ impl LibraryManager {
                 if let Some(row) =
                     sqlx::query("SELECT id FROM albums WHERE title = ? AND artist_id = ?")
                         .bind(album_title)
-                        .bind(&artist_id)
+                        .bind(&album_artist_id)
                         .fetch_optional(&mut *tx)
                         .await
                         .map_err(|e| e.to_string())?
@@ -855,7 +793,7 @@ impl LibraryManager {
                     )
                     .bind(&new_id)
                     .bind(album_title)
-                    .bind(&artist_id)
+                    .bind(&album_artist_id)
                     .bind(&cover_url)
                     .execute(&mut *tx)
                     .await
@@ -898,22 +836,18 @@ impl LibraryManager {
             new_id
         };
 
-        sqlx::query("DELETE FROM search_index WHERE track_id = ?")
-            .bind(&track_id)
-            .execute(&mut *tx)
-            .await
-            .ok();
-
-        sqlx::query(
-            "INSERT INTO search_index (track_id, title, artist, album) VALUES (?, ?, ?, ?)",
+        // Refresh details on re-imports too, they may have been missing the first time
+        write_track_details(
+            &mut tx,
+            &track_id,
+            &artist_id,
+            album_id.as_deref(),
+            "tidal",
+            &details,
         )
-        .bind(&track_id)
-        .bind(&track.title)
-        .bind(&artist_name)
-        .bind(&album_name)
-        .execute(&mut *tx)
-        .await
-        .map_err(|e| e.to_string())?;
+        .await?;
+
+        search::index_track(&mut tx, &track_id, &track.title, &artist_name, &album_name).await?;
 
         tx.commit().await.map_err(|e| e.to_string())?;
         Ok(track_id)
@@ -933,6 +867,7 @@ impl LibraryManager {
         album_external_id: Option<&str>,
         duration_secs: Option<u32>,
         cover_url: Option<String>,
+        details: &TrackDetails,
     ) -> Result<String, String> {
         let mut tx = self.pool.begin().await.map_err(|e| e.to_string())?;
 
@@ -959,6 +894,13 @@ impl LibraryManager {
             new_id
         };
 
+        let album_artist_id = match &details.album_artist {
+            Some(name) if name != artist_name => {
+                find_or_create_artist(&mut tx, name, provider_id).await?
+            }
+            _ => artist_id.clone(),
+        };
+
         // Upsert album if provided
         let mut album_id_opt = None;
         if let Some(album_title) = album_name {
@@ -966,7 +908,7 @@ impl LibraryManager {
                 if let Some(row) =
                     sqlx::query("SELECT id FROM albums WHERE title = ? AND artist_id = ?")
                         .bind(album_title)
-                        .bind(&artist_id)
+                        .bind(&album_artist_id)
                         .fetch_optional(&mut *tx)
                         .await
                         .map_err(|e| e.to_string())?
@@ -979,7 +921,7 @@ impl LibraryManager {
                     )
                     .bind(&new_id)
                     .bind(album_title)
-                    .bind(&artist_id)
+                    .bind(&album_artist_id)
                     .bind(provider_id)
                     .bind(album_external_id.unwrap_or("0"))
                     .bind(&cover_url)
@@ -1027,25 +969,395 @@ impl LibraryManager {
             new_id
         };
 
+        write_track_details(
+            &mut tx,
+            &track_id,
+            &artist_id,
+            album_id_opt.as_deref(),
+            provider_id,
+            details,
+        )
+        .await?;
+
         // Update search index
-        sqlx::query("DELETE FROM search_index WHERE track_id = ?")
-            .bind(&track_id)
-            .execute(&mut *tx)
+        search::index_track(
+            &mut tx,
+            &track_id,
+            title,
+            artist_name,
+            album_name.unwrap_or(""),
+        )
+        .await?;
+
+        tx.commit().await.map_err(|e| e.to_string())?;
+        Ok(track_id)
+    }
+}
+
+fn tidal_track_details(track: &crate::tidal::models::Track) -> TrackDetails {
+    let album = track.album.as_ref();
+    let featured_artists = track
+        .artists
+        .iter()
+        .flatten()
+        .filter(|a| a.artist_type.as_deref() == Some("FEATURED"))
+        .map(|a| a.name.clone())
+        .collect();
+
+    TrackDetails {
+        album_artist: album
+            .and_then(|a| a.artist.as_ref())
+            .map(|a| a.name.clone()),
+        featured_artists,
+        track_number: track.track_number,
+        disc_number: track.volume_number,
+        year: album
+            .and_then(|a| a.release_date.as_deref())
+            .and_then(|d| d.get(..4))
+            .and_then(|y| y.parse().ok()),
+        isrc: track.isrc.clone(),
+        ..Default::default()
+    }
+}
+
+/// Look up an artist by name, creating it under `provider_id` when missing
+pub(crate) async fn find_or_create_artist(
+    tx: &mut Transaction<'_, Sqlite>,
+    name: &str,
+    provider_id: &str,
+) -> Result<String, String> {
+    if let Some(row) = sqlx::query("SELECT id FROM artists WHERE name = ?")
+        .bind(name)
+        .fetch_optional(&mut **tx)
+        .await
+        .map_err(|e| e.to_string())?
+    {
+        return Ok(row.try_get("id").unwrap_or_default());
+    }
+
+    let id = Uuid::new_v4().to_string();
+    sqlx::query("INSERT INTO artists (id, name, provider_id) VALUES (?, ?, ?)")
+        .bind(&id)
+        .bind(name)
+        .bind(provider_id)
+        .execute(&mut **tx)
+        .await
+        .map_err(|e| e.to_string())?;
+    Ok(id)
+}
+
+/// Store numbering, genres, identifiers and artist credits for a track, then
+/// link it to its work. Fields and lists missing from `details` keep whatever
+/// was recorded before, so a sparse provider import can't wipe a richer one.
+pub(crate) async fn write_track_details(
+    tx: &mut Transaction<'_, Sqlite>,
+    track_id: &str,
+    main_artist_id: &str,
+    album_id: Option<&str>,
+    provider_id: &str,
+    details: &TrackDetails,
+) -> Result<(), String> {
+    store_track_details(
+        tx,
+        track_id,
+        main_artist_id,
+        album_id,
+        provider_id,
+        details,
+        false,
+    )
+    .await
+}
+
+/// Like [`write_track_details`], but replaces everything recorded before.
+/// Used for file scans, where the tags are the whole story.
+pub(crate) async fn replace_track_details(
+    tx: &mut Transaction<'_, Sqlite>,
+    track_id: &str,
+    main_artist_id: &str,
+    album_id: Option<&str>,
+    provider_id: &str,
+    details: &TrackDetails,
+) -> Result<(), String> {
+    store_track_details(
+        tx,
+        track_id,
+        main_artist_id,
+        album_id,
+        provider_id,
+        details,
+        true,
+    )
+    .await
+}
+
+async fn store_track_details(
+    tx: &mut Transaction<'_, Sqlite>,
+    track_id: &str,
+    main_artist_id: &str,
+    album_id: Option<&str>,
+    provider_id: &str,
+    details: &TrackDetails,
+    replace: bool,
+) -> Result<(), String> {
+    let assignments = [
+        "track_number",
+        "disc_number",
+        "year",
+        "genre",
+        "isrc",
+        "musicbrainz_id",
+        "acoustid",
+    ]
+    .iter()
+    .map(|column| {
+        if replace {
+            format!("{} = ?", column)
+        } else {
+            format!("{0} = COALESCE(?, {0})", column)
+        }
+    })
+    .collect::<Vec<_>>()
+    .join(", ");
+
+    sqlx::query(&format!("UPDATE tracks SET {} WHERE id = ?", assignments))
+        .bind(details.track_number.map(|n| n as i64))
+        .bind(details.disc_number.map(|n| n as i64))
+        .bind(details.year.map(|n| n as i64))
+        .bind(details.genres.first())
+        .bind(&details.isrc)
+        .bind(&details.musicbrainz_track_id)
+        .bind(&details.acoustid)
+        .bind(track_id)
+        .execute(&mut **tx)
+        .await
+        .map_err(|e| e.to_string())?;
+
+    if let Some(album_id) = album_id {
+        sqlx::query(
+            "UPDATE albums SET year = COALESCE(?, year), musicbrainz_id = COALESCE(?, musicbrainz_id) WHERE id = ?",
+        )
+        .bind(details.year.map(|n| n as i64))
+        .bind(&details.musicbrainz_album_id)
+        .bind(album_id)
+        .execute(&mut **tx)
+        .await
+        .map_err(|e| e.to_string())?;
+    }
+
+    if details.musicbrainz_artist_id.is_some() {
+        sqlx::query(
+            "UPDATE artists SET musicbrainz_id = ? WHERE id = ? AND musicbrainz_id IS NULL",
+        )
+        .bind(&details.musicbrainz_artist_id)
+        .bind(main_artist_id)
+        .execute(&mut **tx)
+        .await
+        .map_err(|e| e.to_string())?;
+    }
+
+    if replace || !details.featured_artists.is_empty() || !details.composers.is_empty() {
+        sqlx::query("DELETE FROM track_artists WHERE track_id = ?")
+            .bind(track_id)
+            .execute(&mut **tx)
+            .await
+            .map_err(|e| e.to_string())?;
+    } else {
+        // Keep the other credits, but make sure the main one matches the track
+        sqlx::query("DELETE FROM track_artists WHERE track_id = ? AND role = 'main'")
+            .bind(track_id)
+            .execute(&mut **tx)
             .await
-            .ok();
+            .map_err(|e| e.to_string())?;
+    }
 
+    let mut credits = vec![(main_artist_id.to_string(), ArtistRole::Main)];
+    for (names, role) in [
+        (&details.featured_artists, ArtistRole::Featured),
+        (&details.composers, ArtistRole::Composer),
+    ] {
+        for name in names {
+            let artist_id = find_or_create_artist(tx, name, provider_id).await?;
+            credits.push((artist_id, role));
+        }
+    }
+
+    for (position, (artist_id, role)) in credits.iter().enumerate() {
         sqlx::query(
-            "INSERT INTO search_index (track_id, title, artist, album) VALUES (?, ?, ?, ?)",
+            "INSERT OR IGNORE INTO track_artists (track_id, artist_id, role, position) VALUES (?, ?, ?, ?)",
         )
-        .bind(&track_id)
-        .bind(title)
-        .bind(artist_name)
-        .bind(album_name.unwrap_or(""))
-        .execute(&mut *tx)
+        .bind(track_id)
+        .bind(artist_id)
+        .bind(role.as_str())
+        .bind(position as i64)
+        .execute(&mut **tx)
         .await
         .map_err(|e| e.to_string())?;
+    }
 
-        tx.commit().await.map_err(|e| e.to_string())?;
-        Ok(track_id)
+    if replace || !details.genres.is_empty() {
+        sqlx::query("DELETE FROM track_genres WHERE track_id = ?")
+            .bind(track_id)
+            .execute(&mut **tx)
+            .await
+            .map_err(|e| e.to_string())?;
+    }
+
+    for genre in &details.genres {
+        sqlx::query("INSERT OR IGNORE INTO track_genres (track_id, genre) VALUES (?, ?)")
+            .bind(track_id)
+            .bind(genre)
+            .execute(&mut **tx)
+            .await
+            .map_err(|e| e.to_string())?;
+    }
+
+    works::link_track(tx, track_id).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::DatabaseManager;
+
+    #[tokio::test]
+    async fn sparse_reimports_keep_details() {
+        let db = DatabaseManager::in_memory().await;
+        let library = LibraryManager::new(db.pool.clone());
+        async fn import(library: &LibraryManager, details: TrackDetails) -> String {
+            library
+                .import_provider_track(
+                    "tidal",
+                    "42",
+                    "Song",
+                    "Main",
+                    None,
+                    Some("Album"),
+                    None,
+                    Some(200),
+                    None,
+                    &details,
+                )
+                .await
+                .unwrap()
+        }
+
+        let id = import(
+            &library,
+            TrackDetails {
+                featured_artists: vec!["Guest".to_string()],
+                composers: vec!["Writer".to_string()],
+                track_number: Some(3),
+                disc_number: Some(1),
+                year: Some(2001),
+                genres: vec!["Rock".to_string(), "Pop".to_string()],
+                isrc: Some("USABC0100001".to_string()),
+                musicbrainz_track_id: Some("mbid".to_string()),
+                acoustid: Some("acoustid".to_string()),
+                ..Default::default()
+            },
+        )
+        .await;
+        // A Spotify import of the same track only knows its ISRC
+        assert_eq!(import(&library, TrackDetails::default()).await, id);
+
+        let row: (
+            Option<i64>,
+            Option<i64>,
+            Option<i64>,
+            Option<String>,
+            Option<String>,
+            Option<String>,
+            Option<String>,
+        ) = sqlx::query_as(
+            "SELECT track_number, disc_number, year, genre, isrc, musicbrainz_id, acoustid FROM tracks WHERE id = ?",
+        )
+        .bind(&id)
+        .fetch_one(&db.pool)
+        .await
+        .unwrap();
+        assert_eq!(
+            row,
+            (
+                Some(3),
+                Some(1),
+                Some(2001),
+                Some("Rock".to_string()),
+                Some("USABC0100001".to_string()),
+                Some("mbid".to_string()),
+                Some("acoustid".to_string())
+            )
+        );
+
+        let credits: Vec<(String, String)> = sqlx::query_as(
+            "SELECT a.name, ta.role FROM track_artists ta JOIN artists a ON a.id = ta.artist_id WHERE ta.track_id = ? ORDER BY ta.position",
+        )
+        .bind(&id)
+        .fetch_all(&db.pool)
+        .await
+        .unwrap();
+        assert_eq!(
+            credits,
+            vec![
+                ("Main".to_string(), "main".to_string()),
+                ("Guest".to_string(), "featured".to_string()),
+                ("Writer".to_string(), "composer".to_string())
+            ]
+        );
+
+        let genres: Vec<String> =
+            sqlx::query_scalar("SELECT genre FROM track_genres WHERE track_id = ? ORDER BY genre")
+                .bind(&id)
+                .fetch_all(&db.pool)
+                .await
+                .unwrap();
+        assert_eq!(genres, vec!["Pop".to_string(), "Rock".to_string()]);
+    }
+
+    #[tokio::test]
+    async fn track_lists_carry_credits_and_genres() {
+        let db = DatabaseManager::in_memory().await;
+        let library = LibraryManager::new(db.pool.clone());
+        for (external_id, title, details) in [
+            (
+                "1",
+                "One",
+                TrackDetails {
+                    composers: vec!["Writer".to_string()],
+                    featured_artists: vec!["Guest".to_string()],
+                    genres: vec!["Rock".to_string(), "Pop".to_string()],
+                    ..Default::default()
+                },
+            ),
+            ("2", "Two", TrackDetails::default()),
+        ] {
+            library
+                .import_provider_track(
+                    "tidal",
+                    external_id,
+                    title,
+                    "Main",
+                    None,
+                    Some("Album"),
+                    None,
+                    Some(200),
+                    None,
+                    &details,
+                )
+                .await
+                .unwrap();
+        }
+
+        let tracks = library.get_all_tracks().await.unwrap();
+        let credits: Vec<&str> = tracks[0]
+            .metadata
+            .artists
+            .iter()
+            .map(|c| c.name.as_str())
+            .collect();
+        assert_eq!(credits, vec!["Main", "Guest", "Writer"]);
+        assert_eq!(tracks[0].metadata.genres, vec!["Pop", "Rock"]);
+        assert_eq!(tracks[1].metadata.artists.len(), 1);
+        assert!(tracks[1].metadata.genres.is_empty());
     }
 }
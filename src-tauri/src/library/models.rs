@@ -1,4 +1,6 @@
 use serde::{Deserialize, Serialize};
+use sqlx::sqlite::SqliteRow;
+use sqlx::Row;
 
 #[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Default)]
 #[serde(rename_all = "SCREAMING_SNAKE_CASE")]
@@ -83,6 +85,322 @@ pub struct UnifiedTrack {
 
     #[serde(skip_serializing_if = "Option::is_none")]
     pub added_at: Option<i64>,
+
+    #[serde(flatten, default)]
+    pub metadata: ExtendedTrackInfo,
+}
+
+impl UnifiedTrack {
+    /// Map a row selecting the usual track/artist/album columns plus
+    /// `TRACK_METADATA_COLUMNS`
+    pub fn from_row(row: &SqliteRow) -> Self {
+        let provider_id: Option<String> = row.try_get("provider_id").ok();
+        let external_id: Option<String> = row.try_get("external_id").ok();
+        let source = TrackSource::from(
+            row.try_get::<String, _>("source_type")
+                .unwrap_or_else(|_| "LOCAL".to_string()),
+        );
+        let local_path: Option<String> = row.try_get("file_path").ok();
+
+        let path = match source {
+            TrackSource::Tidal => {
+                let eid = external_id.clone().unwrap_or_else(|| "0".to_string());
+                format!("tidal:{}", eid)
+            }
+            TrackSource::Local => local_path.clone().unwrap_or_default(),
+            _ => {
+                if let (Some(pid), Some(eid)) = (&provider_id, &external_id) {
+                    format!("{}:{}", pid, eid)
+                } else {
+                    String::new()
+                }
+            }
+        };
+
+        Self {
+            id: row.try_get("id").unwrap_or_default(),
+            title: row.try_get("title").unwrap_or_default(),
+            artist: row.try_get("artist_name").unwrap_or_default(),
+            artist_id: row.try_get("artist_id").ok(),
+            album: row.try_get("album_title").unwrap_or_default(),
+            album_id: row.try_get("album_id").ok(),
+            duration: row.try_get::<i64, _>("duration").unwrap_or(0) as u64,
+            source,
+            cover_image: row.try_get("cover_url").ok(),
+            path,
+            local_path,
+            audio_quality: row.try_get("audio_quality").ok(),
+            play_count: row.try_get::<i64, _>("play_count").unwrap_or(0) as u64,
+            skip_count: row.try_get::<i64, _>("skip_count").unwrap_or(0) as u64,
+            last_played_at: row.try_get("last_played_at").ok(),
+            liked_at: row.try_get("liked_at").ok().flatten(),
+            added_at: row.try_get("added_at").ok(),
+            provider_id,
+            external_id,
+            metadata: ExtendedTrackInfo::from_row(row),
+        }
+    }
+}
+
+/// Artist credits and genres, aggregated once per statement instead of once
+/// per row. Goes after the statement's `WITH`, and is read through
+/// `TRACK_METADATA_JOINS`.
+pub const TRACK_METADATA_CTES: &str = r#"
+    track_credits AS (
+        SELECT ta.track_id, json_group_array(
+            json_object('id', ta.artist_id, 'name', ar.name, 'role', ta.role)
+            ORDER BY ta.role = 'composer', ta.role = 'featured', ta.position
+        ) as artist_credits
+        FROM track_artists ta
+        JOIN artists ar ON ar.id = ta.artist_id
+        GROUP BY ta.track_id
+    ),
+    track_genre_lists AS (
+        SELECT track_id, json_group_array(genre ORDER BY genre) as genres
+        FROM track_genres
+        GROUP BY track_id
+    )
+"#;
+
+/// Joins `TRACK_METADATA_CTES` to tracks aliased `t`
+pub const TRACK_METADATA_JOINS: &str = r#"
+    LEFT JOIN track_credits tc ON tc.track_id = t.id
+    LEFT JOIN track_genre_lists tg ON tg.track_id = t.id
+"#;
+
+/// Extra columns read by `ExtendedTrackInfo::from_row`. Expects the query to
+/// alias `tracks` as `t` and `albums` as `al`, and to include
+/// `TRACK_METADATA_CTES` and `TRACK_METADATA_JOINS`.
+pub const TRACK_METADATA_COLUMNS: &str = r#"
+    t.track_number, t.disc_number, t.year, t.genre, t.isrc, t.musicbrainz_id, t.work_id,
+    t.missing_since, t.rating,
+    (SELECT SUM(w.play_count) FROM tracks w WHERE w.work_id = t.work_id) as work_play_count,
+    (
+        SELECT MAX(f.liked_at) FROM user_favorites f
+        JOIN tracks w ON w.id = f.track_id
+        WHERE w.work_id = t.work_id
+    ) as work_liked_at,
+    al.artist_id as album_artist_id,
+    (SELECT name FROM artists WHERE id = al.artist_id) as album_artist,
+    tc.artist_credits,
+    tg.genres
+"#;
+
+/// `liked_at` column for an album aliased `al`. Likes match on provider and
+/// external id (with or without the provider prefix), or for local albums on
+/// the lowercased album artist and title.
+pub const ALBUM_LIKED_AT: &str = r#"
+    (
+        SELECT f.liked_at FROM user_favorite_items f
+        WHERE f.kind = 'album' AND (
+            (f.provider_id = 'local'
+                AND f.external_id = lower(trim(COALESCE((SELECT name FROM artists WHERE id = al.artist_id), '')))
+                    || char(31) || lower(trim(al.title)))
+            OR (f.provider_id = al.provider_id
+                AND al.external_id IN (f.external_id, f.provider_id || ':' || f.external_id))
+        )
+        ORDER BY f.liked_at DESC LIMIT 1
+    ) as liked_at
+"#;
+
+/// `liked_at` column for an artist aliased `a`, matched like `ALBUM_LIKED_AT`
+pub const ARTIST_LIKED_AT: &str = r#"
+    (
+        SELECT f.liked_at FROM user_favorite_items f
+        WHERE f.kind = 'artist' AND (
+            (f.provider_id = 'local' AND f.external_id = lower(trim(a.name)))
+            OR (f.provider_id = a.provider_id
+                AND a.external_id IN (f.external_id, f.provider_id || ':' || f.external_id))
+        )
+        ORDER BY f.liked_at DESC LIMIT 1
+    ) as liked_at
+"#;
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum ArtistRole {
+    #[default]
+    Main,
+    Featured,
+    Composer,
+}
+
+impl ArtistRole {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ArtistRole::Main => "main",
+            ArtistRole::Featured => "featured",
+            ArtistRole::Composer => "composer",
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ArtistCredit {
+    pub id: String,
+    pub name: String,
+    pub role: ArtistRole,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct ExtendedTrackInfo {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub album_artist: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub album_artist_id: Option<String>,
+    #[serde(default)]
+    pub artists: Vec<ArtistCredit>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub track_number: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub disc_number: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub year: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub genre: Option<String>,
+    /// Every genre tagged on the track, alphabetically
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub genres: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub isrc: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub musicbrainz_id: Option<String>,
+    /// Canonical work shared by copies of this recording on other providers
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub work_id: Option<String>,
+    /// Plays summed over every copy of the work
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub work_play_count: Option<u64>,
+    /// Most recent like of any copy of the work
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub work_liked_at: Option<i64>,
+    /// When the track's file was found to be gone; unset while it is playable
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub missing_since: Option<i64>,
+    /// Half-stars, 0 to 10
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rating: Option<u8>,
+}
+
+impl ExtendedTrackInfo {
+    /// Read the `TRACK_METADATA_COLUMNS` from a row; missing columns are left empty
+    pub fn from_row(row: &SqliteRow) -> Self {
+        let int = |col: &str| {
+            row.try_get::<Option<i64>, _>(col)
+                .ok()
+                .flatten()
+                .map(|v| v as u32)
+        };
+        let text = |col: &str| row.try_get::<Option<String>, _>(col).ok().flatten();
+
+        Self {
+            album_artist: text("album_artist"),
+            album_artist_id: text("album_artist_id"),
+            artists: text("artist_credits")
+                .and_then(|json| serde_json::from_str(&json).ok())
+                .unwrap_or_default(),
+            track_number: int("track_number"),
+            disc_number: int("disc_number"),
+            year: int("year"),
+            genre: text("genre"),
+            genres: text("genres")
+                .and_then(|json| serde_json::from_str(&json).ok())
+                .unwrap_or_default(),
+            isrc: text("isrc"),
+            musicbrainz_id: text("musicbrainz_id"),
+            work_id: text("work_id"),
+            work_play_count: row
+                .try_get::<Option<i64>, _>("work_play_count")
+                .ok()
+                .flatten()
+                .map(|v| v as u64),
+            work_liked_at: row
+                .try_get::<Option<i64>, _>("work_liked_at")
+                .ok()
+                .flatten(),
+            missing_since: row
+                .try_get::<Option<i64>, _>("missing_since")
+                .ok()
+                .flatten(),
+            rating: int("rating").map(|v| v as u8),
+        }
+    }
+}
+
+/// Optional details supplied when importing a track, beyond title/artist/album
+#[derive(Debug, Clone, Default)]
+pub struct TrackDetails {
+    /// Album artist when it differs from the track artist (compilations)
+    pub album_artist: Option<String>,
+    pub featured_artists: Vec<String>,
+    pub composers: Vec<String>,
+    pub track_number: Option<u32>,
+    pub disc_number: Option<u32>,
+    pub year: Option<u32>,
+    pub genres: Vec<String>,
+    pub isrc: Option<String>,
+    pub musicbrainz_track_id: Option<String>,
+    pub musicbrainz_album_id: Option<String>,
+    pub musicbrainz_artist_id: Option<String>,
+    /// AcoustID of the audio fingerprint, as written by taggers like Picard
+    pub acoustid: Option<String>,
+}
+
+/// One copy of a work, as considered by the source resolver
+#[derive(Debug, Serialize, Deserialize, Clone, sqlx::FromRow)]
+pub struct WorkSource {
+    pub track_id: String,
+    pub source_type: String,
+    pub provider_id: Option<String>,
+    pub external_id: Option<String>,
+    pub file_path: Option<String>,
+    pub audio_quality: Option<String>,
+}
+
+/// A file that may be a missing track's file under a new path
+#[derive(Debug, Serialize, Clone)]
+pub struct RelinkCandidate {
+    pub path: String,
+    /// 0.0 to 1.0
+    pub score: f64,
+    /// What matched: "acoustid", "isrc", "tags", "duration", "size" or "filename"
+    pub reasons: Vec<String>,
+    /// Set when the file is already in the library as another track, which
+    /// relinking merges into the missing one
+    pub track_id: Option<String>,
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct MissingTrack {
+    pub track_id: String,
+    pub title: String,
+    pub artist: String,
+    pub album: Option<String>,
+    pub provider_id: Option<String>,
+    pub file_path: String,
+    pub missing_since: Option<i64>,
+    pub suggestions: Vec<RelinkCandidate>,
+}
+
+#[derive(Debug, Serialize, Clone, Default)]
+pub struct IntegrityReport {
+    /// File-backed tracks looked at
+    pub checked: usize,
+    /// Previously missing tracks whose files are back
+    pub restored: usize,
+    pub missing: Vec<MissingTrack>,
+}
+
+#[derive(Debug, Serialize, Clone, Default)]
+pub struct RatingSyncReport {
+    /// Ratings taken from file tags or servers
+    pub pulled: usize,
+    /// Ratings written to file tags or servers
+    pub pushed: usize,
+    /// Tracks rated differently on both sides since the last sync
+    pub conflicts: usize,
+    /// Tracks whose file or server could not be reached; retried next sync
+    pub failed: Vec<String>,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone, sqlx::FromRow)]
@@ -93,6 +411,10 @@ pub struct LibraryAlbum {
     pub cover_image: Option<String>,
     pub provider_id: Option<String>,
     pub external_id: Option<String>,
+    pub year: Option<i64>,
+    #[sqlx(default)]
+    #[serde(default)]
+    pub liked_at: Option<i64>,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone, sqlx::FromRow)]
@@ -102,6 +424,9 @@ pub struct LibraryArtist {
     pub cover_image: Option<String>,
     pub provider_id: Option<String>,
     pub external_id: Option<String>,
+    #[sqlx(default)]
+    #[serde(default)]
+    pub liked_at: Option<i64>,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -110,3 +435,38 @@ pub struct LocalSearchResults {
     pub albums: Vec<LibraryAlbum>,
     pub artists: Vec<LibraryArtist>,
 }
+
+/// One page of a larger result set
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Page<T> {
+    pub items: Vec<T>,
+    pub total: i64,
+    pub offset: i64,
+    pub limit: i64,
+}
+
+/// A bucket in a browse view (genre, decade, year, provider or folder)
+#[derive(Debug, Serialize, Deserialize, Clone, sqlx::FromRow)]
+pub struct BrowseGroup {
+    pub key: String,
+    pub label: String,
+    pub track_count: i64,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct FolderListing {
+    pub path: Option<String>,
+    pub folders: Vec<BrowseGroup>,
+    pub tracks: Page<UnifiedTrack>,
+}
+
+/// Which slice of the library `browse_tracks` returns
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(tag = "type", content = "value", rename_all = "lowercase")]
+pub enum BrowseFilter {
+    Genre(String),
+    /// First year of the decade, e.g. 1990
+    Decade(u32),
+    Year(u32),
+    Provider(String),
+}
@@ -0,0 +1,603 @@
+use super::models::RatingSyncReport;
+use super::scanner::file_modified;
+use super::tag_editor::{read_tags, write_rating};
+use super::LibraryManager;
+use crate::providers::bare_id;
+use crate::providers::traits::MusicProvider;
+use chrono::Utc;
+use sqlx::{Executor, Row, Sqlite, Transaction};
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+/// Ratings are stored in half-stars
+pub const MAX_RATING: u8 = 10;
+
+/// A track's rating, and what the file tag or server held when the two last
+/// agreed. Comparing both sides against that shared value tells which one
+/// changed, like a three-way merge.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub(crate) struct RatingState {
+    pub rating: Option<u8>,
+    /// When the rating was last set in the app
+    pub updated_at: Option<i64>,
+    /// The other side's value at the last sync
+    pub synced: Option<u8>,
+    /// Unset until the track has been synced once
+    pub synced_at: Option<i64>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Resolution {
+    /// Both sides hold this value
+    InSync(Option<u8>),
+    /// Take the other side's value
+    Pull(Option<u8>),
+    /// Write the app's rating, rounded to the other side's precision
+    Push(Option<u8>),
+}
+
+/// Decide how to reconcile a rating with the other side's `remote` value.
+/// `step` is the other side's precision in half-stars (2 for whole stars).
+/// When both sides changed, the newer change wins; a side that cannot say
+/// when it changed loses to the app. Returns whether that was a conflict.
+pub(crate) fn resolve(
+    state: &RatingState,
+    remote: Option<u8>,
+    remote_changed_at: Option<i64>,
+    step: u8,
+) -> (Resolution, bool) {
+    let local = state
+        .rating
+        .filter(|r| *r > 0)
+        .map(|r| r.div_ceil(step) * step);
+    if local == remote {
+        return (Resolution::InSync(remote), false);
+    }
+
+    let (local_changed, remote_changed) = match state.synced_at {
+        None => (local.is_some(), remote.is_some()),
+        Some(synced_at) => (
+            state.updated_at.is_some_and(|t| t > synced_at),
+            remote != state.synced,
+        ),
+    };
+
+    match (local_changed, remote_changed) {
+        (false, true) => (Resolution::Pull(remote), false),
+        (true, true) => {
+            let remote_newer = match (remote_changed_at, state.updated_at) {
+                (Some(remote_at), Some(local_at)) => remote_at > local_at,
+                (Some(_), None) => true,
+                _ => false,
+            };
+            let resolution = if remote_newer {
+                Resolution::Pull(remote)
+            } else {
+                Resolution::Push(local)
+            };
+            (resolution, true)
+        }
+        _ => (Resolution::Push(local), false),
+    }
+}
+
+impl Resolution {
+    fn value(self) -> Option<u8> {
+        match self {
+            Resolution::InSync(value) | Resolution::Pull(value) | Resolution::Push(value) => value,
+        }
+    }
+}
+
+async fn load_state<'e, E>(executor: E, track_id: &str) -> Result<RatingState, String>
+where
+    E: Executor<'e, Database = Sqlite>,
+{
+    let row = sqlx::query(
+        "SELECT rating, rating_updated_at, rating_synced, rating_synced_at FROM tracks WHERE id = ?",
+    )
+    .bind(track_id)
+    .fetch_optional(executor)
+    .await
+    .map_err(|e| e.to_string())?
+    .ok_or_else(|| format!("Track not found: {}", track_id))?;
+    Ok(state_from_row(&row))
+}
+
+fn state_from_row(row: &sqlx::sqlite::SqliteRow) -> RatingState {
+    let rating = |col: &str| {
+        row.try_get::<Option<i64>, _>(col)
+            .ok()
+            .flatten()
+            .map(|v| v.clamp(0, MAX_RATING as i64) as u8)
+    };
+    RatingState {
+        rating: rating("rating"),
+        updated_at: row.try_get("rating_updated_at").ok().flatten(),
+        synced: rating("rating_synced"),
+        synced_at: row.try_get("rating_synced_at").ok().flatten(),
+    }
+}
+
+/// Store the outcome of a sync. A push is only recorded once the other side
+/// has been written.
+async fn record<'e, E>(executor: E, track_id: &str, resolution: Resolution) -> Result<(), String>
+where
+    E: Executor<'e, Database = Sqlite>,
+{
+    let sql = match resolution {
+        Resolution::Pull(_) => {
+            "UPDATE tracks SET rating = ?1, rating_synced = ?1, rating_synced_at = ?2 WHERE id = ?3"
+        }
+        Resolution::InSync(_) | Resolution::Push(_) => {
+            "UPDATE tracks SET rating_synced = ?1, rating_synced_at = ?2 WHERE id = ?3"
+        }
+    };
+    sqlx::query(sql)
+        .bind(resolution.value().map(|v| v as i64))
+        .bind(Utc::now().timestamp())
+        .bind(track_id)
+        .execute(executor)
+        .await
+        .map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+/// Reconcile a rescanned file's tag rating with the database. Ratings that
+/// need writing back to the file are left for `sync_ratings`, since the
+/// scanner does not write files.
+pub(super) async fn merge_file_rating(
+    tx: &mut Transaction<'_, Sqlite>,
+    track_id: &str,
+    file_rating: Option<u8>,
+    file_modified: Option<i64>,
+) -> Result<(), String> {
+    let state = load_state(&mut **tx, track_id).await?;
+    match resolve(&state, file_rating, file_modified, 1).0 {
+        Resolution::Push(_) => Ok(()),
+        resolution => record(&mut **tx, track_id, resolution).await,
+    }
+}
+
+/// A server's tracks with the server's id for their album, when the album
+/// came from that server
+const PROVIDER_TRACKS: &str = r#"
+    SELECT t.id, t.provider_id, t.external_id, t.file_path, t.missing_since,
+           CASE WHEN al.provider_id = t.provider_id AND al.external_id <> '0'
+                THEN al.external_id END as album_external_id
+    FROM tracks t
+    LEFT JOIN albums al ON t.album_id = al.id
+    WHERE t.provider_id = ? AND t.external_id IS NOT NULL
+"#;
+
+/// Where a track's rating is mirrored
+enum RatingSide {
+    File(PathBuf),
+    Provider(Arc<dyn MusicProvider>, String),
+}
+
+struct RatedTrack {
+    id: String,
+    side: RatingSide,
+}
+
+impl LibraryManager {
+    /// Rate a track in half-stars, or clear the rating with `None`, and copy
+    /// it to the file tag or server right away when possible. A failed copy
+    /// is retried by the next `sync_ratings`.
+    pub async fn set_track_rating(
+        &self,
+        track_id: &str,
+        rating: Option<u8>,
+        providers: &[Arc<dyn MusicProvider>],
+    ) -> Result<(), String> {
+        if rating.is_some_and(|r| r > MAX_RATING) {
+            return Err(format!(
+                "Rating must be between 0 and {} half-stars",
+                MAX_RATING
+            ));
+        }
+
+        let result =
+            sqlx::query("UPDATE tracks SET rating = ?, rating_updated_at = ? WHERE id = ?")
+                .bind(rating.filter(|r| *r > 0).map(|r| r as i64))
+                .bind(Utc::now().timestamp())
+                .bind(track_id)
+                .execute(&self.pool)
+                .await
+                .map_err(|e| e.to_string())?;
+        if result.rows_affected() == 0 {
+            return Err(format!("Track not found: {}", track_id));
+        }
+
+        let row = sqlx::query(
+            "SELECT id, provider_id, external_id, file_path, missing_since FROM tracks WHERE id = ?",
+        )
+        .bind(track_id)
+        .fetch_one(&self.pool)
+        .await
+        .map_err(|e| e.to_string())?;
+
+        if let Some(track) = rated_track(&row, providers) {
+            let mut report = RatingSyncReport::default();
+            self.sync_track(&track, &mut report).await?;
+            if !report.failed.is_empty() {
+                log::warn!("[Ratings] Rating of {} saved but not yet synced", track_id);
+            }
+        }
+        Ok(())
+    }
+
+    /// Reconcile ratings with file tags and servers. Local files are only
+    /// visited when rated since their last sync, as the scanner picks up
+    /// changed tags; servers are asked about every track, one album at a
+    /// time where they can.
+    pub async fn sync_ratings(
+        &self,
+        providers: &[Arc<dyn MusicProvider>],
+    ) -> Result<RatingSyncReport, String> {
+        let rows = sqlx::query(
+            r#"
+            SELECT id, provider_id, external_id, file_path, missing_since FROM tracks
+            WHERE provider_id = 'local' AND file_path IS NOT NULL AND missing_since IS NULL
+              AND (rating_synced_at IS NULL OR rating_updated_at > rating_synced_at)
+            "#,
+        )
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| e.to_string())?;
+
+        let mut report = RatingSyncReport::default();
+        for track in rows.iter().filter_map(|row| rated_track(row, providers)) {
+            self.sync_track(&track, &mut report).await?;
+        }
+
+        for provider in providers.iter().filter(|p| p.rating_scale().is_some()) {
+            let rows = sqlx::query(PROVIDER_TRACKS)
+                .bind(provider.id())
+                .fetch_all(&self.pool)
+                .await
+                .map_err(|e| e.to_string())?;
+
+            let mut albums: HashMap<Option<String>, Vec<RatedTrack>> = HashMap::new();
+            for row in &rows {
+                if let Some(track) = rated_track(row, providers) {
+                    let album: Option<String> = row.try_get("album_external_id").ok().flatten();
+                    albums.entry(album).or_default().push(track);
+                }
+            }
+
+            for (album, tracks) in albums {
+                match album {
+                    Some(album) => {
+                        self.sync_album(provider, &album, &tracks, &mut report)
+                            .await?
+                    }
+                    None => {
+                        for track in &tracks {
+                            self.sync_track(track, &mut report).await?;
+                        }
+                    }
+                }
+            }
+        }
+
+        log::info!(
+            "[Ratings] Synced: {} pulled, {} pushed, {} conflicts, {} failed",
+            report.pulled,
+            report.pushed,
+            report.conflicts,
+            report.failed.len()
+        );
+        Ok(report)
+    }
+
+    /// Sync an album's tracks with one request to the server. Tracks the
+    /// album listing leaves out, or every track when it fails, are asked
+    /// about one by one.
+    async fn sync_album(
+        &self,
+        provider: &Arc<dyn MusicProvider>,
+        album_id: &str,
+        tracks: &[RatedTrack],
+        report: &mut RatingSyncReport,
+    ) -> Result<(), String> {
+        let ratings = provider
+            .get_album_ratings(&bare_id(provider.id(), album_id))
+            .await
+            .unwrap_or_else(|e| {
+                log::warn!(
+                    "[Ratings] Could not read ratings of album {}: {}",
+                    album_id,
+                    e
+                );
+                HashMap::new()
+            });
+
+        for track in tracks {
+            let listed = match &track.side {
+                RatingSide::Provider(_, external_id) => {
+                    ratings.get(&bare_id(provider.id(), external_id))
+                }
+                RatingSide::File(_) => None,
+            };
+            match listed {
+                Some(rating) => {
+                    let remote = Ok((*rating, None, rating_step(provider.as_ref())));
+                    self.reconcile(track, remote, report).await?
+                }
+                None => self.sync_track(track, report).await?,
+            }
+        }
+        Ok(())
+    }
+
+    /// Errors reaching the file or server go into the report; only database
+    /// errors are returned
+    async fn sync_track(
+        &self,
+        track: &RatedTrack,
+        report: &mut RatingSyncReport,
+    ) -> Result<(), String> {
+        let remote: Result<(Option<u8>, Option<i64>, u8), String> = match &track.side {
+            RatingSide::File(path) => {
+                let path = path.clone();
+                tauri::async_runtime::spawn_blocking(move || {
+                    let rating = read_tags(&path)?.rating;
+                    Ok((rating, file_modified(&path), 1))
+                })
+                .await
+                .map_err(|e| e.to_string())?
+            }
+            RatingSide::Provider(provider, external_id) => provider
+                .get_rating(external_id)
+                .await
+                .map(|rating| (rating, None, rating_step(provider.as_ref())))
+                .map_err(|e| e.to_string()),
+        };
+        self.reconcile(track, remote, report).await
+    }
+
+    /// Merge the other side's rating, its change time and precision into the
+    /// app's, writing back when the app's rating wins
+    async fn reconcile(
+        &self,
+        track: &RatedTrack,
+        remote: Result<(Option<u8>, Option<i64>, u8), String>,
+        report: &mut RatingSyncReport,
+    ) -> Result<(), String> {
+        let (remote, remote_changed_at, step) = match remote {
+            Ok(remote) => remote,
+            Err(e) => {
+                log::warn!("[Ratings] Could not read rating of {}: {}", track.id, e);
+                report.failed.push(track.id.clone());
+                return Ok(());
+            }
+        };
+
+        let state = load_state(&self.pool, &track.id).await?;
+        let (resolution, conflict) = resolve(&state, remote, remote_changed_at, step);
+        if conflict {
+            report.conflicts += 1;
+        }
+
+        if let Resolution::Push(value) = resolution {
+            let pushed = match &track.side {
+                RatingSide::File(path) => {
+                    let path = path.clone();
+                    tauri::async_runtime::spawn_blocking(move || write_rating(&path, value))
+                        .await
+                        .map_err(|e| e.to_string())?
+                }
+                RatingSide::Provider(provider, external_id) => provider
+                    .set_rating(external_id, value)
+                    .await
+                    .map_err(|e| e.to_string()),
+            };
+            if let Err(e) = pushed {
+                log::warn!("[Ratings] Could not write rating of {}: {}", track.id, e);
+                report.failed.push(track.id.clone());
+                return Ok(());
+            }
+            report.pushed += 1;
+        } else if let Resolution::Pull(_) = resolution {
+            report.pulled += 1;
+        }
+
+        record(&self.pool, &track.id, resolution).await
+    }
+}
+
+/// A server's rating precision in half-stars
+fn rating_step(provider: &dyn MusicProvider) -> u8 {
+    MAX_RATING / provider.rating_scale().unwrap_or(MAX_RATING).max(1)
+}
+
+/// The side a track's rating syncs with: its file for local tracks, or its
+/// server when that supports ratings
+fn rated_track(
+    row: &sqlx::sqlite::SqliteRow,
+    providers: &[Arc<dyn MusicProvider>],
+) -> Option<RatedTrack> {
+    let id: String = row.try_get("id").ok()?;
+    let provider_id: Option<String> = row.try_get("provider_id").ok().flatten();
+    let side = match provider_id.as_deref() {
+        Some("local") | None => {
+            let missing: Option<i64> = row.try_get("missing_since").ok().flatten();
+            let path: String = row.try_get::<Option<String>, _>("file_path").ok()??;
+            if missing.is_some() {
+                return None;
+            }
+            RatingSide::File(PathBuf::from(path))
+        }
+        Some(provider_id) => {
+            let provider = providers
+                .iter()
+                .find(|p| p.id() == provider_id && p.rating_scale().is_some())?;
+            let external_id: String = row.try_get::<Option<String>, _>("external_id").ok()??;
+            RatingSide::Provider(provider.clone(), external_id)
+        }
+    };
+    Some(RatedTrack { id, side })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::DatabaseManager;
+    use crate::models::{Album, Artist, Quality, SearchResults, StreamInfo, Track};
+    use anyhow::anyhow;
+    use async_trait::async_trait;
+    use serde_json::Value;
+    use std::sync::Mutex;
+
+    /// A whole-star server that logs every read
+    #[derive(Default)]
+    struct RatingServer {
+        calls: Mutex<Vec<String>>,
+    }
+
+    #[async_trait]
+    impl MusicProvider for RatingServer {
+        fn id(&self) -> &str {
+            "subsonic"
+        }
+        fn name(&self) -> &str {
+            "Fake"
+        }
+        async fn initialize(&mut self, _config: Value) -> anyhow::Result<()> {
+            Ok(())
+        }
+        async fn search(&self, _query: &str) -> anyhow::Result<SearchResults> {
+            Err(anyhow!("unused"))
+        }
+        async fn get_stream_url(
+            &self,
+            _track_id: &str,
+            _quality: Quality,
+        ) -> anyhow::Result<StreamInfo> {
+            Err(anyhow!("unused"))
+        }
+        async fn get_track_details(&self, _track_id: &str) -> anyhow::Result<Track> {
+            Err(anyhow!("unused"))
+        }
+        async fn get_artist_details(&self, _artist_id: &str) -> anyhow::Result<Artist> {
+            Err(anyhow!("unused"))
+        }
+        async fn get_album_details(&self, _album_id: &str) -> anyhow::Result<Album> {
+            Err(anyhow!("unused"))
+        }
+        async fn get_artist_top_tracks(&self, _artist_id: &str) -> anyhow::Result<Vec<Track>> {
+            Err(anyhow!("unused"))
+        }
+        async fn get_artist_albums(&self, _artist_id: &str) -> anyhow::Result<Vec<Album>> {
+            Err(anyhow!("unused"))
+        }
+        fn rating_scale(&self) -> Option<u8> {
+            Some(5)
+        }
+        async fn get_rating(&self, track_id: &str) -> anyhow::Result<Option<u8>> {
+            self.calls
+                .lock()
+                .unwrap()
+                .push(format!("track {}", track_id));
+            Ok(Some(4))
+        }
+        async fn get_album_ratings(
+            &self,
+            album_id: &str,
+        ) -> anyhow::Result<HashMap<String, Option<u8>>> {
+            self.calls
+                .lock()
+                .unwrap()
+                .push(format!("album {}", album_id));
+            Ok(HashMap::from([
+                ("s1".to_string(), Some(8)),
+                ("s2".to_string(), None),
+            ]))
+        }
+    }
+
+    #[tokio::test]
+    async fn server_ratings_are_read_per_album() {
+        let db = DatabaseManager::in_memory().await;
+        db.seed(&[
+            "INSERT INTO artists (id, name) VALUES ('ar', 'Artist')",
+            "INSERT INTO albums (id, title, artist_id, provider_id, external_id) VALUES ('al', 'Album', 'ar', 'subsonic', 'subsonic:a1')",
+            "INSERT INTO tracks (id, title, artist_id, album_id, duration, source_type, provider_id, external_id) VALUES ('t1', 'One', 'ar', 'al', 1, 'subsonic', 'subsonic', 's1')",
+            "INSERT INTO tracks (id, title, artist_id, album_id, duration, source_type, provider_id, external_id) VALUES ('t2', 'Two', 'ar', 'al', 1, 'subsonic', 'subsonic', 's2')",
+            "INSERT INTO tracks (id, title, artist_id, duration, source_type, provider_id, external_id) VALUES ('t3', 'Three', 'ar', 1, 'subsonic', 'subsonic', 's3')",
+        ])
+        .await;
+        let library = LibraryManager::new(db.pool.clone());
+        let server = Arc::new(RatingServer::default());
+        let providers: Vec<Arc<dyn MusicProvider>> = vec![server.clone()];
+
+        let report = library.sync_ratings(&providers).await.unwrap();
+        assert_eq!(report.pulled, 2);
+
+        let mut calls = server.calls.lock().unwrap().clone();
+        calls.sort();
+        assert_eq!(calls, vec!["album a1", "track s3"]);
+
+        let ratings: Vec<(String, Option<i64>)> =
+            sqlx::query_as("SELECT id, rating FROM tracks ORDER BY id")
+                .fetch_all(&db.pool)
+                .await
+                .unwrap();
+        assert_eq!(
+            ratings,
+            vec![
+                ("t1".to_string(), Some(8)),
+                ("t2".to_string(), None),
+                ("t3".to_string(), Some(4)),
+            ]
+        );
+    }
+
+    fn synced(rating: Option<u8>, updated_at: Option<i64>) -> RatingState {
+        RatingState {
+            rating,
+            updated_at,
+            synced: Some(6),
+            synced_at: Some(100),
+        }
+    }
+
+    #[test]
+    fn one_sided_changes_flow_to_the_other_side() {
+        // Rated 4 stars in the app after the last sync
+        assert_eq!(
+            resolve(&synced(Some(8), Some(150)), Some(6), Some(90), 1),
+            (Resolution::Push(Some(8)), false)
+        );
+        // Changed to 2 stars in another tagger
+        assert_eq!(
+            resolve(&synced(Some(6), Some(50)), Some(4), Some(200), 1),
+            (Resolution::Pull(Some(4)), false)
+        );
+        // Half-stars round up for servers with whole stars
+        assert_eq!(
+            resolve(&synced(Some(7), Some(150)), Some(8), None, 2),
+            (Resolution::InSync(Some(8)), false)
+        );
+    }
+
+    #[test]
+    fn conflicts_go_to_the_newer_change() {
+        assert_eq!(
+            resolve(&synced(Some(8), Some(150)), Some(2), Some(200), 1),
+            (Resolution::Pull(Some(2)), true)
+        );
+        assert_eq!(
+            resolve(&synced(Some(8), Some(250)), Some(2), Some(200), 1),
+            (Resolution::Push(Some(8)), true)
+        );
+        // Servers give no time, so the app wins
+        assert_eq!(
+            resolve(&synced(Some(8), Some(150)), Some(2), None, 2),
+            (Resolution::Push(Some(8)), true)
+        );
+    }
+}
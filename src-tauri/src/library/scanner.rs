@@ -0,0 +1,1178 @@
+use crate::artwork::ArtworkCache;
+use lofty::prelude::*;
+use lofty::probe::Probe;
+use lofty::tag::{ItemKey, Tag};
+use serde::Serialize;
+use sqlx::{Pool, Row, Sqlite, Transaction};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+use tauri::{AppHandle, Emitter};
+use uuid::Uuid;
+use walkdir::WalkDir;
+
+use super::browse::{folder_prefix, prefix_range};
+use super::integrity::{mark_missing, merge_duplicate};
+use super::models::TrackDetails;
+use super::ratings::merge_file_rating;
+use super::search;
+use super::tag_editor::{tag_rating, write_tags, TagEdit, TagEditFailure, TagEditResult};
+use super::works::delete_orphan_works;
+use super::{find_or_create_artist, replace_track_details};
+
+pub const AUDIO_EXTENSIONS: &[&str] = &[
+    "mp3", "flac", "wav", "ogg", "m4a", "aac", "wma", "aiff", "ape", "opus", "webm",
+];
+
+/// Number of files parsed and written per transaction / progress event
+const SCAN_BATCH_SIZE: usize = 100;
+
+#[derive(Debug, Serialize, Clone, sqlx::FromRow)]
+pub struct LibraryFolder {
+    pub id: String,
+    pub path: String,
+    pub added_at: i64,
+    pub last_scanned_at: Option<i64>,
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct ScanProgress {
+    pub folder_id: String,
+    pub path: String,
+    pub scanned: usize,
+    pub total: usize,
+}
+
+#[derive(Debug, Serialize, Clone, Default)]
+pub struct ScanSummary {
+    pub folder_id: String,
+    pub path: String,
+    pub added: usize,
+    pub updated: usize,
+    pub unchanged: usize,
+    /// Tracks whose files vanished, now marked unavailable
+    pub missing: usize,
+    pub failed: usize,
+}
+
+/// Tags read from a single file on disk
+pub struct FileTags {
+    pub title: String,
+    pub artist: String,
+    pub album: String,
+    pub duration: u64,
+    /// Embedded front cover, as an artwork cache URL
+    pub cover: Option<String>,
+    pub file_size: Option<u64>,
+    /// Half-stars from POPM or RATING
+    pub rating: Option<u8>,
+    pub details: TrackDetails,
+}
+
+/// A file queued for tag parsing: path, modification time and existing track id
+type PendingFile = (String, Option<i64>, Option<String>);
+
+struct ExistingTrack {
+    id: String,
+    file_modified: Option<i64>,
+    missing: bool,
+}
+
+pub fn is_audio_file(path: &Path) -> bool {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .map(|ext| AUDIO_EXTENSIONS.contains(&ext.to_lowercase().as_str()))
+        .unwrap_or(false)
+}
+
+pub fn file_modified(path: &Path) -> Option<i64> {
+    std::fs::metadata(path)
+        .and_then(|m| m.modified())
+        .ok()
+        .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+        .map(|d| d.as_secs() as i64)
+}
+
+pub fn read_file_tags(path: &Path) -> Option<FileTags> {
+    let tagged_file = Probe::open(path).and_then(|p| p.read()).ok()?;
+    let tag = tagged_file.primary_tag();
+
+    let title = tag
+        .and_then(|t| t.title().map(|c| c.into_owned()))
+        .filter(|s| !s.trim().is_empty())
+        .unwrap_or_else(|| {
+            path.file_stem()
+                .and_then(|s| s.to_str())
+                .unwrap_or("Unknown Title")
+                .to_string()
+        });
+    let artist = tag
+        .and_then(|t| t.artist().map(|c| c.into_owned()))
+        .filter(|s| !s.trim().is_empty())
+        .unwrap_or_else(|| "Unknown Artist".to_string());
+    let album = tag
+        .and_then(|t| t.album().map(|c| c.into_owned()))
+        .filter(|s| !s.trim().is_empty())
+        .unwrap_or_else(|| "Unknown Album".to_string());
+
+    // Every track of an album usually embeds the same image, which the
+    // content-addressed cache stores only once
+    let cover =
+        tag.and_then(|t| t.pictures().first()).and_then(|picture| {
+            match ArtworkCache::new().store(picture.data()) {
+                Ok(hash) => Some(ArtworkCache::url(&hash, None)),
+                Err(e) => {
+                    log::warn!("[Scanner] Could not cache cover of {:?}: {}", path, e);
+                    None
+                }
+            }
+        });
+
+    let details = tag.map(read_details).unwrap_or_default();
+
+    // "Artist feat. Guest" keeps only the main artist as the track artist
+    let (artist, featured) = split_featured(&artist);
+    let mut details = details;
+    for name in featured {
+        if !details.featured_artists.contains(&name) {
+            details.featured_artists.push(name);
+        }
+    }
+
+    Some(FileTags {
+        title,
+        artist,
+        album,
+        duration: tagged_file.properties().duration().as_secs(),
+        cover,
+        file_size: std::fs::metadata(path).ok().map(|m| m.len()),
+        rating: tag.and_then(tag_rating),
+        details,
+    })
+}
+
+pub(super) fn read_details(tag: &Tag) -> TrackDetails {
+    let text = |key: ItemKey| {
+        tag.get_string(&key)
+            .map(|s| s.trim().to_string())
+            .filter(|s| !s.is_empty())
+    };
+    let list = |key: ItemKey| -> Vec<String> {
+        let mut values: Vec<String> = Vec::new();
+        for value in tag.get_strings(&key) {
+            for part in value.split(';') {
+                let part = part.trim().to_string();
+                if !part.is_empty() && !values.contains(&part) {
+                    values.push(part);
+                }
+            }
+        }
+        values
+    };
+
+    // Extra TrackArtist values beyond the first are guests
+    let featured_artists = list(ItemKey::TrackArtist).into_iter().skip(1).collect();
+
+    TrackDetails {
+        album_artist: text(ItemKey::AlbumArtist),
+        featured_artists,
+        composers: list(ItemKey::Composer),
+        track_number: tag.track(),
+        disc_number: tag.disk(),
+        year: tag.year(),
+        genres: list(ItemKey::Genre),
+        isrc: text(ItemKey::Isrc),
+        musicbrainz_track_id: text(ItemKey::MusicBrainzRecordingId),
+        musicbrainz_album_id: text(ItemKey::MusicBrainzReleaseId),
+        musicbrainz_artist_id: text(ItemKey::MusicBrainzArtistId),
+        acoustid: text(ItemKey::Unknown("ACOUSTID_ID".to_string()))
+            .or_else(|| text(ItemKey::Unknown("Acoustid Id".to_string()))),
+    }
+}
+
+/// Split "Main feat. Guest & Other" into the main artist and the guests
+pub fn split_featured(artist: &str) -> (String, Vec<String>) {
+    // ASCII lowercasing keeps byte offsets valid for slicing `artist`
+    let lower = artist.to_ascii_lowercase();
+    for marker in [" feat. ", " ft. ", " featuring ", " feat "] {
+        if let Some(idx) = lower.find(marker) {
+            let main = artist[..idx].trim().to_string();
+            let guests = artist[idx + marker.len()..]
+                .split([',', '&'])
+                .map(|s| s.trim().to_string())
+                .filter(|s| !s.is_empty())
+                .collect();
+            return (main, guests);
+        }
+    }
+    (artist.to_string(), Vec::new())
+}
+
+/// Keeps the `tracks` table in sync with a set of watched local folders.
+pub struct LibraryScanner {
+    pool: Pool<Sqlite>,
+    // Scans share the same tables, so only one runs at a time
+    scan_lock: tokio::sync::Mutex<()>,
+}
+
+impl LibraryScanner {
+    pub fn new(pool: Pool<Sqlite>) -> Self {
+        Self {
+            pool,
+            scan_lock: tokio::sync::Mutex::new(()),
+        }
+    }
+
+    pub async fn get_folders(&self) -> Result<Vec<LibraryFolder>, String> {
+        sqlx::query_as::<_, LibraryFolder>(
+            "SELECT id, path, added_at, last_scanned_at FROM library_folders ORDER BY path ASC",
+        )
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| e.to_string())
+    }
+
+    pub async fn add_folder(&self, path: &str) -> Result<LibraryFolder, String> {
+        if !Path::new(path).is_dir() {
+            return Err(format!("Not a directory: {}", path));
+        }
+
+        sqlx::query(
+            "INSERT OR IGNORE INTO library_folders (id, path, added_at) VALUES (?, ?, strftime('%s', 'now'))",
+        )
+        .bind(Uuid::new_v4().to_string())
+        .bind(path)
+        .execute(&self.pool)
+        .await
+        .map_err(|e| e.to_string())?;
+
+        sqlx::query_as::<_, LibraryFolder>(
+            "SELECT id, path, added_at, last_scanned_at FROM library_folders WHERE path = ?",
+        )
+        .bind(path)
+        .fetch_one(&self.pool)
+        .await
+        .map_err(|e| e.to_string())
+    }
+
+    /// The paths that lie at or below a watched folder
+    pub async fn within_folders(&self, paths: Vec<PathBuf>) -> Result<Vec<PathBuf>, String> {
+        let roots: Vec<PathBuf> = self
+            .get_folders()
+            .await?
+            .into_iter()
+            .map(|f| PathBuf::from(f.path))
+            .collect();
+        Ok(paths
+            .into_iter()
+            .filter(|p| roots.iter().any(|root| p.starts_with(root)))
+            .collect())
+    }
+
+    /// Stop watching a folder. Its tracks, unless also covered by another
+    /// watched folder, are marked unavailable so their history, favourites
+    /// and playlist entries survive re-adding the folder. They are only
+    /// deleted when `delete` is set.
+    pub async fn remove_folder(&self, folder_id: &str, delete: bool) -> Result<usize, String> {
+        let _guard = self.scan_lock.lock().await;
+
+        let folder = sqlx::query_as::<_, LibraryFolder>(
+            "SELECT id, path, added_at, last_scanned_at FROM library_folders WHERE id = ?",
+        )
+        .bind(folder_id)
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| format!("Library folder not found: {}", folder_id))?;
+
+        sqlx::query("DELETE FROM library_folders WHERE id = ?")
+            .bind(folder_id)
+            .execute(&self.pool)
+            .await
+            .map_err(|e| e.to_string())?;
+
+        let remaining: Vec<PathBuf> = self
+            .get_folders()
+            .await?
+            .into_iter()
+            .map(|f| PathBuf::from(f.path))
+            .collect();
+
+        let stale: Vec<String> = self
+            .local_tracks_under(&[Path::new(&folder.path)])
+            .await?
+            .into_iter()
+            .filter(|(path, t)| {
+                (delete || !t.missing)
+                    && !remaining
+                        .iter()
+                        .any(|root| Path::new(path).starts_with(root))
+            })
+            .map(|(_, t)| t.id)
+            .collect();
+
+        let mut tx = self.pool.begin().await.map_err(|e| e.to_string())?;
+        if delete {
+            delete_tracks(&mut tx, &stale).await?;
+            delete_orphans(&mut tx).await?;
+        } else {
+            mark_missing(&mut tx, &stale).await?;
+        }
+        tx.commit().await.map_err(|e| e.to_string())?;
+
+        log::info!(
+            "[Scanner] Removed folder {} ({} tracks {})",
+            folder.path,
+            stale.len(),
+            if delete { "deleted" } else { "marked missing" }
+        );
+        Ok(stale.len())
+    }
+
+    pub async fn scan_all(&self, app: &AppHandle) -> Result<Vec<ScanSummary>, String> {
+        let mut summaries = Vec::new();
+        for folder in self.get_folders().await? {
+            match self.scan_folder(app, &folder).await {
+                Ok(summary) => summaries.push(summary),
+                Err(e) => log::warn!("[Scanner] Skipping {}: {}", folder.path, e),
+            }
+        }
+        Ok(summaries)
+    }
+
+    /// Incrementally rescan one watched folder. Files whose modification time
+    /// matches the stored `file_modified` are skipped, and tracks whose files
+    /// have vanished are marked unavailable.
+    pub async fn scan_folder(
+        &self,
+        app: &AppHandle,
+        folder: &LibraryFolder,
+    ) -> Result<ScanSummary, String> {
+        let _guard = self.scan_lock.lock().await;
+        let root = PathBuf::from(&folder.path);
+
+        // An unmounted drive must not wipe the library
+        if !root.is_dir() {
+            return Err(format!("Folder is not available: {}", folder.path));
+        }
+
+        log::info!("[Scanner] Scanning {}", folder.path);
+        let mut summary = ScanSummary {
+            folder_id: folder.id.clone(),
+            path: folder.path.clone(),
+            ..Default::default()
+        };
+
+        let walk_root = root.clone();
+        let files: Vec<(String, Option<i64>)> = tauri::async_runtime::spawn_blocking(move || {
+            WalkDir::new(&walk_root)
+                .follow_links(true)
+                .into_iter()
+                .filter_map(|e| e.ok())
+                .filter(|e| e.file_type().is_file() && is_audio_file(e.path()))
+                .filter_map(|e| {
+                    let path = e.path().to_str()?.to_string();
+                    Some((path, file_modified(e.path())))
+                })
+                .collect()
+        })
+        .await
+        .map_err(|e| e.to_string())?;
+
+        let mut existing = self.local_tracks_under(&[root.as_path()]).await?;
+        let total = files.len();
+
+        // Only files that are new or changed since the last scan get parsed
+        let mut pending = Vec::new();
+        for (path, modified) in files {
+            match existing.remove(&path) {
+                Some(track)
+                    if !track.missing
+                        && track.file_modified.is_some()
+                        && track.file_modified == modified =>
+                {
+                    summary.unchanged += 1;
+                }
+                Some(track) => pending.push((path, modified, Some(track.id))),
+                None => pending.push((path, modified, None)),
+            }
+        }
+
+        let mut scanned = summary.unchanged;
+        emit_progress(app, folder, scanned, total);
+
+        for batch in pending.chunks(SCAN_BATCH_SIZE) {
+            self.write_batch(batch, &mut summary).await?;
+            scanned += batch.len();
+            emit_progress(app, folder, scanned, total);
+        }
+
+        // Whatever is left in `existing` was not found on disk. Keeping the rows
+        // lets a moved file be relinked without losing its history.
+        let vanished: Vec<String> = existing
+            .into_values()
+            .filter(|t| !t.missing)
+            .map(|t| t.id)
+            .collect();
+        summary.missing = vanished.len();
+
+        let mut tx = self.pool.begin().await.map_err(|e| e.to_string())?;
+        mark_missing(&mut tx, &vanished).await?;
+        sqlx::query(
+            "UPDATE library_folders SET last_scanned_at = strftime('%s', 'now') WHERE id = ?",
+        )
+        .bind(&folder.id)
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| e.to_string())?;
+        tx.commit().await.map_err(|e| e.to_string())?;
+
+        log::info!(
+            "[Scanner] Finished {}: {} added, {} updated, {} unchanged, {} missing, {} failed",
+            folder.path,
+            summary.added,
+            summary.updated,
+            summary.unchanged,
+            summary.missing,
+            summary.failed
+        );
+        let _ = app.emit("library-scan-complete", summary.clone());
+        Ok(summary)
+    }
+
+    /// Playable queue entries for every scanned track under `root`
+    pub async fn get_folder_tracks(&self, root: &str) -> Result<Vec<crate::queue::Track>, String> {
+        let (lower, upper) = prefix_range(&folder_prefix(root));
+        let rows = sqlx::query(
+            r#"
+            SELECT t.id, t.title, t.duration, t.file_path, t.artist_id, t.album_id,
+                   a.name as artist_name, al.title as album_title, al.cover_url
+            FROM tracks t
+            JOIN artists a ON t.artist_id = a.id
+            LEFT JOIN albums al ON t.album_id = al.id
+            WHERE +t.provider_id = 'local' AND t.file_path >= ? AND t.file_path < ?
+              AND t.missing_since IS NULL
+            ORDER BY a.name ASC, al.title ASC, t.title ASC
+            "#,
+        )
+        .bind(&lower)
+        .bind(&upper)
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| e.to_string())?;
+
+        Ok(rows
+            .into_iter()
+            .filter_map(|row| {
+                Some(crate::queue::Track {
+                    id: row.try_get("id").unwrap_or_default(),
+                    title: row.try_get("title").unwrap_or_default(),
+                    artist: row.try_get("artist_name").unwrap_or_default(),
+                    artist_id: row.try_get("artist_id").ok(),
+                    album: row.try_get("album_title").unwrap_or_default(),
+                    album_id: row.try_get("album_id").ok().flatten(),
+                    duration: row.try_get::<i64, _>("duration").unwrap_or(0) as u64,
+                    cover_image: row.try_get("cover_url").ok().flatten(),
+                    path: row.try_get("file_path").ok()?,
+                    provider_id: Some("local".to_string()),
+                    external_id: None,
+                })
+            })
+            .collect())
+    }
+
+    /// Parse tags for a batch of new or changed files and write them in one transaction
+    async fn write_batch(
+        &self,
+        batch: &[PendingFile],
+        summary: &mut ScanSummary,
+    ) -> Result<(), String> {
+        let paths: Vec<String> = batch.iter().map(|(p, _, _)| p.clone()).collect();
+        let tags: Vec<Option<FileTags>> = tauri::async_runtime::spawn_blocking(move || {
+            paths.iter().map(|p| read_file_tags(Path::new(p))).collect()
+        })
+        .await
+        .map_err(|e| e.to_string())?;
+
+        let mut tx = self.pool.begin().await.map_err(|e| e.to_string())?;
+        for ((path, modified, existing_id), tags) in batch.iter().zip(tags) {
+            let Some(tags) = tags else {
+                log::warn!("[Scanner] Could not read tags from {}", path);
+                summary.failed += 1;
+                continue;
+            };
+            upsert_track(&mut tx, path, *modified, existing_id.as_deref(), &tags).await?;
+            if existing_id.is_some() {
+                summary.updated += 1;
+            } else {
+                summary.added += 1;
+            }
+        }
+        tx.commit().await.map_err(|e| e.to_string())
+    }
+
+    /// Re-read individual files or directories after a filesystem change.
+    /// Files whose modification time is unchanged are left alone.
+    pub async fn scan_paths(&self, paths: Vec<PathBuf>) -> Result<ScanSummary, String> {
+        let _guard = self.scan_lock.lock().await;
+        let mut summary = ScanSummary::default();
+
+        let walk_paths = paths.clone();
+        let files: Vec<(String, Option<i64>)> = tauri::async_runtime::spawn_blocking(move || {
+            walk_paths
+                .iter()
+                .flat_map(|p| WalkDir::new(p).follow_links(true).into_iter())
+                .filter_map(|e| e.ok())
+                .filter(|e| e.file_type().is_file() && is_audio_file(e.path()))
+                .filter_map(|e| {
+                    let path = e.path().to_str()?.to_string();
+                    Some((path, file_modified(e.path())))
+                })
+                .collect()
+        })
+        .await
+        .map_err(|e| e.to_string())?;
+
+        let roots: Vec<&Path> = paths.iter().map(|p| p.as_path()).collect();
+        let mut existing = self.local_tracks_under(&roots).await?;
+
+        let mut pending = Vec::new();
+        for (path, modified) in files {
+            match existing.remove(&path) {
+                Some(track)
+                    if !track.missing
+                        && track.file_modified.is_some()
+                        && track.file_modified == modified =>
+                {
+                    summary.unchanged += 1;
+                }
+                Some(track) => pending.push((path, modified, Some(track.id))),
+                None => pending.push((path, modified, None)),
+            }
+        }
+
+        for batch in pending.chunks(SCAN_BATCH_SIZE) {
+            self.write_batch(batch, &mut summary).await?;
+        }
+        Ok(summary)
+    }
+
+    /// Mark tracks unavailable after their files (or whole directories) were
+    /// deleted on disk
+    pub async fn remove_paths(&self, paths: &[PathBuf]) -> Result<usize, String> {
+        let _guard = self.scan_lock.lock().await;
+        let roots: Vec<&Path> = paths.iter().map(|p| p.as_path()).collect();
+        let ids: Vec<String> = self
+            .local_tracks_under(&roots)
+            .await?
+            .into_values()
+            .filter(|t| !t.missing)
+            .map(|t| t.id)
+            .collect();
+
+        let mut tx = self.pool.begin().await.map_err(|e| e.to_string())?;
+        mark_missing(&mut tx, &ids).await?;
+        tx.commit().await.map_err(|e| e.to_string())?;
+        Ok(ids.len())
+    }
+
+    /// Repoint tracks after a file or directory was renamed. Track ids are
+    /// kept, so play counts, favourites and playlist entries follow the file.
+    /// A track whose new name is not an audio file, e.g. `song.flac.bak`, is
+    /// marked unavailable instead. A row already at the new path is merged in.
+    pub async fn move_path(&self, from: &Path, to: &Path) -> Result<usize, String> {
+        let _guard = self.scan_lock.lock().await;
+        let moved = self.local_tracks_under(&[from]).await?;
+
+        let mut tx = self.pool.begin().await.map_err(|e| e.to_string())?;
+        let mut gone = Vec::new();
+        for (old_path, track) in &moved {
+            let rest = Path::new(old_path)
+                .strip_prefix(from)
+                .unwrap_or(Path::new(""));
+            // Joining an empty path would add a trailing separator
+            let new_path = if rest.as_os_str().is_empty() {
+                to.to_path_buf()
+            } else {
+                to.join(rest)
+            };
+            if !is_audio_file(&new_path) || !new_path.is_file() {
+                gone.push(track.id.clone());
+                continue;
+            }
+            let new_path = new_path.to_string_lossy().to_string();
+
+            // A scan may have picked up the new file before the rename was
+            // seen, so its row is folded into the one that keeps the history
+            let duplicate: Option<String> =
+                sqlx::query_scalar("SELECT id FROM tracks WHERE file_path = ? AND id <> ?")
+                    .bind(&new_path)
+                    .bind(&track.id)
+                    .fetch_optional(&mut *tx)
+                    .await
+                    .map_err(|e| e.to_string())?;
+            if let Some(duplicate) = &duplicate {
+                merge_duplicate(&mut tx, &track.id, duplicate).await?;
+            }
+
+            sqlx::query("UPDATE tracks SET file_path = ?, missing_since = NULL WHERE id = ?")
+                .bind(&new_path)
+                .bind(&track.id)
+                .execute(&mut *tx)
+                .await
+                .map_err(|e| e.to_string())?;
+        }
+        mark_missing(&mut tx, &gone).await?;
+        delete_orphans(&mut tx).await?;
+        tx.commit().await.map_err(|e| e.to_string())?;
+        Ok(moved.len() - gone.len())
+    }
+
+    pub async fn get_track_file_path(&self, track_id: &str) -> Result<Option<String>, String> {
+        sqlx::query_scalar("SELECT file_path FROM tracks WHERE id = ? AND provider_id = 'local'")
+            .bind(track_id)
+            .fetch_optional(&self.pool)
+            .await
+            .map(|p: Option<Option<String>>| p.flatten())
+            .map_err(|e| e.to_string())
+    }
+
+    /// Write a tag edit to the files of the given local tracks, then refresh
+    /// each track's row and search index entry. Every track is reported on
+    /// its own: a file whose row could not be refreshed still has its new
+    /// tags, and its changed modification time gets it re-read on the next
+    /// scan.
+    pub async fn edit_tracks(
+        &self,
+        track_ids: &[String],
+        edit: &TagEdit,
+    ) -> Result<TagEditResult, String> {
+        let _guard = self.scan_lock.lock().await;
+        let mut result = TagEditResult::default();
+
+        let mut targets = Vec::new();
+        for id in track_ids {
+            match self.get_track_file_path(id).await? {
+                Some(path) => targets.push((id.clone(), path)),
+                None => result.failed.push(TagEditFailure {
+                    track_id: id.clone(),
+                    error: "Only local files can be edited".to_string(),
+                }),
+            }
+        }
+
+        let edit_changes_cover = edit.cover_path.is_some() || edit.remove_cover;
+        let edit = edit.clone();
+        let written: Vec<(String, String, Result<FileTags, String>)> =
+            tauri::async_runtime::spawn_blocking(move || {
+                targets
+                    .into_iter()
+                    .map(|(id, path)| {
+                        let res = write_tags(Path::new(&path), &edit).and_then(|_| {
+                            read_file_tags(Path::new(&path))
+                                .ok_or_else(|| "Could not re-read tags".to_string())
+                        });
+                        (id, path, res)
+                    })
+                    .collect()
+            })
+            .await
+            .map_err(|e| e.to_string())?;
+
+        for (id, path, res) in written {
+            let res = match res {
+                Ok(tags) => self
+                    .refresh_edited_track(&id, &path, &tags, edit_changes_cover)
+                    .await
+                    .map_err(|e| {
+                        format!(
+                            "Tags were written but the library could not be updated: {}",
+                            e
+                        )
+                    }),
+                Err(e) => Err(e),
+            };
+            match res {
+                Ok(()) => result.updated.push(id),
+                Err(error) => {
+                    log::warn!("[TagEditor] Failed to edit {}: {}", path, error);
+                    result.failed.push(TagEditFailure {
+                        track_id: id,
+                        error,
+                    });
+                }
+            }
+        }
+
+        let mut tx = self.pool.begin().await.map_err(|e| e.to_string())?;
+        delete_orphans(&mut tx).await?;
+        tx.commit().await.map_err(|e| e.to_string())?;
+
+        Ok(result)
+    }
+
+    async fn refresh_edited_track(
+        &self,
+        id: &str,
+        path: &str,
+        tags: &FileTags,
+        edit_changes_cover: bool,
+    ) -> Result<(), String> {
+        let mut tx = self.pool.begin().await.map_err(|e| e.to_string())?;
+        let modified = file_modified(Path::new(path));
+        upsert_track(&mut tx, path, modified, Some(id), tags).await?;
+        // An explicit cover change replaces the album art too
+        if edit_changes_cover {
+            sqlx::query(
+                "UPDATE albums SET cover_url = ? WHERE id = (SELECT album_id FROM tracks WHERE id = ?)",
+            )
+            .bind(&tags.cover)
+            .bind(id)
+            .execute(&mut *tx)
+            .await
+            .map_err(|e| e.to_string())?;
+        }
+        tx.commit().await.map_err(|e| e.to_string())
+    }
+
+    /// Local tracks at or below any of `roots`, keyed by path. Each root is
+    /// looked up as a range on the file_path index; the `+` keeps SQLite from
+    /// scanning every local track through the provider index instead. The
+    /// lookups share one transaction, so a batch of watcher events reads a
+    /// single snapshot.
+    async fn local_tracks_under(
+        &self,
+        roots: &[&Path],
+    ) -> Result<HashMap<String, ExistingTrack>, String> {
+        let mut tx = self.pool.begin().await.map_err(|e| e.to_string())?;
+        let mut tracks = HashMap::new();
+        for root in roots {
+            let root = root.to_string_lossy();
+            let (lower, upper) = prefix_range(&folder_prefix(&root));
+            let rows = sqlx::query(
+                r#"
+                SELECT id, file_path, file_modified, missing_since FROM tracks
+                WHERE +provider_id = 'local'
+                  AND (file_path = ? OR (file_path >= ? AND file_path < ?))
+                "#,
+            )
+            .bind(root.as_ref())
+            .bind(&lower)
+            .bind(&upper)
+            .fetch_all(&mut *tx)
+            .await
+            .map_err(|e| e.to_string())?;
+
+            for row in rows {
+                let (Ok(path), Ok(id)) = (
+                    row.try_get::<String, _>("file_path"),
+                    row.try_get::<String, _>("id"),
+                ) else {
+                    continue;
+                };
+                tracks.insert(
+                    path,
+                    ExistingTrack {
+                        id,
+                        file_modified: row.try_get("file_modified").ok().flatten(),
+                        missing: row
+                            .try_get::<Option<i64>, _>("missing_since")
+                            .ok()
+                            .flatten()
+                            .is_some(),
+                    },
+                );
+            }
+        }
+        tx.commit().await.map_err(|e| e.to_string())?;
+        Ok(tracks)
+    }
+}
+
+fn emit_progress(app: &AppHandle, folder: &LibraryFolder, scanned: usize, total: usize) {
+    let _ = app.emit(
+        "library-scan-progress",
+        ScanProgress {
+            folder_id: folder.id.clone(),
+            path: folder.path.clone(),
+            scanned,
+            total,
+        },
+    );
+}
+
+pub(crate) async fn find_or_create_album(
+    tx: &mut Transaction<'_, Sqlite>,
+    title: &str,
+    artist_id: &str,
+    cover: Option<&str>,
+) -> Result<String, String> {
+    if let Some(row) =
+        sqlx::query("SELECT id, cover_url FROM albums WHERE title = ? AND artist_id = ?")
+            .bind(title)
+            .bind(artist_id)
+            .fetch_optional(&mut **tx)
+            .await
+            .map_err(|e| e.to_string())?
+    {
+        let id: String = row.try_get("id").unwrap_or_default();
+        let cover_url: Option<String> = row.try_get("cover_url").ok().flatten();
+        if cover_url.is_none() && cover.is_some() {
+            sqlx::query("UPDATE albums SET cover_url = ? WHERE id = ?")
+                .bind(cover)
+                .bind(&id)
+                .execute(&mut **tx)
+                .await
+                .map_err(|e| e.to_string())?;
+        }
+        return Ok(id);
+    }
+
+    let id = Uuid::new_v4().to_string();
+    sqlx::query(
+        "INSERT INTO albums (id, title, artist_id, cover_url, provider_id) VALUES (?, ?, ?, ?, 'local')",
+    )
+    .bind(&id)
+    .bind(title)
+    .bind(artist_id)
+    .bind(cover)
+    .execute(&mut **tx)
+    .await
+    .map_err(|e| e.to_string())?;
+    Ok(id)
+}
+
+async fn upsert_track(
+    tx: &mut Transaction<'_, Sqlite>,
+    path: &str,
+    modified: Option<i64>,
+    existing_id: Option<&str>,
+    tags: &FileTags,
+) -> Result<String, String> {
+    let artist_id = find_or_create_artist(tx, &tags.artist, "local").await?;
+    // Compilations group under their album artist instead of each track artist
+    let album_artist_id = match &tags.details.album_artist {
+        Some(name) if name != &tags.artist => find_or_create_artist(tx, name, "local").await?,
+        _ => artist_id.clone(),
+    };
+    let album_id =
+        find_or_create_album(tx, &tags.album, &album_artist_id, tags.cover.as_deref()).await?;
+
+    let track_id = match existing_id {
+        Some(id) => {
+            sqlx::query(
+                "UPDATE tracks SET title = ?, artist_id = ?, album_id = ?, duration = ?, file_modified = ?, file_size = ?, missing_since = NULL WHERE id = ?",
+            )
+            .bind(&tags.title)
+            .bind(&artist_id)
+            .bind(&album_id)
+            .bind(tags.duration as i64)
+            .bind(modified)
+            .bind(tags.file_size.map(|n| n as i64))
+            .bind(id)
+            .execute(&mut **tx)
+            .await
+            .map_err(|e| e.to_string())?;
+            id.to_string()
+        }
+        None => {
+            let id = Uuid::new_v4().to_string();
+            sqlx::query(
+                r#"
+                INSERT INTO tracks (id, title, artist_id, album_id, duration, source_type, file_path, file_modified, file_size, provider_id, added_at)
+                VALUES (?, ?, ?, ?, ?, 'LOCAL', ?, ?, ?, 'local', strftime('%s', 'now'))
+                "#,
+            )
+            .bind(&id)
+            .bind(&tags.title)
+            .bind(&artist_id)
+            .bind(&album_id)
+            .bind(tags.duration as i64)
+            .bind(path)
+            .bind(modified)
+            .bind(tags.file_size.map(|n| n as i64))
+            .execute(&mut **tx)
+            .await
+            .map_err(|e| e.to_string())?;
+            id
+        }
+    };
+
+    search::index_track(tx, &track_id, &tags.title, &tags.artist, &tags.album).await?;
+    merge_file_rating(tx, &track_id, tags.rating, modified).await?;
+
+    replace_track_details(
+        tx,
+        &track_id,
+        &artist_id,
+        Some(&album_id),
+        "local",
+        &tags.details,
+    )
+    .await?;
+
+    Ok(track_id)
+}
+
+pub(super) async fn delete_tracks(
+    tx: &mut Transaction<'_, Sqlite>,
+    ids: &[String],
+) -> Result<(), String> {
+    for id in ids {
+        // playlist_tracks has no ON DELETE CASCADE for tracks
+        for table in ["playlist_tracks", "search_index", "user_favorites"] {
+            sqlx::query(&format!("DELETE FROM {} WHERE track_id = ?", table))
+                .bind(id)
+                .execute(&mut **tx)
+                .await
+                .map_err(|e| e.to_string())?;
+        }
+        sqlx::query("DELETE FROM tracks WHERE id = ?")
+            .bind(id)
+            .execute(&mut **tx)
+            .await
+            .map_err(|e| e.to_string())?;
+    }
+    Ok(())
+}
+
+/// Drop scanner-created albums and artists, and any works, that no longer have tracks
+pub(super) async fn delete_orphans(tx: &mut Transaction<'_, Sqlite>) -> Result<(), String> {
+    sqlx::query(
+        "DELETE FROM albums WHERE provider_id = 'local' AND id NOT IN (SELECT album_id FROM tracks WHERE album_id IS NOT NULL)",
+    )
+    .execute(&mut **tx)
+    .await
+    .map_err(|e| e.to_string())?;
+    sqlx::query(
+        "DELETE FROM artists WHERE provider_id = 'local' AND id NOT IN (SELECT artist_id FROM tracks) AND id NOT IN (SELECT artist_id FROM albums) AND id NOT IN (SELECT artist_id FROM track_artists)",
+    )
+    .execute(&mut **tx)
+    .await
+    .map_err(|e| e.to_string())?;
+    delete_orphan_works(tx).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::DatabaseManager;
+
+    fn tags_with_guest() -> FileTags {
+        FileTags {
+            title: "Song".to_string(),
+            artist: "Main".to_string(),
+            album: "Album".to_string(),
+            duration: 200,
+            cover: None,
+            file_size: None,
+            rating: None,
+            details: TrackDetails {
+                featured_artists: vec!["Guest".to_string()],
+                ..Default::default()
+            },
+        }
+    }
+
+    #[tokio::test]
+    async fn rescans_keep_featured_credits() {
+        let db = DatabaseManager::in_memory().await;
+        let tags = tags_with_guest();
+
+        let mut existing = None;
+        for _ in 0..2 {
+            let mut tx = db.pool.begin().await.unwrap();
+            let id = upsert_track(
+                &mut tx,
+                "/music/song.flac",
+                Some(1),
+                existing.as_deref(),
+                &tags,
+            )
+            .await
+            .unwrap();
+            delete_orphans(&mut tx).await.unwrap();
+            tx.commit().await.unwrap();
+            existing = Some(id);
+        }
+
+        let credits: Vec<(String, String)> = sqlx::query_as(
+            "SELECT a.name, ta.role FROM track_artists ta JOIN artists a ON a.id = ta.artist_id WHERE ta.track_id = ? ORDER BY ta.position",
+        )
+        .bind(existing.unwrap())
+        .fetch_all(&db.pool)
+        .await
+        .unwrap();
+        assert_eq!(
+            credits,
+            vec![
+                ("Main".to_string(), "main".to_string()),
+                ("Guest".to_string(), "featured".to_string())
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn moves_only_follow_audio_files() {
+        let db = DatabaseManager::in_memory().await;
+        let dir = std::env::temp_dir().join(format!("sonami-move-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("renamed.flac"), b"").unwrap();
+        std::fs::write(dir.join("backup.flac.bak"), b"").unwrap();
+
+        db.seed(&["INSERT INTO artists (id, name) VALUES ('ar', 'Artist')"])
+            .await;
+        for (id, name) in [("a", "song.flac"), ("b", "backup.flac")] {
+            sqlx::query(
+                "INSERT INTO tracks (id, title, artist_id, duration, source_type, provider_id, file_path) VALUES (?, ?, 'ar', 1, 'LOCAL', 'local', ?)",
+            )
+            .bind(id)
+            .bind(id)
+            .bind(dir.join(name).to_string_lossy().to_string())
+            .execute(&db.pool)
+            .await
+            .unwrap();
+        }
+        let scanner = LibraryScanner::new(db.pool.clone());
+
+        let moved = scanner
+            .move_path(&dir.join("song.flac"), &dir.join("renamed.flac"))
+            .await
+            .unwrap();
+        assert_eq!(moved, 1);
+        let moved = scanner
+            .move_path(&dir.join("backup.flac"), &dir.join("backup.flac.bak"))
+            .await
+            .unwrap();
+        assert_eq!(moved, 0);
+
+        let rows: Vec<(String, String, bool)> = sqlx::query_as(
+            "SELECT id, file_path, missing_since IS NOT NULL FROM tracks ORDER BY id",
+        )
+        .fetch_all(&db.pool)
+        .await
+        .unwrap();
+        assert_eq!(
+            rows,
+            vec![
+                (
+                    "a".to_string(),
+                    dir.join("renamed.flac").to_string_lossy().to_string(),
+                    false
+                ),
+                (
+                    "b".to_string(),
+                    dir.join("backup.flac").to_string_lossy().to_string(),
+                    true
+                ),
+            ]
+        );
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[tokio::test]
+    async fn moves_merge_a_row_already_at_the_new_path() {
+        let db = DatabaseManager::in_memory().await;
+        let dir = std::env::temp_dir().join(format!("sonami-merge-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("renamed.flac"), b"").unwrap();
+
+        db.seed(&["INSERT INTO artists (id, name) VALUES ('ar', 'Artist')"])
+            .await;
+        for (id, name, plays) in [("a", "song.flac", 3), ("b", "renamed.flac", 2)] {
+            sqlx::query(
+                "INSERT INTO tracks (id, title, artist_id, duration, source_type, provider_id, file_path, play_count) VALUES (?, ?, 'ar', 1, 'LOCAL', 'local', ?, ?)",
+            )
+            .bind(id)
+            .bind(id)
+            .bind(dir.join(name).to_string_lossy().to_string())
+            .bind(plays)
+            .execute(&db.pool)
+            .await
+            .unwrap();
+        }
+        db.seed(&[
+            "INSERT INTO playlists (id, title) VALUES ('p', 'Playlist')",
+            "INSERT INTO playlist_tracks (id, playlist_id, track_id, position) VALUES ('e', 'p', 'b', 0)",
+        ])
+        .await;
+        let scanner = LibraryScanner::new(db.pool.clone());
+
+        let moved = scanner
+            .move_path(&dir.join("song.flac"), &dir.join("renamed.flac"))
+            .await
+            .unwrap();
+        assert_eq!(moved, 1);
+
+        let rows: Vec<(String, String, i64)> =
+            sqlx::query_as("SELECT id, file_path, play_count FROM tracks")
+                .fetch_all(&db.pool)
+                .await
+                .unwrap();
+        assert_eq!(
+            rows,
+            vec![(
+                "a".to_string(),
+                dir.join("renamed.flac").to_string_lossy().to_string(),
+                5
+            )]
+        );
+        let entry: String = sqlx::query_scalar("SELECT track_id FROM playlist_tracks")
+            .fetch_one(&db.pool)
+            .await
+            .unwrap();
+        assert_eq!(entry, "a");
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[tokio::test]
+    async fn only_paths_in_watched_folders_are_kept() {
+        let db = DatabaseManager::in_memory().await;
+        db.seed(&["INSERT INTO library_folders (id, path, added_at) VALUES ('f', '/music', 0)"])
+            .await;
+        let scanner = LibraryScanner::new(db.pool.clone());
+
+        let kept = scanner
+            .within_folders(vec![
+                PathBuf::from("/music/a.flac"),
+                PathBuf::from("/musicals/b.flac"),
+                PathBuf::from("/removed/c.flac"),
+            ])
+            .await
+            .unwrap();
+        assert_eq!(kept, vec![PathBuf::from("/music/a.flac")]);
+    }
+
+    #[tokio::test]
+    async fn removed_folders_keep_tracks_unless_deleted() {
+        let db = DatabaseManager::in_memory().await;
+        db.seed(&[
+            "INSERT INTO library_folders (id, path, added_at) VALUES ('m', '/music', 0), ('p', '/podcasts', 0)",
+            "INSERT INTO artists (id, name) VALUES ('ar', 'Artist')",
+        ])
+        .await;
+        for (id, path) in [("a", "/music/a.flac"), ("b", "/podcasts/b.mp3")] {
+            sqlx::query(
+                "INSERT INTO tracks (id, title, artist_id, duration, source_type, provider_id, file_path) VALUES (?, ?, 'ar', 1, 'LOCAL', 'local', ?)",
+            )
+            .bind(id)
+            .bind(id)
+            .bind(path)
+            .execute(&db.pool)
+            .await
+            .unwrap();
+        }
+        let scanner = LibraryScanner::new(db.pool.clone());
+
+        assert_eq!(scanner.remove_folder("m", false).await.unwrap(), 1);
+        assert_eq!(scanner.remove_folder("p", true).await.unwrap(), 1);
+
+        let rows: Vec<(String, bool)> =
+            sqlx::query_as("SELECT id, missing_since IS NOT NULL FROM tracks ORDER BY id")
+                .fetch_all(&db.pool)
+                .await
+                .unwrap();
+        assert_eq!(rows, vec![("a".to_string(), true)]);
+        assert!(scanner.get_folders().await.unwrap().is_empty());
+    }
+}
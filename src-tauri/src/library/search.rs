@@ -0,0 +1,772 @@
+use super::browse::{bind_args, prefix_range, Arg};
+use super::models::{
+    UnifiedTrack, TRACK_METADATA_COLUMNS, TRACK_METADATA_CTES, TRACK_METADATA_JOINS,
+};
+use super::LibraryManager;
+use crate::spotify::romanization::{contains_japanese, fold_japanese, romanize_japanese};
+use sqlx::{Row, Sqlite, Transaction};
+
+/// FTS hits fetched before re-ranking by popularity
+const CANDIDATE_LIMIT: i64 = 200;
+
+/// Words shorter than this are never treated as typos
+const MIN_FUZZY_LEN: usize = 4;
+
+/// Spelling alternatives tried for a word that matches nothing
+const MAX_ALTERNATIVES: usize = 3;
+
+/// Index terms are porter stems, which can be this much shorter than the
+/// word they came from, as with "ational" becoming "ate"
+const STEM_SUFFIX_LEN: usize = 4;
+
+/// Close stems looked up to find the words they were made from
+const MAX_STEM_LOOKUPS: usize = 10;
+
+/// The indexed text of the first row holding a term, with the words that
+/// produced it wrapped in \x01 and \x02
+const STEM_SOURCE: &str = r#"
+    SELECT highlight(search_index, 1, char(1), char(2)) || ' ' ||
+           highlight(search_index, 2, char(1), char(2)) || ' ' ||
+           highlight(search_index, 3, char(1), char(2)) || ' ' ||
+           highlight(search_index, 4, char(1), char(2)) || ' ' ||
+           highlight(search_index, 5, char(1), char(2)) || ' ' ||
+           highlight(search_index, 6, char(1), char(2))
+    FROM search_index WHERE search_index MATCH ? LIMIT 1
+"#;
+
+/// bm25 weights for the search_index columns: track_id, title, artist, album
+/// and their romanized/folded aliases
+const BM25_WEIGHTS: &str = "0.0, 4.0, 3.0, 2.0, 3.0, 2.0, 1.5";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TextField {
+    Title,
+    Artist,
+    Album,
+}
+
+impl TextField {
+    /// FTS column filter covering the field and its alias column
+    fn columns(self) -> &'static str {
+        match self {
+            TextField::Title => "{title title_alt}",
+            TextField::Artist => "{artist artist_alt}",
+            TextField::Album => "{album album_alt}",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct TextTerm {
+    pub text: String,
+    pub field: Option<TextField>,
+    /// Quoted: match the words in order, without prefix expansion
+    pub phrase: bool,
+    pub negated: bool,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum SearchFilter {
+    /// Inclusive bounds
+    Year {
+        min: Option<i64>,
+        max: Option<i64>,
+        negated: bool,
+    },
+    Provider {
+        name: String,
+        negated: bool,
+    },
+    Genre {
+        name: String,
+        negated: bool,
+    },
+    Liked(bool),
+}
+
+/// A parsed library search, e.g.
+/// `artist:"daft punk" year:>2000 -live provider:tidal liked:yes`
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SearchQuery {
+    pub terms: Vec<TextTerm>,
+    pub filters: Vec<SearchFilter>,
+}
+
+impl SearchQuery {
+    pub fn parse(input: &str) -> Self {
+        let mut query = SearchQuery::default();
+        let mut chars = input.chars().peekable();
+
+        loop {
+            while chars.next_if(|c| c.is_whitespace()).is_some() {}
+            let Some(&first) = chars.peek() else {
+                break;
+            };
+
+            let negated = first == '-';
+            if negated {
+                chars.next();
+            }
+
+            // Read up to the end of the token, keeping quoted runs together
+            let mut key = String::new();
+            let mut value = String::new();
+            let mut quoted = false;
+            let mut in_quotes = false;
+            while let Some(&c) = chars.peek() {
+                if c.is_whitespace() && !in_quotes {
+                    break;
+                }
+                chars.next();
+                if c == '"' {
+                    in_quotes = !in_quotes;
+                    quoted = true;
+                } else if c == ':' && !in_quotes && !quoted && key.is_empty() && !value.is_empty() {
+                    key = std::mem::take(&mut value).to_lowercase();
+                } else {
+                    value.push(c);
+                }
+            }
+
+            if value.trim().is_empty() {
+                continue;
+            }
+            if !query.push_qualified(&key, &value, quoted, negated) {
+                let text = if key.is_empty() {
+                    value
+                } else {
+                    format!("{} {}", key, value)
+                };
+                query.terms.push(TextTerm {
+                    text,
+                    field: None,
+                    phrase: quoted,
+                    negated,
+                });
+            }
+        }
+
+        query
+    }
+
+    /// Handle a `key:value` token. Returns false if the key isn't a known
+    /// field or the value doesn't fit it, so the token is searched as text.
+    fn push_qualified(&mut self, key: &str, value: &str, quoted: bool, negated: bool) -> bool {
+        let field = match key {
+            "title" => Some(TextField::Title),
+            "artist" => Some(TextField::Artist),
+            "album" => Some(TextField::Album),
+            _ => None,
+        };
+        if let Some(field) = field {
+            self.terms.push(TextTerm {
+                text: value.to_string(),
+                field: Some(field),
+                phrase: quoted,
+                negated,
+            });
+            return true;
+        }
+
+        let filter = match key {
+            "year" => parse_year(value).map(|(min, max)| SearchFilter::Year { min, max, negated }),
+            "provider" | "source" => Some(SearchFilter::Provider {
+                name: value.to_lowercase(),
+                negated,
+            }),
+            "genre" => Some(SearchFilter::Genre {
+                name: value.to_string(),
+                negated,
+            }),
+            "liked" => match value.to_lowercase().as_str() {
+                "yes" | "true" | "1" => Some(SearchFilter::Liked(!negated)),
+                "no" | "false" | "0" => Some(SearchFilter::Liked(negated)),
+                _ => None,
+            },
+            _ => None,
+        };
+        match filter {
+            Some(filter) => {
+                self.filters.push(filter);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Positive text searched in `field`, or in any field, for the plain
+    /// LIKE lookups of albums and artists
+    pub fn text_for(&self, field: TextField) -> String {
+        self.terms
+            .iter()
+            .filter(|t| !t.negated && (t.field.is_none() || t.field == Some(field)))
+            .map(|t| t.text.as_str())
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+}
+
+/// `2010`, `>2010`, `<=1999`, `1990..1999` or `1990s`
+fn parse_year(value: &str) -> Option<(Option<i64>, Option<i64>)> {
+    let value = value.trim();
+    if let Some((from, to)) = value.split_once("..") {
+        return Some((Some(from.parse().ok()?), Some(to.parse().ok()?)));
+    }
+    if let Some(decade) = value.strip_suffix('s') {
+        let start: i64 = decade.parse().ok()?;
+        return Some((Some(start), Some(start + 9)));
+    }
+    if let Some(year) = value.strip_prefix(">=") {
+        return Some((Some(year.parse().ok()?), None));
+    }
+    if let Some(year) = value.strip_prefix("<=") {
+        return Some((None, Some(year.parse().ok()?)));
+    }
+    if let Some(year) = value.strip_prefix('>') {
+        return Some((Some(year.parse::<i64>().ok()? + 1), None));
+    }
+    if let Some(year) = value.strip_prefix('<') {
+        return Some((None, Some(year.parse::<i64>().ok()? - 1)));
+    }
+    let year: i64 = value.parse().ok()?;
+    Some((Some(year), Some(year)))
+}
+
+/// Words of a term with FTS syntax characters stripped and Japanese folded
+/// the same way as the alias columns
+fn fts_words(text: &str) -> Vec<String> {
+    fold_japanese(text)
+        .split_whitespace()
+        .map(|word| {
+            word.chars()
+                .filter(|c| c.is_alphanumeric())
+                .collect::<String>()
+        })
+        .filter(|word| !word.is_empty())
+        .collect()
+}
+
+/// FTS5 expression for one term. Each word matches as a prefix, or as one of
+/// its spelling alternatives; phrases match as written.
+fn fts_expression(term: &TextTerm, alternatives: &[Vec<String>]) -> Option<String> {
+    let words = fts_words(&term.text);
+    if words.is_empty() {
+        return None;
+    }
+
+    let expr = if term.phrase {
+        format!("\"{}\"", words.join(" "))
+    } else {
+        let parts: Vec<String> = words
+            .iter()
+            .enumerate()
+            .map(|(i, word)| {
+                let alts = alternatives.get(i).map(Vec::as_slice).unwrap_or_default();
+                if alts.is_empty() {
+                    format!("\"{}\"*", word)
+                } else {
+                    let options: Vec<String> = std::iter::once(format!("\"{}\"*", word))
+                        .chain(alts.iter().map(|alt| format!("\"{}\"", alt)))
+                        .collect();
+                    format!("({})", options.join(" OR "))
+                }
+            })
+            .collect();
+        parts.join(" ")
+    };
+
+    Some(match term.field {
+        Some(field) => format!("{} : ({})", field.columns(), expr),
+        None => expr,
+    })
+}
+
+/// Romaji with the long-vowel dashes and apostrophes dropped, e.g. "wa-rudo"
+/// becomes "warudo", so it indexes and matches as one word
+fn compact_romaji(text: &str) -> String {
+    text.chars()
+        .filter(|c| *c != '-' && *c != '\'')
+        .collect::<String>()
+        .to_lowercase()
+}
+
+/// Other spellings of displayed text for the alias columns: Japanese folded
+/// to one script and width, plus its romanization. Empty when there are none.
+pub(crate) fn search_aliases(text: &str) -> String {
+    let mut aliases = Vec::new();
+    let folded = fold_japanese(text);
+    if folded != text {
+        aliases.push(folded.clone());
+    }
+    if let Some(romaji) = romanize_japanese(&folded) {
+        let compact = compact_romaji(&romaji);
+        aliases.push(romaji);
+        aliases.push(compact);
+    }
+    aliases.dedup();
+    aliases.join(" ")
+}
+
+/// Replace a track's row in the search index
+pub(crate) async fn index_track(
+    tx: &mut Transaction<'_, Sqlite>,
+    track_id: &str,
+    title: &str,
+    artist: &str,
+    album: &str,
+) -> Result<(), String> {
+    sqlx::query("DELETE FROM search_index WHERE track_id = ?")
+        .bind(track_id)
+        .execute(&mut **tx)
+        .await
+        .map_err(|e| e.to_string())?;
+
+    sqlx::query(
+        r#"
+        INSERT INTO search_index (track_id, title, artist, album, title_alt, artist_alt, album_alt)
+        VALUES (?, ?, ?, ?, ?, ?, ?)
+        "#,
+    )
+    .bind(track_id)
+    .bind(title)
+    .bind(artist)
+    .bind(album)
+    .bind(search_aliases(title))
+    .bind(search_aliases(artist))
+    .bind(search_aliases(album))
+    .execute(&mut **tx)
+    .await
+    .map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+/// Levenshtein distance over chars
+pub(crate) fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut prev: Vec<usize> = (0..=b.len()).collect();
+    let mut cur = vec![0; b.len() + 1];
+
+    for (i, ca) in a.chars().enumerate() {
+        cur[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = prev[j] + usize::from(ca != *cb);
+            cur[j + 1] = substitution.min(prev[j + 1] + 1).min(cur[j] + 1);
+        }
+        std::mem::swap(&mut prev, &mut cur);
+    }
+    prev[b.len()]
+}
+
+/// Typos allowed in a word of `len` chars
+fn max_distance(len: usize) -> usize {
+    if len <= 5 {
+        1
+    } else {
+        2
+    }
+}
+
+/// Distance from a stem to the start of `word`, as a cheap guess at how
+/// close the stem's full word is
+fn stem_distance(word: &str, stem: &str) -> usize {
+    let head: String = word.chars().take(stem.chars().count()).collect();
+    edit_distance(&head, stem)
+}
+
+/// The first highlighted word in `STEM_SOURCE` output
+fn highlighted_word(text: &str) -> Option<String> {
+    let word = text.split('\u{1}').nth(1)?.split('\u{2}').next()?;
+    (!word.is_empty()).then(|| word.to_lowercase())
+}
+
+/// Index terms close enough to `word` to be what the user meant
+fn closest_terms(word: &str, vocab: &[String]) -> Vec<String> {
+    let len = word.chars().count();
+    let max_distance = max_distance(len);
+
+    let mut matches: Vec<(usize, &String)> = vocab
+        .iter()
+        .filter(|term| term.chars().count().abs_diff(len) <= max_distance)
+        .map(|term| (edit_distance(word, term), term))
+        .filter(|(distance, _)| *distance <= max_distance)
+        .collect();
+    matches.sort();
+    matches
+        .into_iter()
+        .take(MAX_ALTERNATIVES)
+        .map(|(_, term)| term.clone())
+        .collect()
+}
+
+/// Text relevance nudged towards tracks the user actually plays
+fn ranking_score(bm25: f64, play_count: i64) -> f64 {
+    // bm25() is negative, more so for better matches
+    -bm25 * (1.0 + 0.25 * (1.0 + play_count.max(0) as f64).ln())
+}
+
+fn filter_clause(filter: &SearchFilter, args: &mut Vec<Arg>) -> String {
+    let not = |negated: bool| if negated { "NOT " } else { "" };
+    match filter {
+        SearchFilter::Year { min, max, negated } => {
+            let mut bounds = vec!["t.year IS NOT NULL".to_string()];
+            if let Some(min) = min {
+                bounds.push("t.year >= ?".to_string());
+                args.push(Arg::Int(*min));
+            }
+            if let Some(max) = max {
+                bounds.push("t.year <= ?".to_string());
+                args.push(Arg::Int(*max));
+            }
+            format!("{}({})", not(*negated), bounds.join(" AND "))
+        }
+        SearchFilter::Provider { name, negated } => {
+            args.push(Arg::Text(name.clone()));
+            format!(
+                "{}(COALESCE(t.provider_id, lower(t.source_type)) = ?)",
+                not(*negated)
+            )
+        }
+        SearchFilter::Genre { name, negated } => {
+            args.push(Arg::Text(name.clone()));
+            format!(
+                "{}EXISTS (SELECT 1 FROM track_genres g WHERE g.track_id = t.id AND g.genre = ? COLLATE NOCASE)",
+                not(*negated)
+            )
+        }
+        SearchFilter::Liked(liked) => format!(
+            "{}EXISTS (SELECT 1 FROM user_favorites f JOIN tracks w ON w.id = f.track_id WHERE w.id = t.id OR w.work_id = t.work_id)",
+            not(!*liked)
+        ),
+    }
+}
+
+impl LibraryManager {
+    /// Tracks matching a parsed query, best first. Words that match nothing
+    /// in the index are widened to close spellings that do.
+    pub async fn search_tracks(
+        &self,
+        query: &SearchQuery,
+        limit: usize,
+    ) -> Result<Vec<UnifiedTrack>, String> {
+        let mut positive = Vec::new();
+        let mut clauses = Vec::new();
+        let mut args = Vec::new();
+
+        for term in &query.terms {
+            if term.negated {
+                if let Some(expr) = fts_expression(term, &[]) {
+                    clauses.push(
+                        "t.id NOT IN (SELECT track_id FROM search_index WHERE search_index MATCH ?)"
+                            .to_string(),
+                    );
+                    args.push(Arg::Text(expr));
+                }
+                continue;
+            }
+
+            let mut alternatives = Vec::new();
+            if !term.phrase {
+                for word in fts_words(&term.text) {
+                    let mut alts = self.typo_alternatives(&word).await?;
+                    // Kana typed for a title stored in romaji
+                    if contains_japanese(&word) {
+                        alts.extend(romanize_japanese(&word).map(|r| compact_romaji(&r)));
+                    }
+                    alternatives.push(alts);
+                }
+            }
+            if let Some(expr) = fts_expression(term, &alternatives) {
+                positive.push(expr);
+            }
+        }
+
+        for filter in &query.filters {
+            clauses.push(filter_clause(filter, &mut args));
+        }
+
+        if positive.is_empty() && clauses.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let columns = format!(
+            r#"
+            t.id, t.title, t.duration, t.source_type, t.file_path,
+            t.play_count, t.skip_count, t.last_played_at, t.added_at, t.audio_quality,
+            t.provider_id, t.external_id, t.artist_id, t.album_id,
+            {},
+            a.name as artist_name,
+            al.title as album_title, al.cover_url
+            "#,
+            TRACK_METADATA_COLUMNS
+        );
+
+        let fts_query = positive.join(" AND ");
+        let sql = if positive.is_empty() {
+            // Filters only: most played first
+            format!(
+                r#"
+                WITH {}
+                SELECT {}, 0.0 as score
+                FROM tracks t
+                JOIN artists a ON t.artist_id = a.id
+                LEFT JOIN albums al ON t.album_id = al.id
+                {}
+                WHERE {}
+                ORDER BY t.play_count DESC, t.title COLLATE NOCASE ASC
+                LIMIT ?
+                "#,
+                TRACK_METADATA_CTES,
+                columns,
+                TRACK_METADATA_JOINS,
+                clauses.join(" AND ")
+            )
+        } else {
+            let filters: String = clauses.iter().map(|c| format!(" AND {}", c)).collect();
+            format!(
+                r#"
+                WITH {}
+                SELECT {}, bm25(search_index, {}) as score
+                FROM search_index si
+                JOIN tracks t ON t.id = si.track_id
+                JOIN artists a ON t.artist_id = a.id
+                LEFT JOIN albums al ON t.album_id = al.id
+                {}
+                WHERE search_index MATCH ?{}
+                ORDER BY score
+                LIMIT ?
+                "#,
+                TRACK_METADATA_CTES, columns, BM25_WEIGHTS, TRACK_METADATA_JOINS, filters
+            )
+        };
+
+        log::info!(
+            "Library search: fts={:?} filters={}",
+            fts_query,
+            clauses.len()
+        );
+
+        let mut sql_query = sqlx::query(&sql);
+        if !positive.is_empty() {
+            sql_query = sql_query.bind(&fts_query);
+        }
+        let rows = bind_args(sql_query, &args)
+            .bind(CANDIDATE_LIMIT)
+            .fetch_all(&self.pool)
+            .await
+            .map_err(|e| {
+                log::error!("Library search failed: {}", e);
+                e.to_string()
+            })?;
+
+        let mut scored: Vec<(f64, UnifiedTrack)> = rows
+            .iter()
+            .map(|row| {
+                let bm25: f64 = row.try_get("score").unwrap_or(0.0);
+                let plays: i64 = row.try_get("play_count").unwrap_or(0);
+                (ranking_score(bm25, plays), UnifiedTrack::from_row(row))
+            })
+            .collect();
+        if !positive.is_empty() {
+            scored.sort_by(|a, b| b.0.total_cmp(&a.0));
+        }
+
+        Ok(scored
+            .into_iter()
+            .take(limit)
+            .map(|(_, track)| track)
+            .collect())
+    }
+
+    /// Close spellings of a word that matches nothing in the index. Only
+    /// index terms sharing its first letter and of a similar length are
+    /// considered, and each is traced back to the word it was stemmed from.
+    async fn typo_alternatives(&self, word: &str) -> Result<Vec<String>, String> {
+        if word.chars().count() < MIN_FUZZY_LEN {
+            return Ok(Vec::new());
+        }
+
+        let found: bool = sqlx::query_scalar(
+            "SELECT EXISTS(SELECT 1 FROM search_index WHERE search_index MATCH ?)",
+        )
+        .bind(format!("\"{}\"*", word))
+        .fetch_one(&self.pool)
+        .await
+        .map_err(|e| e.to_string())?;
+        if found {
+            return Ok(Vec::new());
+        }
+
+        let word = word.to_lowercase();
+        let len = word.chars().count();
+        let max_distance = max_distance(len);
+        let Some(first) = word.chars().next() else {
+            return Ok(Vec::new());
+        };
+        let (lower, upper) = prefix_range(&first.to_string());
+        let stems: Vec<String> = sqlx::query_scalar(
+            "SELECT term FROM search_vocab WHERE term >= ? AND term < ? AND length(term) BETWEEN ? AND ?",
+        )
+        .bind(lower)
+        .bind(upper)
+        .bind(len.saturating_sub(max_distance + STEM_SUFFIX_LEN) as i64)
+        .bind((len + max_distance) as i64)
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| e.to_string())?;
+
+        let mut close: Vec<(usize, String)> = stems
+            .into_iter()
+            .map(|stem| (stem_distance(&word, &stem), stem))
+            .filter(|(distance, _)| *distance <= max_distance)
+            .collect();
+        close.sort();
+
+        let mut words = Vec::new();
+        for (_, stem) in close.into_iter().take(MAX_STEM_LOOKUPS) {
+            let source: Option<String> = sqlx::query_scalar(STEM_SOURCE)
+                .bind(format!("\"{}\"", stem))
+                .fetch_optional(&self.pool)
+                .await
+                .map_err(|e| e.to_string())?;
+            if let Some(found) = source.as_deref().and_then(highlighted_word) {
+                if !words.contains(&found) {
+                    words.push(found);
+                }
+            }
+        }
+
+        let alternatives = closest_terms(&word, &words);
+        if !alternatives.is_empty() {
+            log::debug!(
+                "Search: '{}' matched nothing, trying {:?}",
+                word,
+                alternatives
+            );
+        }
+        Ok(alternatives)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::DatabaseManager;
+
+    #[test]
+    fn parses_fields_phrases_and_negation() {
+        let query =
+            SearchQuery::parse(r#"artist:"daft punk" year:>2000 -live "one more" provider:Tidal"#);
+
+        assert_eq!(
+            query.terms,
+            vec![
+                TextTerm {
+                    text: "daft punk".to_string(),
+                    field: Some(TextField::Artist),
+                    phrase: true,
+                    negated: false,
+                },
+                TextTerm {
+                    text: "live".to_string(),
+                    field: None,
+                    phrase: false,
+                    negated: true,
+                },
+                TextTerm {
+                    text: "one more".to_string(),
+                    field: None,
+                    phrase: true,
+                    negated: false,
+                },
+            ]
+        );
+        assert_eq!(
+            query.filters,
+            vec![
+                SearchFilter::Year {
+                    min: Some(2001),
+                    max: None,
+                    negated: false,
+                },
+                SearchFilter::Provider {
+                    name: "tidal".to_string(),
+                    negated: false,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn unknown_fields_and_bad_values_are_searched_as_text() {
+        let query = SearchQuery::parse("mood:happy year:soon 12:34");
+        let texts: Vec<&str> = query.terms.iter().map(|t| t.text.as_str()).collect();
+        assert_eq!(texts, vec!["mood happy", "year soon", "12 34"]);
+        assert!(query.filters.is_empty());
+    }
+
+    #[test]
+    fn builds_fts_expressions_with_alternatives() {
+        let term = TextTerm {
+            text: "beatels".to_string(),
+            field: Some(TextField::Artist),
+            phrase: false,
+            negated: false,
+        };
+        assert_eq!(
+            fts_expression(&term, &[vec!["beatles".to_string()]]).unwrap(),
+            r#"{artist artist_alt} : (("beatels"* OR "beatles"))"#
+        );
+    }
+
+    #[test]
+    fn aliases_cover_other_scripts() {
+        assert_eq!(search_aliases("Hello"), "");
+        assert_eq!(search_aliases("ヨアソビ"), "よあそび yoasobi");
+        assert_eq!(fts_words("ﾖｱｿﾋﾞ"), vec!["よあそび".to_string()]);
+    }
+
+    #[test]
+    fn finds_close_spellings() {
+        let vocab: Vec<String> = ["beatles", "beast", "metallica", "beat"]
+            .iter()
+            .map(|s| s.to_string())
+            .collect();
+        assert_eq!(edit_distance("beatels", "beatles"), 2);
+        assert_eq!(
+            closest_terms("beatels", &vocab),
+            vec!["beatles".to_string()]
+        );
+        assert_eq!(
+            closest_terms("metalica", &vocab),
+            vec!["metallica".to_string()]
+        );
+    }
+
+    #[tokio::test]
+    async fn typos_suggest_indexed_words_rather_than_stems() {
+        let db = DatabaseManager::in_memory().await;
+        let mut tx = db.pool.begin().await.unwrap();
+        index_track(&mut tx, "t1", "Come Together", "The Beatles", "Abbey Road")
+            .await
+            .unwrap();
+        index_track(&mut tx, "t2", "Running Happily", "Beast", "Generalizations")
+            .await
+            .unwrap();
+        tx.commit().await.unwrap();
+        let library = LibraryManager::new(db.pool.clone());
+
+        assert_eq!(
+            library.typo_alternatives("Beatels").await.unwrap(),
+            vec!["beatles".to_string()]
+        );
+        assert_eq!(
+            library.typo_alternatives("happilly").await.unwrap(),
+            vec!["happily".to_string()]
+        );
+        assert!(library
+            .typo_alternatives("beatles")
+            .await
+            .unwrap()
+            .is_empty());
+    }
+}
@@ -0,0 +1,491 @@
+use lofty::config::{ParseOptions, WriteOptions};
+use lofty::file::FileType;
+use lofty::mp4::Mp4File;
+use lofty::mpeg::MpegFile;
+use lofty::picture::{Picture, PictureType};
+use lofty::prelude::*;
+use lofty::probe::Probe;
+use lofty::tag::{ItemKey, ItemValue, MergeTag, SplitTag, Tag, TagExt, TagItem, TagType};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+/// A set of tag changes. `None` leaves a field alone, so the same edit can be
+/// applied to many tracks; an empty string, or zero for the numbers, clears
+/// the field.
+#[derive(Debug, Deserialize, Clone, Default)]
+pub struct TagEdit {
+    pub title: Option<String>,
+    pub artist: Option<String>,
+    pub album_artist: Option<String>,
+    pub album: Option<String>,
+    pub track_number: Option<u32>,
+    pub track_total: Option<u32>,
+    pub disc_number: Option<u32>,
+    pub disc_total: Option<u32>,
+    pub year: Option<u32>,
+    pub genre: Option<String>,
+    pub lyrics: Option<String>,
+    /// Image file to embed as the front cover
+    pub cover_path: Option<String>,
+    #[serde(default)]
+    pub remove_cover: bool,
+}
+
+/// Current tag values of a file, used to populate the editor
+#[derive(Debug, Serialize, Clone, Default)]
+pub struct TrackTags {
+    pub title: Option<String>,
+    pub artist: Option<String>,
+    pub album_artist: Option<String>,
+    pub album: Option<String>,
+    pub track_number: Option<u32>,
+    pub track_total: Option<u32>,
+    pub disc_number: Option<u32>,
+    pub disc_total: Option<u32>,
+    pub year: Option<u32>,
+    pub genre: Option<String>,
+    pub lyrics: Option<String>,
+    pub has_cover: bool,
+    /// Half-stars, 0 to 10
+    pub rating: Option<u8>,
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct TagEditFailure {
+    pub track_id: String,
+    pub error: String,
+}
+
+#[derive(Debug, Serialize, Clone, Default)]
+pub struct TagEditResult {
+    pub updated: Vec<String>,
+    pub failed: Vec<TagEditFailure>,
+}
+
+pub fn read_tags(path: &Path) -> Result<TrackTags, String> {
+    let tagged_file = Probe::open(path)
+        .map_err(|e| e.to_string())?
+        .read()
+        .map_err(|e| e.to_string())?;
+
+    let Some(tag) = tagged_file.primary_tag() else {
+        return Ok(TrackTags::default());
+    };
+
+    Ok(TrackTags {
+        title: tag.title().map(|s| s.into_owned()),
+        artist: tag.artist().map(|s| s.into_owned()),
+        album_artist: tag.get_string(&ItemKey::AlbumArtist).map(String::from),
+        album: tag.album().map(|s| s.into_owned()),
+        track_number: tag.track(),
+        track_total: tag.track_total(),
+        disc_number: tag.disk(),
+        disc_total: tag.disk_total(),
+        year: tag.year(),
+        genre: tag.genre().map(|s| s.into_owned()),
+        lyrics: tag.get_string(&ItemKey::Lyrics).map(String::from),
+        has_cover: tag
+            .pictures()
+            .iter()
+            .any(|p| p.pic_type() == PictureType::CoverFront),
+        rating: tag_rating(tag),
+    })
+}
+
+/// Apply an edit to the file's primary tag. Items the editor does not know
+/// about, like ID3v2 private frames or MP4 freeform atoms, are written back
+/// untouched.
+pub fn write_tags(path: &Path, edit: &TagEdit) -> Result<(), String> {
+    let cover = match &edit.cover_path {
+        Some(cover_path) => {
+            let mut file = std::fs::File::open(cover_path).map_err(|e| e.to_string())?;
+            let mut picture = Picture::from_reader(&mut file).map_err(|e| e.to_string())?;
+            picture.set_pic_type(PictureType::CoverFront);
+            Some(picture)
+        }
+        None => None,
+    };
+
+    edit_primary_tag(path, |tag| {
+        apply_edit(tag, edit, cover);
+        Ok(())
+    })
+}
+
+/// Run `edit` on the file's primary tag and save it. ID3v2 and MP4 tags hold
+/// items lofty's generic tag can't represent, so those are split off the
+/// concrete tag first and merged back before saving. Vorbis comments and APE
+/// items all survive the generic tag.
+fn edit_primary_tag(
+    path: &Path,
+    edit: impl FnOnce(&mut Tag) -> Result<(), String>,
+) -> Result<(), String> {
+    let file_type = Probe::open(path)
+        .and_then(|probe| probe.guess_file_type())
+        .map_err(|e| e.to_string())?
+        .file_type();
+
+    match file_type {
+        Some(FileType::Mpeg) => {
+            let file = MpegFile::read_from(&mut open(path)?, ParseOptions::new())
+                .map_err(|e| e.to_string())?;
+            edit_split_tag(path, file.id3v2().cloned().unwrap_or_default(), edit)
+        }
+        Some(FileType::Mp4) => {
+            let file = Mp4File::read_from(&mut open(path)?, ParseOptions::new())
+                .map_err(|e| e.to_string())?;
+            edit_split_tag(path, file.ilst().cloned().unwrap_or_default(), edit)
+        }
+        _ => {
+            let mut tagged_file = Probe::open(path)
+                .map_err(|e| e.to_string())?
+                .read()
+                .map_err(|e| e.to_string())?;
+
+            if tagged_file.primary_tag().is_none() {
+                let tag_type = tagged_file.primary_tag_type();
+                tagged_file.insert_tag(Tag::new(tag_type));
+            }
+            let tag = tagged_file
+                .primary_tag_mut()
+                .ok_or("File does not support tags")?;
+
+            edit(tag)?;
+            tag.save_to_path(path, WriteOptions::default())
+                .map_err(|e| e.to_string())
+        }
+    }
+}
+
+fn edit_split_tag<T>(
+    path: &Path,
+    tag: T,
+    edit: impl FnOnce(&mut Tag) -> Result<(), String>,
+) -> Result<(), String>
+where
+    T: SplitTag + TagExt,
+    T::Remainder: MergeTag<Merged = T>,
+    T::Err: std::fmt::Display,
+{
+    let (remainder, mut tag) = tag.split_tag();
+    edit(&mut tag)?;
+    remainder
+        .merge_tag(tag)
+        .save_to_path(path, WriteOptions::default())
+        .map_err(|e| e.to_string())
+}
+
+fn open(path: &Path) -> Result<std::fs::File, String> {
+    std::fs::File::open(path).map_err(|e| e.to_string())
+}
+
+fn apply_edit(tag: &mut Tag, edit: &TagEdit, cover: Option<Picture>) {
+    if let Some(title) = &edit.title {
+        set_or_clear(tag, ItemKey::TrackTitle, title);
+    }
+    if let Some(artist) = &edit.artist {
+        set_or_clear(tag, ItemKey::TrackArtist, artist);
+    }
+    if let Some(album_artist) = &edit.album_artist {
+        set_or_clear(tag, ItemKey::AlbumArtist, album_artist);
+    }
+    if let Some(album) = &edit.album {
+        set_or_clear(tag, ItemKey::AlbumTitle, album);
+    }
+    if let Some(genre) = &edit.genre {
+        set_or_clear(tag, ItemKey::Genre, genre);
+    }
+    if let Some(lyrics) = &edit.lyrics {
+        set_or_clear(tag, ItemKey::Lyrics, lyrics);
+    }
+    match edit.track_number {
+        Some(0) => tag.remove_track(),
+        Some(n) => tag.set_track(n),
+        None => {}
+    }
+    match edit.track_total {
+        Some(0) => tag.remove_track_total(),
+        Some(n) => tag.set_track_total(n),
+        None => {}
+    }
+    match edit.disc_number {
+        Some(0) => tag.remove_disk(),
+        Some(n) => tag.set_disk(n),
+        None => {}
+    }
+    match edit.disc_total {
+        Some(0) => tag.remove_disk_total(),
+        Some(n) => tag.set_disk_total(n),
+        None => {}
+    }
+    match edit.year {
+        Some(0) => tag.remove_year(),
+        Some(year) => tag.set_year(year),
+        None => {}
+    }
+
+    if edit.remove_cover || cover.is_some() {
+        tag.remove_picture_type(PictureType::CoverFront);
+    }
+    if let Some(picture) = cover {
+        tag.push_picture(picture);
+    }
+}
+
+fn set_or_clear(tag: &mut Tag, key: ItemKey, value: &str) {
+    if value.trim().is_empty() {
+        tag.remove_key(&key);
+    } else {
+        tag.insert_text(key, value.to_string());
+    }
+}
+
+/// POPM byte for each half-star rating from 1 to 10, as MusicBee writes them.
+/// The whole-star values are the ones Windows Media Player uses.
+const POPM_VALUES: [u8; 10] = [13, 1, 54, 64, 118, 128, 186, 196, 242, 255];
+
+const VORBIS_RATING: &str = "RATING";
+
+/// The half-star rating stored in an ID3v2 POPM frame or a Vorbis RATING comment
+pub fn tag_rating(tag: &Tag) -> Option<u8> {
+    match tag.tag_type() {
+        TagType::Id3v2 => match tag.get(&ItemKey::Popularimeter)?.value() {
+            ItemValue::Binary(frame) => popm_rating(frame),
+            _ => None,
+        },
+        TagType::VorbisComments => tag
+            .get_string(&ItemKey::Unknown(VORBIS_RATING.to_string()))
+            .and_then(vorbis_rating),
+        _ => None,
+    }
+}
+
+/// Store a half-star rating, or remove it with `None`
+pub fn set_tag_rating(tag: &mut Tag, rating: Option<u8>) -> Result<(), String> {
+    match tag.tag_type() {
+        TagType::Id3v2 => {
+            let Some(rating) = rating.filter(|r| *r > 0) else {
+                tag.remove_key(&ItemKey::Popularimeter);
+                return Ok(());
+            };
+            // Keep the email and play counter of an existing frame
+            let (email, counter) = match tag.get(&ItemKey::Popularimeter).map(TagItem::value) {
+                Some(ItemValue::Binary(frame)) => match frame.iter().position(|b| *b == 0) {
+                    Some(end) => (
+                        frame[..end].to_vec(),
+                        frame.get(end + 2..).unwrap_or_default().to_vec(),
+                    ),
+                    None => (Vec::new(), Vec::new()),
+                },
+                _ => (Vec::new(), Vec::new()),
+            };
+            let mut frame = email;
+            frame.push(0);
+            frame.push(POPM_VALUES[rating.min(10) as usize - 1]);
+            frame.extend(counter);
+            tag.insert(TagItem::new(
+                ItemKey::Popularimeter,
+                ItemValue::Binary(frame),
+            ));
+            Ok(())
+        }
+        TagType::VorbisComments => {
+            let key = ItemKey::Unknown(VORBIS_RATING.to_string());
+            match rating.filter(|r| *r > 0) {
+                Some(rating) => {
+                    tag.insert_text(key, (rating.min(10) as u32 * 10).to_string());
+                }
+                None => tag.remove_key(&key),
+            }
+            Ok(())
+        }
+        other => Err(format!("Ratings cannot be stored in {:?} tags", other)),
+    }
+}
+
+/// Write a rating to the file's primary tag, leaving everything else alone
+pub fn write_rating(path: &Path, rating: Option<u8>) -> Result<(), String> {
+    edit_primary_tag(path, |tag| set_tag_rating(tag, rating))
+}
+
+/// The rating byte follows the NUL-terminated email of the POPM frame
+fn popm_rating(frame: &[u8]) -> Option<u8> {
+    let end = frame.iter().position(|b| *b == 0)?;
+    let value = *frame.get(end + 1)?;
+    if value == 0 {
+        return None;
+    }
+    if let Some(i) = POPM_VALUES.iter().position(|v| *v == value) {
+        return Some(i as u8 + 1);
+    }
+    // Other taggers only agree on these whole-star ranges
+    Some(match value {
+        1..=31 => 2,
+        32..=95 => 4,
+        96..=159 => 6,
+        160..=223 => 8,
+        _ => 10,
+    })
+}
+
+/// Vorbis RATING is usually a percentage, but some taggers write 1 to 5 stars
+fn vorbis_rating(value: &str) -> Option<u8> {
+    let value: f64 = value.trim().parse().ok()?;
+    let half_stars = if value <= 5.0 {
+        value * 2.0
+    } else {
+        value.min(100.0) / 10.0
+    };
+    let half_stars = half_stars.round() as u8;
+    (half_stars > 0).then_some(half_stars)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn popm_round_trips_half_stars() {
+        for rating in 1..=10u8 {
+            let frame = [0, POPM_VALUES[rating as usize - 1], 0, 0, 0, 1];
+            assert_eq!(popm_rating(&frame), Some(rating));
+        }
+        // Values from other taggers fall into whole-star ranges
+        assert_eq!(popm_rating(b"a@b\0\x99"), Some(6));
+        assert_eq!(popm_rating(&[0, 0]), None);
+    }
+
+    #[test]
+    fn vorbis_rating_accepts_percent_and_stars() {
+        assert_eq!(vorbis_rating("80"), Some(8));
+        assert_eq!(vorbis_rating("3.5"), Some(7));
+        assert_eq!(vorbis_rating("0"), None);
+    }
+
+    /// A FLAC file with only a STREAMINFO block: enough for tags, no audio
+    fn temp_flac(dir: &Path) -> std::path::PathBuf {
+        let mut bytes = b"fLaC".to_vec();
+        bytes.extend([0x80, 0, 0, 34]);
+        bytes.extend([0x10, 0x00, 0x10, 0x00, 0, 0, 0, 0, 0, 0]);
+        let packed: u64 = (44100 << 44) | (1 << 41) | (15 << 36) | 44100;
+        bytes.extend(packed.to_be_bytes());
+        bytes.extend([0; 16]);
+        let path = dir.join("track.flac");
+        std::fs::write(&path, bytes).unwrap();
+        path
+    }
+
+    #[test]
+    fn written_tags_read_back() {
+        let dir = std::env::temp_dir().join(format!("sonami-tags-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = temp_flac(&dir);
+        let cover = dir.join("cover.png");
+        image::RgbImage::new(1, 1).save(&cover).unwrap();
+
+        let edit = TagEdit {
+            title: Some("Title".to_string()),
+            artist: Some("Artist".to_string()),
+            album_artist: Some("Album Artist".to_string()),
+            album: Some("Album".to_string()),
+            track_number: Some(3),
+            track_total: Some(10),
+            disc_number: Some(1),
+            disc_total: Some(2),
+            year: Some(2001),
+            genre: Some("Jazz".to_string()),
+            lyrics: Some("La la".to_string()),
+            cover_path: Some(cover.to_string_lossy().into_owned()),
+            remove_cover: false,
+        };
+        write_tags(&path, &edit).unwrap();
+        write_rating(&path, Some(7)).unwrap();
+        let tags = read_tags(&path).unwrap();
+        assert_eq!(tags.title.as_deref(), Some("Title"));
+        assert_eq!(tags.artist.as_deref(), Some("Artist"));
+        assert_eq!(tags.album_artist.as_deref(), Some("Album Artist"));
+        assert_eq!(tags.album.as_deref(), Some("Album"));
+        assert_eq!((tags.track_number, tags.track_total), (Some(3), Some(10)));
+        assert_eq!((tags.disc_number, tags.disc_total), (Some(1), Some(2)));
+        assert_eq!(tags.year, Some(2001));
+        assert_eq!(tags.genre.as_deref(), Some("Jazz"));
+        assert_eq!(tags.lyrics.as_deref(), Some("La la"));
+        assert!(tags.has_cover);
+        assert_eq!(tags.rating, Some(7));
+
+        // Unset fields are left alone, empty ones cleared
+        let edit = TagEdit {
+            title: Some(String::new()),
+            track_total: Some(0),
+            disc_number: Some(0),
+            disc_total: Some(0),
+            year: Some(0),
+            remove_cover: true,
+            ..Default::default()
+        };
+        write_tags(&path, &edit).unwrap();
+        let tags = read_tags(&path).unwrap();
+        assert_eq!(tags.title, None);
+        assert_eq!(tags.artist.as_deref(), Some("Artist"));
+        assert_eq!((tags.track_number, tags.track_total), (Some(3), None));
+        assert_eq!((tags.disc_number, tags.disc_total), (None, None));
+        assert_eq!(tags.year, None);
+        assert!(!tags.has_cover);
+        assert_eq!(tags.rating, Some(7));
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    /// An MP3 with an ID3v2.3 tag holding a title and a PRIV frame, which
+    /// has no generic tag item
+    fn temp_mp3(dir: &Path) -> std::path::PathBuf {
+        let mut frames = Vec::new();
+        for (id, body) in [
+            (b"TIT2", b"\0Old".to_vec()),
+            (b"PRIV", b"com.example\0private".to_vec()),
+        ] {
+            frames.extend(id);
+            frames.extend((body.len() as u32).to_be_bytes());
+            frames.extend([0, 0]);
+            frames.extend(body);
+        }
+
+        let mut bytes = b"ID3\x03\x00\x00".to_vec();
+        let size = frames.len() as u32;
+        bytes.extend([3, 2, 1, 0].map(|shift| (size >> (shift * 7)) as u8 & 0x7F));
+        bytes.extend(frames);
+        // MPEG-1 layer III frames, 128 kbps at 44.1 kHz
+        for _ in 0..4 {
+            let mut frame = vec![0; 417];
+            frame[..4].copy_from_slice(&[0xFF, 0xFB, 0x90, 0x00]);
+            bytes.extend(frame);
+        }
+
+        let path = dir.join("track.mp3");
+        std::fs::write(&path, bytes).unwrap();
+        path
+    }
+
+    #[test]
+    fn edits_keep_unknown_frames() {
+        let dir = std::env::temp_dir().join(format!("sonami-tags-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = temp_mp3(&dir);
+
+        let edit = TagEdit {
+            title: Some("New".to_string()),
+            ..Default::default()
+        };
+        write_tags(&path, &edit).unwrap();
+        write_rating(&path, Some(8)).unwrap();
+
+        let tags = read_tags(&path).unwrap();
+        assert_eq!(tags.title.as_deref(), Some("New"));
+        assert_eq!(tags.rating, Some(8));
+        let bytes = std::fs::read(&path).unwrap();
+        let private = b"com.example\0private";
+        assert!(bytes.windows(private.len()).any(|w| w == private));
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,209 @@
+use notify_debouncer_full::notify::event::{ModifyKind, RenameMode};
+use notify_debouncer_full::notify::{EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+use notify_debouncer_full::{new_debouncer, DebounceEventResult, Debouncer, FileIdMap};
+use parking_lot::Mutex;
+use serde::Serialize;
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use tauri::{AppHandle, Emitter, Manager};
+
+use super::scanner::{is_audio_file, LibraryScanner};
+
+/// How long to wait for a burst of filesystem events to settle
+const DEBOUNCE_TIMEOUT: Duration = Duration::from_secs(2);
+
+#[derive(Debug, Serialize, Clone, Default)]
+pub struct LibraryChange {
+    pub added: usize,
+    pub updated: usize,
+    pub moved: usize,
+    /// Tracks whose files were deleted, now marked unavailable
+    pub missing: usize,
+}
+
+impl LibraryChange {
+    fn is_empty(&self) -> bool {
+        self.added + self.updated + self.moved + self.missing == 0
+    }
+}
+
+/// Watches library root folders and feeds debounced changes to the scanner
+pub struct LibraryWatcher {
+    debouncer: Mutex<Option<Debouncer<RecommendedWatcher, FileIdMap>>>,
+}
+
+impl Default for LibraryWatcher {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl LibraryWatcher {
+    pub fn new() -> Self {
+        Self {
+            debouncer: Mutex::new(None),
+        }
+    }
+
+    /// Start the debouncer and begin watching every given root
+    pub fn start(&self, app: AppHandle, roots: &[String]) -> Result<(), String> {
+        let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel::<Vec<Change>>();
+
+        let debouncer = new_debouncer(
+            DEBOUNCE_TIMEOUT,
+            None,
+            move |result: DebounceEventResult| match result {
+                Ok(events) => {
+                    let changes: Vec<Change> = events
+                        .iter()
+                        .filter_map(|event| Change::from_event(&event.kind, &event.paths))
+                        .collect();
+                    if !changes.is_empty() {
+                        let _ = tx.send(changes);
+                    }
+                }
+                Err(errors) => {
+                    for e in errors {
+                        log::warn!("[Watcher] {}", e);
+                    }
+                }
+            },
+        )
+        .map_err(|e| e.to_string())?;
+
+        *self.debouncer.lock() = Some(debouncer);
+        for root in roots {
+            if let Err(e) = self.watch(root) {
+                log::warn!("[Watcher] Could not watch {}: {}", root, e);
+            }
+        }
+
+        // Apply batches sequentially so a rename is never overtaken by a later edit
+        tauri::async_runtime::spawn(async move {
+            while let Some(changes) = rx.recv().await {
+                let scanner = app.state::<LibraryScanner>();
+                match apply_changes(&scanner, changes).await {
+                    Ok(summary) if summary.is_empty() => {}
+                    Ok(summary) => {
+                        let _ = app.emit("library-changed", summary);
+                    }
+                    Err(e) => log::error!("[Watcher] Failed to apply changes: {}", e),
+                }
+            }
+        });
+
+        log::info!("[Watcher] Watching {} library folders", roots.len());
+        Ok(())
+    }
+
+    pub fn watch(&self, root: &str) -> Result<(), String> {
+        let mut guard = self.debouncer.lock();
+        let debouncer = guard.as_mut().ok_or("Library watcher is not running")?;
+        debouncer
+            .watcher()
+            .watch(Path::new(root), RecursiveMode::Recursive)
+            .map_err(|e| e.to_string())?;
+        debouncer
+            .cache()
+            .add_root(Path::new(root), RecursiveMode::Recursive);
+        Ok(())
+    }
+
+    pub fn unwatch(&self, root: &str) -> Result<(), String> {
+        let mut guard = self.debouncer.lock();
+        let debouncer = guard.as_mut().ok_or("Library watcher is not running")?;
+        debouncer
+            .watcher()
+            .unwatch(Path::new(root))
+            .map_err(|e| e.to_string())?;
+        debouncer.cache().remove_root(Path::new(root));
+        Ok(())
+    }
+}
+
+#[derive(Debug)]
+enum Change {
+    Moved(PathBuf, PathBuf),
+    /// Created, modified or removed; which one is decided by looking at the disk
+    /// when the batch is applied, so bursts of events collapse to the final state
+    Touched(Vec<PathBuf>),
+}
+
+impl Change {
+    fn from_event(kind: &EventKind, paths: &[PathBuf]) -> Option<Self> {
+        match kind {
+            EventKind::Modify(ModifyKind::Name(RenameMode::Both)) if paths.len() == 2 => {
+                Some(Change::Moved(paths[0].clone(), paths[1].clone()))
+            }
+            EventKind::Create(_) | EventKind::Modify(_) | EventKind::Remove(_) => {
+                Some(Change::Touched(paths.to_vec()))
+            }
+            _ => None,
+        }
+    }
+}
+
+async fn apply_changes(
+    scanner: &LibraryScanner,
+    changes: Vec<Change>,
+) -> Result<LibraryChange, String> {
+    let mut summary = LibraryChange::default();
+    let mut touched: HashSet<PathBuf> = HashSet::new();
+
+    for change in changes {
+        match change {
+            Change::Moved(from, to) => {
+                // A move out of the library is a removal
+                if scanner.within_folders(vec![to.clone()]).await?.is_empty() {
+                    touched.insert(from);
+                    continue;
+                }
+                summary.moved += scanner.move_path(&from, &to).await?;
+                // Renaming e.g. `.tmp` to `.flac` is effectively a new file
+                touched.insert(to);
+            }
+            Change::Touched(paths) => touched.extend(paths),
+        }
+    }
+
+    // Events can still arrive for a folder that was just removed
+    let touched = scanner
+        .within_folders(touched.into_iter().collect())
+        .await?;
+
+    let (present, gone): (Vec<PathBuf>, Vec<PathBuf>) =
+        touched.into_iter().partition(|p| p.exists());
+
+    // A vanished parent usually means an unmounted drive, not deleted music
+    let gone: Vec<PathBuf> = gone
+        .into_iter()
+        .filter(|p| p.parent().map(|d| d.is_dir()).unwrap_or(false))
+        .collect();
+
+    // Directories are expanded by the scanner; plain files must look like audio
+    let present: Vec<PathBuf> = present
+        .into_iter()
+        .filter(|p| p.is_dir() || is_audio_file(p))
+        .collect();
+
+    if !gone.is_empty() {
+        summary.missing = scanner.remove_paths(&gone).await?;
+    }
+    if !present.is_empty() {
+        let scanned = scanner.scan_paths(present).await?;
+        summary.added = scanned.added;
+        summary.updated = scanned.updated;
+    }
+
+    if !summary.is_empty() {
+        log::info!(
+            "[Watcher] Applied changes: {} added, {} updated, {} moved, {} missing",
+            summary.added,
+            summary.updated,
+            summary.moved,
+            summary.missing
+        );
+    }
+    Ok(summary)
+}
@@ -0,0 +1,451 @@
+use super::models::WorkSource;
+use super::scanner::split_featured;
+use super::LibraryManager;
+use sqlx::{Row, Sqlite, Transaction};
+use uuid::Uuid;
+
+/// Copies whose durations differ by more than this are different recordings
+pub(crate) const DURATION_TOLERANCE_SECS: i64 = 3;
+
+/// Words in bracketed or dashed title suffixes that describe a release, not
+/// a recording. Matched as whole words, so "(Defeated)" is kept.
+const TITLE_NOISE: &[&str] = &[
+    "feat",
+    "ft",
+    "featuring",
+    "remaster",
+    "remastered",
+    "explicit",
+    "album version",
+];
+
+/// URI schemes that address a streaming provider rather than a file
+const PROVIDER_SCHEMES: &[&str] = &["tidal", "subsonic", "jellyfin"];
+
+const SOURCE_COLUMNS: &str =
+    "id as track_id, source_type, provider_id, external_id, file_path, audio_quality";
+
+fn is_noise(segment: &str) -> bool {
+    let segment = compact(segment);
+    let words: Vec<&str> = segment.split(' ').collect();
+    TITLE_NOISE.iter().any(|noise| {
+        let noise: Vec<&str> = noise.split(' ').collect();
+        words.windows(noise.len()).any(|w| w == noise.as_slice())
+    })
+}
+
+/// Drop punctuation and collapse whitespace
+fn compact(s: &str) -> String {
+    s.chars()
+        .map(|c| if c.is_alphanumeric() { c } else { ' ' })
+        .collect::<String>()
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Normalized title used to match copies of a recording, e.g.
+/// "Song (feat. X) [2011 Remaster]" and "Song - Remastered" both become "song"
+pub fn title_key(title: &str) -> String {
+    let (title, _) = split_featured(title);
+    let lower = title.to_lowercase();
+
+    let mut out = String::new();
+    let mut segment = String::new();
+    let mut depth = 0;
+    for c in lower.chars() {
+        match c {
+            '(' | '[' => {
+                if depth > 0 {
+                    segment.push(c);
+                }
+                depth += 1;
+            }
+            ')' | ']' if depth > 0 => {
+                depth -= 1;
+                if depth > 0 {
+                    segment.push(c);
+                } else {
+                    if !is_noise(&segment) {
+                        out.push(' ');
+                        out.push_str(&segment);
+                    }
+                    segment.clear();
+                }
+            }
+            _ if depth > 0 => segment.push(c),
+            _ => out.push(c),
+        }
+    }
+    out.push_str(&segment);
+
+    if let Some(idx) = out.rfind(" - ") {
+        if is_noise(&out[idx + 3..]) {
+            out.truncate(idx);
+        }
+    }
+    compact(&out)
+}
+
+pub fn artist_key(artist: &str) -> String {
+    let (main, _) = split_featured(artist);
+    let key = compact(&main.to_lowercase());
+    match key.strip_prefix("the ") {
+        Some(rest) => rest.to_string(),
+        None => key,
+    }
+}
+
+/// Attach a track to the work it is a copy of, creating a new work when
+/// nothing matches. Tracks the user merged or split by hand are left alone.
+pub(crate) async fn link_track(
+    tx: &mut Transaction<'_, Sqlite>,
+    track_id: &str,
+) -> Result<(), String> {
+    let Some(row) = sqlx::query(
+        r#"
+        SELECT t.title, t.duration, t.isrc, t.acoustid, t.work_id, t.work_pinned, a.name as artist_name
+        FROM tracks t
+        JOIN artists a ON a.id = t.artist_id
+        WHERE t.id = ?
+        "#,
+    )
+    .bind(track_id)
+    .fetch_optional(&mut **tx)
+    .await
+    .map_err(|e| e.to_string())?
+    else {
+        return Ok(());
+    };
+
+    if row.try_get::<Option<i64>, _>("work_pinned").ok().flatten() == Some(1) {
+        return Ok(());
+    }
+
+    let title: String = row.try_get("title").unwrap_or_default();
+    let artist: String = row.try_get("artist_name").unwrap_or_default();
+    let duration: i64 = row.try_get("duration").unwrap_or(0);
+    let isrc: Option<String> = row.try_get("isrc").ok().flatten();
+    let acoustid: Option<String> = row.try_get("acoustid").ok().flatten();
+    let current: Option<String> = row.try_get("work_id").ok().flatten();
+
+    let title_key = title_key(&title);
+    let artist_key = artist_key(&artist);
+
+    let matched = find_matching_work(
+        tx,
+        track_id,
+        isrc.as_deref(),
+        acoustid.as_deref(),
+        &title_key,
+        &artist_key,
+        duration,
+    )
+    .await?;
+
+    let reusable = match &current {
+        Some(id) => !shares_work(tx, id, track_id).await?,
+        None => false,
+    };
+    let work_id = match (matched, &current) {
+        (Some(id), _) => id,
+        (None, Some(id)) if reusable => {
+            // Nobody else is in the current work, so keep its id and refresh its keys
+            sqlx::query(
+                "UPDATE works SET title_key = ?, artist_key = ?, duration = ? WHERE id = ?",
+            )
+            .bind(&title_key)
+            .bind(&artist_key)
+            .bind(duration)
+            .bind(id)
+            .execute(&mut **tx)
+            .await
+            .map_err(|e| e.to_string())?;
+            id.clone()
+        }
+        (None, _) => create_work(tx, &title_key, &artist_key, duration).await?,
+    };
+
+    if current.as_deref() == Some(work_id.as_str()) {
+        return Ok(());
+    }
+
+    sqlx::query("UPDATE tracks SET work_id = ? WHERE id = ?")
+        .bind(&work_id)
+        .bind(track_id)
+        .execute(&mut **tx)
+        .await
+        .map_err(|e| e.to_string())?;
+
+    if let Some(previous) = current {
+        delete_work_if_empty(tx, &previous).await?;
+    }
+    Ok(())
+}
+
+async fn find_matching_work(
+    tx: &mut Transaction<'_, Sqlite>,
+    track_id: &str,
+    isrc: Option<&str>,
+    acoustid: Option<&str>,
+    title_key: &str,
+    artist_key: &str,
+    duration: i64,
+) -> Result<Option<String>, String> {
+    // Shared identifiers are conclusive on their own
+    for (column, value) in [("isrc", isrc), ("acoustid", acoustid)] {
+        let Some(value) = value else { continue };
+        let found: Option<String> = sqlx::query_scalar(&format!(
+            "SELECT work_id FROM tracks WHERE {} = ? AND id != ? AND work_id IS NOT NULL LIMIT 1",
+            column
+        ))
+        .bind(value)
+        .bind(track_id)
+        .fetch_optional(&mut **tx)
+        .await
+        .map_err(|e| e.to_string())?;
+        if found.is_some() {
+            return Ok(found);
+        }
+    }
+
+    if title_key.is_empty() || artist_key.is_empty() {
+        return Ok(None);
+    }
+
+    // A work only counts as a match if some other track belongs to it
+    sqlx::query_scalar(
+        r#"
+        SELECT w.id FROM works w
+        WHERE w.artist_key = ? AND w.title_key = ?
+          AND (w.duration = 0 OR ? = 0 OR abs(w.duration - ?) <= ?)
+          AND EXISTS (SELECT 1 FROM tracks o WHERE o.work_id = w.id AND o.id != ?)
+        ORDER BY abs(w.duration - ?) ASC
+        LIMIT 1
+        "#,
+    )
+    .bind(artist_key)
+    .bind(title_key)
+    .bind(duration)
+    .bind(duration)
+    .bind(DURATION_TOLERANCE_SECS)
+    .bind(track_id)
+    .bind(duration)
+    .fetch_optional(&mut **tx)
+    .await
+    .map_err(|e| e.to_string())
+}
+
+/// Whether any track other than `track_id` belongs to the work
+async fn shares_work(
+    tx: &mut Transaction<'_, Sqlite>,
+    work_id: &str,
+    track_id: &str,
+) -> Result<bool, String> {
+    let others: i64 =
+        sqlx::query_scalar("SELECT COUNT(*) FROM tracks WHERE work_id = ? AND id != ?")
+            .bind(work_id)
+            .bind(track_id)
+            .fetch_one(&mut **tx)
+            .await
+            .map_err(|e| e.to_string())?;
+    Ok(others > 0)
+}
+
+async fn create_work(
+    tx: &mut Transaction<'_, Sqlite>,
+    title_key: &str,
+    artist_key: &str,
+    duration: i64,
+) -> Result<String, String> {
+    let id = Uuid::new_v4().to_string();
+    sqlx::query("INSERT INTO works (id, title_key, artist_key, duration) VALUES (?, ?, ?, ?)")
+        .bind(&id)
+        .bind(title_key)
+        .bind(artist_key)
+        .bind(duration)
+        .execute(&mut **tx)
+        .await
+        .map_err(|e| e.to_string())?;
+    Ok(id)
+}
+
+async fn delete_work_if_empty(
+    tx: &mut Transaction<'_, Sqlite>,
+    work_id: &str,
+) -> Result<(), String> {
+    sqlx::query(
+        "DELETE FROM works WHERE id = ? AND NOT EXISTS (SELECT 1 FROM tracks WHERE work_id = ?)",
+    )
+    .bind(work_id)
+    .bind(work_id)
+    .execute(&mut **tx)
+    .await
+    .map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+/// Remove works left without tracks after a batch of deletions
+pub(crate) async fn delete_orphan_works(tx: &mut Transaction<'_, Sqlite>) -> Result<(), String> {
+    sqlx::query(
+        "DELETE FROM works WHERE NOT EXISTS (SELECT 1 FROM tracks WHERE work_id = works.id)",
+    )
+    .execute(&mut **tx)
+    .await
+    .map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+impl LibraryManager {
+    /// Link every track that has no work yet, e.g. rows imported before works existed
+    pub async fn link_unassigned_works(&self) -> Result<usize, String> {
+        let ids: Vec<String> = sqlx::query_scalar("SELECT id FROM tracks WHERE work_id IS NULL")
+            .fetch_all(&self.pool)
+            .await
+            .map_err(|e| e.to_string())?;
+        if ids.is_empty() {
+            return Ok(0);
+        }
+
+        let mut tx = self.pool.begin().await.map_err(|e| e.to_string())?;
+        for id in &ids {
+            link_track(&mut tx, id).await?;
+        }
+        tx.commit().await.map_err(|e| e.to_string())?;
+
+        log::info!("[Works] Linked {} tracks to works", ids.len());
+        Ok(ids.len())
+    }
+
+    /// Every copy of the track's work, starting with the track itself
+    pub async fn get_work_sources(&self, track_id: &str) -> Result<Vec<WorkSource>, String> {
+        sqlx::query_as::<_, WorkSource>(&format!(
+            r#"
+            SELECT {} FROM tracks
+            WHERE id = ?1
+               OR work_id = (SELECT work_id FROM tracks WHERE id = ?1)
+            ORDER BY id = ?1 DESC, added_at ASC
+            "#,
+            SOURCE_COLUMNS
+        ))
+        .bind(track_id)
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| e.to_string())
+    }
+
+    /// Like `get_work_sources`, but for a playable URI (`provider:id` or a file path).
+    /// Returns nothing when the URI is not in the library.
+    pub async fn get_work_sources_for_uri(&self, uri: &str) -> Result<Vec<WorkSource>, String> {
+        let provider = uri
+            .split_once(':')
+            .filter(|(scheme, _)| PROVIDER_SCHEMES.contains(scheme));
+
+        let track_id: Option<String> = match provider {
+            Some((scheme, id)) => sqlx::query_scalar(
+                "SELECT id FROM tracks WHERE provider_id = ? AND external_id = ? LIMIT 1",
+            )
+            .bind(scheme)
+            .bind(id),
+            None => {
+                sqlx::query_scalar("SELECT id FROM tracks WHERE file_path = ? LIMIT 1").bind(uri)
+            }
+        }
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(|e| e.to_string())?;
+
+        match track_id {
+            Some(id) => self.get_work_sources(&id).await,
+            None => Ok(Vec::new()),
+        }
+    }
+
+    /// Put the given tracks into one work, taken from the first track. The
+    /// grouping is pinned so automatic relinking won't undo it.
+    pub async fn merge_works(&self, track_ids: &[String]) -> Result<String, String> {
+        let first = track_ids.first().ok_or("No tracks to merge")?;
+        let mut tx = self.pool.begin().await.map_err(|e| e.to_string())?;
+
+        link_track(&mut tx, first).await?;
+        let work_id =
+            sqlx::query_scalar::<_, Option<String>>("SELECT work_id FROM tracks WHERE id = ?")
+                .bind(first)
+                .fetch_optional(&mut *tx)
+                .await
+                .map_err(|e| e.to_string())?
+                .flatten()
+                .ok_or_else(|| format!("Track not found: {}", first))?;
+
+        for id in track_ids {
+            sqlx::query("UPDATE tracks SET work_id = ?, work_pinned = 1 WHERE id = ?")
+                .bind(&work_id)
+                .bind(id)
+                .execute(&mut *tx)
+                .await
+                .map_err(|e| e.to_string())?;
+        }
+        delete_orphan_works(&mut tx).await?;
+
+        tx.commit().await.map_err(|e| e.to_string())?;
+        Ok(work_id)
+    }
+
+    /// Move a wrongly matched track into a work of its own
+    pub async fn split_track_work(&self, track_id: &str) -> Result<String, String> {
+        let mut tx = self.pool.begin().await.map_err(|e| e.to_string())?;
+
+        let row = sqlx::query(
+            "SELECT t.title, t.duration, a.name as artist_name FROM tracks t JOIN artists a ON a.id = t.artist_id WHERE t.id = ?",
+        )
+        .bind(track_id)
+        .fetch_optional(&mut *tx)
+        .await
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| format!("Track not found: {}", track_id))?;
+
+        let title: String = row.try_get("title").unwrap_or_default();
+        let artist: String = row.try_get("artist_name").unwrap_or_default();
+        let duration: i64 = row.try_get("duration").unwrap_or(0);
+
+        let work_id =
+            create_work(&mut tx, &title_key(&title), &artist_key(&artist), duration).await?;
+        sqlx::query("UPDATE tracks SET work_id = ?, work_pinned = 1 WHERE id = ?")
+            .bind(&work_id)
+            .bind(track_id)
+            .execute(&mut *tx)
+            .await
+            .map_err(|e| e.to_string())?;
+        delete_orphan_works(&mut tx).await?;
+
+        tx.commit().await.map_err(|e| e.to_string())?;
+        Ok(work_id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn title_key_ignores_release_noise() {
+        assert_eq!(title_key("Song (feat. Someone) [2011 Remaster]"), "song");
+        assert_eq!(title_key("Song - Remastered 2011"), "song");
+        assert_eq!(title_key("Song (Live)"), "song live");
+        assert_eq!(title_key("Song feat. Someone"), "song");
+        assert_eq!(title_key("Song (Album Version)"), "song");
+    }
+
+    #[test]
+    fn title_key_keeps_words_that_contain_noise() {
+        assert_eq!(title_key("Song (Defeated)"), "song defeated");
+        assert_eq!(title_key("Song - Aft Mix"), "song aft mix");
+        assert_eq!(title_key("Song [Explicit]"), "song");
+    }
+
+    #[test]
+    fn artist_key_ignores_guests_and_article() {
+        assert_eq!(artist_key("The Band feat. Guest"), "band");
+        assert_eq!(artist_key("AC/DC"), "ac dc");
+    }
+}
@@ -1,9 +1,8 @@
-use base64::{engine::general_purpose, Engine as _};
+#[cfg(not(target_os = "android"))]
+use crate::artwork::{ArtworkCache, ThumbnailSize};
 use parking_lot::RwLock;
 #[cfg(not(target_os = "android"))]
 use souvlaki::{MediaControls, MediaMetadata, MediaPlayback, MediaPosition, PlatformConfig};
-#[cfg(not(target_os = "android"))]
-use std::io::Write;
 use std::time::Duration;
 
 // Re-export MediaControlEvent and SeekDirection for use in other modules (or provide stub for Android)
@@ -147,9 +146,11 @@ impl MediaControlsManager {
     ) {
         #[cfg(not(target_os = "android"))]
         let processed_cover_url = cover_url.and_then(|url| {
-            if url.starts_with("data:") {
-                let cache_id = format!("{}_{}", title, artist);
-                data_url_to_file_url(url, &cache_id)
+            if url.starts_with("data:") || ArtworkCache::hash_from_url(url).is_some() {
+                // MPRIS clients can't load our URI scheme, so hand them the cached file
+                ArtworkCache::new()
+                    .local_file(url, Some(ThumbnailSize::Large))
+                    .map(|path| format!("file://{}", path.to_string_lossy()))
             } else if url.starts_with("file://")
                 || url.starts_with("http://")
                 || url.starts_with("https://")
@@ -226,72 +227,22 @@ impl MediaControlsManager {
         self.set_playback(playing, None);
     }
 
+    /// Stopped players show no track
     #[cfg(not(target_os = "android"))]
     pub fn set_stopped(&self) {
+        *self.metadata.write() = CachedMetadata::default();
         if let Some(ref mut controls) = *self.controls.write() {
+            let _ = controls.set_metadata(MediaMetadata::default());
             let _ = controls.set_playback(MediaPlayback::Stopped);
         }
     }
 
     #[cfg(target_os = "android")]
     pub fn set_stopped(&self) {
-        // No-op on Android
+        *self.metadata.write() = CachedMetadata::default();
     }
 
     pub fn get_duration(&self) -> f64 {
         self.metadata.read().duration_secs
     }
 }
-
-#[cfg(not(target_os = "android"))]
-fn save_cover_art_to_temp(cover_data: &[u8], track_id: &str) -> Option<String> {
-    use std::fs;
-
-    let cache_dir = dirs::cache_dir()?.join("sonami").join("covers");
-    fs::create_dir_all(&cache_dir).ok()?;
-
-    let safe_id: String = track_id
-        .chars()
-        .map(|c| {
-            if c.is_alphanumeric() || c == '_' || c == '-' {
-                c
-            } else {
-                '_'
-            }
-        })
-        .take(64)
-        .collect();
-
-    let filename = format!("{}.jpg", safe_id);
-    let cover_path = cache_dir.join(&filename);
-
-    if !cover_path.exists() {
-        let mut file = fs::File::create(&cover_path).ok()?;
-        file.write_all(cover_data).ok()?;
-    }
-
-    Some(format!("file://{}", cover_path.to_string_lossy()))
-}
-
-#[cfg(not(target_os = "android"))]
-fn data_url_to_file_url(data_url: &str, track_id: &str) -> Option<String> {
-    if !data_url.starts_with("data:") {
-        return Some(data_url.to_string());
-    }
-
-    let parts: Vec<&str> = data_url.splitn(2, ',').collect();
-    if parts.len() != 2 {
-        return None;
-    }
-
-    let data_part = parts[1];
-    let is_base64 = parts[0].contains(";base64");
-
-    let bytes = if is_base64 {
-        general_purpose::STANDARD.decode(data_part).ok()?
-    } else {
-        data_part.as_bytes().to_vec()
-    };
-
-    save_cover_art_to_temp(&bytes, track_id)
-}
@@ -70,6 +70,35 @@ pub struct Playlist {
     pub description: Option<String>,
     pub cover_url: Option<String>,
     pub track_count: u32,
+    /// Server-side change marker, when the provider reports one
+    #[serde(default)]
+    pub changed: Option<String>,
+}
+
+/// A track liked (starred or favourited) on a server
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LikedTrack {
+    pub track: Track,
+    /// Unix time it was liked, when the server records it
+    pub liked_at: Option<i64>,
+}
+
+/// An album or artist liked on a server
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LikedItem {
+    pub id: String,
+    /// Album title or artist name
+    pub title: String,
+    /// Album artist, unset for artists
+    pub artist: Option<String>,
+    pub cover_url: Option<String>,
+    pub liked_at: Option<i64>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct LikedItems {
+    pub albums: Vec<LikedItem>,
+    pub artists: Vec<LikedItem>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
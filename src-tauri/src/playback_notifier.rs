@@ -7,6 +7,7 @@
 //! - All playback state changes flow through PlaybackNotifier
 //! - Debounces rapid state changes to prevent flickering
 //! - Ensures consistent state across Discord and MPRIS
+//! - Keeps the listening time of the current track for play history
 //! - Thread-safe with interior mutability
 
 use parking_lot::{Mutex, RwLock};
@@ -16,7 +17,9 @@ use std::thread;
 use std::time::{Duration, Instant};
 
 use crate::discord::{DiscordRpcManager, TrackInfo};
+use crate::history::listening::{ListeningSession, PlayEnd, PlayEvent, PlayRecorder};
 use crate::media_controls::MediaControlsManager;
+use crate::queue::PlayContext;
 
 /// Minimum interval between state updates to prevent flickering
 const DEBOUNCE_INTERVAL: Duration = Duration::from_millis(100);
@@ -48,6 +51,8 @@ pub struct TrackMetadata {
     pub album: String,
     pub duration_secs: f64,
     pub cover_url: Option<String>,
+    /// Queue track id, for play history. Plays without one aren't recorded.
+    pub track_id: Option<String>,
 }
 
 impl TrackMetadata {
@@ -64,8 +69,27 @@ impl TrackMetadata {
             album: album.into(),
             duration_secs,
             cover_url,
+            track_id: None,
         }
     }
+
+    pub fn with_track_id(mut self, track_id: impl Into<String>) -> Self {
+        self.track_id = Some(track_id.into());
+        self
+    }
+}
+
+impl From<&crate::queue::Track> for TrackMetadata {
+    fn from(track: &crate::queue::Track) -> Self {
+        Self::new(
+            &track.title,
+            &track.artist,
+            &track.album,
+            track.duration as f64,
+            track.cover_image.clone(),
+        )
+        .with_track_id(&track.id)
+    }
 }
 
 impl From<&TrackMetadata> for TrackInfo {
@@ -105,6 +129,15 @@ pub struct PlaybackNotifier {
 
     /// Flag indicating if we're currently playing
     is_playing: AtomicBool,
+
+    /// Listening time of the current track
+    session: Mutex<Option<ListeningSession>>,
+
+    /// Where plays are reported once the database is ready
+    recorder: Mutex<Option<PlayRecorder>>,
+
+    /// Context of the current queue, given to each play as it starts
+    context: Mutex<Option<PlayContext>>,
 }
 
 impl PlaybackNotifier {
@@ -122,6 +155,9 @@ impl PlaybackNotifier {
             shutdown: AtomicBool::new(false),
             current_position: AtomicU64::new(0),
             is_playing: AtomicBool::new(false),
+            session: Mutex::new(None),
+            recorder: Mutex::new(None),
+            context: Mutex::new(None),
         });
 
         // Start background position sync thread
@@ -183,6 +219,8 @@ impl PlaybackNotifier {
             position_secs
         );
 
+        self.start_session(&track, true);
+
         let now = Instant::now();
 
         // Update internal state
@@ -235,6 +273,10 @@ impl PlaybackNotifier {
             return;
         };
 
+        if let Some(session) = self.session.lock().as_mut() {
+            session.pause();
+        }
+
         // Update internal state
         {
             let mut state = self.state.write();
@@ -277,6 +319,8 @@ impl PlaybackNotifier {
             return;
         };
 
+        self.resume_session();
+
         let now = Instant::now();
 
         // Update internal state
@@ -366,6 +410,8 @@ impl PlaybackNotifier {
     pub fn notify_stopped(&self) {
         log::info!("[PlaybackNotifier] Stopped");
 
+        self.end_session(PlayEnd::Stopped);
+
         // Update internal state
         {
             let mut state = self.state.write();
@@ -397,6 +443,8 @@ impl PlaybackNotifier {
 
         let is_playing = self.is_playing.load(Ordering::Relaxed);
 
+        self.start_session(&track, is_playing);
+
         // Update internal state
         {
             let mut state = self.state.write();
@@ -440,6 +488,78 @@ impl PlaybackNotifier {
         *self.last_update.lock() = Instant::now();
     }
 
+    /// Notify that the current track played to its end. Called by the audio
+    /// controller before it moves on, so the play isn't taken for a skip.
+    pub fn notify_track_finished(&self) {
+        self.end_session(PlayEnd::Finished);
+    }
+
+    /// Start reporting plays, once play history can record them
+    pub fn attach_recorder(&self, recorder: PlayRecorder) {
+        *self.recorder.lock() = Some(recorder);
+    }
+
+    /// Remember where the queue was started from. Tracks already playing
+    /// keep the context they started with.
+    pub fn set_context(&self, context: Option<PlayContext>) {
+        *self.context.lock() = context;
+    }
+
+    /// Replace the listening session with one for `track`. Whatever was
+    /// still playing was moved on from.
+    fn start_session(&self, track: &TrackMetadata, playing: bool) {
+        self.end_session(PlayEnd::MovedOn);
+
+        let Some(ref track_id) = track.track_id else {
+            return;
+        };
+        let context = self.context.lock().clone();
+        *self.session.lock() = Some(ListeningSession::new(
+            track_id.clone(),
+            track.duration_secs,
+            context,
+        ));
+        if playing {
+            self.resume_session();
+        }
+    }
+
+    fn resume_session(&self) {
+        let started = {
+            let mut session = self.session.lock();
+            match session.as_mut() {
+                Some(session) if session.resume() => {
+                    Some((session.track_id.clone(), session.context.clone()))
+                }
+                _ => None,
+            }
+        };
+        if let Some((track_id, context)) = started {
+            self.report(PlayEvent::Started { track_id, context });
+        }
+    }
+
+    fn end_session(&self, end: PlayEnd) {
+        let Some(mut session) = self.session.lock().take() else {
+            return;
+        };
+        if !session.started() {
+            return;
+        }
+        session.pause();
+        self.report(PlayEvent::Ended {
+            listened_secs: session.listened_secs(),
+            duration_secs: session.duration_secs,
+            end,
+        });
+    }
+
+    fn report(&self, event: PlayEvent) {
+        if let Some(ref recorder) = *self.recorder.lock() {
+            let _ = recorder.send(event);
+        }
+    }
+
     /// Check if we should update (debouncing)
     fn should_update(&self) -> bool {
         let last = self.last_update.lock();
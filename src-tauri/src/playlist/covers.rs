@@ -0,0 +1,366 @@
+use super::manager::PlaylistManager;
+use super::smart::SmartRules;
+use crate::artwork::ArtworkCache;
+use sqlx::Row;
+
+/// Most distinct album covers in a mosaic. With only one, it is used on its
+/// own.
+const MOSAIC_COVERS: usize = 4;
+
+/// Places a cached image can still be referred to from. A replaced mosaic is
+/// only deleted once none of them use it.
+const COVER_REFERENCES: &str = r#"
+    SELECT COUNT(*) FROM (
+        SELECT cover_url as url FROM albums
+        UNION ALL SELECT cover_url FROM artists
+        UNION ALL SELECT cover_url FROM playlists
+        UNION ALL SELECT mosaic_url FROM playlists
+        UNION ALL SELECT cover_url FROM user_favorite_items
+    )
+    WHERE instr(url, ?) > 0
+"#;
+
+impl PlaylistManager {
+    /// Use an image (data URL, file path or web URL) as the playlist cover.
+    /// `None` goes back to the generated mosaic.
+    pub async fn set_playlist_cover(
+        &self,
+        playlist_id: &str,
+        cover: Option<String>,
+    ) -> Result<(), String> {
+        let cache = ArtworkCache::new();
+        let cover_url = match cover {
+            Some(cover) if ArtworkCache::is_reference(&cover) => {
+                Some(cache.cache_url(&cover, None).await?)
+            }
+            // Otherwise a file picked in the cover dialog
+            Some(path) => Some(ArtworkCache::url(&cache.store_file(&path)?, None)),
+            None => None,
+        };
+
+        let old_url: Option<String> =
+            sqlx::query_scalar("SELECT cover_url FROM playlists WHERE id = ?")
+                .bind(playlist_id)
+                .fetch_optional(&self.pool)
+                .await
+                .map_err(|e| e.to_string())?
+                .flatten();
+
+        let result = sqlx::query(
+            "UPDATE playlists SET cover_url = ?, updated_at = CURRENT_TIMESTAMP WHERE id = ?",
+        )
+        .bind(&cover_url)
+        .bind(playlist_id)
+        .execute(&self.pool)
+        .await
+        .map_err(|e| e.to_string())?;
+
+        if result.rows_affected() == 0 {
+            return Err("Playlist not found".to_string());
+        }
+
+        if let Some(old_hash) = old_url.as_deref().and_then(ArtworkCache::hash_from_url) {
+            if cover_url.as_deref().and_then(ArtworkCache::hash_from_url) != Some(old_hash) {
+                self.remove_unused_image(&cache, old_hash).await?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Cached images a playlist's own cover and mosaic point to, read before
+    /// it is deleted so they can be released afterwards
+    pub(super) async fn cover_hashes(&self, playlist_id: &str) -> Result<Vec<String>, String> {
+        let row = sqlx::query("SELECT cover_url, mosaic_url FROM playlists WHERE id = ?")
+            .bind(playlist_id)
+            .fetch_optional(&self.pool)
+            .await
+            .map_err(|e| e.to_string())?;
+        Ok(row
+            .map(|row| {
+                ["cover_url", "mosaic_url"]
+                    .into_iter()
+                    .filter_map(|column| row.try_get::<Option<String>, _>(column).ok().flatten())
+                    .filter_map(|url| ArtworkCache::hash_from_url(&url).map(str::to_string))
+                    .collect()
+            })
+            .unwrap_or_default())
+    }
+
+    pub(super) async fn remove_unused_images(&self, hashes: &[String]) -> Result<(), String> {
+        let cache = ArtworkCache::new();
+        for hash in hashes {
+            self.remove_unused_image(&cache, hash).await?;
+        }
+        Ok(())
+    }
+
+    /// Redraw the mosaic in the background once a playlist's tracks changed,
+    /// so edits don't wait on cover downloads
+    pub(super) fn schedule_cover_refresh(&self, playlist_id: &str) {
+        let manager = self.clone();
+        let playlist_id = playlist_id.to_string();
+        tauri::async_runtime::spawn(async move {
+            if let Err(e) = manager.refresh_cover(&playlist_id).await {
+                log::warn!(
+                    "[Playlists] Failed to refresh the cover of {}: {}",
+                    playlist_id,
+                    e
+                );
+            }
+        });
+    }
+
+    /// Rebuild the mosaic from the first distinct album covers, unless it was
+    /// already made from the same ones
+    pub async fn refresh_cover(&self, playlist_id: &str) -> Result<(), String> {
+        let Some(row) =
+            sqlx::query("SELECT smart_rules, mosaic_url, mosaic_key FROM playlists WHERE id = ?")
+                .bind(playlist_id)
+                .fetch_optional(&self.pool)
+                .await
+                .map_err(|e| e.to_string())?
+        else {
+            return Ok(());
+        };
+        let smart_rules: Option<String> = row.try_get("smart_rules").ok().flatten();
+        let old_url: Option<String> = row.try_get("mosaic_url").ok().flatten();
+        let mosaic_key: Option<String> = row.try_get("mosaic_key").ok().flatten();
+
+        let covers: Vec<String> = match smart_rules {
+            Some(rules) => self
+                .evaluate_smart_rules(&SmartRules::from_json(&rules)?)
+                .await?
+                .into_iter()
+                .filter_map(|track| track.cover_image)
+                .collect(),
+            None => sqlx::query_scalar(
+                r#"
+                SELECT al.cover_url FROM playlist_tracks pt
+                JOIN tracks t ON pt.track_id = t.id
+                JOIN albums al ON t.album_id = al.id
+                WHERE pt.playlist_id = ? AND al.cover_url IS NOT NULL
+                ORDER BY pt.position
+                "#,
+            )
+            .bind(playlist_id)
+            .fetch_all(&self.pool)
+            .await
+            .map_err(|e| e.to_string())?,
+        };
+
+        let mut distinct: Vec<String> = Vec::with_capacity(MOSAIC_COVERS);
+        for cover in covers {
+            if !cover.is_empty() && !distinct.contains(&cover) {
+                distinct.push(cover);
+                if distinct.len() == MOSAIC_COVERS {
+                    break;
+                }
+            }
+        }
+        let key = distinct.join("\n");
+        if mosaic_key.as_deref() == Some(key.as_str()) {
+            return Ok(());
+        }
+
+        let cache = ArtworkCache::new();
+        let mut hashes = Vec::with_capacity(distinct.len());
+        for cover in &distinct {
+            match cache.cache_url(cover, None).await {
+                Ok(url) => hashes.extend(ArtworkCache::hash_from_url(&url).map(str::to_string)),
+                Err(e) => log::warn!("[Playlists] Skipping mosaic cover {}: {}", cover, e),
+            }
+        }
+
+        // Only remember the key when every cover loaded, so failed downloads
+        // are retried on the next refresh
+        let complete = hashes.len() == distinct.len();
+        let mosaic_url = match hashes.len() {
+            0 => None,
+            1 => Some(ArtworkCache::url(&hashes[0], None)),
+            _ => {
+                let cache = cache.clone();
+                let hash = tauri::async_runtime::spawn_blocking(move || cache.mosaic(&hashes))
+                    .await
+                    .map_err(|e| e.to_string())??;
+                Some(ArtworkCache::url(&hash, None))
+            }
+        };
+
+        sqlx::query("UPDATE playlists SET mosaic_url = ?, mosaic_key = ? WHERE id = ?")
+            .bind(&mosaic_url)
+            .bind(complete.then_some(key))
+            .bind(playlist_id)
+            .execute(&self.pool)
+            .await
+            .map_err(|e| e.to_string())?;
+
+        if let Some(old_hash) = old_url.as_deref().and_then(ArtworkCache::hash_from_url) {
+            if mosaic_url.as_deref().and_then(ArtworkCache::hash_from_url) != Some(old_hash) {
+                self.remove_unused_image(&cache, old_hash).await?;
+            }
+        }
+        Ok(())
+    }
+
+    async fn remove_unused_image(&self, cache: &ArtworkCache, hash: &str) -> Result<(), String> {
+        let references: i64 = sqlx::query_scalar(COVER_REFERENCES)
+            .bind(hash)
+            .fetch_one(&self.pool)
+            .await
+            .map_err(|e| e.to_string())?;
+        if references == 0 {
+            cache.remove(hash)?;
+        }
+        Ok(())
+    }
+
+    /// Bring every generated cover up to date, for changes made outside the
+    /// playlist editor such as new album art or a restored backup
+    pub async fn refresh_covers(&self) -> Result<(), String> {
+        let ids: Vec<String> =
+            sqlx::query_scalar("SELECT id FROM playlists WHERE cover_url IS NULL")
+                .fetch_all(&self.pool)
+                .await
+                .map_err(|e| e.to_string())?;
+
+        for id in ids {
+            if let Err(e) = self.refresh_cover(&id).await {
+                log::warn!("[Playlists] Failed to refresh the cover of {}: {}", id, e);
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::DatabaseManager;
+    use sqlx::{Pool, Sqlite};
+
+    fn store_color(cache: &ArtworkCache, rgb: [u8; 3]) -> String {
+        let image = image::RgbImage::from_pixel(8, 8, image::Rgb(rgb));
+        let mut png = std::io::Cursor::new(Vec::new());
+        image.write_to(&mut png, image::ImageFormat::Png).unwrap();
+        ArtworkCache::url(&cache.store(png.get_ref()).unwrap(), None)
+    }
+
+    async fn set_tracks(pool: &Pool<Sqlite>, track_ids: &[&str]) {
+        sqlx::query("DELETE FROM playlist_tracks WHERE playlist_id = 'p'")
+            .execute(pool)
+            .await
+            .unwrap();
+        for (position, track_id) in track_ids.iter().enumerate() {
+            sqlx::query(
+                "INSERT INTO playlist_tracks (id, playlist_id, track_id, position) VALUES (?, 'p', ?, ?)",
+            )
+            .bind(format!("e{}", position))
+            .bind(track_id)
+            .bind(position as i64)
+            .execute(pool)
+            .await
+            .unwrap();
+        }
+    }
+
+    async fn mosaic_hash(pool: &Pool<Sqlite>) -> String {
+        let url: Option<String> =
+            sqlx::query_scalar("SELECT mosaic_url FROM playlists WHERE id = 'p'")
+                .fetch_one(pool)
+                .await
+                .unwrap();
+        ArtworkCache::hash_from_url(&url.unwrap())
+            .unwrap()
+            .to_string()
+    }
+
+    #[tokio::test]
+    async fn mosaics_follow_the_tracks_and_replace_the_old_image() {
+        crate::artwork::init(std::env::temp_dir().join("sonami-artwork-tests"));
+        let cache = ArtworkCache::new();
+        let db = DatabaseManager::in_memory().await;
+        let mut covers = Vec::new();
+        db.seed(&["INSERT INTO artists (id, name) VALUES ('ar', 'Artist')"])
+            .await;
+        for (i, rgb) in [[200, 10, 10], [10, 200, 10], [10, 10, 200]]
+            .iter()
+            .enumerate()
+        {
+            let cover = store_color(&cache, *rgb);
+            sqlx::query(
+                "INSERT INTO albums (id, title, artist_id, cover_url) VALUES (?, ?, 'ar', ?)",
+            )
+            .bind(format!("al{}", i))
+            .bind(format!("Album {}", i))
+            .bind(&cover)
+            .execute(&db.pool)
+            .await
+            .unwrap();
+            sqlx::query(
+                "INSERT INTO tracks (id, title, artist_id, album_id, duration, source_type) VALUES (?, ?, 'ar', ?, 1, 'LOCAL')",
+            )
+            .bind(format!("t{}", i))
+            .bind(format!("Song {}", i))
+            .bind(format!("al{}", i))
+            .execute(&db.pool)
+            .await
+            .unwrap();
+            covers.push(ArtworkCache::hash_from_url(&cover).unwrap().to_string());
+        }
+        db.seed(&["INSERT INTO playlists (id, title) VALUES ('p', 'Mix')"])
+            .await;
+        let manager = PlaylistManager::new(db.pool.clone());
+
+        // Two albums are enough for a grid
+        set_tracks(&db.pool, &["t0", "t1", "t0"]).await;
+        manager.refresh_cover("p").await.unwrap();
+        let two = mosaic_hash(&db.pool).await;
+        assert!(!covers.contains(&two));
+        assert!(cache.read(&two).is_some());
+
+        set_tracks(&db.pool, &["t0", "t1", "t2"]).await;
+        manager.refresh_cover("p").await.unwrap();
+        let three = mosaic_hash(&db.pool).await;
+        assert_ne!(three, two);
+        assert!(cache.read(&two).is_none());
+
+        // A single album shows its own cover, which stays in the cache
+        set_tracks(&db.pool, &["t2"]).await;
+        manager.refresh_cover("p").await.unwrap();
+        assert_eq!(mosaic_hash(&db.pool).await, covers[2]);
+        assert!(cache.read(&three).is_none());
+        assert!(cache.read(&covers[2]).is_some());
+    }
+
+    #[tokio::test]
+    async fn replaced_and_deleted_covers_leave_the_cache() {
+        crate::artwork::init(std::env::temp_dir().join("sonami-artwork-tests"));
+        let cache = ArtworkCache::new();
+        let db = DatabaseManager::in_memory().await;
+        let first = store_color(&cache, [120, 30, 60]);
+        let second = store_color(&cache, [30, 120, 60]);
+        let mosaic = store_color(&cache, [60, 30, 120]);
+        sqlx::query("INSERT INTO playlists (id, title, mosaic_url) VALUES ('p', 'Mix', ?)")
+            .bind(&mosaic)
+            .execute(&db.pool)
+            .await
+            .unwrap();
+        let manager = PlaylistManager::new(db.pool.clone());
+        let hash = |url: &str| ArtworkCache::hash_from_url(url).unwrap().to_string();
+
+        manager
+            .set_playlist_cover("p", Some(first.clone()))
+            .await
+            .unwrap();
+        manager
+            .set_playlist_cover("p", Some(second.clone()))
+            .await
+            .unwrap();
+        assert!(cache.read(&hash(&first)).is_none());
+        assert!(cache.read(&hash(&second)).is_some());
+
+        manager.delete_playlist("p").await.unwrap();
+        assert!(cache.read(&hash(&second)).is_none());
+        assert!(cache.read(&hash(&mosaic)).is_none());
+    }
+}
@@ -0,0 +1,455 @@
+//! Reading and writing M3U/M3U8, PLS and XSPF playlist files, the formats
+//! MPD, foobar2000 and most portable players exchange playlists in.
+
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::{Component, Path, PathBuf};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PlaylistFormat {
+    M3u,
+    M3u8,
+    Pls,
+    Xspf,
+}
+
+impl PlaylistFormat {
+    pub fn from_path(path: &Path) -> Option<Self> {
+        let ext = path.extension()?.to_str()?.to_lowercase();
+        match ext.as_str() {
+            "m3u" => Some(PlaylistFormat::M3u),
+            "m3u8" => Some(PlaylistFormat::M3u8),
+            "pls" => Some(PlaylistFormat::Pls),
+            "xspf" => Some(PlaylistFormat::Xspf),
+            _ => None,
+        }
+    }
+}
+
+/// One line of a playlist file, with whatever the format told about it
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PlaylistEntry {
+    /// Local file, resolved against the playlist's folder when relative
+    pub path: Option<PathBuf>,
+    pub title: Option<String>,
+    pub artist: Option<String>,
+    pub album: Option<String>,
+    /// Seconds
+    pub duration: Option<u64>,
+}
+
+impl PlaylistEntry {
+    /// How the entry is named when it cannot be resolved
+    pub fn describe(&self) -> String {
+        match (&self.artist, &self.title, &self.path) {
+            (Some(artist), Some(title), _) => format!("{} - {}", artist, title),
+            (None, Some(title), _) => title.clone(),
+            (_, None, Some(path)) => path.display().to_string(),
+            _ => "Unknown entry".to_string(),
+        }
+    }
+}
+
+/// Parse a playlist file, choosing the format from its extension
+pub fn read_playlist_file(path: &Path) -> Result<Vec<PlaylistEntry>, String> {
+    let format = PlaylistFormat::from_path(path)
+        .ok_or_else(|| format!("Unsupported playlist file: {}", path.display()))?;
+    let bytes = std::fs::read(path).map_err(|e| e.to_string())?;
+    let text = decode_text(&bytes);
+    let base = path.parent().unwrap_or(Path::new(""));
+
+    match format {
+        PlaylistFormat::M3u | PlaylistFormat::M3u8 => Ok(parse_m3u(&text, base)),
+        PlaylistFormat::Pls => Ok(parse_pls(&text, base)),
+        PlaylistFormat::Xspf => parse_xspf(&text, base),
+    }
+}
+
+/// Render entries in `format`. With `base` set, paths are written relative
+/// to it where possible. Entries without a file are only kept by XSPF, which
+/// players can resolve by title and artist; returns how many were dropped.
+pub fn render_playlist(
+    format: PlaylistFormat,
+    title: &str,
+    entries: &[PlaylistEntry],
+    base: Option<&Path>,
+) -> (String, usize) {
+    let location = |entry: &PlaylistEntry| {
+        entry.path.as_ref().map(|path| match base {
+            Some(base) => relative_path(path, base).unwrap_or_else(|| path.clone()),
+            None => path.clone(),
+        })
+    };
+    let mut skipped = 0;
+    let mut out = String::new();
+
+    match format {
+        PlaylistFormat::M3u | PlaylistFormat::M3u8 => {
+            out.push_str("#EXTM3U\n");
+            for entry in entries {
+                let Some(path) = location(entry) else {
+                    skipped += 1;
+                    continue;
+                };
+                let duration = entry.duration.map(|d| d as i64).unwrap_or(-1);
+                out.push_str(&format!("#EXTINF:{},{}\n", duration, display_name(entry)));
+                out.push_str(&format!("{}\n", path.display()));
+            }
+        }
+        PlaylistFormat::Pls => {
+            out.push_str("[playlist]\n");
+            let mut n = 0;
+            for entry in entries {
+                let Some(path) = location(entry) else {
+                    skipped += 1;
+                    continue;
+                };
+                n += 1;
+                out.push_str(&format!("File{}={}\n", n, path.display()));
+                out.push_str(&format!("Title{}={}\n", n, display_name(entry)));
+                let duration = entry.duration.map(|d| d as i64).unwrap_or(-1);
+                out.push_str(&format!("Length{}={}\n", n, duration));
+            }
+            out.push_str(&format!("NumberOfEntries={}\nVersion=2\n", n));
+        }
+        PlaylistFormat::Xspf => {
+            out.push_str("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+            out.push_str("<playlist version=\"1\" xmlns=\"http://xspf.org/ns/0/\">\n");
+            out.push_str(&format!("  <title>{}</title>\n", escape_xml(title)));
+            out.push_str("  <trackList>\n");
+            for entry in entries {
+                out.push_str("    <track>\n");
+                if let Some(path) = location(entry) {
+                    out.push_str(&format!(
+                        "      <location>{}</location>\n",
+                        escape_xml(&path_to_uri(&path))
+                    ));
+                }
+                for (tag, value) in [
+                    ("title", &entry.title),
+                    ("creator", &entry.artist),
+                    ("album", &entry.album),
+                ] {
+                    if let Some(value) = value {
+                        out.push_str(&format!("      <{0}>{1}</{0}>\n", tag, escape_xml(value)));
+                    }
+                }
+                if let Some(duration) = entry.duration {
+                    out.push_str(&format!("      <duration>{}</duration>\n", duration * 1000));
+                }
+                out.push_str("    </track>\n");
+            }
+            out.push_str("  </trackList>\n</playlist>\n");
+        }
+    }
+
+    (out, skipped)
+}
+
+/// M3U8 is UTF-8 by definition, but plain M3U files are often Latin-1
+fn decode_text(bytes: &[u8]) -> String {
+    let bytes = bytes.strip_prefix(b"\xEF\xBB\xBF").unwrap_or(bytes);
+    match std::str::from_utf8(bytes) {
+        Ok(text) => text.to_string(),
+        Err(_) => bytes.iter().map(|b| *b as char).collect(),
+    }
+}
+
+fn parse_m3u(text: &str, base: &Path) -> Vec<PlaylistEntry> {
+    let mut entries = Vec::new();
+    let mut pending = PlaylistEntry::default();
+
+    for line in text.lines().map(str::trim) {
+        if let Some(info) = line.strip_prefix("#EXTINF:") {
+            // #EXTINF:<seconds>[ attributes],<Artist - Title>
+            let (head, name) = info.split_once(',').unwrap_or((info, ""));
+            let seconds = head.split_whitespace().next().unwrap_or("");
+            pending.duration = seconds
+                .parse::<i64>()
+                .ok()
+                .filter(|d| *d > 0)
+                .map(|d| d as u64);
+            let (artist, title) = split_display_name(name);
+            pending.artist = artist;
+            pending.title = title;
+        } else if line.is_empty() || line.starts_with('#') {
+            continue;
+        } else {
+            let mut entry = std::mem::take(&mut pending);
+            entry.path = resolve_location(line, base, false);
+            entries.push(entry);
+        }
+    }
+    entries
+}
+
+fn parse_pls(text: &str, base: &Path) -> Vec<PlaylistEntry> {
+    let mut entries: BTreeMap<u32, PlaylistEntry> = BTreeMap::new();
+
+    for line in text.lines().map(str::trim) {
+        let Some((key, value)) = line.split_once('=') else {
+            continue;
+        };
+        let key = key.trim().to_lowercase();
+        let value = value.trim();
+        let (field, index) = match key.find(|c: char| c.is_ascii_digit()) {
+            Some(i) => (&key[..i], key[i..].parse::<u32>().ok()),
+            None => continue,
+        };
+        let Some(index) = index else { continue };
+        let entry = entries.entry(index).or_default();
+        match field {
+            "file" => entry.path = resolve_location(value, base, false),
+            "title" => {
+                let (artist, title) = split_display_name(value);
+                entry.artist = artist;
+                entry.title = title;
+            }
+            "length" => {
+                entry.duration = value
+                    .parse::<i64>()
+                    .ok()
+                    .filter(|d| *d > 0)
+                    .map(|d| d as u64)
+            }
+            _ => {}
+        }
+    }
+    entries.into_values().collect()
+}
+
+fn parse_xspf(text: &str, base: &Path) -> Result<Vec<PlaylistEntry>, String> {
+    let doc = roxmltree::Document::parse(text).map_err(|e| format!("Invalid XSPF: {}", e))?;
+    Ok(doc
+        .descendants()
+        .filter(|node| node.has_tag_name("track"))
+        .map(|track| PlaylistEntry {
+            path: xml_element(track, "location").and_then(|l| resolve_location(&l, base, true)),
+            title: xml_element(track, "title"),
+            artist: xml_element(track, "creator"),
+            album: xml_element(track, "album"),
+            duration: xml_element(track, "duration")
+                .and_then(|ms| ms.parse::<u64>().ok())
+                .map(|ms| ms / 1000),
+        })
+        .collect())
+}
+
+/// Text of the first `<tag>` child, with entities and CDATA sections decoded
+fn xml_element(node: roxmltree::Node<'_, '_>, tag: &str) -> Option<String> {
+    let element = node.children().find(|child| child.has_tag_name(tag))?;
+    let value: String = element
+        .children()
+        .filter(|child| child.is_text())
+        .filter_map(|child| child.text())
+        .collect();
+    let value = value.trim();
+    (!value.is_empty()).then(|| value.to_string())
+}
+
+/// A path or `file://` URI, made absolute against the playlist's folder.
+/// XSPF locations are always URIs, so relative ones are percent-decoded too.
+/// Stream URLs have no local file.
+fn resolve_location(location: &str, base: &Path, uri: bool) -> Option<PathBuf> {
+    let path = if let Some(uri) = location.strip_prefix("file://") {
+        let decoded = urlencoding::decode(uri).ok()?.into_owned();
+        // file:///C:/Music -> C:/Music
+        match decoded.strip_prefix('/') {
+            Some(rest) if rest.get(1..2) == Some(":") => PathBuf::from(rest),
+            _ => PathBuf::from(decoded),
+        }
+    } else if location.contains("://") {
+        return None;
+    } else if uri {
+        PathBuf::from(urlencoding::decode(location).ok()?.into_owned())
+    } else {
+        PathBuf::from(location)
+    };
+
+    let path = if path.is_absolute() {
+        path
+    } else {
+        base.join(path)
+    };
+    Some(normalize(&path))
+}
+
+/// Drop `.` and fold `..` without touching the filesystem, so paths compare
+/// equal to the ones the scanner stored
+fn normalize(path: &Path) -> PathBuf {
+    let mut out = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                if !out.pop() {
+                    out.push("..");
+                }
+            }
+            other => out.push(other.as_os_str()),
+        }
+    }
+    out
+}
+
+/// `path` relative to the folder `base`, or `None` when they share no root
+/// (e.g. different Windows drives)
+fn relative_path(path: &Path, base: &Path) -> Option<PathBuf> {
+    let path: Vec<Component> = path.components().collect();
+    let base: Vec<Component> = base.components().collect();
+    let common = path.iter().zip(&base).take_while(|(a, b)| a == b).count();
+    if common == 0 {
+        return None;
+    }
+
+    let mut relative = PathBuf::new();
+    for _ in common..base.len() {
+        relative.push("..");
+    }
+    for component in &path[common..] {
+        relative.push(component.as_os_str());
+    }
+    Some(relative)
+}
+
+fn path_to_uri(path: &Path) -> String {
+    let text = path.to_string_lossy().replace('\\', "/");
+    let encoded = text
+        .split('/')
+        .map(|segment| {
+            // Keep the drive colon of "C:" readable
+            if segment.len() == 2 && segment.ends_with(':') {
+                segment.to_string()
+            } else {
+                urlencoding::encode(segment).into_owned()
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("/");
+
+    if !path.is_absolute() {
+        encoded
+    } else if encoded.starts_with('/') {
+        format!("file://{}", encoded)
+    } else {
+        format!("file:///{}", encoded)
+    }
+}
+
+fn display_name(entry: &PlaylistEntry) -> String {
+    match (&entry.artist, &entry.title) {
+        (Some(artist), Some(title)) => format!("{} - {}", artist, title),
+        (None, Some(title)) => title.clone(),
+        _ => entry
+            .path
+            .as_ref()
+            .and_then(|p| p.file_stem())
+            .map(|s| s.to_string_lossy().into_owned())
+            .unwrap_or_default(),
+    }
+}
+
+/// "Artist - Title" as written by EXTINF and PLS titles
+fn split_display_name(name: &str) -> (Option<String>, Option<String>) {
+    let name = name.trim();
+    if name.is_empty() {
+        return (None, None);
+    }
+    match name.split_once(" - ") {
+        Some((artist, title)) => (
+            Some(artist.trim().to_string()),
+            Some(title.trim().to_string()),
+        ),
+        None => (None, Some(name.to_string())),
+    }
+}
+
+fn escape_xml(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_extended_m3u_and_pls() {
+        let base = Path::new("/music/lists");
+        let m3u = "#EXTM3U\n#EXTINF:215,Daft Punk - One More Time\n../Daft Punk/01.flac\nhttp://radio.example/stream\n";
+        let entries = parse_m3u(m3u, base);
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].artist.as_deref(), Some("Daft Punk"));
+        assert_eq!(entries[0].title.as_deref(), Some("One More Time"));
+        assert_eq!(entries[0].duration, Some(215));
+        assert_eq!(
+            entries[0].path,
+            Some(PathBuf::from("/music/Daft Punk/01.flac"))
+        );
+        assert_eq!(entries[1].path, None);
+
+        let pls =
+            "[playlist]\nFile2=/b.mp3\nFile1=/a.mp3\nTitle1=A\nLength1=-1\nNumberOfEntries=2\n";
+        let entries = parse_pls(pls, base);
+        assert_eq!(entries[0].path, Some(PathBuf::from("/a.mp3")));
+        assert_eq!(entries[0].title.as_deref(), Some("A"));
+        assert_eq!(entries[0].duration, None);
+        assert_eq!(entries[1].path, Some(PathBuf::from("/b.mp3")));
+    }
+
+    #[test]
+    fn xspf_round_trips_with_relative_paths() {
+        let entries = vec![
+            PlaylistEntry {
+                path: Some(PathBuf::from("/music/Rock & Roll/song #1.mp3")),
+                title: Some("Song <1>".to_string()),
+                artist: Some("Band".to_string()),
+                album: None,
+                duration: Some(90),
+            },
+            PlaylistEntry {
+                title: Some("Streamed".to_string()),
+                ..Default::default()
+            },
+        ];
+        let base = Path::new("/music/lists");
+        let (xml, skipped) = render_playlist(PlaylistFormat::Xspf, "Mix", &entries, Some(base));
+        assert_eq!(skipped, 0);
+        assert!(xml.contains("<location>../Rock%20%26%20Roll/song%20%231.mp3</location>"));
+        let mut expected = entries.clone();
+        expected[0].path = Some(PathBuf::from("/music/Rock & Roll/song #1.mp3"));
+        assert_eq!(parse_xspf(&xml, base).unwrap(), expected);
+
+        let (m3u, skipped) = render_playlist(PlaylistFormat::M3u8, "Mix", &entries, None);
+        assert_eq!(skipped, 1);
+        assert!(m3u.contains("#EXTINF:90,Band - Song <1>\n/music/Rock & Roll/song #1.mp3\n"));
+    }
+
+    #[test]
+    fn xspf_reads_cdata_entities_and_attributes() {
+        let xml = r#"<?xml version="1.0" encoding="UTF-8"?>
+<playlist version="1" xmlns="http://xspf.org/ns/0/">
+  <trackList>
+    <!-- <track><title>commented out</title></track> -->
+    <track xml:base="ignored">
+      <title><![CDATA[Rock & <Roll>]]></title>
+      <creator>Simon &#x26; Garfunkel</creator>
+      <location>file:///music/a%20b.flac</location>
+      <duration>61000</duration>
+    </track>
+  </trackList>
+</playlist>"#;
+        let entries = parse_xspf(xml, Path::new("/")).unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].title.as_deref(), Some("Rock & <Roll>"));
+        assert_eq!(entries[0].artist.as_deref(), Some("Simon & Garfunkel"));
+        assert_eq!(entries[0].path, Some(PathBuf::from("/music/a b.flac")));
+        assert_eq!(entries[0].duration, Some(61));
+
+        assert!(parse_xspf("<playlist><track>", Path::new("/")).is_err());
+    }
+}
@@ -0,0 +1,445 @@
+use super::manager::PlaylistManager;
+use super::models::{Playlist, PlaylistFolder, PlaylistFolderNode, PlaylistTree};
+use super::smart::SmartSort;
+use chrono::Utc;
+use sqlx::{Sqlite, Transaction};
+use std::collections::{HashMap, HashSet};
+use uuid::Uuid;
+
+/// Sibling order shared by folders and playlists: manual position, with the
+/// newest first among items that were never moved
+const SIBLING_ORDER: &str = "position, created_at DESC, id";
+
+impl PlaylistManager {
+    pub async fn get_playlist_folders(&self) -> Result<Vec<PlaylistFolder>, String> {
+        sqlx::query_as::<_, PlaylistFolder>(&format!(
+            "SELECT id, name, parent_id, position, CAST(created_at AS TEXT) as created_at FROM playlist_folders ORDER BY {}",
+            SIBLING_ORDER
+        ))
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| e.to_string())
+    }
+
+    pub async fn get_playlist_tree(&self) -> Result<PlaylistTree, String> {
+        let folders = self.get_playlist_folders().await?;
+        let mut playlists = self.get_playlists().await?;
+        // The flat list puts pinned playlists first; folders use sibling order
+        playlists.sort_by(|a, b| {
+            a.position
+                .cmp(&b.position)
+                .then_with(|| b.created_at.cmp(&a.created_at))
+                .then_with(|| a.id.cmp(&b.id))
+        });
+        Ok(build_tree(folders, playlists))
+    }
+
+    pub async fn create_folder(
+        &self,
+        name: String,
+        parent_id: Option<String>,
+    ) -> Result<PlaylistFolder, String> {
+        if let Some(parent) = &parent_id {
+            self.ensure_folder(parent).await?;
+        }
+
+        let id = Uuid::new_v4().to_string();
+        sqlx::query("INSERT INTO playlist_folders (id, name, parent_id) VALUES (?, ?, ?)")
+            .bind(&id)
+            .bind(&name)
+            .bind(&parent_id)
+            .execute(&self.pool)
+            .await
+            .map_err(|e| e.to_string())?;
+
+        sqlx::query_as::<_, PlaylistFolder>(
+            "SELECT id, name, parent_id, position, CAST(created_at AS TEXT) as created_at FROM playlist_folders WHERE id = ?",
+        )
+        .bind(&id)
+        .fetch_one(&self.pool)
+        .await
+        .map_err(|e| e.to_string())
+    }
+
+    pub async fn rename_folder(&self, id: &str, name: &str) -> Result<(), String> {
+        let result = sqlx::query("UPDATE playlist_folders SET name = ? WHERE id = ?")
+            .bind(name)
+            .bind(id)
+            .execute(&self.pool)
+            .await
+            .map_err(|e| e.to_string())?;
+
+        if result.rows_affected() == 0 {
+            return Err("Folder not found".to_string());
+        }
+        Ok(())
+    }
+
+    /// Delete a folder, handing its subfolders and playlists to its parent
+    pub async fn delete_folder(&self, id: &str) -> Result<(), String> {
+        let mut tx = self.pool.begin().await.map_err(|e| e.to_string())?;
+        let parent_id: Option<String> =
+            sqlx::query_scalar("SELECT parent_id FROM playlist_folders WHERE id = ?")
+                .bind(id)
+                .fetch_optional(&mut *tx)
+                .await
+                .map_err(|e| e.to_string())?
+                .ok_or("Folder not found")?;
+
+        sqlx::query("UPDATE playlist_folders SET parent_id = ? WHERE parent_id = ?")
+            .bind(&parent_id)
+            .bind(id)
+            .execute(&mut *tx)
+            .await
+            .map_err(|e| e.to_string())?;
+        sqlx::query("UPDATE playlists SET folder_id = ? WHERE folder_id = ?")
+            .bind(&parent_id)
+            .bind(id)
+            .execute(&mut *tx)
+            .await
+            .map_err(|e| e.to_string())?;
+        sqlx::query("DELETE FROM playlist_folders WHERE id = ?")
+            .bind(id)
+            .execute(&mut *tx)
+            .await
+            .map_err(|e| e.to_string())?;
+
+        for table in [Siblings::Folders, Siblings::Playlists] {
+            let ids = table.ids(&mut tx, parent_id.as_deref()).await?;
+            table.renumber(&mut tx, &ids).await?;
+        }
+        tx.commit().await.map_err(|e| e.to_string())
+    }
+
+    /// Move a folder under `parent_id` (the top level when `None`) at `index`
+    /// among its new siblings, or last when there is no index
+    pub async fn move_folder(
+        &self,
+        id: &str,
+        parent_id: Option<String>,
+        index: Option<usize>,
+    ) -> Result<(), String> {
+        let mut tx = self.pool.begin().await.map_err(|e| e.to_string())?;
+        let old_parent: Option<String> =
+            sqlx::query_scalar("SELECT parent_id FROM playlist_folders WHERE id = ?")
+                .bind(id)
+                .fetch_optional(&mut *tx)
+                .await
+                .map_err(|e| e.to_string())?
+                .ok_or("Folder not found")?;
+
+        // Walk up from the new parent so a folder never ends up inside itself
+        let mut ancestor = parent_id.clone();
+        while let Some(current) = ancestor {
+            if current == id {
+                return Err("A folder can't be moved into itself".to_string());
+            }
+            ancestor = sqlx::query_scalar("SELECT parent_id FROM playlist_folders WHERE id = ?")
+                .bind(&current)
+                .fetch_optional(&mut *tx)
+                .await
+                .map_err(|e| e.to_string())?
+                .ok_or("Folder not found")?;
+        }
+
+        sqlx::query("UPDATE playlist_folders SET parent_id = ? WHERE id = ?")
+            .bind(&parent_id)
+            .bind(id)
+            .execute(&mut *tx)
+            .await
+            .map_err(|e| e.to_string())?;
+
+        Siblings::Folders
+            .place(&mut tx, parent_id.as_deref(), id, index)
+            .await?;
+        if old_parent != parent_id {
+            let ids = Siblings::Folders
+                .ids(&mut tx, old_parent.as_deref())
+                .await?;
+            Siblings::Folders.renumber(&mut tx, &ids).await?;
+        }
+        tx.commit().await.map_err(|e| e.to_string())
+    }
+
+    /// Move a playlist into `folder_id` (the top level when `None`) at `index`
+    /// among the folder's playlists, or last when there is no index
+    pub async fn move_playlist(
+        &self,
+        playlist_id: &str,
+        folder_id: Option<String>,
+        index: Option<usize>,
+    ) -> Result<(), String> {
+        if let Some(folder) = &folder_id {
+            self.ensure_folder(folder).await?;
+        }
+
+        let mut tx = self.pool.begin().await.map_err(|e| e.to_string())?;
+        let old_folder: Option<String> =
+            sqlx::query_scalar("SELECT folder_id FROM playlists WHERE id = ?")
+                .bind(playlist_id)
+                .fetch_optional(&mut *tx)
+                .await
+                .map_err(|e| e.to_string())?
+                .ok_or("Playlist not found")?;
+
+        sqlx::query("UPDATE playlists SET folder_id = ? WHERE id = ?")
+            .bind(&folder_id)
+            .bind(playlist_id)
+            .execute(&mut *tx)
+            .await
+            .map_err(|e| e.to_string())?;
+
+        Siblings::Playlists
+            .place(&mut tx, folder_id.as_deref(), playlist_id, index)
+            .await?;
+        if old_folder != folder_id {
+            let ids = Siblings::Playlists
+                .ids(&mut tx, old_folder.as_deref())
+                .await?;
+            Siblings::Playlists.renumber(&mut tx, &ids).await?;
+        }
+        tx.commit().await.map_err(|e| e.to_string())
+    }
+
+    pub async fn set_playlist_pinned(&self, playlist_id: &str, pinned: bool) -> Result<(), String> {
+        // Re-pinning keeps the original pin time so the pinned order is stable
+        let result = sqlx::query(
+            "UPDATE playlists SET pinned_at = CASE WHEN ? THEN COALESCE(pinned_at, ?) END WHERE id = ?",
+        )
+        .bind(pinned)
+        .bind(Utc::now().timestamp())
+        .bind(playlist_id)
+        .execute(&self.pool)
+        .await
+        .map_err(|e| e.to_string())?;
+
+        if result.rows_affected() == 0 {
+            return Err("Playlist not found".to_string());
+        }
+        Ok(())
+    }
+
+    /// Remember how a playlist's tracks are shown; `None` restores the manual order
+    pub async fn set_playlist_sort(
+        &self,
+        playlist_id: &str,
+        sort: Option<SmartSort>,
+    ) -> Result<(), String> {
+        let sort_order = sort
+            .map(|s| serde_json::to_string(&s))
+            .transpose()
+            .map_err(|e| e.to_string())?;
+        let result = sqlx::query("UPDATE playlists SET sort_order = ? WHERE id = ?")
+            .bind(sort_order)
+            .bind(playlist_id)
+            .execute(&self.pool)
+            .await
+            .map_err(|e| e.to_string())?;
+
+        if result.rows_affected() == 0 {
+            return Err("Playlist not found".to_string());
+        }
+        Ok(())
+    }
+
+    async fn ensure_folder(&self, id: &str) -> Result<(), String> {
+        sqlx::query_scalar::<_, String>("SELECT id FROM playlist_folders WHERE id = ?")
+            .bind(id)
+            .fetch_optional(&self.pool)
+            .await
+            .map_err(|e| e.to_string())?
+            .ok_or("Folder not found")?;
+        Ok(())
+    }
+}
+
+/// The two kinds of sidebar item, each ordered within its parent folder
+#[derive(Clone, Copy)]
+enum Siblings {
+    Folders,
+    Playlists,
+}
+
+impl Siblings {
+    fn table(self) -> (&'static str, &'static str) {
+        match self {
+            Siblings::Folders => ("playlist_folders", "parent_id"),
+            Siblings::Playlists => ("playlists", "folder_id"),
+        }
+    }
+
+    async fn ids(
+        self,
+        tx: &mut Transaction<'_, Sqlite>,
+        parent: Option<&str>,
+    ) -> Result<Vec<String>, String> {
+        let (table, parent_column) = self.table();
+        sqlx::query_scalar(&format!(
+            "SELECT id FROM {} WHERE {} IS ? ORDER BY {}",
+            table, parent_column, SIBLING_ORDER
+        ))
+        .bind(parent)
+        .fetch_all(&mut **tx)
+        .await
+        .map_err(|e| e.to_string())
+    }
+
+    async fn renumber(
+        self,
+        tx: &mut Transaction<'_, Sqlite>,
+        ids: &[String],
+    ) -> Result<(), String> {
+        let (table, _) = self.table();
+        for (position, id) in ids.iter().enumerate() {
+            sqlx::query(&format!(
+                "UPDATE {} SET position = ? WHERE id = ? AND position != ?",
+                table
+            ))
+            .bind(position as i64)
+            .bind(id)
+            .bind(position as i64)
+            .execute(&mut **tx)
+            .await
+            .map_err(|e| e.to_string())?;
+        }
+        Ok(())
+    }
+
+    /// Put `id` at `index` among the children of `parent` and renumber them
+    async fn place(
+        self,
+        tx: &mut Transaction<'_, Sqlite>,
+        parent: Option<&str>,
+        id: &str,
+        index: Option<usize>,
+    ) -> Result<(), String> {
+        let mut ids = self.ids(tx, parent).await?;
+        ids.retain(|other| other != id);
+        let index = index.unwrap_or(ids.len()).min(ids.len());
+        ids.insert(index, id.to_string());
+        self.renumber(tx, &ids).await
+    }
+}
+
+/// Nest folders and playlists, both already in sibling order. Items whose
+/// parent no longer exists land at the top level.
+fn build_tree(folders: Vec<PlaylistFolder>, playlists: Vec<Playlist>) -> PlaylistTree {
+    let known: HashSet<String> = folders.iter().map(|f| f.id.clone()).collect();
+    let parent_of = |id: &Option<String>| id.clone().filter(|id| known.contains(id));
+
+    let mut pinned: Vec<Playlist> = playlists
+        .iter()
+        .filter(|p| p.pinned_at.is_some())
+        .cloned()
+        .collect();
+    pinned.sort_by_key(|p| p.pinned_at);
+
+    let mut child_folders: HashMap<Option<String>, Vec<PlaylistFolder>> = HashMap::new();
+    for folder in folders {
+        child_folders
+            .entry(parent_of(&folder.parent_id))
+            .or_default()
+            .push(folder);
+    }
+    let mut child_playlists: HashMap<Option<String>, Vec<Playlist>> = HashMap::new();
+    for playlist in playlists {
+        child_playlists
+            .entry(parent_of(&playlist.folder_id))
+            .or_default()
+            .push(playlist);
+    }
+
+    fn nest(
+        parent: Option<String>,
+        child_folders: &mut HashMap<Option<String>, Vec<PlaylistFolder>>,
+        child_playlists: &mut HashMap<Option<String>, Vec<Playlist>>,
+    ) -> Vec<PlaylistFolderNode> {
+        child_folders
+            .remove(&parent)
+            .unwrap_or_default()
+            .into_iter()
+            .map(|folder| {
+                let key = Some(folder.id.clone());
+                PlaylistFolderNode {
+                    folders: nest(key.clone(), child_folders, child_playlists),
+                    playlists: child_playlists.remove(&key).unwrap_or_default(),
+                    folder,
+                }
+            })
+            .collect()
+    }
+
+    PlaylistTree {
+        pinned,
+        folders: nest(None, &mut child_folders, &mut child_playlists),
+        playlists: child_playlists.remove(&None).unwrap_or_default(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn folder(id: &str, parent_id: Option<&str>) -> PlaylistFolder {
+        PlaylistFolder {
+            id: id.to_string(),
+            name: id.to_string(),
+            parent_id: parent_id.map(str::to_string),
+            position: 0,
+            created_at: String::new(),
+        }
+    }
+
+    fn playlist(id: &str, folder_id: Option<&str>, pinned_at: Option<i64>) -> Playlist {
+        Playlist {
+            id: id.to_string(),
+            title: id.to_string(),
+            description: None,
+            cover_url: None,
+            created_at: String::new(),
+            updated_at: String::new(),
+            smart_rules: None,
+            allow_duplicates: true,
+            folder_id: folder_id.map(str::to_string),
+            position: 0,
+            pinned_at,
+            sort_order: None,
+        }
+    }
+
+    #[test]
+    fn nests_folders_and_keeps_sibling_order() {
+        let tree = build_tree(
+            vec![
+                folder("rock", None),
+                folder("indie", Some("rock")),
+                folder("jazz", None),
+            ],
+            vec![
+                playlist("a", Some("indie"), None),
+                playlist("b", None, Some(20)),
+                playlist("c", Some("indie"), Some(10)),
+            ],
+        );
+
+        let top: Vec<_> = tree.folders.iter().map(|n| n.folder.id.as_str()).collect();
+        assert_eq!(top, ["rock", "jazz"]);
+        let indie = &tree.folders[0].folders[0];
+        assert_eq!(indie.folder.id, "indie");
+        let ids: Vec<_> = indie.playlists.iter().map(|p| p.id.as_str()).collect();
+        assert_eq!(ids, ["a", "c"]);
+        let pinned: Vec<_> = tree.pinned.iter().map(|p| p.id.as_str()).collect();
+        assert_eq!(pinned, ["c", "b"]);
+        assert_eq!(tree.playlists[0].id, "b");
+    }
+
+    #[test]
+    fn orphans_land_at_the_top_level() {
+        let tree = build_tree(
+            vec![folder("child", Some("gone"))],
+            vec![playlist("p", Some("gone"), None)],
+        );
+        assert_eq!(tree.folders[0].folder.id, "child");
+        assert_eq!(tree.playlists[0].id, "p");
+    }
+}
@@ -1,11 +1,21 @@
-use super::models::{Playlist, PlaylistDetails};
-use crate::library::models::{TrackSource, UnifiedTrack};
+use super::files::{render_playlist, PlaylistEntry, PlaylistFormat};
+use super::models::{Playlist, PlaylistDetails, PlaylistExportReport, PlaylistImportReport};
+use super::smart::{SmartRules, SmartSort, SortField};
+use crate::library::browse::{bind_args, track_select};
+use crate::library::models::{
+    ExtendedTrackInfo, TrackSource, UnifiedTrack, TRACK_METADATA_COLUMNS, TRACK_METADATA_CTES,
+    TRACK_METADATA_JOINS,
+};
+use crate::library::works::{artist_key, title_key, DURATION_TOLERANCE_SECS};
 use chrono::Utc;
-use sqlx::{Pool, Row, Sqlite};
+use sqlx::{Executor, Pool, Row, Sqlite, Transaction};
+use std::collections::HashSet;
+use std::path::Path;
 use uuid::Uuid;
 
+#[derive(Clone)]
 pub struct PlaylistManager {
-    pool: Pool<Sqlite>,
+    pub(super) pool: Pool<Sqlite>,
 }
 
 impl PlaylistManager {
@@ -37,6 +47,130 @@ impl PlaylistManager {
         Ok(playlist)
     }
 
+    pub async fn create_smart_playlist(
+        &self,
+        title: String,
+        description: Option<String>,
+        rules: &SmartRules,
+    ) -> Result<Playlist, String> {
+        // Reject rules that don't compile before they get stored
+        rules.compile(Utc::now().timestamp())?;
+        let id = Uuid::new_v4().to_string();
+
+        sqlx::query(
+            "INSERT INTO playlists (id, title, description, smart_rules) VALUES (?, ?, ?, ?)",
+        )
+        .bind(&id)
+        .bind(&title)
+        .bind(&description)
+        .bind(rules.to_json()?)
+        .execute(&self.pool)
+        .await
+        .map_err(|e| e.to_string())?;
+        self.schedule_cover_refresh(&id);
+
+        sqlx::query_as::<_, Playlist>("SELECT * FROM playlists WHERE id = ?")
+            .bind(&id)
+            .fetch_one(&self.pool)
+            .await
+            .map_err(|e| e.to_string())
+    }
+
+    pub async fn update_smart_rules(&self, id: &str, rules: &SmartRules) -> Result<(), String> {
+        rules.compile(Utc::now().timestamp())?;
+
+        let result = sqlx::query(
+            "UPDATE playlists SET smart_rules = ?, updated_at = CURRENT_TIMESTAMP WHERE id = ? AND smart_rules IS NOT NULL",
+        )
+        .bind(rules.to_json()?)
+        .bind(id)
+        .execute(&self.pool)
+        .await
+        .map_err(|e| e.to_string())?;
+
+        if result.rows_affected() == 0 {
+            return Err("Smart playlist not found".to_string());
+        }
+
+        self.schedule_cover_refresh(id);
+        Ok(())
+    }
+
+    /// Run a rule tree against the library, e.g. to preview it while editing
+    pub async fn evaluate_smart_rules(
+        &self,
+        rules: &SmartRules,
+    ) -> Result<Vec<UnifiedTrack>, String> {
+        let compiled = rules.compile(Utc::now().timestamp())?;
+        let sql = format!(
+            "{} WHERE {} ORDER BY {} LIMIT ?",
+            track_select(),
+            compiled.clause,
+            compiled.order_by
+        );
+
+        // SQLite treats a negative LIMIT as no limit
+        let rows = bind_args(sqlx::query(&sql), &compiled.args)
+            .bind(compiled.limit.map(i64::from).unwrap_or(-1))
+            .fetch_all(&self.pool)
+            .await
+            .map_err(|e| e.to_string())?;
+
+        Ok(rows.iter().map(UnifiedTrack::from_row).collect())
+    }
+
+    /// Freeze a smart playlist into a plain one holding its current tracks
+    pub async fn convert_to_static(&self, id: &str) -> Result<(), String> {
+        let rules: Option<String> =
+            sqlx::query_scalar("SELECT smart_rules FROM playlists WHERE id = ?")
+                .bind(id)
+                .fetch_optional(&self.pool)
+                .await
+                .map_err(|e| e.to_string())?
+                .ok_or("Playlist not found")?;
+        let Some(rules) = rules else {
+            return Err("Playlist is not a smart playlist".to_string());
+        };
+
+        let tracks = self
+            .evaluate_smart_rules(&SmartRules::from_json(&rules)?)
+            .await?;
+        let added_at = Utc::now().timestamp();
+
+        let mut tx = self.pool.begin().await.map_err(|e| e.to_string())?;
+        sqlx::query("DELETE FROM playlist_tracks WHERE playlist_id = ?")
+            .bind(id)
+            .execute(&mut *tx)
+            .await
+            .map_err(|e| e.to_string())?;
+
+        for (position, track) in tracks.iter().enumerate() {
+            sqlx::query(
+                "INSERT INTO playlist_tracks (id, playlist_id, track_id, position, added_at) VALUES (?, ?, ?, ?, ?)",
+            )
+            .bind(Uuid::new_v4().to_string())
+            .bind(id)
+            .bind(&track.id)
+            .bind(position as i64)
+            .bind(added_at)
+            .execute(&mut *tx)
+            .await
+            .map_err(|e| e.to_string())?;
+        }
+
+        sqlx::query(
+            "UPDATE playlists SET smart_rules = NULL, updated_at = CURRENT_TIMESTAMP WHERE id = ?",
+        )
+        .bind(id)
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| e.to_string())?;
+
+        tx.commit().await.map_err(|e| e.to_string())?;
+        self.schedule_cover_refresh(id);
+        Ok(())
+    }
+
     pub async fn get_playlists(&self) -> Result<Vec<Playlist>, String> {
         sqlx::query_as::<_, Playlist>(
             r#"
@@ -44,7 +178,7 @@ impl PlaylistManager {
                 p.id, 
                 p.title, 
                 p.description, 
-                COALESCE(p.cover_url, (
+                COALESCE(p.cover_url, p.mosaic_url, (
                     SELECT GROUP_CONCAT(img, '|') FROM (
                         SELECT DISTINCT al.cover_url as img
                         FROM playlist_tracks pt 
@@ -56,9 +190,17 @@ impl PlaylistManager {
                     )
                 )) as cover_url,
                 p.created_at, 
-                p.updated_at
+                p.updated_at,
+                p.smart_rules,
+                p.allow_duplicates,
+                p.folder_id,
+                p.position,
+                p.pinned_at,
+                p.sort_order,
+                (SELECT provider_id FROM playlist_links WHERE playlist_id = p.id) as linked_provider,
+                p.cover_url IS NOT NULL as custom_cover
             FROM playlists p
-            ORDER BY p.created_at DESC
+            ORDER BY p.pinned_at IS NULL, p.pinned_at, p.position, p.created_at DESC, p.id
             "#,
         )
         .fetch_all(&self.pool)
@@ -73,7 +215,7 @@ impl PlaylistManager {
                 p.id, 
                 p.title, 
                 p.description, 
-                COALESCE(p.cover_url, (
+                COALESCE(p.cover_url, p.mosaic_url, (
                     SELECT GROUP_CONCAT(img, '|') FROM (
                         SELECT DISTINCT al.cover_url as img
                         FROM playlist_tracks pt 
@@ -85,7 +227,15 @@ impl PlaylistManager {
                     )
                 )) as cover_url,
                 p.created_at, 
-                p.updated_at
+                p.updated_at,
+                p.smart_rules,
+                p.allow_duplicates,
+                p.folder_id,
+                p.position,
+                p.pinned_at,
+                p.sort_order,
+                (SELECT provider_id FROM playlist_links WHERE playlist_id = p.id) as linked_provider,
+                p.cover_url IS NOT NULL as custom_cover
             FROM playlists p
             WHERE p.id = ?
             "#,
@@ -95,34 +245,66 @@ impl PlaylistManager {
         .await
         .map_err(|e| e.to_string())?;
 
-        let rows = sqlx::query(
+        let sort = playlist
+            .sort_order
+            .as_deref()
+            .map(serde_json::from_str::<SmartSort>)
+            .transpose()
+            .map_err(|e| format!("Invalid playlist sort: {}", e))?;
+
+        // Smart playlists are re-evaluated on every read
+        if let Some(rules) = &playlist.smart_rules {
+            let mut rules = SmartRules::from_json(rules)?;
+            if sort.is_some() {
+                rules.sort = sort;
+            }
+            let tracks = self.evaluate_smart_rules(&rules).await?;
+            return Ok(PlaylistDetails {
+                playlist,
+                tracks,
+                entry_ids: Vec::new(),
+            });
+        }
+
+        let rows = sqlx::query(&format!(
             r#"
+            WITH {}
             SELECT 
                 t.id, t.title, t.duration, t.source_type, t.file_path,
                 t.play_count, t.skip_count, t.last_played_at, t.added_at as track_added_at, t.audio_quality,
                 t.provider_id, t.external_id, t.artist_id, t.album_id,
+                {},
                 a.name as artist_name,
                 a.provider_id as artist_provider_id,
                 a.external_id as artist_external_id,
                 al.title as album_title, al.cover_url,
                 al.provider_id as album_provider_id,
                 al.external_id as album_external_id,
-                pt.added_at
+                pt.added_at,
+                pt.id as entry_id
             FROM playlist_tracks pt
             JOIN tracks t ON pt.track_id = t.id
             JOIN artists a ON t.artist_id = a.id
             LEFT JOIN albums al ON t.album_id = al.id
+            {}
             WHERE pt.playlist_id = ?
-            ORDER BY pt.position ASC
+            ORDER BY {}pt.position ASC
             "#,
-        )
+            TRACK_METADATA_CTES,
+            TRACK_METADATA_COLUMNS,
+            TRACK_METADATA_JOINS,
+            sort.map(|s| format!("{}, ", s.field.order_by(s.descending)))
+                .unwrap_or_default()
+        ))
         .bind(playlist_id)
         .fetch_all(&self.pool)
         .await
         .map_err(|e| e.to_string())?;
 
         let mut tracks = Vec::new();
+        let mut entry_ids = Vec::new();
         for row in rows {
+            entry_ids.push(row.try_get("entry_id").unwrap_or_default());
             let duration: i64 = row.try_get("duration").unwrap_or(0);
             let provider_id: Option<String> = row.try_get("provider_id").ok();
             let external_id: Option<String> = row.try_get("external_id").ok();
@@ -201,19 +383,25 @@ impl PlaylistManager {
                 added_at,
                 provider_id,
                 external_id,
+                metadata: ExtendedTrackInfo::from_row(&row),
             });
         }
 
-        Ok(PlaylistDetails { playlist, tracks })
+        Ok(PlaylistDetails {
+            playlist,
+            tracks,
+            entry_ids,
+        })
     }
 
     pub async fn delete_playlist(&self, id: &str) -> Result<(), String> {
+        let images = self.cover_hashes(id).await?;
         sqlx::query("DELETE FROM playlists WHERE id = ?")
             .bind(id)
             .execute(&self.pool)
             .await
             .map_err(|e| e.to_string())?;
-        Ok(())
+        self.remove_unused_images(&images).await
     }
 
     pub async fn rename_playlist(&self, id: &str, new_name: &str) -> Result<(), String> {
@@ -234,47 +422,261 @@ impl PlaylistManager {
     }
 
     pub async fn add_track_entry(&self, playlist_id: &str, track_id: &str) -> Result<(), String> {
-        let max_pos: (i64,) = sqlx::query_as(
-            "SELECT COALESCE(MAX(position), -1) FROM playlist_tracks WHERE playlist_id = ?",
-        )
-        .bind(playlist_id)
-        .fetch_one(&self.pool)
-        .await
-        .unwrap_or((-1,));
+        self.insert_track_at(playlist_id, track_id, None)
+            .await
+            .map(|_| ())
+    }
 
-        let new_pos = max_pos.0 + 1;
-        let id = Uuid::new_v4().to_string();
-        let added_at = Utc::now().timestamp();
+    /// Insert a track before `index`, or at the end when there is no index or
+    /// it is past the end. Returns the id of the new entry.
+    pub async fn insert_track_at(
+        &self,
+        playlist_id: &str,
+        track_id: &str,
+        index: Option<usize>,
+    ) -> Result<String, String> {
+        let mut tx = self.pool.begin().await.map_err(|e| e.to_string())?;
+        ensure_static(&mut *tx, playlist_id).await?;
+        let allow_duplicates: bool =
+            sqlx::query_scalar("SELECT allow_duplicates FROM playlists WHERE id = ?")
+                .bind(playlist_id)
+                .fetch_optional(&mut *tx)
+                .await
+                .map_err(|e| e.to_string())?
+                .ok_or("Playlist not found")?;
+
+        let mut entries = load_entries(&mut tx, playlist_id).await?;
+        if !allow_duplicates && entries.iter().any(|(_, track)| track == track_id) {
+            return Err("Track is already in this playlist".to_string());
+        }
 
+        if index.is_some() {
+            ensure_manual_order(&mut tx, playlist_id).await?;
+        }
+
+        let index = index.unwrap_or(entries.len()).min(entries.len());
+        let id = Uuid::new_v4().to_string();
         sqlx::query(
             "INSERT INTO playlist_tracks (id, playlist_id, track_id, position, added_at) VALUES (?, ?, ?, ?, ?)",
         )
-        .bind(id)
+        .bind(&id)
         .bind(playlist_id)
         .bind(track_id)
-        .bind(new_pos)
-        .bind(added_at)
-        .execute(&self.pool)
+        .bind(index as i64)
+        .bind(Utc::now().timestamp())
+        .execute(&mut *tx)
         .await
         .map_err(|e| e.to_string())?;
 
-        Ok(())
+        entries.insert(index, (id.clone(), track_id.to_string()));
+        renumber(&mut tx, &entries).await?;
+        tx.commit().await.map_err(|e| e.to_string())?;
+        self.schedule_cover_refresh(playlist_id);
+        Ok(id)
+    }
+
+    /// Append tracks in one go, skipping ones already present when the
+    /// playlist doesn't allow duplicates. Returns how many were added.
+    pub async fn append_tracks(
+        &self,
+        playlist_id: &str,
+        track_ids: &[String],
+    ) -> Result<usize, String> {
+        let mut tx = self.pool.begin().await.map_err(|e| e.to_string())?;
+        ensure_static(&mut *tx, playlist_id).await?;
+        let allow_duplicates: bool =
+            sqlx::query_scalar("SELECT allow_duplicates FROM playlists WHERE id = ?")
+                .bind(playlist_id)
+                .fetch_optional(&mut *tx)
+                .await
+                .map_err(|e| e.to_string())?
+                .ok_or("Playlist not found")?;
+
+        let entries = load_entries(&mut tx, playlist_id).await?;
+        let mut present: HashSet<&str> = entries.iter().map(|(_, t)| t.as_str()).collect();
+        let now = Utc::now().timestamp();
+        let mut added = 0;
+        for track_id in track_ids {
+            if !present.insert(track_id.as_str()) && !allow_duplicates {
+                continue;
+            }
+            sqlx::query(
+                "INSERT INTO playlist_tracks (id, playlist_id, track_id, position, added_at) VALUES (?, ?, ?, ?, ?)",
+            )
+            .bind(Uuid::new_v4().to_string())
+            .bind(playlist_id)
+            .bind(track_id)
+            .bind((entries.len() + added) as i64)
+            .bind(now)
+            .execute(&mut *tx)
+            .await
+            .map_err(|e| e.to_string())?;
+            added += 1;
+        }
+
+        tx.commit().await.map_err(|e| e.to_string())?;
+        if added > 0 {
+            self.schedule_cover_refresh(playlist_id);
+        }
+        Ok(added)
     }
 
+    /// Remove every occurrence of a track
     pub async fn remove_track_entry(
         &self,
         playlist_id: &str,
         track_id: &str,
     ) -> Result<(), String> {
+        let mut tx = self.pool.begin().await.map_err(|e| e.to_string())?;
+        ensure_static(&mut *tx, playlist_id).await?;
         sqlx::query("DELETE FROM playlist_tracks WHERE playlist_id = ? AND track_id = ?")
             .bind(playlist_id)
             .bind(track_id)
-            .execute(&self.pool)
+            .execute(&mut *tx)
             .await
             .map_err(|e| e.to_string())?;
+
+        let entries = load_entries(&mut tx, playlist_id).await?;
+        renumber(&mut tx, &entries).await?;
+        tx.commit().await.map_err(|e| e.to_string())?;
+        self.schedule_cover_refresh(playlist_id);
+        Ok(())
+    }
+
+    /// Remove a single occurrence, leaving other copies of the track in place
+    pub async fn remove_entry(&self, playlist_id: &str, entry_id: &str) -> Result<(), String> {
+        let mut tx = self.pool.begin().await.map_err(|e| e.to_string())?;
+        ensure_static(&mut *tx, playlist_id).await?;
+        let result = sqlx::query("DELETE FROM playlist_tracks WHERE id = ? AND playlist_id = ?")
+            .bind(entry_id)
+            .bind(playlist_id)
+            .execute(&mut *tx)
+            .await
+            .map_err(|e| e.to_string())?;
+        if result.rows_affected() == 0 {
+            return Err("Playlist entry not found".to_string());
+        }
+
+        let entries = load_entries(&mut tx, playlist_id).await?;
+        renumber(&mut tx, &entries).await?;
+        tx.commit().await.map_err(|e| e.to_string())?;
+        self.schedule_cover_refresh(playlist_id);
+        Ok(())
+    }
+
+    /// Move an entry so it ends up at `index`, clamped to the end of the list
+    pub async fn move_entry(
+        &self,
+        playlist_id: &str,
+        entry_id: &str,
+        index: usize,
+    ) -> Result<(), String> {
+        let mut tx = self.pool.begin().await.map_err(|e| e.to_string())?;
+        ensure_static(&mut *tx, playlist_id).await?;
+        ensure_manual_order(&mut tx, playlist_id).await?;
+        let mut entries = load_entries(&mut tx, playlist_id).await?;
+        let from = entries
+            .iter()
+            .position(|(id, _)| id == entry_id)
+            .ok_or("Playlist entry not found")?;
+
+        let entry = entries.remove(from);
+        entries.insert(index.min(entries.len()), entry);
+        renumber(&mut tx, &entries).await?;
+        tx.commit().await.map_err(|e| e.to_string())?;
+        self.schedule_cover_refresh(playlist_id);
         Ok(())
     }
 
+    /// Switch the duplicate policy. Forbidding duplicates drops every repeat
+    /// after a track's first occurrence; returns how many were removed.
+    pub async fn set_allow_duplicates(
+        &self,
+        playlist_id: &str,
+        allow: bool,
+    ) -> Result<usize, String> {
+        let mut tx = self.pool.begin().await.map_err(|e| e.to_string())?;
+        let result = sqlx::query(
+            "UPDATE playlists SET allow_duplicates = ?, updated_at = CURRENT_TIMESTAMP WHERE id = ?",
+        )
+        .bind(allow)
+        .bind(playlist_id)
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| e.to_string())?;
+        if result.rows_affected() == 0 {
+            return Err("Playlist not found".to_string());
+        }
+
+        let mut removed = 0;
+        if !allow {
+            let entries = load_entries(&mut tx, playlist_id).await?;
+            let mut seen = HashSet::new();
+            let mut kept = Vec::with_capacity(entries.len());
+            for (id, track_id) in entries {
+                if seen.insert(track_id.clone()) {
+                    kept.push((id, track_id));
+                    continue;
+                }
+                sqlx::query("DELETE FROM playlist_tracks WHERE id = ?")
+                    .bind(&id)
+                    .execute(&mut *tx)
+                    .await
+                    .map_err(|e| e.to_string())?;
+                removed += 1;
+            }
+            renumber(&mut tx, &kept).await?;
+        }
+
+        tx.commit().await.map_err(|e| e.to_string())?;
+        self.schedule_cover_refresh(playlist_id);
+        Ok(removed)
+    }
+
+    /// Reorder all entries by a track field. Ties keep their current order.
+    pub async fn sort_entries(
+        &self,
+        playlist_id: &str,
+        field: SortField,
+        descending: bool,
+    ) -> Result<(), String> {
+        let mut tx = self.pool.begin().await.map_err(|e| e.to_string())?;
+        ensure_static(&mut *tx, playlist_id).await?;
+        let rows = sqlx::query(&format!(
+            r#"
+            SELECT pt.id, pt.track_id
+            FROM playlist_tracks pt
+            JOIN tracks t ON pt.track_id = t.id
+            LEFT JOIN artists a ON t.artist_id = a.id
+            LEFT JOIN albums al ON t.album_id = al.id
+            WHERE pt.playlist_id = ?
+            ORDER BY {}, pt.position
+            "#,
+            field.order_by(descending)
+        ))
+        .bind(playlist_id)
+        .fetch_all(&mut *tx)
+        .await
+        .map_err(|e| e.to_string())?;
+
+        let entries: Vec<(String, String)> = rows
+            .iter()
+            .map(|r| (r.get("id"), r.get("track_id")))
+            .collect();
+        renumber(&mut tx, &entries).await?;
+        tx.commit().await.map_err(|e| e.to_string())?;
+        self.schedule_cover_refresh(playlist_id);
+        Ok(())
+    }
+
+    /// Close gaps in `position` left by rows deleted outside the entry operations
+    pub async fn compact_positions(&self, playlist_id: &str) -> Result<(), String> {
+        let mut tx = self.pool.begin().await.map_err(|e| e.to_string())?;
+        let entries = load_entries(&mut tx, playlist_id).await?;
+        renumber(&mut tx, &entries).await?;
+        tx.commit().await.map_err(|e| e.to_string())
+    }
+
     pub async fn find_track_id_by_external_id(
         &self,
         provider_id: &str,
@@ -304,4 +706,411 @@ impl PlaylistManager {
         let playlist_ids = rows.iter().map(|r| r.get("playlist_id")).collect();
         Ok(playlist_ids)
     }
+
+    /// Create a playlist from the entries of a playlist file. Entries match a
+    /// library track by file path, then by title, artist and duration. The
+    /// playlist is only created once every entry could be written.
+    pub async fn import_entries(
+        &self,
+        title: String,
+        entries: &[PlaylistEntry],
+    ) -> Result<PlaylistImportReport, String> {
+        let mut track_ids = Vec::new();
+        let mut unresolved = Vec::new();
+        for entry in entries {
+            match self.resolve_entry(entry).await? {
+                Some(track_id) => track_ids.push(track_id),
+                None => unresolved.push(entry.describe()),
+            }
+        }
+
+        let id = Uuid::new_v4().to_string();
+        let now = Utc::now().timestamp();
+        let mut tx = self.pool.begin().await.map_err(|e| e.to_string())?;
+        sqlx::query("INSERT INTO playlists (id, title) VALUES (?, ?)")
+            .bind(&id)
+            .bind(&title)
+            .execute(&mut *tx)
+            .await
+            .map_err(|e| e.to_string())?;
+        for (position, track_id) in track_ids.iter().enumerate() {
+            sqlx::query(
+                "INSERT INTO playlist_tracks (id, playlist_id, track_id, position, added_at) VALUES (?, ?, ?, ?, ?)",
+            )
+            .bind(Uuid::new_v4().to_string())
+            .bind(&id)
+            .bind(track_id)
+            .bind(position as i64)
+            .bind(now)
+            .execute(&mut *tx)
+            .await
+            .map_err(|e| e.to_string())?;
+        }
+        let playlist = sqlx::query_as::<_, Playlist>("SELECT * FROM playlists WHERE id = ?")
+            .bind(&id)
+            .fetch_one(&mut *tx)
+            .await
+            .map_err(|e| e.to_string())?;
+        tx.commit().await.map_err(|e| e.to_string())?;
+        self.schedule_cover_refresh(&id);
+        let added = track_ids.len();
+
+        log::info!(
+            "Imported playlist '{}': {} tracks, {} unresolved",
+            playlist.title,
+            added,
+            unresolved.len()
+        );
+        Ok(PlaylistImportReport {
+            playlist,
+            added,
+            unresolved,
+        })
+    }
+
+    async fn resolve_entry(&self, entry: &PlaylistEntry) -> Result<Option<String>, String> {
+        if let Some(path) = entry.path.as_ref().and_then(|p| p.to_str()) {
+            let found: Option<String> =
+                sqlx::query_scalar("SELECT id FROM tracks WHERE file_path = ? LIMIT 1")
+                    .bind(path)
+                    .fetch_optional(&self.pool)
+                    .await
+                    .map_err(|e| e.to_string())?;
+            if found.is_some() {
+                return Ok(found);
+            }
+        }
+
+        let (Some(title), Some(artist)) = (&entry.title, &entry.artist) else {
+            return Ok(None);
+        };
+        let duration = entry.duration.map(|d| d as i64);
+
+        // Prefer a playable local copy, then the closest duration
+        sqlx::query_scalar(
+            r#"
+            SELECT t.id FROM tracks t
+            JOIN works w ON w.id = t.work_id
+            WHERE w.title_key = ?1 AND w.artist_key = ?2
+              AND (?3 IS NULL OR t.duration = 0 OR abs(t.duration - ?3) <= ?4)
+            ORDER BY (t.file_path IS NOT NULL AND t.missing_since IS NULL) DESC,
+                     abs(t.duration - COALESCE(?3, t.duration)) ASC
+            LIMIT 1
+            "#,
+        )
+        .bind(title_key(title))
+        .bind(artist_key(artist))
+        .bind(duration)
+        .bind(DURATION_TOLERANCE_SECS)
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(|e| e.to_string())
+    }
+
+    /// Write a playlist to `path`. With `relative` set, file paths are
+    /// written relative to the playlist file's folder where possible.
+    pub async fn export_playlist_file(
+        &self,
+        playlist_id: &str,
+        path: &Path,
+        format: PlaylistFormat,
+        relative: bool,
+    ) -> Result<PlaylistExportReport, String> {
+        let details = self.get_playlist_details(playlist_id).await?;
+        let non_empty = |s: &str| (!s.is_empty()).then(|| s.to_string());
+        let entries: Vec<PlaylistEntry> = details
+            .tracks
+            .iter()
+            .map(|track| PlaylistEntry {
+                path: track
+                    .local_path
+                    .as_deref()
+                    .filter(|p| !p.is_empty())
+                    .map(Into::into),
+                title: non_empty(&track.title),
+                artist: non_empty(&track.artist),
+                album: non_empty(&track.album),
+                duration: (track.duration > 0).then_some(track.duration),
+            })
+            .collect();
+
+        let base = if relative { path.parent() } else { None };
+        let (text, skipped) = render_playlist(format, &details.playlist.title, &entries, base);
+        std::fs::write(path, text).map_err(|e| format!("Failed to write playlist: {}", e))?;
+
+        Ok(PlaylistExportReport {
+            written: entries.len() - skipped,
+            skipped,
+        })
+    }
+}
+
+/// Smart playlists are filled by their rules
+pub(super) async fn ensure_static<'e, E>(executor: E, playlist_id: &str) -> Result<(), String>
+where
+    E: Executor<'e, Database = Sqlite>,
+{
+    let smart: Option<String> =
+        sqlx::query_scalar("SELECT smart_rules FROM playlists WHERE id = ?")
+            .bind(playlist_id)
+            .fetch_optional(executor)
+            .await
+            .map_err(|e| e.to_string())?
+            .flatten();
+    if smart.is_some() {
+        return Err("Smart playlists can't be edited track by track".to_string());
+    }
+    Ok(())
+}
+
+/// Indices from a sorted view don't match stored positions, so positional
+/// edits need the manual order to be showing
+async fn ensure_manual_order(
+    tx: &mut Transaction<'_, Sqlite>,
+    playlist_id: &str,
+) -> Result<(), String> {
+    let sort_order: Option<String> =
+        sqlx::query_scalar("SELECT sort_order FROM playlists WHERE id = ?")
+            .bind(playlist_id)
+            .fetch_optional(&mut **tx)
+            .await
+            .map_err(|e| e.to_string())?
+            .flatten();
+    if sort_order.is_some() {
+        return Err("Switch back to the manual order to move tracks".to_string());
+    }
+    Ok(())
+}
+
+/// Entry ids and their track ids in playlist order
+pub(super) async fn load_entries(
+    tx: &mut Transaction<'_, Sqlite>,
+    playlist_id: &str,
+) -> Result<Vec<(String, String)>, String> {
+    let rows = sqlx::query(
+        "SELECT id, track_id FROM playlist_tracks WHERE playlist_id = ? ORDER BY position, added_at, id",
+    )
+    .bind(playlist_id)
+    .fetch_all(&mut **tx)
+    .await
+    .map_err(|e| e.to_string())?;
+
+    Ok(rows
+        .iter()
+        .map(|r| (r.get("id"), r.get("track_id")))
+        .collect())
+}
+
+/// Rewrite positions as 0..n in the given order, skipping rows already in place
+pub(super) async fn renumber(
+    tx: &mut Transaction<'_, Sqlite>,
+    entries: &[(String, String)],
+) -> Result<(), String> {
+    for (position, (id, _)) in entries.iter().enumerate() {
+        sqlx::query("UPDATE playlist_tracks SET position = ? WHERE id = ? AND position != ?")
+            .bind(position as i64)
+            .bind(id)
+            .bind(position as i64)
+            .execute(&mut **tx)
+            .await
+            .map_err(|e| e.to_string())?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::DatabaseManager;
+    use std::collections::HashMap;
+
+    async fn setup() -> (DatabaseManager, PlaylistManager) {
+        let db = DatabaseManager::in_memory().await;
+        db.seed(&["INSERT INTO artists (id, name) VALUES ('ar', 'Artist')"])
+            .await;
+        for id in ["a", "b", "c", "d"] {
+            sqlx::query(
+                "INSERT INTO tracks (id, title, artist_id, duration, source_type, file_path) VALUES (?, ?, 'ar', 1, 'LOCAL', ?)",
+            )
+            .bind(id)
+            .bind(id)
+            .bind(format!("/music/{}.flac", id))
+            .execute(&db.pool)
+            .await
+            .unwrap();
+        }
+        let manager = PlaylistManager::new(db.pool.clone());
+        (db, manager)
+    }
+
+    async fn tracks(db: &DatabaseManager, playlist_id: &str) -> Vec<String> {
+        sqlx::query_scalar(
+            "SELECT track_id FROM playlist_tracks WHERE playlist_id = ? ORDER BY position",
+        )
+        .bind(playlist_id)
+        .fetch_all(&db.pool)
+        .await
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn imports_resolved_entries_in_file_order() {
+        let (db, manager) = setup().await;
+        let entry = |path: &str| PlaylistEntry {
+            path: Some(path.into()),
+            ..Default::default()
+        };
+        let entries = [
+            entry("/music/b.flac"),
+            entry("/elsewhere/x.flac"),
+            entry("/music/a.flac"),
+            entry("/music/b.flac"),
+        ];
+
+        let report = manager
+            .import_entries("Imported".to_string(), &entries)
+            .await
+            .unwrap();
+        assert_eq!(report.added, 3);
+        assert_eq!(report.unresolved, ["/elsewhere/x.flac"]);
+        assert_eq!(tracks(&db, &report.playlist.id).await, ["b", "a", "b"]);
+    }
+
+    async fn positions(db: &DatabaseManager, playlist_id: &str) -> Vec<i64> {
+        sqlx::query_scalar(
+            "SELECT position FROM playlist_tracks WHERE playlist_id = ? ORDER BY position",
+        )
+        .bind(playlist_id)
+        .fetch_all(&db.pool)
+        .await
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn inserts_and_moves_keep_positions_contiguous() {
+        let (db, manager) = setup().await;
+        let playlist = manager
+            .create_playlist("P".to_string(), None)
+            .await
+            .unwrap();
+        let mut entry_ids = HashMap::new();
+        for track in ["a", "b", "c"] {
+            let entry = manager
+                .insert_track_at(&playlist.id, track, None)
+                .await
+                .unwrap();
+            entry_ids.insert(track, entry);
+        }
+
+        manager
+            .insert_track_at(&playlist.id, "d", Some(1))
+            .await
+            .unwrap();
+        assert_eq!(tracks(&db, &playlist.id).await, ["a", "d", "b", "c"]);
+        manager
+            .insert_track_at(&playlist.id, "a", Some(99))
+            .await
+            .unwrap();
+        assert_eq!(tracks(&db, &playlist.id).await, ["a", "d", "b", "c", "a"]);
+
+        manager
+            .move_entry(&playlist.id, &entry_ids["c"], 0)
+            .await
+            .unwrap();
+        assert_eq!(tracks(&db, &playlist.id).await, ["c", "a", "d", "b", "a"]);
+        manager
+            .move_entry(&playlist.id, &entry_ids["c"], 99)
+            .await
+            .unwrap();
+        assert_eq!(tracks(&db, &playlist.id).await, ["a", "d", "b", "a", "c"]);
+        assert_eq!(positions(&db, &playlist.id).await, [0, 1, 2, 3, 4]);
+
+        assert!(manager
+            .move_entry(&playlist.id, "missing", 0)
+            .await
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn sorting_reorders_and_blocks_positional_edits() {
+        let (db, manager) = setup().await;
+        let playlist = manager
+            .create_playlist("P".to_string(), None)
+            .await
+            .unwrap();
+        for track in ["b", "d", "a", "c"] {
+            manager
+                .insert_track_at(&playlist.id, track, None)
+                .await
+                .unwrap();
+        }
+
+        manager
+            .sort_entries(&playlist.id, SortField::Title, true)
+            .await
+            .unwrap();
+        assert_eq!(tracks(&db, &playlist.id).await, ["d", "c", "b", "a"]);
+        assert_eq!(positions(&db, &playlist.id).await, [0, 1, 2, 3]);
+
+        sqlx::query("UPDATE playlists SET sort_order = 'title' WHERE id = ?")
+            .bind(&playlist.id)
+            .execute(&db.pool)
+            .await
+            .unwrap();
+        assert!(manager
+            .insert_track_at(&playlist.id, "a", Some(0))
+            .await
+            .is_err());
+        manager
+            .insert_track_at(&playlist.id, "a", None)
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn compacting_closes_gaps() {
+        let (db, manager) = setup().await;
+        let playlist = manager
+            .create_playlist("P".to_string(), None)
+            .await
+            .unwrap();
+        for (position, track) in [(5, "a"), (10, "b"), (20, "c")] {
+            sqlx::query(
+                "INSERT INTO playlist_tracks (id, playlist_id, track_id, position) VALUES (?, ?, ?, ?)",
+            )
+            .bind(track)
+            .bind(&playlist.id)
+            .bind(track)
+            .bind(position)
+            .execute(&db.pool)
+            .await
+            .unwrap();
+        }
+
+        manager.compact_positions(&playlist.id).await.unwrap();
+        assert_eq!(tracks(&db, &playlist.id).await, ["a", "b", "c"]);
+        assert_eq!(positions(&db, &playlist.id).await, [0, 1, 2]);
+    }
+
+    #[tokio::test]
+    async fn smart_playlists_reject_entry_edits() {
+        let (_db, manager) = setup().await;
+        let playlist = manager
+            .create_playlist("P".to_string(), None)
+            .await
+            .unwrap();
+        sqlx::query("UPDATE playlists SET smart_rules = '{}' WHERE id = ?")
+            .bind(&playlist.id)
+            .execute(&manager.pool)
+            .await
+            .unwrap();
+        assert!(manager
+            .insert_track_at(&playlist.id, "a", None)
+            .await
+            .is_err());
+        assert!(manager
+            .append_tracks(&playlist.id, &["a".to_string()])
+            .await
+            .is_err());
+    }
 }
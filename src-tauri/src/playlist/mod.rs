@@ -1,5 +1,12 @@
+pub mod covers;
+pub mod files;
+pub mod folders;
 pub mod manager;
 pub mod models;
+pub mod remote;
+pub mod smart;
+pub mod tidal;
 
 pub use manager::PlaylistManager;
 pub use models::{Playlist, PlaylistDetails, PlaylistTrack};
+pub use smart::SmartRules;
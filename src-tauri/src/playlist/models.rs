@@ -5,9 +5,65 @@ pub struct Playlist {
     pub id: String,
     pub title: String,
     pub description: Option<String>,
+    /// The user's chosen cover, else the generated mosaic
     pub cover_url: Option<String>,
     pub created_at: String, // SQLite returns DATETIME as string usually
     pub updated_at: String,
+    /// JSON-encoded `SmartRules`; `None` for a plain track list
+    #[sqlx(default)]
+    #[serde(default)]
+    pub smart_rules: Option<String>,
+    /// Whether the same track may appear more than once
+    pub allow_duplicates: bool,
+    /// Containing folder; `None` at the top level
+    #[sqlx(default)]
+    #[serde(default)]
+    pub folder_id: Option<String>,
+    /// Manual order among the playlists of the same folder
+    #[sqlx(default)]
+    #[serde(default)]
+    pub position: i64,
+    /// Unix time the playlist was pinned
+    #[sqlx(default)]
+    #[serde(default)]
+    pub pinned_at: Option<i64>,
+    /// JSON-encoded `SmartSort` the tracks are shown in; `None` keeps the manual order
+    #[sqlx(default)]
+    #[serde(default)]
+    pub sort_order: Option<String>,
+    /// Provider holding the server copy this playlist syncs with
+    #[sqlx(default)]
+    #[serde(default)]
+    pub linked_provider: Option<String>,
+    /// Whether `cover_url` is a user-chosen image rather than the mosaic
+    #[sqlx(default)]
+    #[serde(default)]
+    pub custom_cover: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+pub struct PlaylistFolder {
+    pub id: String,
+    pub name: String,
+    pub parent_id: Option<String>,
+    pub position: i64,
+    pub created_at: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PlaylistFolderNode {
+    pub folder: PlaylistFolder,
+    pub folders: Vec<PlaylistFolderNode>,
+    pub playlists: Vec<Playlist>,
+}
+
+/// Sidebar layout: pinned playlists, then the folder hierarchy and the
+/// playlists outside any folder. Pinned playlists also stay in their folder.
+#[derive(Debug, Clone, Serialize, Default)]
+pub struct PlaylistTree {
+    pub pinned: Vec<Playlist>,
+    pub folders: Vec<PlaylistFolderNode>,
+    pub playlists: Vec<Playlist>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
@@ -23,4 +79,33 @@ pub struct PlaylistTrack {
 pub struct PlaylistDetails {
     pub playlist: Playlist,
     pub tracks: Vec<crate::library::models::UnifiedTrack>,
+    /// `playlist_tracks` ids in the same order as `tracks`; empty for smart playlists
+    #[serde(default)]
+    pub entry_ids: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PlaylistImportReport {
+    pub playlist: Playlist,
+    pub added: usize,
+    /// Entries that matched no file or library track, as "Artist - Title" or a path
+    pub unresolved: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Default)]
+pub struct PlaylistExportReport {
+    pub written: usize,
+    /// Streaming tracks left out of formats that need a file path
+    pub skipped: usize,
+}
+
+#[derive(Debug, Clone, Serialize, Default)]
+pub struct PlaylistSyncReport {
+    /// Playlists updated from their server copy
+    pub pulled: usize,
+    /// Server playlists updated from local edits
+    pub pushed: usize,
+    /// Playlists edited on both sides since the last sync, then merged
+    pub conflicts: usize,
+    pub failed: Vec<String>,
 }
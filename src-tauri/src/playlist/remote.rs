@@ -0,0 +1,588 @@
+use super::manager::{ensure_static, renumber, PlaylistManager};
+use super::models::{Playlist, PlaylistSyncReport};
+use crate::library::LibraryManager;
+use crate::models::{Playlist as RemotePlaylist, Track};
+use crate::providers::bare_id;
+use crate::providers::traits::MusicProvider;
+use chrono::Utc;
+use sqlx::{Executor, Row, Sqlite};
+use std::collections::{HashMap, VecDeque};
+use std::sync::Arc;
+use uuid::Uuid;
+
+#[derive(sqlx::FromRow)]
+struct PlaylistLink {
+    playlist_id: String,
+    provider_id: String,
+    remote_id: String,
+    snapshot: String,
+    remote_changed: Option<String>,
+}
+
+impl PlaylistManager {
+    /// Copy a server playlist into the library and keep the two linked
+    pub async fn import_remote_playlist(
+        &self,
+        library: &LibraryManager,
+        provider: &dyn MusicProvider,
+        remote_id: &str,
+    ) -> Result<Playlist, String> {
+        let linked: Option<String> = sqlx::query_scalar(
+            "SELECT playlist_id FROM playlist_links WHERE provider_id = ? AND remote_id = ?",
+        )
+        .bind(provider.id())
+        .bind(remote_id)
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(|e| e.to_string())?;
+        if linked.is_some() {
+            return Err("This playlist is already in the library".to_string());
+        }
+
+        let remote = provider
+            .get_playlists()
+            .await
+            .map_err(|e| e.to_string())?
+            .into_iter()
+            .find(|p| p.id == remote_id)
+            .ok_or("Playlist not found on the server")?;
+        let tracks = provider
+            .get_playlist_tracks(remote_id)
+            .await
+            .map_err(|e| e.to_string())?;
+
+        let ids: Vec<String> = tracks
+            .iter()
+            .map(|t| bare_id(provider.id(), &t.id))
+            .collect();
+        let track_ids = self
+            .resolve_remote_tracks(library, provider.id(), &ids, &tracks)
+            .await?;
+
+        let mut playlist = self
+            .create_playlist(remote.title, remote.description)
+            .await?;
+        self.replace_linked_entries(&playlist.id, provider.id(), &track_ids)
+            .await?;
+        insert_link(
+            &self.pool,
+            &playlist.id,
+            provider.id(),
+            remote_id,
+            &ids,
+            remote.changed.as_deref(),
+        )
+        .await?;
+
+        playlist.linked_provider = Some(provider.id().to_string());
+        Ok(playlist)
+    }
+
+    /// Create a server copy of a local playlist and link the two. Only tracks
+    /// from that server are sent.
+    pub async fn publish_playlist(
+        &self,
+        playlist_id: &str,
+        provider: &dyn MusicProvider,
+    ) -> Result<(), String> {
+        ensure_static(&self.pool, playlist_id).await?;
+
+        let title: String = sqlx::query_scalar("SELECT title FROM playlists WHERE id = ?")
+            .bind(playlist_id)
+            .fetch_optional(&self.pool)
+            .await
+            .map_err(|e| e.to_string())?
+            .ok_or("Playlist not found")?;
+        let linked: Option<String> =
+            sqlx::query_scalar("SELECT provider_id FROM playlist_links WHERE playlist_id = ?")
+                .bind(playlist_id)
+                .fetch_optional(&self.pool)
+                .await
+                .map_err(|e| e.to_string())?;
+        if let Some(linked) = linked {
+            return Err(format!("Playlist is already linked to {}", linked));
+        }
+
+        let ids = self.linked_ids(playlist_id, provider.id()).await?;
+        let remote_id = provider
+            .create_playlist(&title, &ids)
+            .await
+            .map_err(|e| e.to_string())?;
+        insert_link(
+            &self.pool,
+            playlist_id,
+            provider.id(),
+            &remote_id,
+            &ids,
+            None,
+        )
+        .await
+    }
+
+    /// Stop syncing a playlist; both copies are kept
+    pub async fn unlink_playlist(&self, playlist_id: &str) -> Result<(), String> {
+        sqlx::query("DELETE FROM playlist_links WHERE playlist_id = ?")
+            .bind(playlist_id)
+            .execute(&self.pool)
+            .await
+            .map_err(|e| e.to_string())?;
+        Ok(())
+    }
+
+    /// Bring every linked playlist in line with its server copy. Server and
+    /// network errors go into the report; only database errors are returned.
+    pub async fn sync_remote_playlists(
+        &self,
+        library: &LibraryManager,
+        providers: &[Arc<dyn MusicProvider>],
+    ) -> Result<PlaylistSyncReport, String> {
+        let links: Vec<PlaylistLink> = sqlx::query_as(
+            "SELECT playlist_id, provider_id, remote_id, snapshot, remote_changed FROM playlist_links",
+        )
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| e.to_string())?;
+
+        let mut report = PlaylistSyncReport::default();
+        for provider in providers.iter().filter(|p| p.supports_playlists()) {
+            let links: Vec<&PlaylistLink> = links
+                .iter()
+                .filter(|link| link.provider_id == provider.id())
+                .collect();
+            if links.is_empty() {
+                continue;
+            }
+
+            let remote = match provider.get_playlists().await {
+                Ok(remote) => remote,
+                Err(e) => {
+                    report.failed.push(format!("{}: {}", provider.name(), e));
+                    continue;
+                }
+            };
+
+            for link in links {
+                match remote.iter().find(|p| p.id == link.remote_id) {
+                    Some(playlist) => {
+                        self.sync_link(library, provider.as_ref(), link, playlist, &mut report)
+                            .await?
+                    }
+                    // A missing listing can be a server hiccup as much as a
+                    // deletion, so the link stays until the user removes it
+                    None => report.failed.push(format!(
+                        "{}: playlist {} not found on the server",
+                        provider.name(),
+                        link.remote_id
+                    )),
+                }
+            }
+        }
+
+        log::info!(
+            "[Playlists] Synced: {} pulled, {} pushed, {} conflicts, {} failed",
+            report.pulled,
+            report.pushed,
+            report.conflicts,
+            report.failed.len()
+        );
+        Ok(report)
+    }
+
+    async fn sync_link(
+        &self,
+        library: &LibraryManager,
+        provider: &dyn MusicProvider,
+        link: &PlaylistLink,
+        remote: &RemotePlaylist,
+        report: &mut PlaylistSyncReport,
+    ) -> Result<(), String> {
+        let snapshot: Vec<String> = serde_json::from_str(&link.snapshot).unwrap_or_default();
+        let local = self.linked_ids(&link.playlist_id, provider.id()).await?;
+
+        // The server's change marker lets untouched playlists skip the track fetch
+        if local == snapshot && remote.changed.is_some() && remote.changed == link.remote_changed {
+            return Ok(());
+        }
+
+        let tracks = match provider.get_playlist_tracks(&link.remote_id).await {
+            Ok(tracks) => tracks,
+            Err(e) => {
+                report.failed.push(format!("{}: {}", remote.title, e));
+                return Ok(());
+            }
+        };
+        let remote_ids: Vec<String> = tracks
+            .iter()
+            .map(|t| bare_id(provider.id(), &t.id))
+            .collect();
+
+        let merged = match (local != snapshot, remote_ids != snapshot) {
+            (true, true) => {
+                report.conflicts += 1;
+                merge_lists(&snapshot, &local, &remote_ids)
+            }
+            (true, false) => local.clone(),
+            (false, _) => remote_ids.clone(),
+        };
+
+        if merged != remote_ids {
+            if let Err(e) = provider.set_playlist_tracks(&link.remote_id, &merged).await {
+                // Leave the snapshot alone so the next sync tries again
+                report.failed.push(format!("{}: {}", remote.title, e));
+                return Ok(());
+            }
+
+            // Only record the merge once the server is known to hold it
+            let pushed = match provider.get_playlist_tracks(&link.remote_id).await {
+                Ok(tracks) => tracks
+                    .iter()
+                    .map(|t| bare_id(provider.id(), &t.id))
+                    .collect::<Vec<_>>(),
+                Err(e) => {
+                    report.failed.push(format!("{}: {}", remote.title, e));
+                    return Ok(());
+                }
+            };
+            if pushed != merged {
+                report
+                    .failed
+                    .push(format!("{}: server did not keep the update", remote.title));
+                return Ok(());
+            }
+            report.pushed += 1;
+        }
+        if merged != local {
+            let track_ids = self
+                .resolve_remote_tracks(library, provider.id(), &merged, &tracks)
+                .await?;
+            self.replace_linked_entries(&link.playlist_id, provider.id(), &track_ids)
+                .await?;
+            report.pulled += 1;
+        }
+
+        sqlx::query(
+            "UPDATE playlist_links SET snapshot = ?, remote_changed = ?, synced_at = ? WHERE playlist_id = ?",
+        )
+        .bind(serde_json::to_string(&merged).map_err(|e| e.to_string())?)
+        .bind(&remote.changed)
+        .bind(Utc::now().timestamp())
+        .bind(&link.playlist_id)
+        .execute(&self.pool)
+        .await
+        .map_err(|e| e.to_string())?;
+        Ok(())
+    }
+
+    /// Server track ids of the playlist's entries from that server, in order
+    async fn linked_ids(
+        &self,
+        playlist_id: &str,
+        provider_id: &str,
+    ) -> Result<Vec<String>, String> {
+        let rows = sqlx::query(
+            r#"
+            SELECT t.external_id FROM playlist_tracks pt
+            JOIN tracks t ON pt.track_id = t.id
+            WHERE pt.playlist_id = ? AND t.provider_id = ? AND t.external_id IS NOT NULL
+            ORDER BY pt.position
+            "#,
+        )
+        .bind(playlist_id)
+        .bind(provider_id)
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| e.to_string())?;
+
+        Ok(rows
+            .iter()
+            .map(|r| bare_id(provider_id, &r.get::<String, _>("external_id")))
+            .collect())
+    }
+
+    /// Library track ids for server track ids, importing tracks new to the library
+    async fn resolve_remote_tracks(
+        &self,
+        library: &LibraryManager,
+        provider_id: &str,
+        ids: &[String],
+        tracks: &[Track],
+    ) -> Result<Vec<String>, String> {
+        let details: HashMap<String, &Track> = tracks
+            .iter()
+            .map(|t| (bare_id(provider_id, &t.id), t))
+            .collect();
+
+        let mut track_ids = Vec::with_capacity(ids.len());
+        for id in ids {
+            // Older imports kept the provider prefix on external ids
+            let existing: Option<String> = sqlx::query_scalar(
+                "SELECT id FROM tracks WHERE provider_id = ? AND external_id IN (?, ?) LIMIT 1",
+            )
+            .bind(provider_id)
+            .bind(id)
+            .bind(format!("{}:{}", provider_id, id))
+            .fetch_optional(&self.pool)
+            .await
+            .map_err(|e| e.to_string())?;
+
+            let track_id = match (existing, details.get(id)) {
+                (Some(track_id), _) => track_id,
+                (None, Some(track)) => {
+                    let track = Track {
+                        id: id.clone(),
+                        ..(*track).clone()
+                    };
+                    library.import_external_track(&track, provider_id).await?
+                }
+                (None, None) => return Err(format!("Track {} not found", id)),
+            };
+            track_ids.push(track_id);
+        }
+        Ok(track_ids)
+    }
+
+    /// Rewrite the entries from the linked server in the given order.
+    /// Entries for tracks the server doesn't have keep their place, and
+    /// entries for tracks still on the server keep their ids.
+    async fn replace_linked_entries(
+        &self,
+        playlist_id: &str,
+        provider_id: &str,
+        track_ids: &[String],
+    ) -> Result<(), String> {
+        let mut tx = self.pool.begin().await.map_err(|e| e.to_string())?;
+        let rows = sqlx::query(
+            r#"
+            SELECT pt.id, pt.track_id,
+                   COALESCE(t.provider_id = ? AND t.external_id IS NOT NULL, 0) as linked
+            FROM playlist_tracks pt
+            LEFT JOIN tracks t ON pt.track_id = t.id
+            WHERE pt.playlist_id = ?
+            ORDER BY pt.position, pt.added_at, pt.id
+            "#,
+        )
+        .bind(provider_id)
+        .bind(playlist_id)
+        .fetch_all(&mut *tx)
+        .await
+        .map_err(|e| e.to_string())?;
+
+        let mut reusable: HashMap<String, VecDeque<String>> = HashMap::new();
+        for row in rows.iter().filter(|r| r.get::<i64, _>("linked") != 0) {
+            reusable
+                .entry(row.get("track_id"))
+                .or_default()
+                .push_back(row.get("id"));
+        }
+
+        let mut fresh = Vec::new();
+        let mut entry_for = |track_id: &String| {
+            let id = match reusable.get_mut(track_id).and_then(|ids| ids.pop_front()) {
+                Some(id) => id,
+                None => {
+                    let id = Uuid::new_v4().to_string();
+                    fresh.push(id.clone());
+                    id
+                }
+            };
+            (id, track_id.clone())
+        };
+
+        // Server tracks fill the slots server tracks held before, in the new order
+        let mut linked = track_ids.iter();
+        let mut entries: Vec<(String, String)> =
+            Vec::with_capacity(rows.len().max(track_ids.len()));
+        for row in &rows {
+            if row.get::<i64, _>("linked") == 0 {
+                entries.push((row.get("id"), row.get("track_id")));
+            } else if let Some(track_id) = linked.next() {
+                entries.push(entry_for(track_id));
+            }
+        }
+        entries.extend(linked.map(&mut entry_for));
+
+        for id in reusable.values().flatten() {
+            sqlx::query("DELETE FROM playlist_tracks WHERE id = ?")
+                .bind(id)
+                .execute(&mut *tx)
+                .await
+                .map_err(|e| e.to_string())?;
+        }
+
+        let now = Utc::now().timestamp();
+        for (position, (id, track_id)) in entries.iter().enumerate() {
+            if !fresh.contains(id) {
+                continue;
+            }
+            sqlx::query(
+                "INSERT INTO playlist_tracks (id, playlist_id, track_id, position, added_at) VALUES (?, ?, ?, ?, ?)",
+            )
+            .bind(id)
+            .bind(playlist_id)
+            .bind(track_id)
+            .bind(position as i64)
+            .bind(now)
+            .execute(&mut *tx)
+            .await
+            .map_err(|e| e.to_string())?;
+        }
+        renumber(&mut tx, &entries).await?;
+
+        tx.commit().await.map_err(|e| e.to_string())?;
+        self.schedule_cover_refresh(playlist_id);
+        Ok(())
+    }
+}
+
+/// Record that a playlist syncs with `remote_id` on a provider
+pub(super) async fn insert_link<'e, E>(
+    executor: E,
+    playlist_id: &str,
+    provider_id: &str,
+    remote_id: &str,
+    snapshot: &[String],
+    remote_changed: Option<&str>,
+) -> Result<(), String>
+where
+    E: Executor<'e, Database = Sqlite>,
+{
+    sqlx::query(
+        r#"
+        INSERT INTO playlist_links (playlist_id, provider_id, remote_id, snapshot, remote_changed, synced_at)
+        VALUES (?, ?, ?, ?, ?, ?)
+        "#,
+    )
+    .bind(playlist_id)
+    .bind(provider_id)
+    .bind(remote_id)
+    .bind(serde_json::to_string(snapshot).map_err(|e| e.to_string())?)
+    .bind(remote_changed)
+    .bind(Utc::now().timestamp())
+    .execute(executor)
+    .await
+    .map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+/// Three-way merge of server track id lists. Keeps the server's order, drops
+/// tracks removed locally and appends tracks added locally, without doubling
+/// up on a track both sides added.
+fn merge_lists(base: &[String], local: &[String], remote: &[String]) -> Vec<String> {
+    fn counts(ids: &[String]) -> HashMap<&str, i64> {
+        let mut counts = HashMap::new();
+        for id in ids {
+            *counts.entry(id.as_str()).or_insert(0) += 1;
+        }
+        counts
+    }
+    let (base_n, local_n, remote_n) = (counts(base), counts(local), counts(remote));
+    let count = |counts: &HashMap<&str, i64>, id: &str| counts.get(id).copied().unwrap_or(0);
+
+    let mut removed: HashMap<&str, i64> = base_n
+        .iter()
+        .map(|(id, n)| (*id, n - count(&local_n, id)))
+        .collect();
+    let mut added: HashMap<&str, i64> = local_n
+        .iter()
+        .map(|(id, n)| {
+            let before = count(&base_n, id);
+            let added_remotely = (count(&remote_n, id) - before).max(0);
+            (*id, n - before - added_remotely)
+        })
+        .collect();
+
+    let mut merged = Vec::with_capacity(remote.len() + local.len());
+    for id in remote {
+        match removed.get_mut(id.as_str()) {
+            Some(n) if *n > 0 => *n -= 1,
+            _ => merged.push(id.clone()),
+        }
+    }
+    for id in local {
+        if let Some(n) = added.get_mut(id.as_str()).filter(|n| **n > 0) {
+            *n -= 1;
+            merged.push(id.clone());
+        }
+    }
+    merged
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::DatabaseManager;
+
+    fn ids(list: &[&str]) -> Vec<String> {
+        list.iter().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn merges_edits_from_both_sides() {
+        let merged = merge_lists(
+            &ids(&["a", "b", "c"]),
+            &ids(&["a", "c", "d"]),
+            &ids(&["c", "a", "b", "e"]),
+        );
+        assert_eq!(merged, ids(&["c", "a", "e", "d"]));
+    }
+
+    #[test]
+    fn track_added_on_both_sides_appears_once() {
+        let merged = merge_lists(&ids(&["a"]), &ids(&["a", "x"]), &ids(&["x", "a"]));
+        assert_eq!(merged, ids(&["x", "a"]));
+    }
+
+    #[tokio::test]
+    async fn linked_entries_keep_local_slots_and_ids() {
+        let db = DatabaseManager::in_memory().await;
+        let manager = PlaylistManager::new(db.pool.clone());
+        db.seed(&["INSERT INTO artists (id, name) VALUES ('ar', 'Artist')"])
+            .await;
+        for (id, provider) in [
+            ("x", "subsonic"),
+            ("y", "subsonic"),
+            ("z", "subsonic"),
+            ("l", "local"),
+        ] {
+            sqlx::query(
+                "INSERT INTO tracks (id, title, artist_id, duration, source_type, provider_id, external_id) VALUES (?, ?, 'ar', 1, 'X', ?, ?)",
+            )
+            .bind(id)
+            .bind(id)
+            .bind(provider)
+            .bind((provider != "local").then(|| id.to_uppercase()))
+            .execute(&db.pool)
+            .await
+            .unwrap();
+        }
+        let playlist = manager
+            .create_playlist("P".to_string(), None)
+            .await
+            .unwrap();
+        let mut entry_ids = HashMap::new();
+        for track in ["x", "l", "y"] {
+            let entry = manager
+                .insert_track_at(&playlist.id, track, None)
+                .await
+                .unwrap();
+            entry_ids.insert(track, entry);
+        }
+
+        manager
+            .replace_linked_entries(&playlist.id, "subsonic", &ids(&["y", "z"]))
+            .await
+            .unwrap();
+
+        let rows: Vec<(String, String)> = sqlx::query_as(
+            "SELECT id, track_id FROM playlist_tracks WHERE playlist_id = ? ORDER BY position",
+        )
+        .bind(&playlist.id)
+        .fetch_all(&db.pool)
+        .await
+        .unwrap();
+        let tracks: Vec<&str> = rows.iter().map(|(_, t)| t.as_str()).collect();
+        assert_eq!(tracks, ["y", "l", "z"]);
+        assert_eq!(rows[0].0, entry_ids["y"]);
+        assert_eq!(rows[1].0, entry_ids["l"]);
+    }
+}
@@ -0,0 +1,500 @@
+use crate::library::browse::Arg;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+/// Definition of a smart playlist, stored as JSON in `playlists.smart_rules`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SmartRules {
+    pub rule: RuleNode,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sort: Option<SmartSort>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub limit: Option<u32>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum RuleNode {
+    Group {
+        #[serde(rename = "match")]
+        combinator: Combinator,
+        rules: Vec<RuleNode>,
+    },
+    Condition {
+        field: RuleField,
+        op: RuleOp,
+        #[serde(default)]
+        value: Value,
+    },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Combinator {
+    All,
+    Any,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RuleField {
+    PlayCount,
+    SkipCount,
+    LastPlayedAt,
+    AddedAt,
+    LikedAt,
+    Provider,
+    Artist,
+    Album,
+    Genre,
+    /// Seconds
+    Duration,
+    Quality,
+    /// Half-stars, 0 to 10; unrated tracks only match `is_not_set`
+    Rating,
+}
+
+/// Timestamps compare against unix seconds, except `in_last`/`not_in_last`
+/// which take a number of days
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RuleOp {
+    Eq,
+    Ne,
+    Gt,
+    Gte,
+    Lt,
+    Lte,
+    /// `[min, max]`, both inclusive
+    Between,
+    Contains,
+    NotContains,
+    StartsWith,
+    In,
+    NotIn,
+    InLast,
+    NotInLast,
+    IsSet,
+    IsNotSet,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SmartSort {
+    pub field: SortField,
+    #[serde(default)]
+    pub descending: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SortField {
+    Title,
+    Artist,
+    Album,
+    Year,
+    PlayCount,
+    SkipCount,
+    LastPlayedAt,
+    AddedAt,
+    LikedAt,
+    Duration,
+    Rating,
+    Random,
+}
+
+/// A rule tree lowered to SQL over `tracks t`, `artists a` and `albums al`
+#[derive(Debug)]
+pub(crate) struct CompiledRules {
+    pub clause: String,
+    pub args: Vec<Arg>,
+    pub order_by: String,
+    pub limit: Option<u32>,
+}
+
+/// When the track, or any other copy of its work, was liked
+const LIKED_AT_EXPR: &str = "(SELECT MAX(f.liked_at) FROM user_favorites f JOIN tracks w ON w.id = f.track_id WHERE w.id = t.id OR w.work_id = t.work_id)";
+
+const QUALITY_RANK_EXPR: &str = "(CASE upper(t.audio_quality) WHEN 'LOW' THEN 0 WHEN 'HIGH' THEN 1 WHEN 'LOSSLESS' THEN 2 WHEN 'HI_RES' THEN 3 WHEN 'HI_RES_LOSSLESS' THEN 3 END)";
+
+const SECONDS_PER_DAY: i64 = 86_400;
+
+enum FieldKind {
+    Number(&'static str),
+    Timestamp(&'static str),
+    Text(&'static str),
+    Quality,
+    /// Fields a track can have several values for, as the rows to search and
+    /// the column holding the value; the condition matches if any row does
+    Multi(&'static str, &'static str),
+}
+
+impl RuleField {
+    fn kind(self) -> FieldKind {
+        match self {
+            RuleField::PlayCount => FieldKind::Number("COALESCE(t.play_count, 0)"),
+            RuleField::SkipCount => FieldKind::Number("COALESCE(t.skip_count, 0)"),
+            RuleField::Duration => FieldKind::Number("t.duration"),
+            RuleField::Rating => FieldKind::Number("t.rating"),
+            RuleField::LastPlayedAt => FieldKind::Timestamp("t.last_played_at"),
+            RuleField::AddedAt => FieldKind::Timestamp("t.added_at"),
+            RuleField::LikedAt => FieldKind::Timestamp(LIKED_AT_EXPR),
+            RuleField::Provider => FieldKind::Text("COALESCE(t.provider_id, lower(t.source_type))"),
+            RuleField::Album => FieldKind::Text("al.title"),
+            RuleField::Quality => FieldKind::Quality,
+            RuleField::Artist => FieldKind::Multi(
+                "artists ar WHERE (ar.id = t.artist_id OR ar.id IN (SELECT ta.artist_id FROM track_artists ta WHERE ta.track_id = t.id))",
+                "ar.name",
+            ),
+            RuleField::Genre => FieldKind::Multi(
+                "track_genres g WHERE g.track_id = t.id",
+                "g.genre",
+            ),
+        }
+    }
+}
+
+impl SortField {
+    /// ORDER BY terms over the `t`/`a`/`al` track, artist and album aliases
+    pub fn order_by(self, descending: bool) -> String {
+        let dir = if descending { "DESC" } else { "ASC" };
+        match self {
+            SortField::Random => "RANDOM()".to_string(),
+            SortField::Title => format!("t.title COLLATE NOCASE {}", dir),
+            SortField::Artist => format!(
+                "a.name COLLATE NOCASE {0}, al.title COLLATE NOCASE {0}, t.disc_number, t.track_number",
+                dir
+            ),
+            SortField::Album => format!(
+                "al.title COLLATE NOCASE {}, t.disc_number, t.track_number",
+                dir
+            ),
+            SortField::Year => format!("t.year {}", dir),
+            SortField::PlayCount => format!("COALESCE(t.play_count, 0) {}", dir),
+            SortField::SkipCount => format!("COALESCE(t.skip_count, 0) {}", dir),
+            SortField::LastPlayedAt => format!("t.last_played_at {}", dir),
+            SortField::AddedAt => format!("t.added_at {}", dir),
+            SortField::LikedAt => format!("{} {}", LIKED_AT_EXPR, dir),
+            SortField::Duration => format!("t.duration {}", dir),
+            SortField::Rating => format!("COALESCE(t.rating, -1) {}", dir),
+        }
+    }
+}
+
+impl SmartRules {
+    pub fn from_json(json: &str) -> Result<Self, String> {
+        serde_json::from_str(json).map_err(|e| format!("Invalid smart playlist rules: {}", e))
+    }
+
+    pub fn to_json(&self) -> Result<String, String> {
+        serde_json::to_string(self).map_err(|e| e.to_string())
+    }
+
+    /// Lower the rules to SQL. `now` is the unix time relative dates count back from.
+    pub(crate) fn compile(&self, now: i64) -> Result<CompiledRules, String> {
+        let mut args = Vec::new();
+        let clause = compile_node(&self.rule, now, &mut args)?;
+
+        let order_by = match &self.sort {
+            Some(sort) => sort.field.order_by(sort.descending),
+            None => "t.added_at DESC".to_string(),
+        };
+
+        Ok(CompiledRules {
+            clause,
+            args,
+            order_by: format!("{}, t.id", order_by),
+            limit: self.limit,
+        })
+    }
+}
+
+fn compile_node(node: &RuleNode, now: i64, args: &mut Vec<Arg>) -> Result<String, String> {
+    match node {
+        RuleNode::Group { combinator, rules } => {
+            if rules.is_empty() {
+                // An empty "all" matches everything, an empty "any" nothing
+                return Ok(match combinator {
+                    Combinator::All => "1".to_string(),
+                    Combinator::Any => "0".to_string(),
+                });
+            }
+            let joiner = match combinator {
+                Combinator::All => " AND ",
+                Combinator::Any => " OR ",
+            };
+            let parts = rules
+                .iter()
+                .map(|rule| compile_node(rule, now, args))
+                .collect::<Result<Vec<_>, _>>()?;
+            Ok(format!("({})", parts.join(joiner)))
+        }
+        RuleNode::Condition { field, op, value } => {
+            compile_condition(*field, *op, value, now, args)
+        }
+    }
+}
+
+fn compile_condition(
+    field: RuleField,
+    op: RuleOp,
+    value: &Value,
+    now: i64,
+    args: &mut Vec<Arg>,
+) -> Result<String, String> {
+    let unsupported = || {
+        Err(format!(
+            "Operator {:?} is not supported for {:?}",
+            op, field
+        ))
+    };
+
+    match field.kind() {
+        FieldKind::Number(expr) => match op {
+            RuleOp::InLast | RuleOp::NotInLast => unsupported(),
+            _ => compare_number(expr, op, value, args).unwrap_or_else(unsupported),
+        },
+        FieldKind::Timestamp(expr) => match op {
+            RuleOp::InLast => {
+                args.push(Arg::Int(now - int_value(value)? * SECONDS_PER_DAY));
+                Ok(format!("{} >= ?", expr))
+            }
+            RuleOp::NotInLast => {
+                args.push(Arg::Int(now - int_value(value)? * SECONDS_PER_DAY));
+                Ok(format!("({0} IS NULL OR {0} < ?)", expr))
+            }
+            _ => compare_number(expr, op, value, args).unwrap_or_else(unsupported),
+        },
+        FieldKind::Text(expr) => compare_text(expr, op, value, args).unwrap_or_else(unsupported),
+        FieldKind::Quality => match op {
+            RuleOp::Gt | RuleOp::Gte | RuleOp::Lt | RuleOp::Lte => {
+                let rank = quality_rank(&text_value(value)?)?;
+                args.push(Arg::Int(rank));
+                Ok(format!("{} {} ?", QUALITY_RANK_EXPR, comparison(op)))
+            }
+            _ => compare_text("t.audio_quality", op, value, args).unwrap_or_else(unsupported),
+        },
+        FieldKind::Multi(rows, column) => {
+            // Negative operators mean "no value matches", not "some value differs"
+            let (positive, negated) = match op {
+                RuleOp::Ne => (RuleOp::Eq, true),
+                RuleOp::NotContains => (RuleOp::Contains, true),
+                RuleOp::NotIn => (RuleOp::In, true),
+                RuleOp::IsNotSet => (RuleOp::IsSet, true),
+                other => (other, false),
+            };
+            let inner = match compare_text(column, positive, value, args) {
+                Some(inner) => inner?,
+                None => return unsupported(),
+            };
+            Ok(format!(
+                "{}EXISTS (SELECT 1 FROM {} AND {})",
+                if negated { "NOT " } else { "" },
+                rows,
+                inner
+            ))
+        }
+    }
+}
+
+fn comparison(op: RuleOp) -> &'static str {
+    match op {
+        RuleOp::Gt => ">",
+        RuleOp::Gte => ">=",
+        RuleOp::Lt => "<",
+        RuleOp::Lte => "<=",
+        RuleOp::Ne => "<>",
+        _ => "=",
+    }
+}
+
+/// `None` if the operator makes no sense for numbers
+fn compare_number(
+    expr: &str,
+    op: RuleOp,
+    value: &Value,
+    args: &mut Vec<Arg>,
+) -> Option<Result<String, String>> {
+    let sql = match op {
+        RuleOp::Eq | RuleOp::Gt | RuleOp::Gte | RuleOp::Lt | RuleOp::Lte => {
+            int_value(value).map(|v| {
+                args.push(Arg::Int(v));
+                format!("{} {} ?", expr, comparison(op))
+            })
+        }
+        RuleOp::Ne => int_value(value).map(|v| {
+            args.push(Arg::Int(v));
+            format!("({0} IS NULL OR {0} <> ?)", expr)
+        }),
+        RuleOp::Between => match value.as_array().map(|v| v.as_slice()) {
+            Some([min, max]) => int_value(min).and_then(|min| {
+                let max = int_value(max)?;
+                args.push(Arg::Int(min));
+                args.push(Arg::Int(max));
+                Ok(format!("{} BETWEEN ? AND ?", expr))
+            }),
+            _ => Err("between expects [min, max]".to_string()),
+        },
+        RuleOp::IsSet => Ok(format!("{} IS NOT NULL", expr)),
+        RuleOp::IsNotSet => Ok(format!("{} IS NULL", expr)),
+        _ => return None,
+    };
+    Some(sql)
+}
+
+/// Case-insensitive text comparisons. `None` if the operator makes no sense for text.
+fn compare_text(
+    expr: &str,
+    op: RuleOp,
+    value: &Value,
+    args: &mut Vec<Arg>,
+) -> Option<Result<String, String>> {
+    let sql = match op {
+        RuleOp::Eq => text_value(value).map(|v| {
+            args.push(Arg::Text(v));
+            format!("{} = ? COLLATE NOCASE", expr)
+        }),
+        RuleOp::Ne => text_value(value).map(|v| {
+            args.push(Arg::Text(v));
+            format!("({0} IS NULL OR {0} <> ? COLLATE NOCASE)", expr)
+        }),
+        RuleOp::Contains => text_value(value).map(|v| {
+            args.push(Arg::Text(format!("%{}%", escape_like(&v))));
+            format!("{} LIKE ? ESCAPE '\\'", expr)
+        }),
+        RuleOp::NotContains => text_value(value).map(|v| {
+            args.push(Arg::Text(format!("%{}%", escape_like(&v))));
+            format!("({0} IS NULL OR {0} NOT LIKE ? ESCAPE '\\')", expr)
+        }),
+        RuleOp::StartsWith => text_value(value).map(|v| {
+            args.push(Arg::Text(format!("{}%", escape_like(&v))));
+            format!("{} LIKE ? ESCAPE '\\'", expr)
+        }),
+        RuleOp::In | RuleOp::NotIn => list_value(value).map(|values| {
+            let placeholders = vec!["?"; values.len()].join(", ");
+            args.extend(values.into_iter().map(Arg::Text));
+            if op == RuleOp::In {
+                format!("{} COLLATE NOCASE IN ({})", expr, placeholders)
+            } else {
+                format!(
+                    "({0} IS NULL OR {0} COLLATE NOCASE NOT IN ({1}))",
+                    expr, placeholders
+                )
+            }
+        }),
+        RuleOp::IsSet => Ok(format!("({0} IS NOT NULL AND {0} <> '')", expr)),
+        RuleOp::IsNotSet => Ok(format!("({0} IS NULL OR {0} = '')", expr)),
+        _ => return None,
+    };
+    Some(sql)
+}
+
+fn escape_like(s: &str) -> String {
+    s.replace('\\', "\\\\")
+        .replace('%', "\\%")
+        .replace('_', "\\_")
+}
+
+fn int_value(value: &Value) -> Result<i64, String> {
+    match value {
+        Value::Number(n) => n
+            .as_i64()
+            .or_else(|| n.as_f64().map(|f| f as i64))
+            .ok_or_else(|| format!("Expected an integer, got {}", n)),
+        Value::String(s) => s
+            .trim()
+            .parse()
+            .map_err(|_| format!("Expected an integer, got \"{}\"", s)),
+        other => Err(format!("Expected an integer, got {}", other)),
+    }
+}
+
+fn text_value(value: &Value) -> Result<String, String> {
+    match value {
+        Value::String(s) => Ok(s.clone()),
+        Value::Number(n) => Ok(n.to_string()),
+        other => Err(format!("Expected text, got {}", other)),
+    }
+}
+
+fn list_value(value: &Value) -> Result<Vec<String>, String> {
+    let values = match value {
+        Value::Array(items) => items
+            .iter()
+            .map(text_value)
+            .collect::<Result<Vec<_>, _>>()?,
+        other => vec![text_value(other)?],
+    };
+    if values.is_empty() {
+        return Err("Expected at least one value".to_string());
+    }
+    Ok(values)
+}
+
+fn quality_rank(quality: &str) -> Result<i64, String> {
+    match quality.to_uppercase().as_str() {
+        "LOW" => Ok(0),
+        "HIGH" => Ok(1),
+        "LOSSLESS" => Ok(2),
+        "HI_RES" | "HI_RES_LOSSLESS" => Ok(3),
+        other => Err(format!("Unknown quality: {}", other)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn compiles_nested_groups_in_placeholder_order() {
+        let rules = SmartRules::from_json(
+            r#"{
+                "rule": {"type": "group", "match": "all", "rules": [
+                    {"type": "condition", "field": "play_count", "op": "gte", "value": 5},
+                    {"type": "group", "match": "any", "rules": [
+                        {"type": "condition", "field": "genre", "op": "eq", "value": "Jazz"},
+                        {"type": "condition", "field": "last_played_at", "op": "in_last", "value": 7}
+                    ]}
+                ]},
+                "sort": {"field": "play_count", "descending": true},
+                "limit": 50
+            }"#,
+        )
+        .unwrap();
+
+        let compiled = rules.compile(1_000_000).unwrap();
+        assert!(compiled
+            .clause
+            .starts_with("(COALESCE(t.play_count, 0) >= ? AND (EXISTS"));
+        assert_eq!(
+            compiled.args,
+            vec![
+                Arg::Int(5),
+                Arg::Text("Jazz".to_string()),
+                Arg::Int(1_000_000 - 7 * SECONDS_PER_DAY)
+            ]
+        );
+        assert_eq!(compiled.order_by, "COALESCE(t.play_count, 0) DESC, t.id");
+        assert_eq!(compiled.limit, Some(50));
+    }
+
+    #[test]
+    fn negated_multi_value_fields_use_not_exists() {
+        let rules = SmartRules::from_json(
+            r#"{"rule": {"type": "condition", "field": "artist", "op": "not_contains", "value": "50%"}}"#,
+        )
+        .unwrap();
+
+        let compiled = rules.compile(0).unwrap();
+        assert!(compiled.clause.starts_with("NOT EXISTS"));
+        assert_eq!(compiled.args, vec![Arg::Text("%50\\%%".to_string())]);
+    }
+
+    #[test]
+    fn rejects_operators_that_do_not_fit_the_field() {
+        let rules = SmartRules::from_json(
+            r#"{"rule": {"type": "condition", "field": "album", "op": "in_last", "value": 3}}"#,
+        )
+        .unwrap();
+        assert!(rules.compile(0).is_err());
+    }
+}
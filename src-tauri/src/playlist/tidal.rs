@@ -0,0 +1,277 @@
+use super::manager::PlaylistManager;
+use super::models::Playlist;
+use super::remote::insert_link;
+use crate::library::LibraryManager;
+use crate::tidal::{get_cover_url, CoverSize, TidalClient, Track};
+use chrono::Utc;
+use sqlx::Row;
+use std::collections::HashSet;
+use uuid::Uuid;
+
+/// Tidal sources are stored as playlist links under this provider id. Tidal
+/// doesn't support playlist sync, so these links are only ever pulled from.
+const TIDAL: &str = "tidal";
+
+#[derive(Debug, Clone, PartialEq)]
+enum TidalSource {
+    Playlist(String),
+    Album(u64),
+}
+
+struct SourceContents {
+    title: String,
+    description: Option<String>,
+    /// Cover for tracks that don't carry their album's
+    cover: Option<String>,
+    tracks: Vec<Track>,
+}
+
+impl TidalSource {
+    /// Accepts share links such as `https://tidal.com/browse/playlist/<uuid>`
+    /// or `tidal://album/<id>`, bare ids, and the `album:<id>` form kept in
+    /// playlist links
+    fn parse(input: &str) -> Option<Self> {
+        let input = input.trim();
+        let input = input.split(['?', '#']).next().unwrap_or(input);
+
+        let segments: Vec<&str> = input.split(['/', ':']).collect();
+        for pair in segments.windows(2) {
+            match pair {
+                ["playlist", id] => return Self::playlist(id),
+                ["album", id] => return id.parse().ok().map(TidalSource::Album),
+                _ => {}
+            }
+        }
+
+        if let Ok(id) = input.parse() {
+            return Some(TidalSource::Album(id));
+        }
+        Self::playlist(input)
+    }
+
+    fn playlist(id: &str) -> Option<Self> {
+        Uuid::parse_str(id)
+            .ok()
+            .map(|_| TidalSource::Playlist(id.to_lowercase()))
+    }
+
+    fn remote_id(&self) -> String {
+        match self {
+            TidalSource::Playlist(id) => format!("playlist:{}", id),
+            TidalSource::Album(id) => format!("album:{}", id),
+        }
+    }
+
+    async fn fetch(&self, client: &TidalClient) -> Result<SourceContents, String> {
+        match self {
+            TidalSource::Playlist(id) => {
+                let playlist = client.get_playlist(id).await.map_err(|e| e.to_string())?;
+                let tracks = client
+                    .get_playlist_tracks(id)
+                    .await
+                    .map_err(|e| e.to_string())?;
+                Ok(SourceContents {
+                    title: playlist.title,
+                    description: playlist.description.filter(|d| !d.is_empty()),
+                    cover: None,
+                    tracks,
+                })
+            }
+            TidalSource::Album(id) => {
+                let album = client.get_album(*id).await.map_err(|e| e.to_string())?;
+                let tracks = client
+                    .get_album_tracks(*id)
+                    .await
+                    .map_err(|e| e.to_string())?;
+                Ok(SourceContents {
+                    title: album.title,
+                    description: album.artist.map(|a| a.name),
+                    cover: album.cover,
+                    tracks,
+                })
+            }
+        }
+    }
+}
+
+impl PlaylistManager {
+    /// Create a local playlist from a Tidal playlist or album link, keeping
+    /// the link so tracks added upstream can be picked up later
+    pub async fn import_tidal_source(
+        &self,
+        library: &LibraryManager,
+        client: &TidalClient,
+        url: &str,
+    ) -> Result<Playlist, String> {
+        let source = TidalSource::parse(url).ok_or("Not a Tidal playlist or album link")?;
+        let remote_id = source.remote_id();
+
+        let linked: Option<String> = sqlx::query_scalar(
+            "SELECT playlist_id FROM playlist_links WHERE provider_id = ? AND remote_id = ?",
+        )
+        .bind(TIDAL)
+        .bind(&remote_id)
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(|e| e.to_string())?;
+        if linked.is_some() {
+            return Err("This playlist is already in the library".to_string());
+        }
+
+        let contents = source.fetch(client).await?;
+        let track_ids = import_tracks(library, &contents.tracks, contents.cover.as_deref()).await?;
+
+        // The playlist, its tracks and the link appear together or not at all
+        let id = Uuid::new_v4().to_string();
+        let now = Utc::now().timestamp();
+        let mut tx = self.pool.begin().await.map_err(|e| e.to_string())?;
+        sqlx::query("INSERT INTO playlists (id, title, description) VALUES (?, ?, ?)")
+            .bind(&id)
+            .bind(&contents.title)
+            .bind(&contents.description)
+            .execute(&mut *tx)
+            .await
+            .map_err(|e| e.to_string())?;
+        for (position, track_id) in track_ids.iter().enumerate() {
+            sqlx::query(
+                "INSERT INTO playlist_tracks (id, playlist_id, track_id, position, added_at) VALUES (?, ?, ?, ?, ?)",
+            )
+            .bind(Uuid::new_v4().to_string())
+            .bind(&id)
+            .bind(track_id)
+            .bind(position as i64)
+            .bind(now)
+            .execute(&mut *tx)
+            .await
+            .map_err(|e| e.to_string())?;
+        }
+        insert_link(
+            &mut *tx,
+            &id,
+            TIDAL,
+            &remote_id,
+            &tidal_ids(&contents.tracks),
+            None,
+        )
+        .await?;
+        let mut playlist = sqlx::query_as::<_, Playlist>("SELECT * FROM playlists WHERE id = ?")
+            .bind(&id)
+            .fetch_one(&mut *tx)
+            .await
+            .map_err(|e| e.to_string())?;
+        tx.commit().await.map_err(|e| e.to_string())?;
+        self.schedule_cover_refresh(&id);
+
+        playlist.linked_provider = Some(TIDAL.to_string());
+        Ok(playlist)
+    }
+
+    /// Append the tracks added to the Tidal source since the last import or
+    /// re-sync. Tracks removed upstream or locally are left as they are.
+    /// Returns how many were added.
+    pub async fn resync_tidal_playlist(
+        &self,
+        library: &LibraryManager,
+        client: &TidalClient,
+        playlist_id: &str,
+    ) -> Result<usize, String> {
+        let row = sqlx::query(
+            "SELECT remote_id, snapshot FROM playlist_links WHERE playlist_id = ? AND provider_id = ?",
+        )
+        .bind(playlist_id)
+        .bind(TIDAL)
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(|e| e.to_string())?
+        .ok_or("Playlist wasn't imported from Tidal")?;
+        let remote_id: String = row.get("remote_id");
+        let snapshot: Vec<String> =
+            serde_json::from_str(&row.get::<String, _>("snapshot")).unwrap_or_default();
+
+        let source = TidalSource::parse(&remote_id)
+            .ok_or_else(|| format!("Unknown Tidal source {}", remote_id))?;
+        let contents = source.fetch(client).await?;
+
+        let known: HashSet<&str> = snapshot.iter().map(String::as_str).collect();
+        let new_tracks: Vec<Track> = contents
+            .tracks
+            .iter()
+            .filter(|t| !known.contains(t.id.to_string().as_str()))
+            .cloned()
+            .collect();
+        let track_ids = import_tracks(library, &new_tracks, contents.cover.as_deref()).await?;
+        let added = self.append_tracks(playlist_id, &track_ids).await?;
+
+        let snapshot =
+            serde_json::to_string(&tidal_ids(&contents.tracks)).map_err(|e| e.to_string())?;
+        sqlx::query("UPDATE playlist_links SET snapshot = ?, synced_at = ? WHERE playlist_id = ?")
+            .bind(snapshot)
+            .bind(Utc::now().timestamp())
+            .bind(playlist_id)
+            .execute(&self.pool)
+            .await
+            .map_err(|e| e.to_string())?;
+
+        log::info!(
+            "[Playlists] Re-synced {} from Tidal: {} new tracks",
+            playlist_id,
+            added
+        );
+        Ok(added)
+    }
+}
+
+/// Register Tidal tracks in the library, returning their library ids in order
+async fn import_tracks(
+    library: &LibraryManager,
+    tracks: &[Track],
+    fallback_cover: Option<&str>,
+) -> Result<Vec<String>, String> {
+    let mut track_ids = Vec::with_capacity(tracks.len());
+    for track in tracks {
+        let cover_url = track
+            .album
+            .as_ref()
+            .and_then(|a| a.cover.as_deref())
+            .or(track.cover.as_deref())
+            .or(fallback_cover)
+            .map(|c| get_cover_url(c, CoverSize::Large.px()));
+        track_ids.push(library.import_tidal_track(track, cover_url).await?);
+    }
+    Ok(track_ids)
+}
+
+fn tidal_ids(tracks: &[Track]) -> Vec<String> {
+    tracks.iter().map(|t| t.id.to_string()).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_links_and_ids() {
+        let uuid = "36ea71a8-445e-41a4-82ab-6628c581535d";
+        let playlist = Some(TidalSource::Playlist(uuid.to_string()));
+
+        assert_eq!(
+            TidalSource::parse(&format!("https://tidal.com/browse/playlist/{}?u", uuid)),
+            playlist
+        );
+        assert_eq!(TidalSource::parse(&format!("playlist:{}", uuid)), playlist);
+        assert_eq!(TidalSource::parse(uuid), playlist);
+        assert_eq!(
+            TidalSource::parse("https://listen.tidal.com/album/77646169/track/77646170"),
+            Some(TidalSource::Album(77646169))
+        );
+        assert_eq!(
+            TidalSource::parse("tidal://album/77646169"),
+            Some(TidalSource::Album(77646169))
+        );
+        assert_eq!(
+            TidalSource::parse("album:42").unwrap().remote_id(),
+            "album:42"
+        );
+        assert_eq!(TidalSource::parse("https://tidal.com/browse/track/1"), None);
+    }
+}
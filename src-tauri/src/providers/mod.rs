@@ -3,3 +3,23 @@ pub mod traits;
 pub mod types;
 
 pub use manager::ProviderManagerArc as ProviderManager;
+
+/// Strip the `provider:` prefix some ids carry, as stored in the library
+pub fn bare_id(provider_id: &str, id: &str) -> String {
+    id.strip_prefix(provider_id)
+        .and_then(|rest| rest.strip_prefix(':'))
+        .unwrap_or(id)
+        .to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn strips_only_its_own_prefix() {
+        assert_eq!(bare_id("subsonic", "subsonic:42"), "42");
+        assert_eq!(bare_id("subsonic", "42"), "42");
+        assert_eq!(bare_id("subsonic", "jellyfin:42"), "jellyfin:42");
+    }
+}
@@ -1,7 +1,10 @@
-use crate::models::{Album, Artist, Quality, SearchResults, StreamInfo, Track};
+use crate::models::{
+    Album, Artist, LikedItems, LikedTrack, Playlist, Quality, SearchResults, StreamInfo, Track,
+};
 use anyhow::Result;
 use async_trait::async_trait;
 use serde_json::Value;
+use std::collections::HashMap;
 
 #[async_trait]
 pub trait MusicProvider: Send + Sync {
@@ -35,6 +38,79 @@ pub trait MusicProvider: Send + Sync {
     async fn get_album_tracks(&self, _album_id: &str) -> Result<Vec<Track>> {
         Err(anyhow::anyhow!("Not implemented"))
     }
+
+    /// Number of rating steps the server stores (5 for whole stars, 10 for
+    /// half-stars), or `None` when it has no ratings
+    fn rating_scale(&self) -> Option<u8> {
+        None
+    }
+
+    /// Track rating in half-stars, 0 to 10
+    async fn get_rating(&self, _track_id: &str) -> Result<Option<u8>> {
+        Err(anyhow::anyhow!("Not implemented"))
+    }
+
+    /// Ratings in half-stars of every track on an album, keyed by track id,
+    /// so a library can be synced one album per request
+    async fn get_album_ratings(&self, _album_id: &str) -> Result<HashMap<String, Option<u8>>> {
+        Err(anyhow::anyhow!("Not implemented"))
+    }
+
+    /// Set a rating in half-stars, rounded up to the server's scale; `None` clears it
+    async fn set_rating(&self, _track_id: &str, _rating: Option<u8>) -> Result<()> {
+        Err(anyhow::anyhow!("Not implemented"))
+    }
+
+    /// Whether the server stores playlists that can be read and edited
+    fn supports_playlists(&self) -> bool {
+        false
+    }
+
+    /// Playlists the signed-in user can edit
+    async fn get_playlists(&self) -> Result<Vec<Playlist>> {
+        Err(anyhow::anyhow!("Not implemented"))
+    }
+
+    async fn get_playlist_tracks(&self, _playlist_id: &str) -> Result<Vec<Track>> {
+        Err(anyhow::anyhow!("Not implemented"))
+    }
+
+    /// Create a playlist holding `track_ids` in order; returns its id
+    async fn create_playlist(&self, _name: &str, _track_ids: &[String]) -> Result<String> {
+        Err(anyhow::anyhow!("Not implemented"))
+    }
+
+    /// Replace a playlist's tracks with `track_ids`, in order
+    async fn set_playlist_tracks(&self, _playlist_id: &str, _track_ids: &[String]) -> Result<()> {
+        Err(anyhow::anyhow!("Not implemented"))
+    }
+
+    /// Whether the server keeps a list of liked tracks
+    fn supports_likes(&self) -> bool {
+        false
+    }
+
+    /// Every track liked on the server
+    async fn get_liked_tracks(&self) -> Result<Vec<LikedTrack>> {
+        Err(anyhow::anyhow!("Not implemented"))
+    }
+
+    async fn set_track_liked(&self, _track_id: &str, _liked: bool) -> Result<()> {
+        Err(anyhow::anyhow!("Not implemented"))
+    }
+
+    /// Every album and artist liked on the server
+    async fn get_liked_items(&self) -> Result<LikedItems> {
+        Err(anyhow::anyhow!("Not implemented"))
+    }
+
+    async fn set_album_liked(&self, _album_id: &str, _liked: bool) -> Result<()> {
+        Err(anyhow::anyhow!("Not implemented"))
+    }
+
+    async fn set_artist_liked(&self, _artist_id: &str, _liked: bool) -> Result<()> {
+        Err(anyhow::anyhow!("Not implemented"))
+    }
 }
 
 #[async_trait]
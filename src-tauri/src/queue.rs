@@ -10,6 +10,91 @@ pub enum RepeatMode {
     One,
 }
 
+/// One-shot trigger that ends playback early instead of advancing
+#[derive(Clone, PartialEq, Eq, Debug, Default, Serialize, Deserialize)]
+#[serde(tag = "type", content = "track_id", rename_all = "snake_case")]
+pub enum StopAfter {
+    #[default]
+    Off,
+    CurrentTrack,
+    /// Stop once the track with this id has finished playing
+    Track(String),
+}
+
+/// What happens when the queue runs out or a stop-after trigger fires
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum EndAction {
+    #[default]
+    Stop,
+    /// Cue the next track (if any) but leave it paused
+    Pause,
+    Quit,
+}
+
+impl EndAction {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            EndAction::Stop => "stop",
+            EndAction::Pause => "pause",
+            EndAction::Quit => "quit",
+        }
+    }
+}
+
+/// Stop settings as shown to the frontend, sent with `stop-state-changed`
+#[derive(Clone, Debug, Serialize)]
+pub struct StopState {
+    pub stop_after: StopAfter,
+    pub end_action: EndAction,
+    pub loop_manual_queue: bool,
+}
+
+impl std::str::FromStr for EndAction {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "stop" => Ok(EndAction::Stop),
+            "pause" => Ok(EndAction::Pause),
+            "quit" => Ok(EndAction::Quit),
+            _ => Err(format!("Invalid end action: {}", s)),
+        }
+    }
+}
+
+/// Where the current queue was started from (used for history and "jump back in")
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ContextType {
+    Album,
+    Playlist,
+    Artist,
+    Search,
+    Recommendation,
+}
+
+impl ContextType {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ContextType::Album => "album",
+            ContextType::Playlist => "playlist",
+            ContextType::Artist => "artist",
+            ContextType::Search => "search",
+            ContextType::Recommendation => "recommendation",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PlayContext {
+    pub context_type: ContextType,
+    /// Identifier of the source, e.g. a playlist id, "tidal:123" for an album or the search query
+    pub uri: String,
+    /// Display name shown in "jump back in"
+    pub name: Option<String>,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Track {
     pub id: String,
@@ -32,6 +117,12 @@ pub struct PlayQueue {
     queue: VecDeque<Track>,
     pub shuffle: bool,
     pub repeat: RepeatMode,
+    pub context: Option<PlayContext>,
+    pub stop_after: StopAfter,
+    pub end_action: EndAction,
+    /// Re-append manually queued tracks after they play so the manual queue repeats
+    pub loop_manual_queue: bool,
+    now_playing: Option<Track>,
 }
 
 impl Default for PlayQueue {
@@ -49,11 +140,17 @@ impl PlayQueue {
             queue: VecDeque::new(),
             shuffle: false,
             repeat: RepeatMode::Off,
+            context: None,
+            stop_after: StopAfter::Off,
+            end_action: EndAction::Stop,
+            loop_manual_queue: false,
+            now_playing: None,
         }
     }
 
-    pub fn set_tracks(&mut self, tracks: Vec<Track>) {
+    pub fn set_tracks(&mut self, tracks: Vec<Track>, context: Option<PlayContext>) {
         self.tracks = tracks;
+        self.context = context;
         if self.shuffle {
             self.reshuffle();
         }
@@ -110,7 +207,18 @@ impl PlayQueue {
     }
 
     pub fn get_next_track(&mut self, manual_skip: bool) -> Option<Track> {
+        let track = self.advance(manual_skip);
+        if track.is_some() {
+            self.now_playing = track.clone();
+        }
+        track
+    }
+
+    fn advance(&mut self, manual_skip: bool) -> Option<Track> {
         if let Some(track) = self.queue.pop_front() {
+            if self.loop_manual_queue {
+                self.queue.push_back(track.clone());
+            }
             return Some(track);
         }
 
@@ -170,7 +278,11 @@ impl PlayQueue {
         };
 
         self.current_index = Some(prev_idx);
-        self.get_track_at(prev_idx)
+        let track = self.get_track_at(prev_idx);
+        if track.is_some() {
+            self.now_playing = track.clone();
+        }
+        track
     }
 
     fn get_track_at(&self, index: usize) -> Option<Track> {
@@ -224,6 +336,127 @@ impl PlayQueue {
             } else {
                 self.current_index = Some(index);
             }
+            self.now_playing = self.tracks.get(index).cloned();
         }
     }
+
+    /// Whether playback should end once the currently playing track finishes
+    pub fn stop_pending(&self) -> bool {
+        match &self.stop_after {
+            StopAfter::Off => false,
+            StopAfter::CurrentTrack => true,
+            StopAfter::Track(id) => self
+                .now_playing
+                .as_ref()
+                .map(|t| &t.id == id)
+                .unwrap_or(false),
+        }
+    }
+
+    /// Go back to the start of the queue once it has run out, so there is
+    /// something to resume from
+    pub fn rewind(&mut self) -> Option<Track> {
+        if self.tracks.is_empty() {
+            return None;
+        }
+        self.current_index = Some(0);
+        self.now_playing = self.get_track_at(0);
+        self.now_playing.clone()
+    }
+
+    pub fn stop_state(&self) -> StopState {
+        StopState {
+            stop_after: self.stop_after.clone(),
+            end_action: self.end_action,
+            loop_manual_queue: self.loop_manual_queue,
+        }
+    }
+
+    /// Nothing is playing once playback stops, so a stop-after trigger for
+    /// the last track can't fire when it is played again later
+    pub fn clear_now_playing(&mut self) {
+        self.now_playing = None;
+    }
+
+    /// Consume the stop-after trigger if it applies to the track that just finished
+    pub fn take_stop_trigger(&mut self) -> bool {
+        let pending = self.stop_pending();
+        if pending {
+            self.stop_after = StopAfter::Off;
+        }
+        pending
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn track(id: &str) -> Track {
+        Track {
+            id: id.to_string(),
+            title: id.to_string(),
+            artist: String::new(),
+            artist_id: None,
+            album: String::new(),
+            album_id: None,
+            duration: 0,
+            cover_image: None,
+            path: format!("/music/{}.flac", id),
+            provider_id: Some("local".to_string()),
+            external_id: None,
+        }
+    }
+
+    #[test]
+    fn test_stop_after_specific_track() {
+        let mut q = PlayQueue::new();
+        q.set_tracks(vec![track("a"), track("b"), track("c")], None);
+        q.play_track_by_path("/music/a.flac");
+        q.stop_after = StopAfter::Track("b".to_string());
+
+        assert!(!q.take_stop_trigger());
+        assert_eq!(q.get_next_track(false).unwrap().id, "b");
+        assert!(q.take_stop_trigger());
+        assert_eq!(q.stop_after, StopAfter::Off);
+    }
+
+    #[test]
+    fn test_stop_clears_now_playing() {
+        let mut q = PlayQueue::new();
+        q.set_tracks(vec![track("a"), track("b")], None);
+        q.play_track_by_path("/music/a.flac");
+        q.stop_after = StopAfter::Track("a".to_string());
+        assert!(q.stop_pending());
+
+        q.clear_now_playing();
+        assert!(!q.stop_pending());
+        assert_eq!(q.stop_state().stop_after, StopAfter::Track("a".to_string()));
+    }
+
+    #[test]
+    fn test_loop_manual_queue() {
+        let mut q = PlayQueue::new();
+        q.loop_manual_queue = true;
+        q.add_to_queue(track("x"));
+        q.add_to_queue(track("y"));
+
+        let ids: Vec<String> = (0..4)
+            .filter_map(|_| q.get_next_track(false))
+            .map(|t| t.id)
+            .collect();
+        assert_eq!(ids, vec!["x", "y", "x", "y"]);
+    }
+
+    #[test]
+    fn test_rewind_after_queue_end() {
+        let mut q = PlayQueue::new();
+        q.set_tracks(vec![track("a"), track("b")], None);
+        q.play_track_by_path("/music/b.flac");
+        assert!(q.get_next_track(false).is_none());
+
+        assert_eq!(q.rewind().unwrap().id, "a");
+        assert_eq!(q.get_current_track().unwrap().id, "a");
+        assert_eq!(q.get_next_track(false).unwrap().id, "b");
+    }
 }
@@ -15,6 +15,47 @@ pub fn is_kana_char(c: char) -> bool {
     matches!(c, '\u{3040}'..='\u{309F}' | '\u{30A0}'..='\u{30FF}')
 }
 
+const HALF_WIDTH_KATAKANA: &str = "ｦｧｨｩｪｫｬｭｮｯｰｱｲｳｴｵｶｷｸｹｺｻｼｽｾｿﾀﾁﾂﾃﾄﾅﾆﾇﾈﾉﾊﾋﾌﾍﾎﾏﾐﾑﾒﾓﾔﾕﾖﾗﾘﾙﾚﾛﾜﾝ";
+const FULL_WIDTH_KATAKANA: &str = "ヲァィゥェォャュョッーアイウエオカキクケコサシスセソタチツテトナニヌネノハヒフヘホマミムメモヤユヨラリルレロワン";
+
+/// Fold full-width ASCII and half-width katakana to their usual widths, then
+/// katakana to hiragana, so a word compares equal however it was typed
+pub fn fold_japanese(text: &str) -> String {
+    let mut out: Vec<char> = Vec::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '\u{FF01}'..='\u{FF5E}' => out.push(char::from_u32(c as u32 - 0xFEE0).unwrap_or(c)),
+            '\u{3000}' => out.push(' '),
+            // Half-width (semi-)voiced marks combine with the preceding kana
+            '\u{FF9E}' | '\u{FF9F}' => {
+                let voiced = c == '\u{FF9E}';
+                if let Some(prev) = out.pop() {
+                    let combined = match prev {
+                        'ウ' if voiced => Some('ヴ'),
+                        'カ'..='ト' if voiced => char::from_u32(prev as u32 + 1),
+                        'ハ' | 'ヒ' | 'フ' | 'ヘ' | 'ホ' => {
+                            char::from_u32(prev as u32 + if voiced { 1 } else { 2 })
+                        }
+                        _ => None,
+                    };
+                    out.push(combined.unwrap_or(prev));
+                }
+            }
+            _ => match HALF_WIDTH_KATAKANA.chars().position(|h| h == c) {
+                Some(i) => out.extend(FULL_WIDTH_KATAKANA.chars().nth(i)),
+                None => out.push(c),
+            },
+        }
+    }
+
+    out.into_iter()
+        .map(|c| match c {
+            '\u{30A1}'..='\u{30F6}' => char::from_u32(c as u32 - 0x60).unwrap_or(c),
+            _ => c,
+        })
+        .collect()
+}
+
 pub fn romanize_japanese(text: &str) -> Option<String> {
     if !contains_japanese(text) {
         return None;
@@ -100,6 +141,14 @@ mod tests {
         assert!(s.contains("wa-rudo"));
     }
 
+    #[test]
+    fn test_fold_japanese() {
+        assert_eq!(fold_japanese("ヨアソビ"), "よあそび");
+        assert_eq!(fold_japanese("ﾖｱｿﾋﾞ"), "よあそび");
+        assert_eq!(fold_japanese("ﾊﾟﾌﾟﾘｶ"), "ぱぷりか");
+        assert_eq!(fold_japanese("ＹＯＡＳＯＢＩ　2"), "YOASOBI 2");
+    }
+
     #[test]
     fn test_no_japanese() {
         assert_eq!(romanize_japanese("Hello World"), None);
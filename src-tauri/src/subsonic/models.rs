@@ -69,6 +69,11 @@ pub struct SubsonicSong {
     pub size: Option<u64>,
     #[serde(default, deserialize_with = "deserialize_option_u32_from_any")]
     pub disc_number: Option<u32>,
+    /// 1 to 5 stars; absent when unrated
+    #[serde(default, deserialize_with = "deserialize_option_u32_from_any")]
+    pub user_rating: Option<u32>,
+    /// ISO 8601 time the song was starred; absent when it isn't
+    pub starred: Option<String>,
 }
 
 #[derive(Debug, Clone, Deserialize)]
@@ -88,6 +93,8 @@ pub struct SubsonicAlbum {
     #[serde(default, deserialize_with = "deserialize_option_u32_from_any")]
     pub year: Option<u32>,
     pub genre: Option<String>,
+    #[serde(default)]
+    pub starred: Option<String>,
 }
 
 #[derive(Debug, Clone, Deserialize)]
@@ -99,6 +106,8 @@ pub struct SubsonicArtist {
     pub cover_art: Option<String>,
     #[serde(default, deserialize_with = "deserialize_option_u32_from_any")]
     pub album_count: Option<u32>,
+    #[serde(default)]
+    pub starred: Option<String>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -152,6 +161,59 @@ pub struct SubsonicArtistFull {
     pub album: Vec<SubsonicAlbum>,
 }
 
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PlaylistsData {
+    pub playlists: SubsonicPlaylists,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SubsonicPlaylists {
+    #[serde(default)]
+    pub playlist: Vec<SubsonicPlaylist>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PlaylistData {
+    pub playlist: SubsonicPlaylist,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SubsonicPlaylist {
+    #[serde(deserialize_with = "deserialize_string_from_any")]
+    pub id: String,
+    pub name: String,
+    pub comment: Option<String>,
+    pub owner: Option<String>,
+    #[serde(default, deserialize_with = "deserialize_option_u32_from_any")]
+    pub song_count: Option<u32>,
+    pub cover_art: Option<String>,
+    /// ISO 8601 time of the last edit
+    pub changed: Option<String>,
+    #[serde(default)]
+    pub entry: Vec<SubsonicSong>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Starred2Data {
+    pub starred2: SubsonicStarred,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SubsonicStarred {
+    #[serde(default)]
+    pub song: Vec<SubsonicSong>,
+    #[serde(default)]
+    pub album: Vec<SubsonicAlbum>,
+    #[serde(default)]
+    pub artist: Vec<SubsonicArtist>,
+}
+
 // Helpers for robust deserialization
 
 fn deserialize_string_from_any<'de, D>(deserializer: D) -> Result<String, D::Error>
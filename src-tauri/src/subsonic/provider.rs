@@ -1,4 +1,7 @@
-use crate::models::{Album, Artist, Quality, SearchResults, StreamInfo, Track};
+use crate::models::{
+    Album, Artist, LikedItem, LikedItems, LikedTrack, Playlist, Quality, SearchResults, StreamInfo,
+    Track,
+};
 use crate::providers::traits::MusicProvider;
 use anyhow::{anyhow, Result};
 use async_trait::async_trait;
@@ -7,6 +10,7 @@ use md5;
 use rand::Rng;
 use reqwest::Client;
 use serde_json::Value;
+use std::collections::HashMap;
 
 use super::models::*;
 
@@ -95,6 +99,49 @@ impl SubsonicProvider {
         )
     }
 
+    /// Everything the user starred, via getStarred2
+    async fn starred(&self) -> Result<SubsonicStarred> {
+        if !self.initialized {
+            return Err(anyhow!("Subsonic provider not initialized"));
+        }
+
+        let url = self.build_url("getStarred2", "");
+        let resp: SubsonicResponse<Starred2Data> =
+            self.client.get(&url).send().await?.json().await?;
+
+        if resp.subsonic_response.status != "ok" {
+            if let Some(err) = resp.subsonic_response.error {
+                return Err(anyhow!("Subsonic error {}: {}", err.code, err.message));
+            }
+            return Err(anyhow!("Unknown Subsonic error"));
+        }
+
+        resp.subsonic_response
+            .data
+            .map(|d| d.starred2)
+            .ok_or_else(|| anyhow!("No starred data"))
+    }
+
+    /// Star or unstar a song, album or artist; `param` names the id kind
+    async fn set_starred(&self, param: &str, id: &str, starred: bool) -> Result<()> {
+        if !self.initialized {
+            return Err(anyhow!("Subsonic provider not initialized"));
+        }
+
+        let endpoint = if starred { "star" } else { "unstar" };
+        let clean_id = id.strip_prefix("subsonic:").unwrap_or(id);
+        let url = self.build_url(endpoint, &format!("{}={}", param, clean_id));
+        let resp: SubsonicResponse<()> = self.client.get(&url).send().await?.json().await?;
+
+        if resp.subsonic_response.status == "ok" {
+            Ok(())
+        } else if let Some(err) = resp.subsonic_response.error {
+            Err(anyhow!("Subsonic error {}: {}", err.code, err.message))
+        } else {
+            Err(anyhow!("Unknown Subsonic error"))
+        }
+    }
+
     pub async fn ping(&self) -> Result<bool> {
         let url = self.build_url("ping", "");
         let resp: SubsonicResponse<()> = self.client.get(&url).send().await?.json().await?;
@@ -529,4 +576,315 @@ impl MusicProvider for SubsonicProvider {
 
         Ok(tracks)
     }
+
+    fn rating_scale(&self) -> Option<u8> {
+        Some(5)
+    }
+
+    async fn get_rating(&self, track_id: &str) -> Result<Option<u8>> {
+        if !self.initialized {
+            return Err(anyhow!("Subsonic provider not initialized"));
+        }
+
+        let clean_id = track_id.strip_prefix("subsonic:").unwrap_or(track_id);
+        let url = self.build_url("getSong", &format!("id={}", clean_id));
+        let resp: SubsonicResponse<SongData> = self.client.get(&url).send().await?.json().await?;
+
+        if resp.subsonic_response.status != "ok" {
+            if let Some(err) = resp.subsonic_response.error {
+                return Err(anyhow!("Subsonic error {}: {}", err.code, err.message));
+            }
+            return Err(anyhow!("Unknown Subsonic error"));
+        }
+
+        let song = resp
+            .subsonic_response
+            .data
+            .ok_or_else(|| anyhow!("No song data"))?
+            .song;
+
+        Ok(half_stars(song.user_rating))
+    }
+
+    async fn get_album_ratings(&self, album_id: &str) -> Result<HashMap<String, Option<u8>>> {
+        if !self.initialized {
+            return Err(anyhow!("Subsonic provider not initialized"));
+        }
+
+        let clean_id = album_id.strip_prefix("subsonic:").unwrap_or(album_id);
+        let url = self.build_url("getAlbum", &format!("id={}", clean_id));
+        let resp: SubsonicResponse<AlbumData> = self.client.get(&url).send().await?.json().await?;
+
+        if resp.subsonic_response.status != "ok" {
+            if let Some(err) = resp.subsonic_response.error {
+                return Err(anyhow!("Subsonic error {}: {}", err.code, err.message));
+            }
+            return Err(anyhow!("Unknown Subsonic error"));
+        }
+
+        let album = resp
+            .subsonic_response
+            .data
+            .ok_or_else(|| anyhow!("No album data"))?
+            .album;
+
+        Ok(album
+            .song
+            .into_iter()
+            .map(|s| (s.id, half_stars(s.user_rating)))
+            .collect())
+    }
+
+    async fn set_rating(&self, track_id: &str, rating: Option<u8>) -> Result<()> {
+        if !self.initialized {
+            return Err(anyhow!("Subsonic provider not initialized"));
+        }
+
+        // setRating takes whole stars, and 0 removes the rating
+        let stars = rating.map(|r| r.min(10).div_ceil(2)).unwrap_or(0);
+        let clean_id = track_id.strip_prefix("subsonic:").unwrap_or(track_id);
+        let url = self.build_url("setRating", &format!("id={}&rating={}", clean_id, stars));
+        let resp: SubsonicResponse<()> = self.client.get(&url).send().await?.json().await?;
+
+        if resp.subsonic_response.status == "ok" {
+            Ok(())
+        } else if let Some(err) = resp.subsonic_response.error {
+            Err(anyhow!("Subsonic error {}: {}", err.code, err.message))
+        } else {
+            Err(anyhow!("Unknown Subsonic error"))
+        }
+    }
+
+    fn supports_playlists(&self) -> bool {
+        true
+    }
+
+    async fn get_playlists(&self) -> Result<Vec<Playlist>> {
+        if !self.initialized {
+            return Err(anyhow!("Subsonic provider not initialized"));
+        }
+
+        let url = self.build_url("getPlaylists", "");
+        let resp: SubsonicResponse<PlaylistsData> =
+            self.client.get(&url).send().await?.json().await?;
+
+        if resp.subsonic_response.status != "ok" {
+            if let Some(err) = resp.subsonic_response.error {
+                return Err(anyhow!("Subsonic error {}: {}", err.code, err.message));
+            }
+            return Err(anyhow!("Unknown Subsonic error"));
+        }
+
+        let playlists = resp
+            .subsonic_response
+            .data
+            .map(|d| d.playlists.playlist)
+            .unwrap_or_default();
+
+        Ok(playlists
+            .into_iter()
+            // Other users' public playlists are listed too but can't be edited
+            .filter(|p| p.owner.as_deref().map_or(true, |o| o == self.username))
+            .map(|p| Playlist {
+                id: p.id,
+                title: p.name,
+                description: p.comment,
+                cover_url: p.cover_art.map(|c| self.cover_art_url(&c, 640)),
+                track_count: p.song_count.unwrap_or(0),
+                changed: p.changed,
+            })
+            .collect())
+    }
+
+    async fn get_playlist_tracks(&self, playlist_id: &str) -> Result<Vec<Track>> {
+        if !self.initialized {
+            return Err(anyhow!("Subsonic provider not initialized"));
+        }
+
+        let url = self.build_url("getPlaylist", &format!("id={}", playlist_id));
+        let resp: SubsonicResponse<PlaylistData> =
+            self.client.get(&url).send().await?.json().await?;
+
+        if resp.subsonic_response.status != "ok" {
+            if let Some(err) = resp.subsonic_response.error {
+                return Err(anyhow!("Subsonic error {}: {}", err.code, err.message));
+            }
+            return Err(anyhow!("Unknown Subsonic error"));
+        }
+
+        let playlist = resp
+            .subsonic_response
+            .data
+            .ok_or_else(|| anyhow!("No playlist data"))?
+            .playlist;
+
+        Ok(playlist
+            .entry
+            .into_iter()
+            .map(|s| Track {
+                id: format!("subsonic:{}", s.id),
+                title: s.title,
+                artist: s.artist.unwrap_or_default(),
+                artist_id: s.artist_id.map(|id| format!("subsonic:{}", id)),
+                album: s.album.unwrap_or_default(),
+                album_id: s.album_id.map(|id| format!("subsonic:{}", id)),
+                duration: s.duration.unwrap_or(0),
+                cover_url: s.cover_art.map(|c| self.cover_art_url(&c, 640)),
+            })
+            .collect())
+    }
+
+    async fn create_playlist(&self, name: &str, track_ids: &[String]) -> Result<String> {
+        if !self.initialized {
+            return Err(anyhow!("Subsonic provider not initialized"));
+        }
+
+        let mut form = vec![("name", name.to_string())];
+        form.extend(track_ids.iter().map(|id| {
+            let clean_id = id.strip_prefix("subsonic:").unwrap_or(id);
+            ("songId", clean_id.to_string())
+        }));
+
+        // Posted as a form, as long playlists overflow a GET query string
+        let url = self.build_url("createPlaylist", "");
+        let resp: SubsonicResponse<PlaylistData> = self
+            .client
+            .post(&url)
+            .form(&form)
+            .send()
+            .await?
+            .json()
+            .await?;
+
+        if resp.subsonic_response.status != "ok" {
+            if let Some(err) = resp.subsonic_response.error {
+                return Err(anyhow!("Subsonic error {}: {}", err.code, err.message));
+            }
+            return Err(anyhow!("Unknown Subsonic error"));
+        }
+
+        if let Some(data) = resp.subsonic_response.data {
+            return Ok(data.playlist.id);
+        }
+
+        // Servers before API 1.14 don't return the new playlist
+        self.get_playlists()
+            .await?
+            .into_iter()
+            .filter(|p| p.title == name)
+            .max_by(|a, b| a.changed.cmp(&b.changed))
+            .map(|p| p.id)
+            .ok_or_else(|| anyhow!("Created playlist not found on the server"))
+    }
+
+    async fn set_playlist_tracks(&self, playlist_id: &str, track_ids: &[String]) -> Result<()> {
+        if !self.initialized {
+            return Err(anyhow!("Subsonic provider not initialized"));
+        }
+
+        // createPlaylist with an existing playlistId replaces its songs
+        let mut form = vec![("playlistId", playlist_id.to_string())];
+        form.extend(track_ids.iter().map(|id| {
+            let clean_id = id.strip_prefix("subsonic:").unwrap_or(id);
+            ("songId", clean_id.to_string())
+        }));
+
+        let url = self.build_url("createPlaylist", "");
+        let resp: SubsonicResponse<Value> = self
+            .client
+            .post(&url)
+            .form(&form)
+            .send()
+            .await?
+            .json()
+            .await?;
+
+        if resp.subsonic_response.status == "ok" {
+            Ok(())
+        } else if let Some(err) = resp.subsonic_response.error {
+            Err(anyhow!("Subsonic error {}: {}", err.code, err.message))
+        } else {
+            Err(anyhow!("Unknown Subsonic error"))
+        }
+    }
+
+    fn supports_likes(&self) -> bool {
+        true
+    }
+
+    async fn get_liked_tracks(&self) -> Result<Vec<LikedTrack>> {
+        Ok(self
+            .starred()
+            .await?
+            .song
+            .into_iter()
+            .map(|s| LikedTrack {
+                liked_at: starred_at(s.starred.as_deref()),
+                track: Track {
+                    id: format!("subsonic:{}", s.id),
+                    title: s.title,
+                    artist: s.artist.unwrap_or_default(),
+                    artist_id: s.artist_id.map(|id| format!("subsonic:{}", id)),
+                    album: s.album.unwrap_or_default(),
+                    album_id: s.album_id.map(|id| format!("subsonic:{}", id)),
+                    duration: s.duration.unwrap_or(0),
+                    cover_url: s.cover_art.map(|c| self.cover_art_url(&c, 640)),
+                },
+            })
+            .collect())
+    }
+
+    async fn set_track_liked(&self, track_id: &str, liked: bool) -> Result<()> {
+        self.set_starred("id", track_id, liked).await
+    }
+
+    async fn get_liked_items(&self) -> Result<LikedItems> {
+        let starred = self.starred().await?;
+        Ok(LikedItems {
+            albums: starred
+                .album
+                .into_iter()
+                .map(|a| LikedItem {
+                    id: format!("subsonic:{}", a.id),
+                    title: a.name,
+                    artist: a.artist,
+                    cover_url: a.cover_art.map(|c| self.cover_art_url(&c, 640)),
+                    liked_at: starred_at(a.starred.as_deref()),
+                })
+                .collect(),
+            artists: starred
+                .artist
+                .into_iter()
+                .map(|a| LikedItem {
+                    id: format!("subsonic:{}", a.id),
+                    title: a.name,
+                    artist: None,
+                    cover_url: a.cover_art.map(|c| self.cover_art_url(&c, 640)),
+                    liked_at: starred_at(a.starred.as_deref()),
+                })
+                .collect(),
+        })
+    }
+
+    async fn set_album_liked(&self, album_id: &str, liked: bool) -> Result<()> {
+        self.set_starred("albumId", album_id, liked).await
+    }
+
+    async fn set_artist_liked(&self, artist_id: &str, liked: bool) -> Result<()> {
+        self.set_starred("artistId", artist_id, liked).await
+    }
+}
+
+/// Half-stars from a 1 to 5 star `userRating`
+fn half_stars(user_rating: Option<u32>) -> Option<u8> {
+    user_rating
+        .filter(|stars| *stars > 0)
+        .map(|stars| stars.min(5) as u8 * 2)
+}
+
+/// Unix time from a `starred` timestamp
+fn starred_at(starred: Option<&str>) -> Option<i64> {
+    starred
+        .and_then(|t| chrono::DateTime::parse_from_rfc3339(t).ok())
+        .map(|t| t.timestamp())
 }
@@ -389,7 +389,38 @@ impl TidalClient {
         let data = self
             .make_request("/playlist/", &[("id", playlist_id)], "get_playlist")
             .await?;
-        serde_json::from_value(data).map_err(|e| e.into())
+
+        let item = if let Some(inner) = data.get("playlist") {
+            inner
+        } else {
+            &data
+        };
+        serde_json::from_value(item.clone()).map_err(|e| e.into())
+    }
+
+    pub async fn get_playlist_tracks(&self, playlist_id: &str) -> Result<Vec<Track>, TidalError> {
+        let limit = PLAYLIST_PAGE_SIZE.to_string();
+        let mut tracks: Vec<Track> = Vec::new();
+        let mut offset = 0;
+        loop {
+            let data = self
+                .make_request(
+                    "/playlist/",
+                    &[
+                        ("id", playlist_id),
+                        ("limit", &limit),
+                        ("offset", &offset.to_string()),
+                    ],
+                    "get_playlist_tracks",
+                )
+                .await?;
+            let page: Vec<Track> = self.extract_items(&data, "tracks");
+            offset += page_size(&data);
+            tracks.extend(page);
+            if last_page(&data, offset) {
+                return Ok(tracks);
+            }
+        }
     }
 
     pub async fn debug_endpoint(
@@ -407,3 +438,51 @@ impl TidalClient {
         Ok(pretty)
     }
 }
+
+/// Items on a playlist page. Videos are dropped by the parse, so this counts
+/// the raw items.
+fn page_size(data: &Value) -> usize {
+    data.get("items")
+        .or_else(|| data.get("tracks").and_then(|t| t.get("items")))
+        .and_then(|v| v.as_array())
+        .map_or(0, |items| items.len())
+}
+
+/// Whether `offset` is past the playlist's end. Pages report the total; when
+/// one doesn't, a short page is the last.
+fn last_page(data: &Value, offset: usize) -> bool {
+    let fetched = page_size(data);
+    if fetched == 0 {
+        return true;
+    }
+    let total = data
+        .get("totalNumberOfItems")
+        .or_else(|| data.get("tracks").and_then(|t| t.get("totalNumberOfItems")))
+        .and_then(|v| v.as_u64());
+    match total {
+        Some(total) => offset as u64 >= total,
+        None => fetched < PLAYLIST_PAGE_SIZE,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn pages_end_at_the_reported_total() {
+        let full = |total: u64| json!({ "totalNumberOfItems": total, "items": vec![json!({}); PLAYLIST_PAGE_SIZE] });
+        assert!(!last_page(&full(250), 100));
+        assert!(last_page(&full(200), 200));
+        assert!(last_page(
+            &json!({ "totalNumberOfItems": 250, "items": [] }),
+            200
+        ));
+        assert!(last_page(&json!({ "items": [{}] }), 101));
+        assert!(!last_page(
+            &json!({ "tracks": { "items": vec![json!({}); PLAYLIST_PAGE_SIZE] } }),
+            100
+        ));
+    }
+}
@@ -10,6 +10,7 @@ pub const CACHE_TTL_SECONDS: u64 = 86400; // 24 hours
 pub const REQUEST_TIMEOUT_SECONDS: u64 = 10;
 pub const RATE_LIMIT_SLEEP_MS: u64 = 2000;
 pub const MAX_STICKY_FAILURES: u32 = 3;
+pub const PLAYLIST_PAGE_SIZE: usize = 100;
 
 pub fn get_cache_dir() -> PathBuf {
     dirs::config_dir()
@@ -46,6 +46,13 @@ pub struct Track {
     pub cover: Option<String>,
     #[serde(rename = "trackNumber", skip_serializing_if = "Option::is_none")]
     pub track_number: Option<u32>,
+    #[serde(rename = "volumeNumber", skip_serializing_if = "Option::is_none")]
+    pub volume_number: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub isrc: Option<String>,
+    /// All credited artists, each tagged MAIN or FEATURED
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub artists: Option<Vec<Artist>>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -56,6 +63,8 @@ pub struct Artist {
     pub picture: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub banner: Option<String>,
+    #[serde(rename = "type", skip_serializing_if = "Option::is_none")]
+    pub artist_type: Option<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -127,6 +127,7 @@ impl MusicProvider for TidalProvider {
                     .cover
                     .map(|c| crate::tidal::models::get_cover_url(&c, 640)),
                 track_count: p.number_of_tracks.unwrap_or(0),
+                changed: None,
             })
             .collect();
 
@@ -17,14 +17,6 @@ export const getPlayCount = async (trackId: string): Promise<number> => {
   return await invoke("get_play_count", { trackId });
 };
 
-export const recordPlay = async (
-  trackId: string,
-  contextUri?: string,
-  contextType?: string,
-): Promise<string> => {
-  return await invoke("record_play", { trackId, contextUri, contextType });
-};
-
 export const updatePlayCompletion = async (
   entryId: string,
   durationPlayed: number,
@@ -2,7 +2,7 @@ import { useState, useMemo, useCallback } from "react";
 import { usePlayer } from "../context/PlayerContext";
 import { useDownload } from "../context/DownloadContext";
 import { AppLogo } from "./icons/AppLogo";
-import { Track } from "../types";
+import { PlayContext, Track } from "../types";
 import { ContextMenu, ContextMenuItem } from "./ContextMenu";
 import { getPlaylistsContainingTrack } from "../api/playlist";
 import { DownloadIndicator } from "./DownloadIndicator";
@@ -150,6 +150,11 @@ const TrackRow = ({
 export const AlbumPage = ({ albumId, onNavigate }: AlbumPageProps) => {
   const { album, tracks, isLoading, error } = useAlbum(albumId);
 
+  const playContext = useMemo<PlayContext>(
+    () => ({ context_type: "album", uri: albumId, name: album?.title }),
+    [albumId, album],
+  );
+
   const {
     playTrack,
     currentTrack,
@@ -187,8 +192,8 @@ export const AlbumPage = ({ albumId, onNavigate }: AlbumPageProps) => {
       ...t,
       cover_image: t.cover_image || album.cover_url,
     }));
-    await playTrack(queue[0], queue);
-  }, [album, tracks, playTrack]);
+    await playTrack(queue[0], queue, playContext);
+  }, [album, tracks, playContext, playTrack]);
 
   const handleShufflePlay = useCallback(async () => {
     if (!album || tracks.length === 0) return;
@@ -199,8 +204,8 @@ export const AlbumPage = ({ albumId, onNavigate }: AlbumPageProps) => {
       cover_image: t.cover_image || album.cover_url,
     }));
     const randomIndex = Math.floor(Math.random() * queue.length);
-    await playTrack(queue[randomIndex], queue);
-  }, [album, tracks, shuffle, toggleShuffle, playTrack]);
+    await playTrack(queue[randomIndex], queue, playContext);
+  }, [album, tracks, shuffle, toggleShuffle, playContext, playTrack]);
 
   const handlePlayTrack = useCallback(
     async (track: Track) => {
@@ -218,9 +223,9 @@ export const AlbumPage = ({ albumId, onNavigate }: AlbumPageProps) => {
       console.log("[AlbumPage] Track Path:", trackToPlay.path);
       console.log("[AlbumPage] Track Provider ID:", trackToPlay.provider_id);
 
-      await playTrack(trackToPlay, queue);
+      await playTrack(trackToPlay, queue, playContext);
     },
-    [album, tracks, playTrack],
+    [album, tracks, playContext, playTrack],
   );
 
   const handleNavigateToArtist = () => {
@@ -301,7 +306,7 @@ export const AlbumPage = ({ albumId, onNavigate }: AlbumPageProps) => {
               ...t,
               cover_image: t.cover_image || album.cover_url,
             }));
-            playTrack(track, queue);
+            playTrack(track, queue, playContext);
           }
         },
       },
@@ -333,6 +338,7 @@ export const AlbumPage = ({ albumId, onNavigate }: AlbumPageProps) => {
     toggleFavorite,
     addToPlaylist,
     downloadTrack,
+    playContext,
     playTrack,
   ]);
 
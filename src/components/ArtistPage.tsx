@@ -3,7 +3,7 @@ import { AppLogo } from "./icons/AppLogo";
 import { usePlayer } from "../context/PlayerContext";
 import { useDownload } from "../context/DownloadContext";
 import { ContextMenu, ContextMenuItem } from "./ContextMenu";
-import { Track } from "../types";
+import { PlayContext, Track } from "../types";
 import { getPlaylistsContainingTrack } from "../api/playlist";
 import { useArtist } from "../hooks/useData";
 
@@ -15,6 +15,11 @@ interface ArtistPageProps {
 export const ArtistPage = ({ artistId, onNavigate }: ArtistPageProps) => {
   const { artist, topTracks, albums, isLoading, error } = useArtist(artistId);
 
+  const playContext = useMemo<PlayContext>(
+    () => ({ context_type: "artist", uri: artistId, name: artist?.name }),
+    [artistId, artist],
+  );
+
   const { playTrack, toggleFavorite, favorites, playlists, addToPlaylist } =
     usePlayer();
   const { downloadTrack } = useDownload();
@@ -47,7 +52,7 @@ export const ArtistPage = ({ artistId, onNavigate }: ArtistPageProps) => {
     console.log("[ArtistPage] Track Path:", trackToPlay.path);
     console.log("[ArtistPage] Track Provider ID:", trackToPlay.provider_id);
 
-    await playTrack(trackToPlay, tracksToPlay);
+    await playTrack(trackToPlay, tracksToPlay, playContext);
   };
 
   const formatDuration = (seconds?: number | null) => {
@@ -100,7 +105,7 @@ export const ArtistPage = ({ artistId, onNavigate }: ArtistPageProps) => {
         action: () => {
           const displayCount = showAllTracks ? 10 : 5;
           const queue = topTracks.slice(0, displayCount);
-          playTrack(track, queue);
+          playTrack(track, queue, playContext);
         },
       },
       {
@@ -131,6 +136,7 @@ export const ArtistPage = ({ artistId, onNavigate }: ArtistPageProps) => {
     toggleFavorite,
     addToPlaylist,
     downloadTrack,
+    playContext,
     playTrack,
   ]);
 
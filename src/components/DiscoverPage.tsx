@@ -2,7 +2,7 @@ import { useState, useCallback, useRef, useMemo, useEffect } from "react";
 import { usePlayer } from "../context/PlayerContext";
 import { useContextMenu } from "../context/ContextMenuContext";
 import { useTopArtists, QUERY_KEYS } from "../hooks/queries";
-import { Track } from "../types";
+import { PlayContext, Track } from "../types";
 import { ImageWithFallback } from "./shared/ImageWithFallback";
 import {
   RecommendedTrack,
@@ -57,6 +57,12 @@ const toPlayableTrack = (rec: RecommendedTrack): Track | null => {
 const toPlayableQueue = (tracks: RecommendedTrack[]): Track[] =>
   tracks.map(toPlayableTrack).filter((t): t is Track => t !== null);
 
+const toPlayContext = (section: RecommendationSection): PlayContext => ({
+  context_type: "recommendation",
+  uri: section.source_playlist_uri || section.title,
+  name: section.title,
+});
+
 // ---------------------------------------------------------------------------
 // ProviderBadge (consistent with SearchPage)
 // ---------------------------------------------------------------------------
@@ -660,7 +666,7 @@ export const DiscoverPage = ({
           const track = toPlayableTrack(rec);
           if (!track) return;
           const queue = toPlayableQueue(section.tracks);
-          playTrack(track, queue);
+          playTrack(track, queue, toPlayContext(section));
         }
       } catch (e) {
         console.error(`Failed to play ${rec.matched_provider_id} track:`, e);
@@ -685,7 +691,7 @@ export const DiscoverPage = ({
         [
           {
             label: "Play",
-            action: () => playTrack(track, queue),
+            action: () => playTrack(track, queue, toPlayContext(section)),
           },
           {
             label: "Add to Liked Songs",
@@ -5,7 +5,7 @@ import { useIsMobile } from "../hooks/useIsMobile";
 import { usePlaylistMenu } from "../hooks/usePlaylistMenu";
 import { useState, useMemo, useEffect, useCallback } from "react";
 import { UnifiedTrack } from "../api/library";
-import { Track } from "../types";
+import { PlayContext, Track } from "../types";
 import { ContextMenuItem } from "./ContextMenu";
 import { usePlaylistDetails } from "../hooks/queries";
 import { DeletePlaylistModal } from "./DeletePlaylistModal";
@@ -134,6 +134,15 @@ export const PlaylistView = ({ playlistId, onNavigate }: PlaylistViewProps) => {
   } = usePlaylistDetails(playlistId);
   const error = queryError ? "Failed to load playlist." : null;
 
+  const playContext = useMemo<PlayContext>(
+    () => ({
+      context_type: "playlist",
+      uri: playlistId,
+      name: details?.playlist.title,
+    }),
+    [playlistId, details],
+  );
+
   const [isEditing, setIsEditing] = useState(false);
   const [editName, setEditName] = useState("");
   const [isDeleteModalOpen, setIsDeleteModalOpen] = useState(false);
@@ -227,7 +236,7 @@ export const PlaylistView = ({ playlistId, onNavigate }: PlaylistViewProps) => {
       const items: ContextMenuItem[] = [
         {
           label: "Play",
-          action: () => playTrack(track, sortedTracks),
+          action: () => playTrack(track, sortedTracks, playContext),
         },
         {
           label: isLiked ? "Remove from Liked Songs" : "Add to Liked Songs",
@@ -259,6 +268,7 @@ export const PlaylistView = ({ playlistId, onNavigate }: PlaylistViewProps) => {
       playlistId,
       favorites,
       sortedTracks,
+      playContext,
       playTrack,
       toggleFavorite,
       downloadTrack,
@@ -382,7 +392,7 @@ export const PlaylistView = ({ playlistId, onNavigate }: PlaylistViewProps) => {
     if (tracks.length === 0) return;
 
     const queue = tracks.map(mapToTrack);
-    await playTrack(queue[0], queue);
+    await playTrack(queue[0], queue, playContext);
   };
 
   const handleShufflePlay = async () => {
@@ -391,13 +401,13 @@ export const PlaylistView = ({ playlistId, onNavigate }: PlaylistViewProps) => {
 
     const queue = tracks.map(mapToTrack);
     const randomIndex = Math.floor(Math.random() * queue.length);
-    await playTrack(queue[randomIndex], queue);
+    await playTrack(queue[randomIndex], queue, playContext);
   };
 
   const handlePlayTrack = async (track: Track) => {
     const queue = sortedTracks.map(mapToTrack);
     const trackToPlay = mapToTrack(track);
-    await playTrack(trackToPlay, queue);
+    await playTrack(trackToPlay, queue, playContext);
   };
 
   const handleDownloadAll = async () => {
@@ -331,9 +331,13 @@ export const SearchPage = ({
     async (track: UnifiedSearchTrack) => {
       const t = convertToTrack(track);
       console.log("[SearchPage] Playing track:", t);
-      await playTrack(t, [t]);
+      await playTrack(t, [t], {
+        context_type: "search",
+        uri: query,
+        name: query,
+      });
     },
-    [playTrack, convertToTrack],
+    [query, playTrack, convertToTrack],
   );
 
   // Build context menu items for a track
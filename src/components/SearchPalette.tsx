@@ -714,7 +714,11 @@ export const SearchPalette = ({
   const handlePlay = async (result: SearchResult) => {
     if (result.type === "local") {
       const track = result.track as UnifiedTrack;
-      playTrack(track as any, [track] as any);
+      playTrack(track as any, [track] as any, {
+        context_type: "search",
+        uri: query,
+        name: query,
+      });
       onClose();
     } else if (result.type === "tidal") {
       const track = result.track as TidalTrack;
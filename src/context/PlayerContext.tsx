@@ -9,9 +9,14 @@ import {
 } from "react";
 import { invoke } from "@tauri-apps/api/core";
 import { listen } from "@tauri-apps/api/event";
-import { Track, Playlist } from "../types";
+import {
+  Track,
+  Playlist,
+  PlayContext,
+  LibraryFolder,
+  ScanSummary,
+} from "../types";
 import { UnifiedTrack } from "../api/library";
-import { recordPlay } from "../api/history";
 import { useQueryClient } from "@tanstack/react-query";
 import { QUERY_KEYS } from "../hooks/queries";
 
@@ -61,7 +66,11 @@ interface PlayerContextType {
   openSettings: (tab?: "appearance" | "playback" | "services") => void;
   importMusic: () => Promise<void>;
   importFolder: () => Promise<void>;
-  playTrack: (track: Track, contextQueue?: Track[]) => Promise<void>;
+  playTrack: (
+    track: Track,
+    contextQueue?: Track[],
+    context?: PlayContext,
+  ) => Promise<void>;
   togglePlay: () => Promise<void>;
   seek: (time: number) => Promise<void>;
   setVolume: (vol: number) => Promise<void>;
@@ -276,9 +285,6 @@ export const PlayerProvider = ({ children }: { children: ReactNode }) => {
     currentTrackRef.current = currentTrack;
   }, [currentTrack]);
 
-  const lastRecordedToken = useRef<string | null>(null);
-  const recordingThreshold = 30; // seconds
-
   useEffect(() => {
     let animationId: number;
     let lastPollTime = 0;
@@ -305,23 +311,6 @@ export const PlayerProvider = ({ children }: { children: ReactNode }) => {
 
           setDuration(info.duration);
           setIsPlaying(info.is_playing);
-
-          // Recording Logic
-          const track = currentTrackRef.current;
-          if (
-            track &&
-            info.is_playing &&
-            info.position > recordingThreshold &&
-            lastRecordedToken.current !== track.id
-          ) {
-            lastRecordedToken.current = track.id;
-            recordPlay(track.id)
-              .then(() => {
-                queryClient.invalidateQueries({ queryKey: QUERY_KEYS.history });
-              })
-              .catch((e) => console.error(e));
-            bumpDataVersion();
-          }
         } catch (e) {}
       }
 
@@ -339,7 +328,12 @@ export const PlayerProvider = ({ children }: { children: ReactNode }) => {
       setDuration(event.payload.duration);
       setCurrentTime(0);
       seekTarget.current = null;
-      lastRecordedToken.current = null; // Reset recording for new track
+    });
+
+    // Plays are recorded by the player itself as tracks start and end
+    const unlistenHistory = listen("play-history-changed", () => {
+      queryClient.invalidateQueries({ queryKey: QUERY_KEYS.history });
+      bumpDataVersion();
     });
 
     const unlistenQuality = listen<PlaybackQuality>(
@@ -354,6 +348,7 @@ export const PlayerProvider = ({ children }: { children: ReactNode }) => {
       cancelAnimationFrame(animationId);
       unlisten.then((f) => f());
       unlistenQuality.then((f) => f());
+      unlistenHistory.then((f) => f());
     };
   }, []); // Run once, depend on refs
 
@@ -505,22 +500,54 @@ export const PlayerProvider = ({ children }: { children: ReactNode }) => {
     }
   };
 
+  // Folders whose tracks are added once their background scan completes
+  const importedFolders = useRef<Set<string>>(new Set());
+
   const importFolder = async () => {
     try {
-      const newTracks = await invoke<Track[]>("import_folder");
-      if (newTracks && newTracks.length > 0) {
-        setTracks((prev) => {
-          const existingPaths = new Set(prev.map((t) => t.path));
-          const uniqueNew = newTracks.filter((t) => !existingPaths.has(t.path));
-          return [...prev, ...uniqueNew];
-        });
+      const folder = await invoke<LibraryFolder | null>("import_folder");
+      if (folder) {
+        importedFolders.current.add(folder.id);
       }
     } catch (e) {
       console.error("Failed to import folder:", e);
     }
   };
 
-  const playTrack = async (track: Track, contextQueue?: Track[]) => {
+  useEffect(() => {
+    const unlistenScan = listen<ScanSummary>(
+      "library-scan-complete",
+      async (event) => {
+        bumpDataVersion();
+        const folderId = event.payload.folder_id;
+        if (!importedFolders.current.delete(folderId)) return;
+        try {
+          const newTracks = await invoke<Track[]>("get_library_folder_tracks", {
+            folderId,
+          });
+          setTracks((prev) => {
+            const existingPaths = new Set(prev.map((t) => t.path));
+            const uniqueNew = newTracks.filter(
+              (t) => !existingPaths.has(t.path),
+            );
+            return [...prev, ...uniqueNew];
+          });
+        } catch (e) {
+          console.error("Failed to load imported folder:", e);
+        }
+      },
+    );
+
+    return () => {
+      unlistenScan.then((f) => f());
+    };
+  }, []); // eslint-disable-line react-hooks/exhaustive-deps
+
+  const playTrack = async (
+    track: Track,
+    contextQueue?: Track[],
+    context?: PlayContext,
+  ) => {
     try {
       setCurrentTrack(track);
       setCurrentTime(0);
@@ -530,7 +557,10 @@ export const PlayerProvider = ({ children }: { children: ReactNode }) => {
       const queueToSet =
         contextQueue && contextQueue.length > 0 ? contextQueue : tracks;
 
-      await invoke("set_queue", { tracks: queueToSet });
+      await invoke("set_queue", {
+        tracks: queueToSet,
+        context: context ?? null,
+      });
 
       setQueue(queueToSet);
 
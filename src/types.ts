@@ -18,6 +18,14 @@ export interface Track {
   external_id?: string;
 }
 
+/** Where a queue was started from, recorded with each play for "jump back in" */
+export interface PlayContext {
+  context_type: "album" | "playlist" | "artist" | "search" | "recommendation";
+  /** Playlist id, album or artist id as navigated to, or the search query */
+  uri: string;
+  name?: string;
+}
+
 export interface PlayerState {
   currentTrack: Track | null;
   isPlaying: boolean;
@@ -57,3 +65,20 @@ export interface Album {
   track_count?: number;
   duration?: number;
 }
+
+export interface LibraryFolder {
+  id: string;
+  path: string;
+  added_at: number;
+  last_scanned_at?: number;
+}
+
+export interface ScanSummary {
+  folder_id: string;
+  path: string;
+  added: number;
+  updated: number;
+  unchanged: number;
+  missing: number;
+  failed: number;
+}